@@ -0,0 +1,85 @@
+use std::sync::Barrier;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use futures_util::future::BoxFuture;
+use tracing::Dispatch;
+use tracing_newrelic::{Exporter, NewrLogs, NewrSpans};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+const THREADS: usize = 16;
+const CHILDREN: usize = 16;
+
+/// Drops every trace, only the layer is measured
+struct Discard;
+
+impl Exporter for Discard {
+    fn export(&mut self, _: NewrLogs, _: NewrSpans) -> BoxFuture<'_, ()> {
+        Box::pin(async {})
+    }
+
+    fn shutdown(&mut self) -> BoxFuture<'_, ()> {
+        Box::pin(async {})
+    }
+}
+
+/// Time taken by 16 threads to each close `CHILDREN` children of the same parent, each child
+/// having `grandchildren` children and logs of its own
+fn close_children(dispatch: &Dispatch, grandchildren: usize) -> Duration {
+    tracing::dispatcher::with_default(dispatch, || {
+        let root = tracing::info_span!("root");
+        let barrier = Barrier::new(THREADS + 1);
+
+        thread::scope(|scope| {
+            for _ in 0..THREADS {
+                scope.spawn(|| {
+                    tracing::dispatcher::with_default(dispatch, || {
+                        barrier.wait();
+
+                        for n in 0..CHILDREN {
+                            tracing::info_span!(parent: &root, "child", n).in_scope(|| {
+                                for _ in 0..grandchildren {
+                                    tracing::info_span!("grandchild")
+                                        .in_scope(|| tracing::info!("inside grandchild"));
+                                }
+                            });
+                        }
+
+                        barrier.wait();
+                    });
+                });
+            }
+
+            barrier.wait();
+            let start = Instant::now();
+            barrier.wait();
+            start.elapsed()
+        })
+    })
+}
+
+fn fan_out(c: &mut Criterion) {
+    let dispatch =
+        Dispatch::new(Registry::default().with(tracing_newrelic::layer_with_exporter(Discard)));
+
+    for grandchildren in [0, 32] {
+        let name =
+            format!("16 threads closing children of one parent, {grandchildren} grandchildren");
+
+        c.bench_function(&name, |b| {
+            b.iter_custom(|iters| {
+                (0..iters)
+                    .map(|_| close_children(&dispatch, grandchildren))
+                    .sum()
+            })
+        });
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = fan_out
+}
+criterion_main!(benches);
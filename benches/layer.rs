@@ -0,0 +1,59 @@
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use futures_util::future::BoxFuture;
+use tracing::Dispatch;
+use tracing_newrelic::{Exporter, NewrLogs, NewrSpans};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+/// Events recorded inside each span, measured without the span itself
+const EVENTS: u64 = 1000;
+
+/// Drops every trace, only the layer is measured
+struct Discard;
+
+impl Exporter for Discard {
+    fn export(&mut self, _: NewrLogs, _: NewrSpans) -> BoxFuture<'_, ()> {
+        Box::pin(async {})
+    }
+
+    fn shutdown(&mut self) -> BoxFuture<'_, ()> {
+        Box::pin(async {})
+    }
+}
+
+fn layer(c: &mut Criterion) {
+    let dispatch =
+        Dispatch::new(Registry::default().with(tracing_newrelic::layer_with_exporter(Discard)));
+
+    tracing::dispatcher::with_default(&dispatch, || {
+        c.bench_function("span create and close", |b| {
+            b.iter(|| {
+                tracing::info_span!("request", http.method = "GET", http.status_code = 200)
+                    .in_scope(|| {})
+            })
+        });
+
+        c.bench_function("event record", |b| {
+            b.iter_custom(|iters| {
+                let mut elapsed = Duration::ZERO;
+
+                for chunk in (0..iters).step_by(EVENTS as usize) {
+                    let _span = tracing::info_span!("request").entered();
+                    let start = Instant::now();
+
+                    for n in chunk..iters.min(chunk + EVENTS) {
+                        tracing::info!(n, user.id = "abc", "handling request");
+                    }
+
+                    elapsed += start.elapsed();
+                }
+
+                elapsed
+            })
+        });
+    });
+}
+
+criterion_group!(benches, layer);
+criterion_main!(benches);
@@ -0,0 +1,83 @@
+use axum::{
+    extract::{MatchedPath, Path},
+    http::StatusCode,
+    routing::get,
+    Router,
+};
+use std::{net::SocketAddr, time::Duration};
+use tracing::Level;
+use tracing_newrelic::http::RequestSpanLayer;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+const HTML: &str = r#"<ul>
+    <li><a href="/">home</a></li>
+    <li><a href="/sleep/100">sleep 100ms</a></li>
+    <li><a href="/sleep/300">sleep 300ms</a></li>
+    <li><a href="/fail">fail</a></li>
+</ul>"#;
+
+async fn home() -> axum::response::Html<&'static str> {
+    axum::response::Html(HTML)
+}
+
+async fn sleep(Path(ms): Path<u64>) -> axum::response::Html<&'static str> {
+    tracing::info!(ms, "sleep {}ms", ms);
+
+    tokio::time::sleep(Duration::from_millis(ms)).await;
+
+    axum::response::Html(HTML)
+}
+
+async fn fail() -> StatusCode {
+    tracing::error!("something went wrong");
+
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+#[tokio::main]
+async fn main() {
+    let newrelic = tracing_newrelic::layer_from_env()
+        .expect("invalid New Relic configuration")
+        .with_service_name("tracing-newrelic-demo")
+        .with_name_normalizer(tracing_newrelic::normalizers::numeric_path_segments());
+
+    let handle = newrelic.handle();
+
+    let fmt = tracing_subscriber::fmt::layer();
+
+    let target = tracing_subscriber::filter::Targets::new()
+        .with_target("axum", Level::INFO)
+        .with_target("tracing_newrelic", Level::INFO);
+
+    let subscriber = Registry::default().with(newrelic).with(fmt).with(target);
+
+    tracing::subscriber::set_global_default(subscriber)
+        .expect("failed to initilize tracing subscriber");
+
+    let app = Router::new()
+        .route("/", get(home))
+        .route("/sleep/:ms", get(sleep))
+        .route("/fail", get(fail))
+        .layer(RequestSpanLayer::new().with_route(|extensions| {
+            extensions
+                .get::<MatchedPath>()
+                .map(|path| path.as_str().to_string())
+        }));
+
+    let addr: SocketAddr = "127.0.0.1:5555".parse().unwrap();
+
+    println!("API server running at {}", addr);
+
+    axum::Server::bind(&addr)
+        .serve(app.into_make_service())
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await
+        .expect("failed to run the server");
+
+    // the global subscriber is never dropped, so flush before exiting
+    if !handle.flush().await {
+        eprintln!("failed to flush data to New Relic");
+    }
+}
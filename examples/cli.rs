@@ -0,0 +1,46 @@
+//! A short-lived command line tool, sending its trace synchronously before exiting
+//!
+//! ```sh
+//! NEW_RELIC_LICENSE_KEY=... cargo run --example cli --features blocking -- count 3
+//! ```
+
+use std::env::args;
+use std::thread::sleep;
+use std::time::Duration;
+
+use tracing_newrelic::Api;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+#[tracing::instrument]
+fn count(to: u32) {
+    for n in 1..=to {
+        tracing::info!(n, "counting");
+        sleep(Duration::from_millis(50));
+    }
+}
+
+fn main() {
+    env_logger::init();
+
+    let api = Api::from_env()
+        .expect("invalid New Relic configuration")
+        .with_blocking(true);
+
+    let newrelic = tracing_newrelic::try_layer(api)
+        .expect("invalid New Relic configuration")
+        .with_service_name("tracing-newrelic-cli");
+
+    let subscriber = Registry::default().with(newrelic);
+
+    // the layer is dropped along with the subscriber, after the trace has been sent
+    tracing::subscriber::with_default(subscriber, || {
+        let command: Vec<String> = args().skip(1).collect();
+
+        let _span = tracing::info_span!("command", args = %command.join(" ")).entered();
+
+        match command.first().map(String::as_str) {
+            Some("count") => count(command.get(1).and_then(|n| n.parse().ok()).unwrap_or(3)),
+            _ => tracing::error!("unknown command, try `count 3`"),
+        }
+    });
+}
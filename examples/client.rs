@@ -0,0 +1,40 @@
+use reqwest_middleware::ClientBuilder;
+use tracing::Instrument;
+use tracing_newrelic::client::ClientSpans;
+use tracing_newrelic::testing::with_captured;
+use tracing_newrelic::Value;
+
+fn main() {
+    let client = ClientBuilder::new(reqwest::Client::new())
+        .with(ClientSpans::new().with_peer_service("httpbin"))
+        .build();
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let captured = with_captured(|| {
+        let span = tracing::info_span!("GET /status", span.kind = "server");
+
+        runtime.block_on(
+            async {
+                match client.get("https://httpbin.org/status/200").send().await {
+                    Ok(response) => println!("httpbin replied {}", response.status()),
+                    Err(error) => println!("failed to call httpbin: {}", error),
+                }
+            }
+            .instrument(span),
+        );
+    });
+
+    // the server span, then the client one
+    for span in captured.spans() {
+        let parent = match span.attributes.get("parent.id") {
+            Some(Value::String(parent)) => parent.as_str(),
+            _ => "none",
+        };
+
+        println!("{} (parent: {}) {:?}", span.id, parent, span.attributes);
+    }
+}
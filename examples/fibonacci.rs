@@ -1,4 +1,3 @@
-use std::env::var;
 use std::thread::sleep;
 use std::time::Duration;
 
@@ -21,7 +20,9 @@ fn fibonacci(n: u32) -> u32 {
 fn main() {
     env_logger::init();
 
-    let newrelic = tracing_newrelic::layer(var("API_KEY").expect("API_KEY not found"));
+    let newrelic = tracing_newrelic::layer_from_env().expect("invalid New Relic configuration");
+
+    let handle = newrelic.handle();
 
     let fmt = tracing_subscriber::fmt::layer();
 
@@ -37,4 +38,9 @@ fn main() {
 
         fibonacci(3);
     });
+
+    // make sure everything is sent before exiting
+    if !handle.flush_timeout(Duration::from_secs(10)) {
+        eprintln!("failed to flush data to New Relic");
+    }
 }
@@ -0,0 +1,115 @@
+//! JSON logs on stdout carrying the linking metadata of New Relic, e.g. for logs shipped by an
+//! external forwarder
+//!
+//! ```sh
+//! NEW_RELIC_LICENSE_KEY=... cargo run --example linking
+//! ```
+
+use std::fmt;
+use std::time::Duration;
+
+use serde_json::{Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_newrelic::{LinkingMetadata, NewrAttributes};
+use tracing_subscriber::fmt::format::{FormatEvent, FormatFields, Writer};
+use tracing_subscriber::fmt::FmtContext;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+/// One JSON object per line, with the fields of the event and the linking metadata of its span
+struct JsonLines;
+
+impl<S, N> FormatEvent<S, N> for JsonLines
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let mut line = Map::new();
+        line.insert("level".into(), event.metadata().level().as_str().into());
+        line.insert("target".into(), event.metadata().target().into());
+
+        event.record(&mut Fields(&mut line));
+
+        // `tracing_newrelic::linking_metadata()` doesn't see the current span from here
+        if let Some(linking) = ctx.lookup_current().as_ref().and_then(LinkingMetadata::of) {
+            if let Value::Object(linking) = serde_json::to_value(linking).map_err(|_| fmt::Error)? {
+                line.extend(linking);
+            }
+        }
+
+        writeln!(writer, "{}", Value::Object(line))
+    }
+}
+
+struct Fields<'a>(&'a mut Map<String, Value>);
+
+impl Visit for Fields<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().into(), format!("{:?}", value).into());
+    }
+}
+
+#[tracing::instrument]
+fn charge(amount: u64) {
+    tracing::info!(amount, "charging");
+}
+
+fn main() {
+    let mut global = NewrAttributes::default();
+    global.insert("hostname", "web-1");
+
+    let newrelic = tracing_newrelic::layer_from_env()
+        .expect("invalid New Relic configuration")
+        .with_service_name("checkout")
+        .with_entity(Some("MXxBUE18QVBQTElDQVRJT058MTIz".into()), None, None)
+        .with_global_attributes(global);
+
+    let handle = newrelic.handle();
+
+    let fmt = tracing_subscriber::fmt::layer().event_format(JsonLines);
+
+    let subscriber = Registry::default().with(newrelic).with(fmt);
+
+    tracing::subscriber::set_global_default(subscriber)
+        .expect("failed to initilize tracing subscriber");
+
+    let span = tracing::info_span!("checkout");
+    let _span = span.enter();
+
+    // outside of the subscriber, e.g. for a serializer of your own
+    println!("{:?}", tracing_newrelic::linking_metadata());
+
+    charge(42);
+
+    drop(_span);
+    drop(span);
+
+    // the global subscriber is never dropped, so flush before exiting
+    if !handle.flush_timeout(Duration::from_secs(10)) {
+        eprintln!("failed to flush data to New Relic");
+    }
+}
@@ -0,0 +1,77 @@
+//! The recommended setup for production services
+//!
+//! ```sh
+//! NEW_RELIC_LICENSE_KEY=... NEW_RELIC_APP_NAME=checkout cargo run --example production
+//! ```
+
+use std::env::var;
+use std::thread::sleep;
+use std::time::Duration;
+
+use tracing_newrelic::synthetic::health_check;
+use tracing_newrelic::{Api, EmptyValuePolicy, ExportPolicy};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+#[tracing::instrument]
+fn handle_request(path: &str) {
+    tracing::info!("handling request");
+
+    sleep(Duration::from_millis(20));
+
+    if path == "/fail" {
+        tracing::error!("request failed");
+    }
+}
+
+fn main() {
+    env_logger::init();
+
+    let service_name =
+        var("NEW_RELIC_APP_NAME").unwrap_or_else(|_| env!("CARGO_PKG_NAME").to_string());
+    let hostname = var("HOSTNAME").unwrap_or_else(|_| "localhost".to_string());
+
+    // batch more, send less often
+    let api = Api::from_env()
+        .expect("invalid New Relic configuration")
+        .with_log_batch_size(100)
+        .with_trace_batch_size(100)
+        .with_log_flush_interval(Duration::from_secs(5))
+        .with_trace_flush_interval(Duration::from_secs(10));
+
+    let newrelic = match tracing_newrelic::try_layer(api) {
+        Ok(layer) => layer,
+        Err(err) => panic!("invalid New Relic configuration: {}", err),
+    };
+
+    let newrelic = newrelic
+        .with_service_name(service_name)
+        .with_sampling_ratio(0.5)
+        // keep every error and slow request, drop the rest
+        .with_export_policy(ExportPolicy::ErrorsOrSlowerThan(Duration::from_millis(500)))
+        .with_synthetic_detector(health_check)
+        .with_empty_value_policy(EmptyValuePolicy::Drop)
+        .with_shutdown_timeout(Duration::from_secs(10));
+
+    let handle = newrelic.handle();
+
+    let subscriber = Registry::default()
+        .with(newrelic)
+        .with(tracing_subscriber::fmt::layer());
+
+    tracing::subscriber::with_default(subscriber, || {
+        for path in ["/", "/fail", "/healthz"] {
+            let span = tracing::info_span!(
+                "request",
+                hostname = hostname.as_str(),
+                span.kind = "server",
+                name = path,
+            );
+            let _span = span.enter();
+
+            handle_request(path);
+        }
+    });
+
+    // the layer is dropped above, which flushes what's left within the shutdown timeout
+    println!("{:#?}", handle.stats().snapshot());
+}
@@ -0,0 +1,165 @@
+//! A gRPC client and server in one binary, the spans of the server continuing the traces of
+//! the client through the `traceparent` metadata
+//!
+//! The service is written by hand instead of generated by `tonic-build`, to keep the example
+//! free of a `build.rs`.
+
+use std::convert::Infallible;
+use std::future::{ready, Ready};
+use std::net::SocketAddr;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tonic::body::BoxBody;
+use tonic::codec::ProstCodec;
+use tonic::codegen::{http, Body, BoxFuture, StdError};
+use tonic::server::{Grpc, NamedService, UnaryService};
+use tonic::service::interceptor::InterceptedService;
+use tonic::transport::{Channel, Server};
+use tonic::{Request, Response, Status};
+use tower_service::Service;
+use tracing::{Instrument, Level};
+use tracing_newrelic::grpc::{self, GrpcSpanLayer};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct HelloRequest {
+    #[prost(string, tag = "1")]
+    name: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct HelloReply {
+    #[prost(string, tag = "1")]
+    message: String,
+}
+
+const SAY_HELLO: &str = "/demo.Greeter/SayHello";
+
+/// Greets by name, fails without one
+struct SayHello;
+
+impl UnaryService<HelloRequest> for SayHello {
+    type Response = HelloReply;
+    type Future = Ready<Result<Response<HelloReply>, Status>>;
+
+    fn call(&mut self, request: Request<HelloRequest>) -> Self::Future {
+        let name = request.into_inner().name;
+
+        tracing::info!(name = name.as_str(), "saying hello");
+
+        ready(if name.is_empty() {
+            Err(Status::internal("no name to greet"))
+        } else {
+            Ok(Response::new(HelloReply {
+                message: format!("Hello {}!", name),
+            }))
+        })
+    }
+}
+
+#[derive(Clone)]
+struct Greeter;
+
+impl NamedService for Greeter {
+    const NAME: &'static str = "demo.Greeter";
+}
+
+impl<B> Service<http::Request<B>> for Greeter
+where
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        if request.uri().path() != SAY_HELLO {
+            return Box::pin(async { Ok(Status::unimplemented("").to_http()) });
+        }
+
+        Box::pin(async move {
+            let mut grpc = Grpc::new(ProstCodec::default());
+            Ok(grpc.unary(SayHello, request).await)
+        })
+    }
+}
+
+async fn say_hello(channel: Channel, name: &str) -> Result<String, Status> {
+    let mut client = tonic::client::Grpc::new(InterceptedService::new(channel, grpc::inject));
+
+    client
+        .ready()
+        .await
+        .map_err(|error| Status::unavailable(error.to_string()))?;
+
+    let response: Response<HelloReply> = client
+        .unary(
+            Request::new(HelloRequest {
+                name: name.to_string(),
+            }),
+            http::uri::PathAndQuery::from_static(SAY_HELLO),
+            ProstCodec::default(),
+        )
+        .await?;
+
+    Ok(response.into_inner().message)
+}
+
+#[tokio::main]
+async fn main() {
+    let newrelic = tracing_newrelic::layer_from_env()
+        .expect("invalid New Relic configuration")
+        .with_service_name("tracing-newrelic-demo");
+
+    let handle = newrelic.handle();
+
+    let fmt = tracing_subscriber::fmt::layer();
+
+    let target = tracing_subscriber::filter::Targets::new()
+        .with_target("tonic", Level::INFO)
+        .with_target("tracing_newrelic", Level::INFO);
+
+    let subscriber = Registry::default().with(newrelic).with(fmt).with(target);
+
+    tracing::subscriber::set_global_default(subscriber)
+        .expect("failed to initilize tracing subscriber");
+
+    let addr: SocketAddr = "127.0.0.1:5556".parse().unwrap();
+
+    let server = tokio::spawn(
+        Server::builder()
+            .layer(GrpcSpanLayer::new())
+            .add_service(Greeter)
+            .serve(addr),
+    );
+
+    // give the server some time to start
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let channel = Channel::from_static("http://127.0.0.1:5556")
+        .connect()
+        .await
+        .expect("failed to connect to the server");
+
+    for name in ["Ferris", ""].iter() {
+        let span = tracing::info_span!("greet", span.kind = "client", name = "greet");
+
+        match say_hello(channel.clone(), name).instrument(span).await {
+            Ok(message) => println!("{}", message),
+            Err(status) => println!("failed to greet: {}", status),
+        }
+    }
+
+    server.abort();
+
+    // the global subscriber is never dropped, so flush before exiting
+    if !handle.flush().await {
+        eprintln!("failed to flush data to New Relic");
+    }
+}
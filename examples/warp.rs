@@ -1,4 +1,4 @@
-use std::{convert::Infallible, env, net::SocketAddr, time::Duration};
+use std::{convert::Infallible, net::SocketAddr, time::Duration};
 use tracing::{field::Empty, Level, Span};
 use tracing_subscriber::{layer::SubscriberExt, Registry};
 use warp::{http::StatusCode, Filter};
@@ -12,13 +12,13 @@ const HTML: &str = r#"<ul>
 </ul>"#;
 
 fn home() -> impl warp::Reply {
-    Span::current().record("name", &"GET /");
+    Span::current().record("name", "GET /");
 
     warp::reply::html(HTML)
 }
 
 async fn sleep(ms: u64) -> Result<impl warp::Reply, Infallible> {
-    Span::current().record("name", &"GET /sleep/:ms");
+    Span::current().record("name", "GET /sleep/:ms");
 
     tracing::info!(ms, "sleep {}ms", ms);
 
@@ -29,16 +29,18 @@ async fn sleep(ms: u64) -> Result<impl warp::Reply, Infallible> {
 
 fn not_found() -> impl warp::Reply {
     Span::current()
-        .record("name", &"not found")
-        .record("otel.status_code", &"ERROR")
-        .record("otel.status_description", &"not found");
+        .record("name", "not found")
+        .record("otel.status_code", "ERROR")
+        .record("otel.status_description", "not found");
 
     warp::reply::with_status(warp::reply::html(HTML), StatusCode::NOT_FOUND)
 }
 
 #[tokio::main]
 async fn main() {
-    let newrelic = tracing_newrelic::layer(env::var("API_KEY").expect("API_KEY not found"));
+    let newrelic = tracing_newrelic::layer_from_env().expect("invalid New Relic configuration");
+
+    let handle = newrelic.handle();
 
     let fmt = tracing_subscriber::fmt::layer();
 
@@ -70,5 +72,14 @@ async fn main() {
 
     println!("API server running at {}", addr);
 
-    warp::serve(routes).run(addr).await;
+    let (_, server) = warp::serve(routes).bind_with_graceful_shutdown(addr, async {
+        let _ = tokio::signal::ctrl_c().await;
+    });
+
+    server.await;
+
+    // the global subscriber is never dropped, so flush before exiting
+    if !handle.flush().await {
+        eprintln!("failed to flush data to New Relic");
+    }
 }
@@ -1,30 +1,183 @@
-use flate2::{write::GzEncoder, Compression};
+use futures_util::future::join_all;
 use futures_util::join;
-use reqwest::{
-    header::{CONTENT_ENCODING, CONTENT_TYPE},
-    Client, RequestBuilder,
+use http::header::{
+    HeaderMap, HeaderName, HeaderValue, CONTENT_ENCODING, CONTENT_TYPE, USER_AGENT,
 };
-use serde::Serialize;
-use std::cmp::max;
-use std::time::Duration;
+#[cfg(feature = "reqwest")]
+use reqwest::{Client, NoProxy, Proxy};
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+use serde_json::value::RawValue;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use std::sync::atomic::AtomicU8;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 use tokio::time::sleep;
 
-use super::types::{NewrLogs, NewrSpans};
+use super::backlog::Backlog;
+use super::breaker::{Admission, Breaker, CircuitBreaker};
+use super::capture::Capture;
+use super::compression::{Compression, Encoders};
+use super::error::{ConfigError, EnvError, ExportError};
+use super::metrics::Aggregator;
+#[cfg(feature = "otlp")]
+use super::otlp;
+use super::retry::RetryPolicy;
+use super::stats::{self, Stats};
+use super::stream::{Batching, Stream};
+#[cfg(not(feature = "reqwest"))]
+use super::transport::NoTransport;
+use super::transport::{HttpTransport, Rejection, TelemetryRequest};
+use super::types::{NewrCommon, NewrEvent, NewrLogs, NewrMetrics, NewrSpans};
+use super::utils::BoundedCache;
 
-#[derive(Clone)]
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
 /// Api Endpoint
 pub enum ApiEndpoint {
     /// United States, Default
+    #[default]
     US,
     /// European Union
     EU,
+    /// FedRAMP-authorized endpoints, for US government workloads
+    FedRamp,
     /// Custom
     Custom(String),
 }
 
-impl Default for ApiEndpoint {
+impl ApiEndpoint {
+    /// Url of the Log API
+    pub fn log_url(&self) -> String {
+        match self {
+            ApiEndpoint::US => "https://log-api.newrelic.com/log/v1".into(),
+            ApiEndpoint::EU => "https://log-api.eu.newrelic.com/log/v1".into(),
+            ApiEndpoint::FedRamp => "https://gov-log-api.newrelic.com/log/v1".into(),
+            ApiEndpoint::Custom(domain) => format!("{domain}/log/v1"),
+        }
+    }
+
+    /// Url of the Trace API
+    pub fn trace_url(&self) -> String {
+        match self {
+            ApiEndpoint::US => "https://trace-api.newrelic.com/trace/v1".into(),
+            ApiEndpoint::EU => "https://trace-api.eu.newrelic.com/trace/v1".into(),
+            ApiEndpoint::FedRamp => "https://gov-trace-api.newrelic.com/trace/v1".into(),
+            ApiEndpoint::Custom(domain) => format!("{domain}/trace/v1"),
+        }
+    }
+
+    /// Url of the Metric API
+    pub fn metric_url(&self) -> String {
+        match self {
+            ApiEndpoint::US => "https://metric-api.newrelic.com/metric/v1".into(),
+            ApiEndpoint::EU => "https://metric-api.eu.newrelic.com/metric/v1".into(),
+            ApiEndpoint::FedRamp => "https://gov-metric-api.newrelic.com/metric/v1".into(),
+            ApiEndpoint::Custom(domain) => format!("{domain}/metric/v1"),
+        }
+    }
+
+    /// Url of the Event API, for the given account
+    pub fn event_url(&self, account_id: u64) -> String {
+        match self {
+            ApiEndpoint::US => {
+                format!("https://insights-collector.newrelic.com/v1/accounts/{account_id}/events")
+            }
+            ApiEndpoint::EU => {
+                format!(
+                    "https://insights-collector.eu01.nr-data.net/v1/accounts/{account_id}/events"
+                )
+            }
+            ApiEndpoint::FedRamp => {
+                format!(
+                    "https://gov-insights-collector.newrelic.com/v1/accounts/{account_id}/events"
+                )
+            }
+            ApiEndpoint::Custom(domain) => format!("{domain}/v1/accounts/{account_id}/events"),
+        }
+    }
+
+    /// Url of the OTLP traces endpoint
+    #[cfg(feature = "otlp")]
+    pub fn otlp_trace_url(&self) -> String {
+        format!("{}/v1/traces", self.otlp_base())
+    }
+
+    /// Url of the OTLP logs endpoint
+    #[cfg(feature = "otlp")]
+    pub fn otlp_log_url(&self) -> String {
+        format!("{}/v1/logs", self.otlp_base())
+    }
+
+    #[cfg(feature = "otlp")]
+    fn otlp_base(&self) -> &str {
+        match self {
+            ApiEndpoint::US => "https://otlp.nr-data.net",
+            ApiEndpoint::EU => "https://otlp.eu01.nr-data.net",
+            ApiEndpoint::FedRamp => "https://gov-otlp.nr-data.net",
+            ApiEndpoint::Custom(domain) => domain,
+        }
+    }
+
+    /// Region of an account, given one of its keys: `EU` if the key starts with `eu0`, `US` otherwise
+    pub fn region_of(key: &str) -> ApiEndpoint {
+        if key.trim_start().starts_with("eu0") {
+            ApiEndpoint::EU
+        } else {
+            ApiEndpoint::US
+        }
+    }
+}
+
+/// Format of the logs and traces sent, see [`Api::with_export_format`]
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Payloads of the Log and Trace APIs, Default
+    #[default]
+    Native,
+    /// OTLP/JSON, sent to the OTLP endpoint of New Relic
+    ///
+    /// `service.name` and the other common attributes become resource attributes, `name`,
+    /// `span.kind`, `parent.id`, `otel.status_code` and `otel.status_description` become fields of
+    /// the span. Ids which aren't hex already are hashed into hex ones.
+    #[cfg(feature = "otlp")]
+    Otlp,
+}
+
+/// Key authenticating the requests
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Credential {
+    /// Insert or License key sent as `Api-Key`, Default
+    ApiKey(String),
+    /// License key sent as `X-License-Key`
+    LicenseKey(String),
+}
+
+impl Credential {
+    fn key(&self) -> &str {
+        match self {
+            Credential::ApiKey(key) | Credential::LicenseKey(key) => key,
+        }
+    }
+
+    fn key_mut(&mut self) -> &mut String {
+        match self {
+            Credential::ApiKey(key) | Credential::LicenseKey(key) => key,
+        }
+    }
+
+    fn header(&self) -> &'static str {
+        match self {
+            Credential::ApiKey(_) => "Api-Key",
+            Credential::LicenseKey(_) => "X-License-Key",
+        }
+    }
+}
+
+impl Default for Credential {
     fn default() -> Self {
-        ApiEndpoint::US
+        Credential::ApiKey(String::new())
     }
 }
 
@@ -34,111 +187,1450 @@ pub struct Api {
     pub log_endpoint: ApiEndpoint,
     /// Trace Api Endpoint
     pub trace_endpoint: ApiEndpoint,
-    /// Api Key
+    /// Metric Api Endpoint, see [`NewRelicLayer::with_metrics`]
+    ///
+    /// [`NewRelicLayer::with_metrics`]: crate::NewRelicLayer::with_metrics
+    pub metric_endpoint: ApiEndpoint,
+    /// Event Api Endpoint, see [`Api::with_account_id`]
+    pub event_endpoint: ApiEndpoint,
+    /// Key authenticating the requests
+    pub credential: Credential,
+    /// Api Key, overrides `credential` as a [`Credential::ApiKey`] if not empty
+    #[deprecated(note = "use `credential` instead")]
     pub key: String,
     /// Http Client
+    #[cfg(feature = "reqwest")]
     pub client: Client,
     /// Batch request size
     pub batch_size: usize,
 
-    logs_queue: Vec<NewrLogs>,
-    spans_queue: Vec<NewrSpans>,
+    log_batch_size: Option<usize>,
+    trace_batch_size: Option<usize>,
+    log_flush_interval: Option<Duration>,
+    trace_flush_interval: Option<Duration>,
+    traces_before_logs: bool,
+    logs_hold_timeout: Duration,
+    next_token: u64,
+    // tasks draining the logs and spans queues, spawned on first push
+    streams: Option<Streams>,
+    // resolves the accounts traces are routed to, see `with_account_router`
+    router: Option<Arc<Router>>,
+    // queues of the accounts traces have been routed to lately, with when they were last used
+    routes: BoundedCache<String, (Streams, Instant)>,
+    // accounts the router couldn't resolve, with when it was asked
+    unknown_accounts: BoundedCache<String, Instant>,
+    route_idle_timeout: Duration,
+    // released once a trace has been sent, instead of when the worker receives it
+    pub(crate) backlog: Option<Arc<Backlog>>,
+    // span durations summarized by the layer, sent once per `metrics_interval`
+    pub(crate) metrics: Arc<Aggregator>,
+    metrics_interval: Duration,
+    account_id: Option<u64>,
+    events_as_logs: bool,
+    // whether events have been kept as logs for a lack of account id already
+    warned_no_account: bool,
+    pub(crate) capture: Arc<Mutex<Option<Capture>>>,
+    // key set by `Handle::set_api_key`, replacing the one of `credential`
+    pub(crate) rotated_key: Arc<RwLock<Option<String>>>,
+    pub(crate) stats: Arc<Stats>,
+    error_handler: Option<Arc<ErrorHandler>>,
+    retry_policy: RetryPolicy,
+    circuit_breaker: Option<CircuitBreaker>,
+    requeue_max_age: Option<Duration>,
+    max_queue_bytes: Option<usize>,
+    max_payload_bytes: usize,
+    request_timeout: Duration,
+    compression: Compression,
+    client_options: ClientOptions,
+    // reported by `validate`, the previous client is kept meanwhile
+    client_error: Option<ConfigError>,
+    // sends the requests instead of `client`, see `with_blocking`
+    #[cfg(feature = "blocking")]
+    agent: Option<ureq::Agent>,
+    // sends the requests instead of `client` and `agent`, see `with_transport`
+    transport: Option<Arc<dyn HttpTransport>>,
+    headers: HeaderMap,
+    infer_region: bool,
+    max_concurrent_requests: usize,
+    format: ExportFormat,
+}
+
+type ErrorHandler = dyn Fn(ExportError) + Send + Sync;
+
+type Router = dyn Fn(&str) -> Option<ApiKeyRoute> + Send + Sync;
+
+/// Where the traces of an account are sent, see [`Api::with_account_router`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ApiKeyRoute {
+    /// Key of the account
+    pub credential: Credential,
+    /// Endpoint of the logs, traces and events of the account, the configured ones if `None`
+    pub endpoint: Option<ApiEndpoint>,
+}
+
+impl From<&str> for ApiKeyRoute {
+    fn from(key: &str) -> Self {
+        ApiKeyRoute {
+            credential: Credential::ApiKey(key.to_string()),
+            endpoint: None,
+        }
+    }
+}
+
+impl From<String> for ApiKeyRoute {
+    fn from(key: String) -> Self {
+        ApiKeyRoute {
+            credential: Credential::ApiKey(key),
+            endpoint: None,
+        }
+    }
+}
+
+struct Streams {
+    logs: Arc<Stream<NewrLogs>>,
+    spans: Stream<NewrSpans>,
+    metrics: Stream<NewrMetrics>,
+    events: Stream<NewrEvent>,
+}
+
+impl Streams {
+    /// Send every queue, returning whether every payload has been accepted
+    async fn flush(&self, traces_before_logs: bool) -> bool {
+        let (metrics_succeeded, events_succeeded) =
+            join!(self.metrics.flush(), self.events.flush());
+
+        if traces_before_logs {
+            let spans_succeeded = self.spans.flush().await;
+            // failed traces are dropped or sent again later, send the remaining logs anyway
+            let logs_succeeded = self.logs.release_all_and_flush().await;
+            return spans_succeeded && logs_succeeded && metrics_succeeded && events_succeeded;
+        }
+
+        let (logs_succeeded, spans_succeeded) = join!(self.logs.flush(), self.spans.flush());
+
+        logs_succeeded && spans_succeeded && metrics_succeeded && events_succeeded
+    }
+}
+
+impl Api {
+    /// Normalize the configuration and check it for common mistakes
+    pub(crate) fn validate(&mut self) -> Result<(), ConfigError> {
+        if let Some(err) = self.client_error.take() {
+            return Err(err);
+        }
+
+        #[cfg(not(feature = "reqwest"))]
+        if self.http_client_missing() {
+            return Err(ConfigError::MissingTransport);
+        }
+
+        self.credential = self.credential();
+
+        #[allow(deprecated)]
+        self.key.clear();
+
+        let key = self.credential.key().trim();
+
+        if key.len() != self.credential.key().len() {
+            *self.credential.key_mut() = key.to_string();
+        }
+
+        let key = self.credential.key();
+
+        if key.is_empty() {
+            return Err(ConfigError::EmptyApiKey);
+        }
+
+        if key.contains(char::is_whitespace) {
+            return Err(ConfigError::ApiKeyWhitespace);
+        }
+
+        for (field, endpoint) in [
+            ("log_endpoint", &self.log_endpoint),
+            ("trace_endpoint", &self.trace_endpoint),
+            ("metric_endpoint", &self.metric_endpoint),
+            ("event_endpoint", &self.event_endpoint),
+        ] {
+            if let ApiEndpoint::Custom(endpoint) = endpoint {
+                if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
+                    return Err(ConfigError::EndpointMissingScheme {
+                        field,
+                        endpoint: endpoint.clone(),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read the configuration from the standard New Relic environment variables
+    ///
+    /// - `NEW_RELIC_API_KEY` or `NEW_RELIC_LICENSE_KEY`, the former takes precedence
+    /// - `NEW_RELIC_REGION`, `US`, `EU` or `FedRAMP`, optional
+    /// - `NEW_RELIC_LOG_ENDPOINT`, `NEW_RELIC_TRACE_ENDPOINT`, `NEW_RELIC_METRIC_ENDPOINT` and
+    ///   `NEW_RELIC_EVENT_ENDPOINT`, urls of custom endpoints, optional
+    /// - `NEW_RELIC_ACCOUNT_ID`, see [`Api::with_account_id`], optional
+    ///
+    /// Without `NEW_RELIC_REGION`, the region is inferred from the key, see [`Api::infer_region`].
+    pub fn from_env() -> Result<Api, EnvError> {
+        let credential = match (var("NEW_RELIC_API_KEY"), var("NEW_RELIC_LICENSE_KEY")) {
+            (Some(key), _) => Credential::ApiKey(key),
+            (None, Some(key)) => Credential::LicenseKey(key),
+            (None, None) => return Err(EnvError::MissingKey),
+        };
+
+        let mut api = Api::from(credential);
+
+        if let Some(region) = var("NEW_RELIC_REGION") {
+            let endpoint = match region.to_ascii_uppercase().as_str() {
+                "US" => ApiEndpoint::US,
+                "EU" => ApiEndpoint::EU,
+                "FEDRAMP" => ApiEndpoint::FedRamp,
+                _ => {
+                    return Err(EnvError::Malformed {
+                        variable: "NEW_RELIC_REGION",
+                        value: region,
+                        expected: "US, EU or FedRAMP",
+                    })
+                }
+            };
+
+            api.log_endpoint = endpoint.clone();
+            api.trace_endpoint = endpoint.clone();
+            api.metric_endpoint = endpoint.clone();
+            api.event_endpoint = endpoint;
+            api.infer_region = false;
+        }
+
+        for variable in [
+            "NEW_RELIC_LOG_ENDPOINT",
+            "NEW_RELIC_TRACE_ENDPOINT",
+            "NEW_RELIC_METRIC_ENDPOINT",
+            "NEW_RELIC_EVENT_ENDPOINT",
+        ] {
+            if let Some(url) = var(variable) {
+                if !url.starts_with("http://") && !url.starts_with("https://") {
+                    return Err(EnvError::Malformed {
+                        variable,
+                        value: url,
+                        expected: "an url starting with http:// or https://",
+                    });
+                }
+
+                let endpoint = ApiEndpoint::Custom(url.trim_end_matches('/').to_string());
+
+                match variable {
+                    "NEW_RELIC_LOG_ENDPOINT" => api.log_endpoint = endpoint,
+                    "NEW_RELIC_TRACE_ENDPOINT" => api.trace_endpoint = endpoint,
+                    "NEW_RELIC_METRIC_ENDPOINT" => api.metric_endpoint = endpoint,
+                    _ => api.event_endpoint = endpoint,
+                }
+            }
+        }
+
+        if let Some(id) = var("NEW_RELIC_ACCOUNT_ID") {
+            match id.parse() {
+                Ok(id) => api.account_id = Some(id),
+                Err(_) => {
+                    return Err(EnvError::Malformed {
+                        variable: "NEW_RELIC_ACCOUNT_ID",
+                        value: id,
+                        expected: "a numeric account id",
+                    })
+                }
+            }
+        }
+
+        Ok(api)
+    }
+
+    /// Set the batch request size of logs, overriding `batch_size`
+    pub fn with_log_batch_size(mut self, size: usize) -> Self {
+        self.log_batch_size = Some(size);
+        self
+    }
+
+    /// Set the batch request size of traces, overriding `batch_size`
+    pub fn with_trace_batch_size(mut self, size: usize) -> Self {
+        self.trace_batch_size = Some(size);
+        self
+    }
+
+    /// Flush queued logs at least once per `interval`, even if the batch isn't full
+    pub fn with_log_flush_interval(mut self, interval: Duration) -> Self {
+        self.log_flush_interval = Some(interval);
+        self
+    }
+
+    /// Flush queued traces at least once per `interval`, even if the batch isn't full
+    pub fn with_trace_flush_interval(mut self, interval: Duration) -> Self {
+        self.trace_flush_interval = Some(interval);
+        self
+    }
+
+    /// Set how often span durations are sent as metrics, see [`NewRelicLayer::with_metrics`].
+    /// Default to 10 seconds.
+    ///
+    /// [`NewRelicLayer::with_metrics`]: crate::NewRelicLayer::with_metrics
+    pub fn with_metrics_interval(mut self, interval: Duration) -> Self {
+        self.metrics_interval = interval;
+        self
+    }
+
+    /// Send the events recorded with a `newrelic.event_type` field as custom events of the given
+    /// account, through the Event API, instead of as logs
+    ///
+    /// E.g. `tracing::info!(newrelic.event_type = "OrderPlaced", order_id = 123)` inside a span is sent
+    /// as an `OrderPlaced` event along with its trace, carrying the fields of the event and the
+    /// common attributes of the trace. Without an account id, such events are sent as logs.
+    pub fn with_account_id(mut self, account_id: u64) -> Self {
+        self.account_id = Some(account_id);
+        self
+    }
+
+    /// Send custom events as logs as well, see [`Api::with_account_id`]. Default to `false`.
+    pub fn with_events_as_logs(mut self, enabled: bool) -> Self {
+        self.events_as_logs = enabled;
+        self
+    }
+
+    /// Set how long a request can take before it's retried. Default to 10 seconds.
+    ///
+    /// Applies to a custom `client` as well.
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Set how long connecting to New Relic can take before the request is retried. Default to 5 seconds.
+    ///
+    /// This builds a new `client`, replacing a custom one.
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.client_options.connect_timeout = timeout;
+        self.rebuild_client()
+    }
+
+    /// Send requests to both the Log and Trace APIs through the proxy at `url`, e.g. `http://proxy:3128`
+    ///
+    /// Hosts listed in the `NO_PROXY` environment variable are still reached directly. Without it, the
+    /// proxy is taken from the `HTTP_PROXY` and `HTTPS_PROXY` environment variables, if set.
+    ///
+    /// This builds a new `client`, replacing a custom one. An invalid url is reported by [`try_layer`].
+    ///
+    /// [`try_layer`]: crate::try_layer
+    pub fn with_proxy(mut self, url: impl Into<String>) -> Self {
+        self.client_options.proxy = Some(url.into());
+        self.rebuild_client()
+    }
+
+    /// Trust an additional PEM-encoded root certificate, e.g. of a TLS-intercepting proxy
+    ///
+    /// This builds a new `client`, replacing a custom one. An invalid certificate is reported by [`try_layer`].
+    ///
+    /// [`try_layer`]: crate::try_layer
+    #[cfg(any(feature = "tls-rustls", feature = "tls-native"))]
+    pub fn with_ca_certificate(mut self, pem: impl Into<Vec<u8>>) -> Self {
+        self.client_options.ca_certificates.push(pem.into());
+        self.rebuild_client()
+    }
+
+    #[cfg_attr(not(any(feature = "reqwest", feature = "blocking")), allow(unused_mut))]
+    fn rebuild_client(mut self) -> Self {
+        #[cfg(feature = "reqwest")]
+        match build_client(&self.client_options) {
+            Ok(client) => self.client = client,
+            Err(err) => self.client_error = Some(err),
+        }
+
+        #[cfg(feature = "blocking")]
+        if self.agent.is_some() {
+            self = self.with_blocking(true);
+        }
+
+        self
+    }
+
+    /// Send requests synchronously with [`ureq`](https://docs.rs/ureq), instead of `client`.
+    /// Default to `false`.
+    ///
+    /// Requests are sent one at a time from the worker thread, with the same batching, retries
+    /// and rate limiting. Useful for short-lived tools, which don't need reqwest's async stack.
+    /// Don't combine it with [`layer_on_runtime`], each request would block a thread of the runtime.
+    ///
+    /// The connect timeout and the proxy apply, additional CA certificates don't.
+    ///
+    /// [`layer_on_runtime`]: crate::layer_on_runtime
+    #[cfg(feature = "blocking")]
+    pub fn with_blocking(mut self, enabled: bool) -> Self {
+        if !enabled {
+            self.agent = None;
+            return self;
+        }
+
+        let mut builder = ureq::AgentBuilder::new()
+            .timeout_connect(self.client_options.connect_timeout)
+            .try_proxy_from_env(true);
+
+        if let Some(url) = &self.client_options.proxy {
+            match ureq::Proxy::new(url) {
+                Ok(proxy) => builder = builder.proxy(proxy),
+                Err(_) => {
+                    self.client_error = Some(ConfigError::InvalidProxy { proxy: url.clone() })
+                }
+            }
+        }
+
+        self.agent = Some(builder.build());
+        self
+    }
+
+    /// Send requests with a custom client, e.g. with its own TLS settings
+    ///
+    /// The connect timeout is up to the client, the request timeout still applies.
+    #[cfg(feature = "reqwest")]
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Send requests through a custom http stack, instead of `client` or the blocking agent
+    ///
+    /// Required without the `reqwest` feature. The batching, retries and rate limiting stay the
+    /// same, the connect timeout and the proxy are up to the transport.
+    pub fn with_transport(mut self, transport: impl HttpTransport) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// Add a header to every request, e.g. for a gateway set as [`ApiEndpoint::Custom`]
+    ///
+    /// Replaces the default `User-Agent`, `tracing-newrelic/<version>`, if named so. Headers required by
+    /// New Relic, like `Api-Key`, `X-License-Key` or `Content-Type`, and invalid ones are ignored with
+    /// a warning.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        let header = HeaderName::from_bytes(name.as_bytes())
+            .ok()
+            .zip(HeaderValue::from_str(value).ok());
+
+        match header {
+            Some((name, _)) if is_reserved(&name) => {
+                log::warn!("ignoring header {}, it's set by tracing-newrelic", name);
+            }
+            Some((name, value)) => {
+                self.headers.insert(name, value);
+            }
+            None => log::warn!("ignoring invalid header {}", name),
+        }
+
+        self
+    }
+
+    /// Set how many batches of logs, and of traces, can be sent at the same time. Default to `2`.
+    ///
+    /// A `429` response pauses every request to its endpoint until the `retry-after` delay is over.
+    pub fn with_max_concurrent_requests(mut self, max: usize) -> Self {
+        self.max_concurrent_requests = max.max(1);
+        self
+    }
+
+    /// Set how failed requests are retried. Default to [`RetryPolicy::default`].
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Set when to stop sending to an endpoint that keeps failing, `None` to never stop.
+    /// Default to [`CircuitBreaker::default`].
+    ///
+    /// The state of each circuit is exposed by [`Stats`].
+    pub fn with_circuit_breaker(mut self, breaker: Option<CircuitBreaker>) -> Self {
+        self.circuit_breaker = breaker;
+        self
+    }
+
+    /// Set the format of the logs and traces sent. Default to [`ExportFormat::Native`].
+    ///
+    /// Metrics and custom events are sent to their own APIs either way.
+    pub fn with_export_format(mut self, format: ExportFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set how request bodies are compressed. Default to [`Compression::default`].
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Keep data failing with a transient error, e.g. a `5xx` response or a timeout, to send it
+    /// again with the next batch, until it's older than `max_age`. Default to `None`, dropping it.
+    ///
+    /// Data explicitly rejected by New Relic is always dropped. The data kept counts toward
+    /// the queue capacity, see [`NewRelicLayer::with_queue_capacity`].
+    ///
+    /// [`NewRelicLayer::with_queue_capacity`]: crate::NewRelicLayer::with_queue_capacity
+    pub fn with_requeue_max_age(mut self, max_age: Option<Duration>) -> Self {
+        self.requeue_max_age = max_age;
+        self
+    }
+
+    /// Cap the memory taken by each queue of data waiting to be sent, `None` for no cap.
+    /// Default to 64 MB.
+    ///
+    /// The size of the data is approximated by the length of its JSON. Once a queue is over
+    /// `max_bytes`, e.g. while New Relic keeps replying `429`, the oldest data is dropped, and
+    /// reported to the error handler. Traces containing an error, with a span whose
+    /// `otel.status_code` is `ERROR` or an `ERROR` log, are sent ahead of the others and
+    /// dropped last.
+    pub fn with_max_queue_bytes(mut self, max_bytes: Option<usize>) -> Self {
+        self.max_queue_bytes = max_bytes;
+        self
+    }
+
+    /// Split requests so that their uncompressed body stays under `max_bytes`. Default to 800 KB.
+    ///
+    /// A trace too big on its own is split into several payloads carrying the same common
+    /// attributes. A `413` response still halves the request, in case the limit is too high.
+    pub fn with_max_payload_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_payload_bytes = max_bytes;
+        self
+    }
+
+    /// Counters of the data sent through this `Api`, e.g. to one destination of a [`TeeExporter`]
+    ///
+    /// The same as [`Handle::stats`] when the layer is created from this `Api`.
+    ///
+    /// [`TeeExporter`]: crate::TeeExporter
+    /// [`Handle::stats`]: crate::Handle::stats
+    pub fn stats(&self) -> Arc<Stats> {
+        self.stats.clone()
+    }
+
+    /// Send the traces of some accounts with their own key, e.g. to the New Relic account of
+    /// each tenant of a platform
+    ///
+    /// The account of a trace is the `newrelic.account` field of its root span, a string. It's
+    /// resolved by `router` once per account, into the key and optionally the endpoint to send
+    /// the trace to. Each account has its own queues, retries and rate limiting. Traces without
+    /// the field are sent as usual, traces whose account can't be resolved are dropped.
+    ///
+    /// The queues of an account are sent and closed once it's unused for the idle timeout, or
+    /// to make room for another one, see [`Api::with_account_routes_limits`]. Accounts that
+    /// can't be resolved are asked for again after the idle timeout only.
+    ///
+    /// Custom events of routed traces are sent as logs, metrics are sent as usual.
+    pub fn with_account_router<F>(mut self, router: F) -> Self
+    where
+        F: Fn(&str) -> Option<ApiKeyRoute> + Send + Sync + 'static,
+    {
+        self.router = Some(Arc::new(router));
+        self
+    }
+
+    /// Set how many accounts can have their own queues at once, and how long they're kept unused,
+    /// see [`Api::with_account_router`]. Default to `64` and 5 minutes.
+    ///
+    /// Idle accounts are checked once per metrics interval, see [`Api::with_metrics_interval`].
+    pub fn with_account_routes_limits(mut self, capacity: usize, idle_timeout: Duration) -> Self {
+        self.routes = BoundedCache::new(capacity);
+        self.route_idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Call `handler` from the worker thread whenever data is dropped for good
+    ///
+    /// That is when New Relic rejects a request, e.g. `403` for an invalid key, or when
+    /// a request runs out of retries.
+    pub fn with_error_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(ExportError) + Send + Sync + 'static,
+    {
+        self.error_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Send the logs of a trace only once its spans have been accepted, or after the hold
+    /// timeout has elapsed. Default to `false`.
+    ///
+    /// This avoids logs showing up in New Relic linked to a trace which hasn't arrived yet.
+    pub fn with_traces_before_logs(mut self, enabled: bool) -> Self {
+        self.traces_before_logs = enabled;
+        self
+    }
+
+    /// Set how long logs can be held back waiting for their trace. Default to 10 seconds.
+    pub fn with_logs_hold_timeout(mut self, timeout: Duration) -> Self {
+        self.logs_hold_timeout = timeout;
+        self
+    }
+
+    /// The credential in use, taking the deprecated `key` into account
+    #[allow(deprecated)]
+    fn credential(&self) -> Credential {
+        if self.key.is_empty() {
+            self.credential.clone()
+        } else {
+            Credential::ApiKey(self.key.clone())
+        }
+    }
+
+    /// Send data of EU accounts to the EU endpoints, even if `US` is configured. Default to `true`.
+    ///
+    /// The region is inferred from the key with [`ApiEndpoint::region_of`], other endpoints are left as is.
+    pub fn infer_region(mut self, enabled: bool) -> Self {
+        self.infer_region = enabled;
+        self
+    }
+
+    /// The endpoint in use, taking the region of the key into account
+    fn endpoint(&self, endpoint: &ApiEndpoint, credential: &Credential) -> ApiEndpoint {
+        match endpoint {
+            ApiEndpoint::US if self.infer_region => ApiEndpoint::region_of(credential.key()),
+            endpoint => endpoint.clone(),
+        }
+    }
+
+    #[cfg(not(feature = "reqwest"))]
+    fn http_client_missing(&self) -> bool {
+        #[cfg(feature = "blocking")]
+        if self.agent.is_some() {
+            return false;
+        }
+
+        self.transport.is_none()
+    }
+
+    /// The transport sending the requests, in order of precedence
+    fn http_client(&self) -> Arc<dyn HttpTransport> {
+        if let Some(transport) = &self.transport {
+            return transport.clone();
+        }
+
+        #[cfg(feature = "blocking")]
+        if let Some(agent) = &self.agent {
+            return Arc::new(agent.clone());
+        }
+
+        #[cfg(feature = "reqwest")]
+        return Arc::new(self.client.clone());
+
+        #[cfg(not(feature = "reqwest"))]
+        Arc::new(NoTransport)
+    }
+
+    /// What's needed to send requests, without the queues
+    pub(crate) fn transport(&self) -> Transport {
+        let mut transport = self.transport_with(self.credential(), None);
+        transport.rotated_key = Some(self.rotated_key.clone());
+        transport
+    }
+
+    /// What's needed to send requests with the given credential, to `endpoint` if any instead of
+    /// the configured ones, except for metrics
+    fn transport_with(&self, credential: Credential, endpoint: Option<&ApiEndpoint>) -> Transport {
+        Transport {
+            log_endpoint: self.endpoint(endpoint.unwrap_or(&self.log_endpoint), &credential),
+            trace_endpoint: self.endpoint(endpoint.unwrap_or(&self.trace_endpoint), &credential),
+            metric_endpoint: self.endpoint(&self.metric_endpoint, &credential),
+            event_endpoint: self.endpoint(endpoint.unwrap_or(&self.event_endpoint), &credential),
+            account_id: self.account_id.unwrap_or_default(),
+            credential,
+            rotated_key: None,
+            client: self.http_client(),
+            capture: self.capture.clone(),
+            stats: self.stats.clone(),
+            error_handler: self.error_handler.clone(),
+            retry_policy: self.retry_policy.clone(),
+            max_payload_bytes: self.max_payload_bytes,
+            request_timeout: self.request_timeout,
+            encoders: Encoders::new(self.compression, self.max_payload_bytes),
+            format: self.format,
+            headers: self.headers.clone(),
+            logs_paused_until: Mutex::new(None),
+            spans_paused_until: Mutex::new(None),
+            metrics_paused_until: Mutex::new(None),
+            events_paused_until: Mutex::new(None),
+            logs_breaker: Breaker::new(self.circuit_breaker.clone(), Kind::Logs.name()),
+            spans_breaker: Breaker::new(self.circuit_breaker.clone(), Kind::Spans.name()),
+            metrics_breaker: Breaker::new(self.circuit_breaker.clone(), Kind::Metrics.name()),
+            events_breaker: Breaker::new(self.circuit_breaker.clone(), Kind::Events.name()),
+        }
+    }
+
+    /// Spawn the tasks draining the queues, logs, spans, metrics and events are sent independently
+    /// of each other
+    ///
+    /// Must be called from within the worker runtime.
+    fn streams(&mut self) -> &Streams {
+        if self.streams.is_none() {
+            self.streams = Some(self.spawn_streams(self.transport()));
+        }
+
+        self.streams.as_ref().unwrap()
+    }
+
+    /// The queues of the traces routed to `account`, see [`Api::with_account_router`]
+    ///
+    /// `None` if the account can't be resolved.
+    fn routed_streams(&mut self, account: &str) -> Option<&Streams> {
+        let now = Instant::now();
+
+        if self.routes.get(account).is_none() {
+            let timeout = self.route_idle_timeout;
+
+            if let Some(asked_at) = self.unknown_accounts.get(account) {
+                if asked_at.elapsed() < timeout {
+                    return None;
+                }
+            }
+
+            let route = self.router.as_ref().and_then(|router| router(account));
+
+            let Some(route) = route else {
+                self.unknown_accounts.insert(account.to_string(), now);
+                log::warn!("no route to account {}, dropping its traces", account);
+                return None;
+            };
+
+            let transport = self.transport_with(route.credential, route.endpoint.as_ref());
+            let streams = self.spawn_streams(transport);
+
+            // the streams of the evicted account, if any, send their queues before ending
+            if self.routes.insert(account.to_string(), (streams, now)) {
+                log::debug!("too many accounts routed, closed the least recently used one");
+            }
+        }
+
+        let (streams, used_at) = self.routes.get_mut(account)?;
+        *used_at = now;
+        Some(streams)
+    }
+
+    /// Close the queues of the accounts unused for the idle timeout, once they're sent
+    pub(crate) fn close_idle_routes(&mut self) {
+        let timeout = self.route_idle_timeout;
+
+        self.routes
+            .retain(|_, (_, used_at)| used_at.elapsed() < timeout);
+    }
+
+    /// Spawn the tasks draining the queues of one destination
+    ///
+    /// Must be called from within the worker runtime.
+    fn spawn_streams(&self, transport: Transport) -> Streams {
+        let transport = Arc::new(transport);
+
+        // tokens sent by one stream and still waiting for the other
+        let sent_once = Arc::new(Mutex::new(HashSet::new()));
+
+        let on_sent = {
+            let backlog = self.backlog.clone();
+
+            move |sent: &[(u64, bool)]| {
+                let Some(backlog) = &backlog else {
+                    return;
+                };
+
+                let mut sent_once = sent_once.lock().unwrap();
+
+                // a trace leaves the backlog once both its logs and spans are sent
+                for (token, _) in sent {
+                    if !sent_once.remove(token) {
+                        sent_once.insert(*token);
+                    } else {
+                        backlog.release();
+                    }
+                }
+            }
+        };
+
+        let logs = Arc::new(Stream::spawn(
+            transport.clone(),
+            Batching {
+                batch_size: self.log_batch_size.unwrap_or(self.batch_size),
+                flush_interval: self.log_flush_interval,
+                hold_timeout: Some(self.logs_hold_timeout).filter(|_| self.traces_before_logs),
+                concurrency: self.max_concurrent_requests,
+                requeue_max_age: self.requeue_max_age,
+                max_queue_bytes: self.max_queue_bytes,
+            },
+            on_sent.clone(),
+        ));
+
+        let spans = Stream::spawn(
+            transport.clone(),
+            Batching {
+                batch_size: self.trace_batch_size.unwrap_or(self.batch_size),
+                flush_interval: self.trace_flush_interval,
+                hold_timeout: None,
+                concurrency: self.max_concurrent_requests,
+                requeue_max_age: self.requeue_max_age,
+                max_queue_bytes: self.max_queue_bytes,
+            },
+            {
+                let logs = logs.clone();
+
+                move |sent: &[(u64, bool)]| {
+                    // release the logs whose traces have been accepted
+                    logs.release(
+                        sent.iter()
+                            .filter(|(_, accepted)| *accepted)
+                            .map(|(token, _)| *token)
+                            .collect(),
+                    );
+                    on_sent(sent);
+                }
+            },
+        );
+
+        let events = Stream::spawn(
+            transport.clone(),
+            Batching {
+                batch_size: self.log_batch_size.unwrap_or(self.batch_size),
+                flush_interval: self.log_flush_interval,
+                hold_timeout: None,
+                concurrency: self.max_concurrent_requests,
+                requeue_max_age: self.requeue_max_age,
+                max_queue_bytes: self.max_queue_bytes,
+            },
+            |_: &[(u64, bool)]| {},
+        );
+
+        // metrics are summarized already, each window is sent as soon as it's closed
+        let metrics = Stream::spawn(
+            transport,
+            Batching {
+                batch_size: 1,
+                flush_interval: None,
+                hold_timeout: None,
+                concurrency: self.max_concurrent_requests,
+                requeue_max_age: self.requeue_max_age,
+                max_queue_bytes: self.max_queue_bytes,
+            },
+            |_: &[(u64, bool)]| {},
+        );
+
+        Streams {
+            logs,
+            spans,
+            metrics,
+            events,
+        }
+    }
+
+    pub(crate) fn push(&mut self, mut logs: NewrLogs, traces: NewrSpans) {
+        let token = self.next_token;
+        self.next_token += 1;
+
+        let held = self.traces_before_logs;
+        let priority = traces.common.priority;
+
+        if let Some(account) = traces.common.account.clone() {
+            let Some(streams) = self.routed_streams(&account) else {
+                let reason = format!("no route to account {}", account);
+                let handler = self.error_handler.as_deref();
+                // the endpoints of the account aren't known, the configured ones stand for them
+                let (trace_url, log_url) =
+                    (self.trace_endpoint.trace_url(), self.log_endpoint.log_url());
+
+                report_dropped(
+                    &self.stats,
+                    handler,
+                    &[traces],
+                    trace_url,
+                    None,
+                    reason.clone(),
+                    None,
+                );
+                report_dropped(&self.stats, handler, &[logs], log_url, None, reason, None);
+
+                if let Some(backlog) = &self.backlog {
+                    backlog.release();
+                }
+                return;
+            };
+
+            // events are sent as logs, the account id of the route isn't known
+            streams.spans.push(token, traces, false, priority);
+            streams.logs.push(token, logs, held, priority);
+            return;
+        }
+
+        let events = self.take_events(&mut logs);
+
+        let streams = self.streams();
+
+        streams.spans.push(token, traces, false, priority);
+        streams.logs.push(token, logs, held, priority);
+
+        for event in events {
+            streams.events.push(token, event, false, priority);
+        }
+    }
+
+    /// Take the custom events out of the logs, unless they are to be sent as logs as well
+    fn take_events(&mut self, logs: &mut NewrLogs) -> Vec<NewrEvent> {
+        let common = &logs.common;
+        let mut events = Vec::new();
+
+        if self.account_id.is_none() {
+            if !self.warned_no_account
+                && logs
+                    .logs
+                    .iter()
+                    .any(|log| NewrEvent::from_log(log, common).is_some())
+            {
+                self.warned_no_account = true;
+                log::warn!("no account id set, sending events with newrelic.event_type as logs");
+            }
+
+            return events;
+        }
+
+        let as_logs = self.events_as_logs;
+
+        logs.logs
+            .retain(|log| match NewrEvent::from_log(log, common) {
+                Some(event) => {
+                    events.push(event);
+                    as_logs
+                }
+                None => true,
+            });
+
+        events
+    }
+
+    /// Close the current window of metrics and queue it
+    pub(crate) fn push_metrics(&mut self) {
+        let payloads = self.metrics.take();
+
+        if payloads.is_empty() {
+            return;
+        }
+
+        let streams = self.streams();
+
+        for payload in payloads {
+            streams.metrics.push(0, payload, false, false);
+        }
+    }
+
+    /// How often metrics are queued
+    pub(crate) fn metrics_interval(&self) -> Duration {
+        self.metrics_interval
+    }
+
+    /// Number of traces queued and not accepted yet
+    pub(crate) fn pending(&self) -> usize {
+        self.streams
+            .iter()
+            .chain(self.routes.values().map(|(streams, _)| streams))
+            .map(|streams| streams.spans.pending())
+            .sum()
+    }
+
+    /// Send every queue and stop the tasks draining them
+    pub(crate) async fn shutdown(&mut self) {
+        self.flush().await;
+        self.streams = None;
+        self.routes.clear();
+    }
+
+    /// Send every queue, along with the current window of metrics, returning whether every
+    /// payload has been accepted
+    pub(crate) async fn flush(&mut self) -> bool {
+        self.push_metrics();
+
+        let traces_before_logs = self.traces_before_logs;
+
+        let flushed = join_all(
+            self.streams
+                .iter()
+                .chain(self.routes.values().map(|(streams, _)| streams))
+                .map(|streams| streams.flush(traces_before_logs)),
+        )
+        .await;
+
+        flushed.into_iter().all(|succeeded| succeeded)
+    }
+
+    /// Send the payloads of a file written by [`FileExporter`], e.g. on another host
+    ///
+    /// The file is read line by line and payloads are sent in batches of `batch_size` as they
+    /// accumulate, retried like any other request. Lines that can't be parsed, or aren't valid
+    /// UTF-8, are skipped with a warning. Returns the number of lines replayed.
+    ///
+    /// [`FileExporter`]: crate::FileExporter
+    pub async fn replay_file(&self, path: impl AsRef<Path>) -> io::Result<usize> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let transport = self.transport();
+        let batch_size = self.batch_size.max(1);
+
+        let mut line = Vec::new();
+        let mut logs = Vec::new();
+        let mut spans = Vec::new();
+        let mut replayed = 0;
+        let mut index = 0;
+
+        loop {
+            line.clear();
+
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+
+            index += 1;
+
+            let parsed = match std::str::from_utf8(&line) {
+                Ok(line) if line.trim().is_empty() => continue,
+                Ok(line) => serde_json::from_str::<RawPayload>(line).map_err(|err| err.to_string()),
+                Err(err) => Err(err.to_string()),
+            };
+
+            match parsed {
+                Ok(mut payload) => {
+                    logs.append(&mut payload.logs);
+                    spans.append(&mut payload.spans);
+                    replayed += 1;
+                }
+                Err(err) => log::warn!("skipping corrupted line {}: {}", index, err),
+            }
+
+            if spans.len() >= batch_size || logs.len() >= batch_size {
+                send_replayed(&transport, &mut spans, &mut logs, batch_size).await;
+            }
+        }
+
+        send_replayed(&transport, &mut spans, &mut logs, batch_size).await;
+
+        Ok(replayed)
+    }
+}
+
+/// Send and clear the payloads replayed so far, spans before the logs linked to them
+async fn send_replayed(
+    transport: &Transport,
+    spans: &mut Vec<RawSpans>,
+    logs: &mut Vec<RawLogs>,
+    batch_size: usize,
+) {
+    for chunk in spans.chunks(batch_size) {
+        transport.send_all(chunk, false).await;
+    }
+
+    for chunk in logs.chunks(batch_size) {
+        transport.send_all(chunk, false).await;
+    }
+
+    spans.clear();
+    logs.clear();
+}
+
+impl Default for Api {
+    #[allow(deprecated)]
+    fn default() -> Self {
+        Api {
+            log_endpoint: ApiEndpoint::default(),
+            trace_endpoint: ApiEndpoint::default(),
+            metric_endpoint: ApiEndpoint::default(),
+            event_endpoint: ApiEndpoint::default(),
+            credential: Credential::default(),
+            key: String::new(),
+            #[cfg(feature = "reqwest")]
+            client: build_client(&ClientOptions::default()).unwrap(),
+            batch_size: 10,
+            log_batch_size: None,
+            trace_batch_size: None,
+            log_flush_interval: None,
+            trace_flush_interval: None,
+            traces_before_logs: false,
+            logs_hold_timeout: Duration::from_secs(10),
+            next_token: 0,
+            streams: None,
+            router: None,
+            routes: BoundedCache::new(64),
+            unknown_accounts: BoundedCache::new(1024),
+            route_idle_timeout: Duration::from_secs(300),
+            backlog: None,
+            metrics: Arc::default(),
+            metrics_interval: Duration::from_secs(10),
+            account_id: None,
+            events_as_logs: false,
+            warned_no_account: false,
+            capture: Arc::default(),
+            rotated_key: Arc::default(),
+            stats: Arc::default(),
+            error_handler: None,
+            retry_policy: RetryPolicy::default(),
+            circuit_breaker: Some(CircuitBreaker::default()),
+            requeue_max_age: None,
+            max_queue_bytes: Some(64 * 1024 * 1024),
+            compression: Compression::default(),
+            max_payload_bytes: 800_000,
+            request_timeout: Duration::from_secs(10),
+            client_options: ClientOptions::default(),
+            client_error: None,
+            #[cfg(feature = "blocking")]
+            agent: None,
+            transport: None,
+            headers: default_headers(),
+            infer_region: true,
+            max_concurrent_requests: 2,
+            format: ExportFormat::default(),
+        }
+    }
 }
 
-impl Api {
-    pub(crate) async fn push(&mut self, logs: NewrLogs, traces: NewrSpans) {
-        log::debug!(
-            "pushing logs and traces, logs_queue_len={}, spans_queue_len={}",
-            self.logs_queue.len(),
-            self.spans_queue.len(),
-        );
+/// Everything needed to send requests, shared by the tasks draining the queues
+pub(crate) struct Transport {
+    log_endpoint: ApiEndpoint,
+    trace_endpoint: ApiEndpoint,
+    metric_endpoint: ApiEndpoint,
+    event_endpoint: ApiEndpoint,
+    // only used when events are sent, i.e. once it's set
+    account_id: u64,
+    credential: Credential,
+    // key replacing the one of `credential` once rotated, see `Handle::set_api_key`
+    rotated_key: Option<Arc<RwLock<Option<String>>>>,
+    client: Arc<dyn HttpTransport>,
+    capture: Arc<Mutex<Option<Capture>>>,
+    stats: Arc<Stats>,
+    error_handler: Option<Arc<ErrorHandler>>,
+    retry_policy: RetryPolicy,
+    max_payload_bytes: usize,
+    request_timeout: Duration,
+    // compress request bodies, reusing buffers from one to the next
+    encoders: Encoders,
+    format: ExportFormat,
+    headers: HeaderMap,
+    // until when each endpoint asked not to be sent anything
+    logs_paused_until: Mutex<Option<Instant>>,
+    spans_paused_until: Mutex<Option<Instant>>,
+    metrics_paused_until: Mutex<Option<Instant>>,
+    events_paused_until: Mutex<Option<Instant>>,
+    logs_breaker: Breaker,
+    spans_breaker: Breaker,
+    metrics_breaker: Breaker,
+    events_breaker: Breaker,
+}
+
+impl Transport {
+    fn paused_until(&self, kind: &Kind) -> &Mutex<Option<Instant>> {
+        match kind {
+            Kind::Logs => &self.logs_paused_until,
+            Kind::Spans => &self.spans_paused_until,
+            Kind::Metrics => &self.metrics_paused_until,
+            Kind::Events => &self.events_paused_until,
+        }
+    }
+
+    /// Remaining time the endpoint is paused for
+    fn paused_for(&self, kind: &Kind) -> Option<Duration> {
+        let until = (*self.paused_until(kind).lock().unwrap())?;
+
+        until
+            .checked_duration_since(Instant::now())
+            .filter(|delay| !delay.is_zero())
+    }
+
+    fn pause(&self, kind: &Kind, delay: Duration) {
+        let mut paused_until = self.paused_until(kind).lock().unwrap();
+        let until = Instant::now() + delay;
+
+        if paused_until.is_none_or(|paused_until| paused_until < until) {
+            *paused_until = Some(until);
+        }
+    }
+
+    /// The circuit breaker of the endpoint, with its state exposed by the stats
+    fn breaker(&self, kind: &Kind) -> (&Breaker, &AtomicU8) {
+        match kind {
+            Kind::Logs => (&self.logs_breaker, &self.stats.logs_circuit),
+            Kind::Spans => (&self.spans_breaker, &self.stats.spans_circuit),
+            Kind::Metrics => (&self.metrics_breaker, &self.stats.metrics_circuit),
+            Kind::Events => (&self.events_breaker, &self.stats.events_circuit),
+        }
+    }
+
+    pub(crate) fn url(&self, kind: &Kind) -> String {
+        #[cfg(feature = "otlp")]
+        if self.format == ExportFormat::Otlp {
+            match kind {
+                Kind::Logs => return self.log_endpoint.otlp_log_url(),
+                Kind::Spans => return self.trace_endpoint.otlp_trace_url(),
+                _ => {}
+            }
+        }
+
+        match kind {
+            Kind::Logs => self.log_endpoint.log_url(),
+            Kind::Spans => self.trace_endpoint.trace_url(),
+            Kind::Metrics => self.metric_endpoint.metric_url(),
+            Kind::Events => self.event_endpoint.event_url(self.account_id),
+        }
+    }
+
+    /// A request to `url`, with the headers common to every endpoint
+    fn post(&self, url: String, body: Vec<u8>) -> TelemetryRequest {
+        TelemetryRequest::post(
+            url,
+            body,
+            self.encoders.compression(),
+            &self.headers,
+            self.request_timeout,
+        )
+    }
 
-        self.logs_queue.push(logs);
-        self.spans_queue.push(traces);
+    /// The key to send, read for every request so that retries pick up a rotated key
+    fn key(&self) -> String {
+        let rotated = self
+            .rotated_key
+            .as_ref()
+            .and_then(|key| key.read().unwrap().clone());
 
-        if self.logs_queue.len() >= self.batch_size || self.spans_queue.len() >= self.batch_size {
-            self.flush().await
-        }
+        rotated.unwrap_or_else(|| self.credential.key().to_string())
     }
 
-    pub(crate) async fn flush(&mut self) {
-        if self.logs_queue.is_empty() && self.spans_queue.is_empty() {
-            return;
+    fn capture<T: Serialize>(&self, endpoint: &str, data: T) {
+        if let Some(capture) = self.capture.lock().unwrap().as_mut() {
+            if let Err(err) = capture.write(endpoint, data) {
+                log::warn!("failed to capture request body: {}", err);
+            }
         }
+    }
 
-        log::debug!(
-            "flushing logs and traces, logs_queue_len={}, spans_queue_len={}",
-            self.logs_queue.len(),
-            self.spans_queue.len(),
-        );
+    /// Send all data, returning what became of each item
+    ///
+    /// Data failing with a transient error is returned as [`Outcome::Failed`] if `requeue`,
+    /// it's dropped otherwise.
+    pub(crate) async fn send_all<T: Sendable>(&self, data: &[T], requeue: bool) -> Vec<Outcome> {
+        let max_bytes = self.max_payload_bytes;
 
-        let mut logs_service = Service::new(&self.logs_queue);
-        let mut trace_service = Service::new(&self.spans_queue);
+        // merge the payloads sharing their common attributes, then split the ones too big
+        // to be sent on their own, remembering where each part comes from
+        let mut parts = Vec::new();
+        let mut sizes = Vec::new();
+        let mut origins = Vec::new();
 
-        loop {
-            use ServiceStatus::*;
+        for (payload, merged) in T::merge(data) {
+            let size = json_len(&payload);
 
-            match join!(logs_service.send(self), trace_service.send(self)) {
-                (Timeount(d1), Timeount(d2)) => sleep(max(d1, d2)).await,
+            if size > max_bytes {
+                for part in payload.split(max_bytes) {
+                    sizes.push(json_len(&part));
+                    parts.push(part);
+                    origins.push(merged.clone());
+                }
+            } else {
+                sizes.push(size);
+                parts.push(payload);
+                origins.push(merged);
+            }
+        }
 
-                (Timeount(d), _) | (_, Timeount(d)) => sleep(d).await,
+        let parts = self.send_sized(&parts, &sizes, requeue).await;
 
-                (Finished, Finished) => {
-                    log::info!(
-                        "flushed logs and traces, logs_queue_len={}, spans_queue_len={}",
-                        self.logs_queue.len(),
-                        self.spans_queue.len(),
-                    );
+        // a payload is delivered once all of its parts are, and dropped once any of them is
+        let mut outcomes = vec![Outcome::Delivered; data.len()];
 
-                    self.logs_queue.clear();
-                    self.spans_queue.clear();
-                    return;
+        for (outcome, merged) in parts.into_iter().zip(origins) {
+            for origin in merged {
+                match (outcome, outcomes[origin]) {
+                    (Outcome::Dropped, _) => outcomes[origin] = Outcome::Dropped,
+                    (Outcome::Failed, Outcome::Delivered) => outcomes[origin] = Outcome::Failed,
+                    _ => {}
                 }
+            }
+        }
 
-                _ => {}
+        outcomes
+    }
+
+    /// Send data whose serialized sizes are known, returning what became of each item
+    async fn send_sized<T: Sendable>(
+        &self,
+        data: &[T],
+        sizes: &[usize],
+        requeue: bool,
+    ) -> Vec<Outcome> {
+        let mut service = Service::new(data, sizes, self.max_payload_bytes, requeue);
+
+        loop {
+            match service.send(self).await {
+                ServiceStatus::Timeount(d) => sleep(d).await,
+                ServiceStatus::Remaining => {}
+                ServiceStatus::Finished => return service.outcomes,
             }
         }
     }
+
+    /// Count the data as dropped, and report it to the error handler
+    pub(crate) fn report_dropped<T: Sendable>(
+        &self,
+        data: &[T],
+        endpoint: String,
+        status: Option<u16>,
+        body: String,
+        request_id: Option<String>,
+    ) {
+        report_dropped(
+            &self.stats,
+            self.error_handler.as_deref(),
+            data,
+            endpoint,
+            status,
+            body,
+            request_id,
+        );
+    }
 }
 
-impl Default for Api {
+/// Count the data as dropped, and report it to the error handler if any
+fn report_dropped<T: Sendable>(
+    stats: &Stats,
+    error_handler: Option<&ErrorHandler>,
+    data: &[T],
+    endpoint: String,
+    status: Option<u16>,
+    body: String,
+    request_id: Option<String>,
+) {
+    stats::add(&stats.payloads_dropped, data.len());
+
+    if let Some(handler) = error_handler {
+        let dropped = T::count(data);
+
+        handler(ExportError {
+            endpoint,
+            status,
+            body,
+            request_id,
+            spans_dropped: if T::KIND == Kind::Spans { dropped } else { 0 },
+            logs_dropped: if T::KIND == Kind::Logs { dropped } else { 0 },
+        });
+    }
+}
+
+/// Value of an environment variable, `None` if unset or blank
+fn var(name: &str) -> Option<String> {
+    std::env::var(name)
+        .ok()
+        .map(|val| val.trim().to_string())
+        .filter(|val| !val.is_empty())
+}
+
+fn default_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        USER_AGENT,
+        HeaderValue::from_static(concat!("tracing-newrelic/", env!("CARGO_PKG_VERSION"))),
+    );
+    headers
+}
+
+/// Whether the header is set on every request already
+fn is_reserved(name: &HeaderName) -> bool {
+    name == CONTENT_TYPE
+        || name == CONTENT_ENCODING
+        || name.as_str() == "api-key"
+        || name.as_str() == "x-license-key"
+        || name.as_str().starts_with("data-format")
+}
+
+/// Settings of the client built by `Api`
+#[cfg_attr(not(any(feature = "reqwest", feature = "blocking")), allow(dead_code))]
+struct ClientOptions {
+    connect_timeout: Duration,
+    proxy: Option<String>,
+    #[cfg(any(feature = "tls-rustls", feature = "tls-native"))]
+    ca_certificates: Vec<Vec<u8>>,
+}
+
+impl Default for ClientOptions {
     fn default() -> Self {
-        Api {
-            log_endpoint: ApiEndpoint::default(),
-            trace_endpoint: ApiEndpoint::default(),
-            key: String::new(),
-            client: Client::new(),
-            batch_size: 10,
-            logs_queue: Vec::with_capacity(10),
-            spans_queue: Vec::with_capacity(10),
+        ClientOptions {
+            connect_timeout: Duration::from_secs(5),
+            proxy: None,
+            #[cfg(any(feature = "tls-rustls", feature = "tls-native"))]
+            ca_certificates: Vec::new(),
+        }
+    }
+}
+
+#[cfg(feature = "reqwest")]
+fn build_client(options: &ClientOptions) -> Result<Client, ConfigError> {
+    let mut builder = Client::builder().connect_timeout(options.connect_timeout);
+
+    // rustls wins if both backends are enabled, e.g. by another crate
+    #[cfg(feature = "tls-rustls")]
+    {
+        builder = builder.use_rustls_tls();
+    }
+    #[cfg(all(feature = "tls-native", not(feature = "tls-rustls")))]
+    {
+        builder = builder.use_native_tls();
+    }
+
+    if let Some(url) = &options.proxy {
+        let proxy = Proxy::all(url.as_str())
+            .map_err(|_| ConfigError::InvalidProxy { proxy: url.clone() })?;
+
+        builder = builder.proxy(proxy.no_proxy(NoProxy::from_env()));
+    }
+
+    #[cfg(any(feature = "tls-rustls", feature = "tls-native"))]
+    for pem in &options.ca_certificates {
+        // parsed upfront, rustls would skip anything but certificates while building the client
+        let certificates = reqwest::Certificate::from_pem_bundle(pem)
+            .ok()
+            .filter(|certificates| !certificates.is_empty())
+            .ok_or(ConfigError::InvalidCertificate)?;
+
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
         }
     }
+
+    Ok(builder.build().expect("failed to build the http client"))
 }
 
 impl From<String> for Api {
     fn from(key: String) -> Self {
-        Api {
-            key,
-            ..Default::default()
-        }
+        Api::from(Credential::ApiKey(key))
     }
 }
 
 impl From<&str> for Api {
     fn from(key: &str) -> Self {
+        Api::from(key.to_string())
+    }
+}
+
+impl From<(String, ApiEndpoint)> for Api {
+    fn from(t: (String, ApiEndpoint)) -> Self {
+        Api::from((Credential::ApiKey(t.0), t.1))
+    }
+}
+
+impl From<Credential> for Api {
+    fn from(credential: Credential) -> Self {
         Api {
-            key: key.to_string(),
+            credential,
             ..Default::default()
         }
     }
 }
 
-impl From<(String, ApiEndpoint)> for Api {
-    fn from(t: (String, ApiEndpoint)) -> Self {
+impl From<(Credential, ApiEndpoint)> for Api {
+    fn from(t: (Credential, ApiEndpoint)) -> Self {
         Api {
-            key: t.0,
+            credential: t.0,
             log_endpoint: t.1.clone(),
-            trace_endpoint: t.1,
+            trace_endpoint: t.1.clone(),
+            metric_endpoint: t.1.clone(),
+            event_endpoint: t.1,
             ..Default::default()
         }
     }
@@ -157,31 +1649,140 @@ enum ServiceStatus {
 
 struct Service<'a, T: Sendable> {
     data: &'a [T],
+    // serialized size of each item of `data`
+    sizes: &'a [usize],
+    max_bytes: usize,
     // number of items to send each request,
     batch_len: usize,
     retry_count: u32,
+    // index of `data[0]` in the original slice
+    offset: usize,
+    // what became of each item of the original slice
+    outcomes: Vec<Outcome>,
+    // whether data failing with a transient error is left to be sent again, instead of dropped
+    requeue: bool,
+    // compressed body of `data[..batch_len]`, reused across retries
+    body: Option<Vec<u8>>,
+    // whether this is the single batch probing an endpoint whose circuit is half-open
+    probe: bool,
 }
 
 impl<'a, T: Sendable> Service<'a, T> {
-    fn new(data: &'a [T]) -> Self {
+    fn new(data: &'a [T], sizes: &'a [usize], max_bytes: usize, requeue: bool) -> Self {
         Service {
-            batch_len: data.len(),
+            batch_len: fit(sizes, max_bytes),
             data,
+            sizes,
+            max_bytes,
             retry_count: 0,
+            offset: 0,
+            outcomes: vec![Outcome::Dropped; data.len()],
+            requeue,
+            body: None,
+            probe: false,
         }
     }
 
-    async fn send(&mut self, api: &Api) -> ServiceStatus {
+    async fn send(&mut self, transport: &Transport) -> ServiceStatus {
         // nothing to send
         if self.data.is_empty() {
             return ServiceStatus::Finished;
         }
 
+        if !self.probe {
+            let (breaker, state) = transport.breaker(&T::KIND);
+
+            match breaker.admit(state) {
+                Admission::Send => {}
+                Admission::Probe => self.probe = true,
+                Admission::Reject if self.requeue => {
+                    log::debug!("{} circuit open, requeueing batch", T::KIND.name());
+                    return self.fail_remaining();
+                }
+                Admission::Reject => {
+                    log::debug!("{} circuit open, dropping batch", T::KIND.name());
+                    let endpoint = transport.url(&T::KIND);
+                    return self.drop_remaining(transport, endpoint, None);
+                }
+            }
+        }
+
+        // another request to the endpoint has been rate limited
+        if let Some(delay) = transport.paused_for(&T::KIND) {
+            return ServiceStatus::Timeount(delay);
+        }
+
         let (left, right) = self.data.split_at(self.batch_len);
 
-        let res = T::build_request(left, api).send().await.unwrap();
+        let body = match &self.body {
+            Some(body) => body.clone(),
+            None => match T::serialize_body(left, transport) {
+                Ok(body) => self.body.insert(body).clone(),
+                Err(err) => {
+                    let reason = format!("failed to serialize {}: {}", T::KIND.name(), err);
+                    return self.skip_batch(transport, reason);
+                }
+            },
+        };
+
+        let request = T::build_request(left, body, transport);
+
+        if let Err(err) = request.header_map() {
+            let reason = format!("failed to build {} request: {}", T::KIND.name(), err);
+            return self.skip_batch(transport, reason);
+        }
+
+        let endpoint = request.url.clone();
+
+        let bytes = request.body.len();
+
+        let res = match transport.client.post(request).await {
+            Ok(res) => res,
+            // e.g. timed out or couldn't connect
+            Err(err) => {
+                stats::add(&transport.stats.send_failures, 1);
+                let reason = format!("request failed: {}", err);
+                return self.retry(transport, endpoint, reason, None);
+            }
+        };
+
+        let status = res.status;
+
+        let delay = res
+            .retry_after
+            .as_deref()
+            .and_then(|val| transport.retry_policy.retry_after(val));
+
+        let rejection = if (200..300).contains(&status) {
+            None
+        } else {
+            let rejection = Rejection::new(&res);
+
+            log::warn!(
+                "received {} response from {}, request_id={}, body={}",
+                status,
+                endpoint,
+                rejection.request_id.as_deref().unwrap_or("none"),
+                rejection.body,
+            );
 
-        let status = res.status().as_u16();
+            Some(rejection)
+        };
+        let rejection = rejection.as_ref();
+
+        if rejection.is_none() {
+            stats::add(&transport.stats.batches_sent, 1);
+            stats::add(&transport.stats.bytes_sent, bytes);
+            let counter = match T::KIND {
+                Kind::Logs => &transport.stats.logs_sent,
+                Kind::Spans => &transport.stats.spans_sent,
+                Kind::Metrics => &transport.stats.metrics_sent,
+                Kind::Events => &transport.stats.events_sent,
+            };
+            stats::add(counter, T::count(left));
+        } else {
+            stats::add(&transport.stats.send_failures, 1);
+        }
 
         // https://docs.newrelic.com/docs/distributed-tracing/trace-api/trace-api-general-requirements-limits#status-codes
         match status {
@@ -197,19 +1798,17 @@ impl<'a, T: Sendable> Service<'a, T> {
                 // reset retry_count
                 self.retry_count = 0;
 
-                self.data = right;
+                let (breaker, state) = transport.breaker(&T::KIND);
+                breaker.succeeded(state);
 
-                if self.data.is_empty() {
-                    ServiceStatus::Finished
-                } else {
-                    ServiceStatus::Remaining
-                }
+                self.outcomes[self.offset..self.offset + left.len()].fill(Outcome::Delivered);
+                self.advance()
             }
 
             400 | 401 | 403 | 404 | 405 | 409 | 410 | 411 => {
                 log::info!("recevied {} response", status);
 
-                ServiceStatus::Finished
+                self.give_up(transport, endpoint, rejection, false)
             }
 
             // 	The payload was too big.
@@ -219,106 +1818,553 @@ impl<'a, T: Sendable> Service<'a, T> {
                 if self.batch_len == 1 {
                     log::info!("dropping paylod");
 
-                    ServiceStatus::Finished
+                    self.give_up(transport, endpoint, rejection, false)
                 } else {
-                    self.batch_len %= 2;
+                    self.batch_len /= 2;
+                    self.body = None;
                     ServiceStatus::Remaining
                 }
             }
 
             // The request rate quota has been exceeded.
             429 => {
-                let seconds = res
-                    .headers()
-                    .get("retry-after")
-                    .and_then(|val| val.to_str().ok())
-                    .and_then(|val| val.parse::<u64>().ok());
-
-                match seconds {
-                    Some(s) => {
-                        log::debug!("recevied 429 response, retry after {} seconds", s);
-                        ServiceStatus::Timeount(Duration::from_secs(s))
+                // hold back every request to the endpoint, not only this one
+                if let Some(delay) = delay {
+                    transport.pause(&T::KIND, delay);
+                }
+
+                match delay {
+                    Some(_) if self.retry_count + 1 >= transport.retry_policy.max_attempts => {
+                        log::info!("recevied 429 response, reached max retry count");
+                        self.give_up(transport, endpoint, rejection, true)
+                    }
+                    Some(delay) => {
+                        log::debug!("recevied 429 response, retry after {:?}", delay);
+                        self.retry_count += 1;
+                        ServiceStatus::Timeount(delay)
                     }
                     None => {
                         log::debug!("recevied 429 response, but `retry-after` not persent");
-                        ServiceStatus::Finished
+                        self.give_up(transport, endpoint, rejection, true)
                     }
                 }
             }
 
             _ => {
-                if self.retry_count == 0 {
-                    log::info!(
-                        "recevied {} response, retry immediately, retry_count={}",
-                        status,
-                        self.retry_count,
-                    );
-                    self.retry_count += 1;
-                    ServiceStatus::Timeount(Duration::from_secs(0))
-                } else if self.retry_count <= 5 {
-                    let s = 2_u64.pow(self.retry_count - 1_u32); // 2^n
-                    log::info!(
-                        "recevied {} response, retry after {} seconds, retry_count={}",
-                        status,
-                        s,
-                        self.retry_count,
-                    );
-                    self.retry_count += 1;
-                    ServiceStatus::Timeount(Duration::from_secs(s))
-                } else {
-                    log::info!("recevied {} response, reached max retry count", status);
-                    ServiceStatus::Finished
-                }
+                let reason = format!("recevied {} response", status);
+                self.retry(transport, endpoint, reason, rejection)
             }
         }
     }
+
+    /// Retry after a backoff, or give up once out of attempts
+    fn retry(
+        &mut self,
+        transport: &Transport,
+        endpoint: String,
+        reason: String,
+        rejection: Option<&Rejection>,
+    ) -> ServiceStatus {
+        if self.retry_count + 1 < transport.retry_policy.max_attempts {
+            self.retry_count += 1;
+            let delay = transport.retry_policy.backoff(self.retry_count);
+            log::info!(
+                "{}, retry after {:?}, retry_count={}",
+                reason,
+                delay,
+                self.retry_count,
+            );
+            ServiceStatus::Timeount(delay)
+        } else {
+            log::info!("{}, reached max retry count", reason);
+            self.give_up(transport, endpoint, rejection, true)
+        }
+    }
+
+    /// Stop sending the remaining data, requeueing it on `transient` errors if enabled
+    fn give_up(
+        &mut self,
+        transport: &Transport,
+        endpoint: String,
+        rejection: Option<&Rejection>,
+        transient: bool,
+    ) -> ServiceStatus {
+        let (breaker, state) = transport.breaker(&T::KIND);
+        breaker.failed(state);
+
+        if transient && self.requeue {
+            self.fail_remaining()
+        } else {
+            self.drop_remaining(transport, endpoint, rejection)
+        }
+    }
+
+    /// Leave the remaining data to be sent again later
+    fn fail_remaining(&mut self) -> ServiceStatus {
+        self.outcomes[self.offset..].fill(Outcome::Failed);
+
+        ServiceStatus::Finished
+    }
+
+    /// Move on to the data after the current batch
+    fn advance(&mut self) -> ServiceStatus {
+        let len = self.batch_len;
+
+        self.offset += len;
+        self.data = &self.data[len..];
+        self.sizes = &self.sizes[len..];
+        self.batch_len = fit(self.sizes, self.max_bytes);
+        self.body = None;
+
+        if self.data.is_empty() {
+            ServiceStatus::Finished
+        } else {
+            ServiceStatus::Remaining
+        }
+    }
+
+    /// Drop the current batch without sending it, and report it to the error handler
+    fn skip_batch(&mut self, transport: &Transport, reason: String) -> ServiceStatus {
+        let batch = &self.data[..self.batch_len];
+
+        log::error!("{}, dropping {} payloads", reason, batch.len());
+
+        transport.report_dropped(batch, transport.url(&T::KIND), None, reason, None);
+
+        self.advance()
+    }
+
+    /// Drop the remaining data, and report it to the error handler
+    fn drop_remaining(
+        &mut self,
+        transport: &Transport,
+        endpoint: String,
+        rejection: Option<&Rejection>,
+    ) -> ServiceStatus {
+        transport.report_dropped(
+            self.data,
+            endpoint,
+            rejection.map(|rejection| rejection.status),
+            rejection
+                .map(|rejection| rejection.body.clone())
+                .unwrap_or_default(),
+            rejection.and_then(|rejection| rejection.request_id.clone()),
+        );
+
+        ServiceStatus::Finished
+    }
+}
+
+/// What became of a payload given to [`Transport::send_all`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Outcome {
+    /// Accepted by New Relic
+    Delivered,
+    /// Rejected or given up on, and reported as dropped
+    Dropped,
+    /// Failed with a transient error, to be sent again later
+    Failed,
+}
+
+#[derive(PartialEq)]
+pub(crate) enum Kind {
+    Logs,
+    Spans,
+    Metrics,
+    Events,
+}
+
+impl Kind {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Kind::Logs => "logs",
+            Kind::Spans => "traces",
+            Kind::Metrics => "metrics",
+            Kind::Events => "events",
+        }
+    }
 }
 
-trait Sendable {
-    fn build_request(data: &[Self], api: &Api) -> RequestBuilder
+pub(crate) trait Sendable: Serialize + Clone {
+    /// Whether the data holds logs, spans, metrics or events
+    const KIND: Kind;
+
+    /// Serialize and compress the request body, once for all the retries
+    fn serialize_body(data: &[Self], transport: &Transport) -> io::Result<Vec<u8>>
+    where
+        Self: Sized,
+    {
+        transport.encoders.encode(data)
+    }
+
+    fn build_request(data: &[Self], body: Vec<u8>, transport: &Transport) -> TelemetryRequest
+    where
+        Self: Sized;
+
+    /// Number of logs, spans, metrics or events
+    fn count(data: &[Self]) -> usize
     where
         Self: Sized;
+
+    /// Split a payload into smaller ones of at most `max_bytes` each, if possible
+    fn split(&self, _max_bytes: usize) -> Vec<Self> {
+        vec![self.clone()]
+    }
+
+    /// Merge payloads into fewer ones, along with the indexes of the payloads each one is made of
+    fn merge(data: &[Self]) -> Vec<(Self, Vec<usize>)>
+    where
+        Self: Sized,
+    {
+        data.iter()
+            .cloned()
+            .enumerate()
+            .map(|(index, payload)| (payload, vec![index]))
+            .collect()
+    }
 }
 
 impl Sendable for NewrLogs {
-    fn build_request(data: &[NewrLogs], api: &Api) -> RequestBuilder {
-        let url = match &api.log_endpoint {
-            ApiEndpoint::US => "https://log-api.newrelic.com/log/v1".into(),
-            ApiEndpoint::EU => "https://log-api.eu.newrelic.com/log/v1".into(),
-            ApiEndpoint::Custom(domain) => format!("{domain}/log/v1"),
+    const KIND: Kind = Kind::Logs;
+
+    fn serialize_body(data: &[NewrLogs], transport: &Transport) -> io::Result<Vec<u8>> {
+        match transport.format {
+            ExportFormat::Native => transport.encoders.encode(data),
+            #[cfg(feature = "otlp")]
+            ExportFormat::Otlp => transport.encoders.encode(otlp::logs(data)),
+        }
+    }
+
+    fn build_request(data: &[NewrLogs], body: Vec<u8>, transport: &Transport) -> TelemetryRequest {
+        match transport.format {
+            ExportFormat::Native => logs_request(data, body, transport),
+            #[cfg(feature = "otlp")]
+            ExportFormat::Otlp => {
+                let url = transport.log_endpoint.otlp_log_url();
+                otlp_request(url, otlp::logs(data), body, transport)
+            }
+        }
+    }
+
+    fn count(data: &[NewrLogs]) -> usize {
+        data.iter().map(|logs| logs.logs.len()).sum()
+    }
+
+    fn split(&self, max_bytes: usize) -> Vec<NewrLogs> {
+        let empty = NewrLogs {
+            logs: Vec::new(),
+            common: self.common.clone(),
         };
-        // https://docs.newrelic.com/docs/logs/log-api/introduction-log-api/#json-headers
-        api.client
-            .post(url)
-            .header(CONTENT_TYPE, "application/json")
-            .header(CONTENT_ENCODING, "gzip")
-            .header("Api-Key", &api.key)
-            .body(to_gz(&data))
+
+        split_items(&self.logs, json_len(&empty), max_bytes)
+            .into_iter()
+            .map(|logs| NewrLogs {
+                logs,
+                common: self.common.clone(),
+            })
+            .collect()
+    }
+
+    fn merge(data: &[NewrLogs]) -> Vec<(NewrLogs, Vec<usize>)> {
+        merge_by_common(
+            data,
+            |logs| &logs.common,
+            |merged, logs| merged.logs.extend_from_slice(&logs.logs),
+        )
     }
 }
 
 impl Sendable for NewrSpans {
-    fn build_request(data: &[NewrSpans], api: &Api) -> RequestBuilder {
-        let url = match &api.log_endpoint {
-            ApiEndpoint::US => "https://trace-api.newrelic.com/trace/v1".into(),
-            ApiEndpoint::EU => "https://trace-api.eu.newrelic.com/trace/v1".into(),
-            ApiEndpoint::Custom(domain) => format!("{domain}/trace/v1"),
+    const KIND: Kind = Kind::Spans;
+
+    fn serialize_body(data: &[NewrSpans], transport: &Transport) -> io::Result<Vec<u8>> {
+        match transport.format {
+            ExportFormat::Native => transport.encoders.encode(data),
+            #[cfg(feature = "otlp")]
+            ExportFormat::Otlp => transport.encoders.encode(otlp::traces(data)),
+        }
+    }
+
+    fn build_request(data: &[NewrSpans], body: Vec<u8>, transport: &Transport) -> TelemetryRequest {
+        match transport.format {
+            ExportFormat::Native => spans_request(data, body, transport),
+            #[cfg(feature = "otlp")]
+            ExportFormat::Otlp => {
+                let url = transport.trace_endpoint.otlp_trace_url();
+                otlp_request(url, otlp::traces(data), body, transport)
+            }
+        }
+    }
+
+    fn count(data: &[NewrSpans]) -> usize {
+        data.iter().map(|spans| spans.spans.len()).sum()
+    }
+
+    fn split(&self, max_bytes: usize) -> Vec<NewrSpans> {
+        let empty = NewrSpans {
+            spans: Vec::new(),
+            common: self.common.clone(),
         };
-        // https://docs.newrelic.com/docs/distributed-tracing/trace-api/trace-api-general-requirements-limits/#headers-query-parameters
-        api.client
-            .post(&url)
-            .header(CONTENT_TYPE, "application/json")
-            .header(CONTENT_ENCODING, "gzip")
-            .header("Api-Key", &api.key)
-            .header("Data-Format", "newrelic")
-            .header("Data-Format-Version", "1")
-            .body(to_gz(&data))
-    }
-}
-
-#[inline]
-fn to_gz<T: Serialize>(data: T) -> Vec<u8> {
-    let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
-    serde_json::to_writer(&mut encoder, &data).unwrap();
-    encoder.finish().unwrap()
+
+        split_items(&self.spans, json_len(&empty), max_bytes)
+            .into_iter()
+            .map(|spans| NewrSpans {
+                spans,
+                common: self.common.clone(),
+            })
+            .collect()
+    }
+
+    fn merge(data: &[NewrSpans]) -> Vec<(NewrSpans, Vec<usize>)> {
+        merge_by_common(
+            data,
+            |spans| &spans.common,
+            |merged, spans| merged.spans.extend_from_slice(&spans.spans),
+        )
+    }
+}
+
+impl Sendable for NewrMetrics {
+    const KIND: Kind = Kind::Metrics;
+
+    fn build_request(
+        data: &[NewrMetrics],
+        body: Vec<u8>,
+        transport: &Transport,
+    ) -> TelemetryRequest {
+        metrics_request(data, body, transport)
+    }
+
+    fn count(data: &[NewrMetrics]) -> usize {
+        data.iter().map(|metrics| metrics.metrics.len()).sum()
+    }
+}
+
+impl Sendable for NewrEvent {
+    const KIND: Kind = Kind::Events;
+
+    fn build_request(data: &[NewrEvent], body: Vec<u8>, transport: &Transport) -> TelemetryRequest {
+        events_request(data, body, transport)
+    }
+
+    fn count(data: &[NewrEvent]) -> usize {
+        data.len()
+    }
+}
+
+/// A logs payload read back from a file, sent as is
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(transparent)]
+struct RawLogs(Box<RawValue>);
+
+/// A spans payload read back from a file, sent as is
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(transparent)]
+struct RawSpans(Box<RawValue>);
+
+impl Sendable for RawLogs {
+    const KIND: Kind = Kind::Logs;
+
+    fn build_request(data: &[RawLogs], body: Vec<u8>, transport: &Transport) -> TelemetryRequest {
+        logs_request(data, body, transport)
+    }
+
+    fn count(data: &[RawLogs]) -> usize {
+        data.iter().map(|raw| raw_len(&raw.0, "logs")).sum()
+    }
+}
+
+impl Sendable for RawSpans {
+    const KIND: Kind = Kind::Spans;
+
+    fn build_request(data: &[RawSpans], body: Vec<u8>, transport: &Transport) -> TelemetryRequest {
+        spans_request(data, body, transport)
+    }
+
+    fn count(data: &[RawSpans]) -> usize {
+        data.iter().map(|raw| raw_len(&raw.0, "spans")).sum()
+    }
+}
+
+/// A line written by [`FileExporter`](crate::FileExporter)
+#[derive(Deserialize)]
+struct RawPayload {
+    logs: Vec<RawLogs>,
+    spans: Vec<RawSpans>,
+}
+
+/// Length of the `key` array of a raw payload
+fn raw_len(raw: &RawValue, key: &str) -> usize {
+    serde_json::from_str::<HashMap<&str, &RawValue>>(raw.get())
+        .ok()
+        .and_then(|payload| serde_json::from_str::<Vec<IgnoredAny>>(payload.get(key)?.get()).ok())
+        .map_or(0, |items| items.len())
+}
+
+fn logs_request<T: Serialize>(
+    data: &[T],
+    body: Vec<u8>,
+    transport: &Transport,
+) -> TelemetryRequest {
+    let url = transport.log_endpoint.log_url();
+    transport.capture(&url, data);
+    // https://docs.newrelic.com/docs/logs/log-api/introduction-log-api/#json-headers
+    transport
+        .post(url, body)
+        .header(transport.credential.header(), &transport.key())
+}
+
+pub(crate) fn spans_request<T: Serialize>(
+    data: &[T],
+    body: Vec<u8>,
+    transport: &Transport,
+) -> TelemetryRequest {
+    let url = transport.trace_endpoint.trace_url();
+    transport.capture(&url, data);
+    // https://docs.newrelic.com/docs/distributed-tracing/trace-api/trace-api-general-requirements-limits/#headers-query-parameters
+    transport
+        .post(url, body)
+        .header(transport.credential.header(), &transport.key())
+        .header("Data-Format", "newrelic")
+        .header("Data-Format-Version", "1")
+}
+
+#[cfg(feature = "otlp")]
+fn otlp_request<T: Serialize>(
+    url: String,
+    data: T,
+    body: Vec<u8>,
+    transport: &Transport,
+) -> TelemetryRequest {
+    transport.capture(&url, data);
+    // https://docs.newrelic.com/docs/opentelemetry/best-practices/opentelemetry-otlp/
+    // the OTLP endpoint takes license keys as `api-key` only
+    transport
+        .post(url, body)
+        .header("api-key", &transport.key())
+}
+
+fn metrics_request<T: Serialize>(
+    data: &[T],
+    body: Vec<u8>,
+    transport: &Transport,
+) -> TelemetryRequest {
+    let url = transport.metric_endpoint.metric_url();
+    transport.capture(&url, data);
+    // https://docs.newrelic.com/docs/data-apis/ingest-apis/metric-api/report-metrics-metric-api/#request-headers
+    transport
+        .post(url, body)
+        .header(transport.credential.header(), &transport.key())
+}
+
+fn events_request<T: Serialize>(
+    data: &[T],
+    body: Vec<u8>,
+    transport: &Transport,
+) -> TelemetryRequest {
+    let url = transport.event_endpoint.event_url(transport.account_id);
+    transport.capture(&url, data);
+    // https://docs.newrelic.com/docs/data-apis/ingest-apis/event-api/introduction-event-api/#submit-event
+    // the Event API takes license keys as `Api-Key` only
+    transport
+        .post(url, body)
+        .header("Api-Key", &transport.key())
+}
+
+/// Number of leading items fitting in a request of `max_bytes`, at least one
+fn fit(sizes: &[usize], max_bytes: usize) -> usize {
+    // brackets, then each item followed by a comma
+    let mut total = 2;
+
+    let len = sizes
+        .iter()
+        .take_while(|&&size| {
+            total += size + 1;
+            total <= max_bytes
+        })
+        .count();
+
+    if sizes.is_empty() {
+        0
+    } else {
+        len.max(1)
+    }
+}
+
+/// Merge the payloads with the same common attributes, in order of first appearance
+fn merge_by_common<P: Clone>(
+    data: &[P],
+    common: impl Fn(&P) -> &NewrCommon,
+    append: impl Fn(&mut P, &P),
+) -> Vec<(P, Vec<usize>)> {
+    let mut merged: Vec<(P, Vec<usize>)> = Vec::new();
+
+    for (index, payload) in data.iter().enumerate() {
+        let attributes = common(payload).attributes();
+
+        match merged
+            .iter_mut()
+            .find(|(other, _)| common(other).attributes() == attributes)
+        {
+            Some((other, indexes)) => {
+                append(other, payload);
+                indexes.push(index);
+            }
+            None => merged.push((payload.clone(), vec![index])),
+        }
+    }
+
+    merged
+}
+
+/// Group `items` so that each group, along with `overhead`, serializes to at most `max_bytes`
+///
+/// An item too big on its own still gets a group of its own.
+fn split_items<I: Serialize + Clone>(
+    items: &[I],
+    overhead: usize,
+    max_bytes: usize,
+) -> Vec<Vec<I>> {
+    let mut groups = Vec::new();
+    let mut group = Vec::new();
+    let mut size = overhead;
+
+    for item in items {
+        let len = json_len(item) + 1;
+
+        if !group.is_empty() && size + len > max_bytes {
+            groups.push(std::mem::take(&mut group));
+            size = overhead;
+        }
+
+        size += len;
+        group.push(item.clone());
+    }
+
+    if !group.is_empty() || groups.is_empty() {
+        groups.push(group);
+    }
+
+    groups
+}
+
+/// Length of the JSON serialization of `data`, without allocating it
+pub(crate) fn json_len<T: Serialize>(data: &T) -> usize {
+    struct Counter(usize);
+
+    impl io::Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0 += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    // the error is reported once the data is serialized for good
+    let _ = serde_json::to_writer(&mut counter, data);
+    counter.0
 }
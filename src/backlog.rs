@@ -0,0 +1,49 @@
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+/// What happens to a completed trace when the queue to the worker thread is full
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DropPolicy {
+    /// Drop the trace, never blocking the traced application, Default
+    #[default]
+    DropNewest,
+    /// Block the traced application up to the given duration, then drop the trace
+    Block(Duration),
+}
+
+/// Number of traces sent to the worker thread and not exported yet
+#[derive(Default)]
+pub(crate) struct Backlog {
+    len: Mutex<usize>,
+    space: Condvar,
+}
+
+impl Backlog {
+    /// Reserve a slot for a trace, returning `false` if the backlog stayed full
+    pub(crate) fn acquire(&self, capacity: usize, policy: DropPolicy) -> bool {
+        let mut len = self.len.lock().unwrap();
+
+        if let DropPolicy::Block(timeout) = policy {
+            len = self
+                .space
+                .wait_timeout_while(len, timeout, |len| *len >= capacity)
+                .unwrap()
+                .0;
+        }
+
+        if *len >= capacity {
+            return false;
+        }
+
+        *len += 1;
+
+        true
+    }
+
+    /// Free the slot of a trace exported by the worker thread
+    pub(crate) fn release(&self) {
+        let mut len = self.len.lock().unwrap();
+        *len = len.saturating_sub(1);
+        self.space.notify_one();
+    }
+}
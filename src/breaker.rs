@@ -0,0 +1,148 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// When to stop sending to an endpoint that keeps failing
+///
+/// After `failure_threshold` batches given up on in a row, nothing is sent to the endpoint for
+/// `cooldown`, and the batches queued meanwhile are dropped right away. A single batch is sent
+/// once the cooldown is over, closing the circuit if it's accepted, or opening it again otherwise.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CircuitBreaker {
+    /// Number of batches given up on in a row before the circuit opens
+    pub failure_threshold: u32,
+    /// How long nothing is sent once the circuit is open
+    pub cooldown: Duration,
+}
+
+impl Default for CircuitBreaker {
+    /// Open after 5 failed batches, for 30 seconds
+    fn default() -> Self {
+        CircuitBreaker {
+            failure_threshold: 5,
+            cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+/// State of the circuit breaker of an endpoint
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Batches are sent
+    #[default]
+    Closed,
+    /// Batches are dropped until the cooldown is over
+    Open,
+    /// A single batch is being sent to find out whether the endpoint has recovered
+    HalfOpen,
+}
+
+impl CircuitState {
+    pub(crate) fn load(state: &AtomicU8) -> Self {
+        match state.load(Ordering::Relaxed) {
+            1 => CircuitState::Open,
+            2 => CircuitState::HalfOpen,
+            _ => CircuitState::Closed,
+        }
+    }
+
+    fn store(self, state: &AtomicU8) {
+        state.store(self as u8, Ordering::Relaxed);
+    }
+}
+
+/// Whether a batch can be sent
+pub(crate) enum Admission {
+    Send,
+    /// Send it to probe the endpoint, nothing else is sent meanwhile
+    Probe,
+    /// Drop it, the circuit is open
+    Reject,
+}
+
+/// The circuit breaker of an endpoint
+pub(crate) struct Breaker {
+    policy: Option<CircuitBreaker>,
+    name: &'static str,
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    // batches given up on in a row
+    failures: u32,
+    opened_at: Instant,
+}
+
+impl Breaker {
+    pub(crate) fn new(policy: Option<CircuitBreaker>, name: &'static str) -> Self {
+        Breaker {
+            policy,
+            name,
+            inner: Mutex::new(Inner {
+                failures: 0,
+                opened_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Whether a batch can be sent, `state` is the state exposed by the stats
+    pub(crate) fn admit(&self, state: &AtomicU8) -> Admission {
+        let policy = match &self.policy {
+            Some(policy) => policy,
+            None => return Admission::Send,
+        };
+
+        let inner = self.inner.lock().unwrap();
+
+        match CircuitState::load(state) {
+            CircuitState::Closed => Admission::Send,
+            CircuitState::Open if inner.opened_at.elapsed() >= policy.cooldown => {
+                log::info!("{} circuit half-open, probing the endpoint", self.name);
+                CircuitState::HalfOpen.store(state);
+                Admission::Probe
+            }
+            CircuitState::Open | CircuitState::HalfOpen => Admission::Reject,
+        }
+    }
+
+    /// A batch has been accepted
+    pub(crate) fn succeeded(&self, state: &AtomicU8) {
+        let mut inner = self.inner.lock().unwrap();
+
+        inner.failures = 0;
+
+        if CircuitState::load(state) != CircuitState::Closed {
+            log::info!("{} circuit closed, the endpoint has recovered", self.name);
+            CircuitState::Closed.store(state);
+        }
+    }
+
+    /// A batch has been given up on
+    pub(crate) fn failed(&self, state: &AtomicU8) {
+        let policy = match &self.policy {
+            Some(policy) => policy,
+            None => return,
+        };
+
+        let mut inner = self.inner.lock().unwrap();
+
+        inner.failures = inner.failures.saturating_add(1);
+
+        let open = match CircuitState::load(state) {
+            CircuitState::Closed => inner.failures >= policy.failure_threshold,
+            CircuitState::HalfOpen => true,
+            CircuitState::Open => false,
+        };
+
+        if open {
+            log::warn!(
+                "{} circuit open after {} failed batches, dropping batches for {:?}",
+                self.name,
+                inner.failures,
+                policy.cooldown
+            );
+            inner.opened_at = Instant::now();
+            CircuitState::Open.store(state);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Share of the budget only traces containing an error can use
+const ERROR_RESERVE: f64 = 0.1;
+
+/// How often the traces dropped over budget are summarized at most
+const SUMMARY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How much data a layer exports at most, see [`NewRelicLayer::with_ingest_budget`]
+///
+/// [`NewRelicLayer::with_ingest_budget`]: crate::NewRelicLayer::with_ingest_budget
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Budget {
+    /// Spans exported per minute, on average
+    pub spans_per_minute: u64,
+    /// Logs exported per minute, on average
+    pub logs_per_minute: u64,
+}
+
+/// Token buckets enforcing a [`Budget`], checked when root spans close
+pub(crate) struct Governor {
+    budget: Budget,
+    state: Mutex<State>,
+}
+
+struct State {
+    spans: f64,
+    logs: f64,
+    refilled_at: Instant,
+    // dropped since the last summary
+    dropped_traces: u64,
+    dropped_spans: u64,
+    dropped_logs: u64,
+    summarized_at: Option<Instant>,
+}
+
+impl Governor {
+    pub(crate) fn new(budget: Budget) -> Self {
+        Governor {
+            budget,
+            state: Mutex::new(State {
+                spans: budget.spans_per_minute as f64,
+                logs: budget.logs_per_minute as f64,
+                refilled_at: Instant::now(),
+                dropped_traces: 0,
+                dropped_spans: 0,
+                dropped_logs: 0,
+                summarized_at: None,
+            }),
+        }
+    }
+
+    /// Whether a trace fits in the budget, taking its spans and logs out of it if so
+    ///
+    /// Traces without an error can't use the last tenth of the budget, kept for the ones with.
+    pub(crate) fn admit(&self, spans: usize, logs: usize, error: bool) -> bool {
+        let spans_per_minute = self.budget.spans_per_minute as f64;
+        let logs_per_minute = self.budget.logs_per_minute as f64;
+
+        let mut state = self.state.lock().unwrap();
+
+        let now = Instant::now();
+        let minutes = now.duration_since(state.refilled_at).as_secs_f64() / 60.0;
+        state.spans = (state.spans + minutes * spans_per_minute).min(spans_per_minute);
+        state.logs = (state.logs + minutes * logs_per_minute).min(logs_per_minute);
+        state.refilled_at = now;
+
+        let reserve = if error { 0.0 } else { ERROR_RESERVE };
+
+        if spans as f64 <= state.spans - reserve * spans_per_minute
+            && logs as f64 <= state.logs - reserve * logs_per_minute
+        {
+            state.spans -= spans as f64;
+            state.logs -= logs as f64;
+            return true;
+        }
+
+        state.dropped_traces += 1;
+        state.dropped_spans += spans as u64;
+        state.dropped_logs += logs as u64;
+
+        if state
+            .summarized_at
+            .is_none_or(|at| at.elapsed() >= SUMMARY_INTERVAL)
+        {
+            log::warn!(
+                "over the ingest budget, dropped {} traces with {} spans and {} logs, spans_per_minute={}, logs_per_minute={}",
+                state.dropped_traces,
+                state.dropped_spans,
+                state.dropped_logs,
+                self.budget.spans_per_minute,
+                self.budget.logs_per_minute
+            );
+
+            state.dropped_traces = 0;
+            state.dropped_spans = 0;
+            state.dropped_logs = 0;
+            state.summarized_at = Some(now);
+        }
+
+        false
+    }
+}
@@ -0,0 +1,77 @@
+use serde::Serialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::utils::{now, serialize_system_time};
+
+/// Appends lines of JSON to a local file, rotated by size
+///
+/// Used for capturing outgoing request bodies and by [`FileExporter`](crate::FileExporter).
+pub(crate) struct Capture {
+    path: PathBuf,
+    max_bytes: u64,
+    file: File,
+    written: u64,
+}
+
+#[derive(Serialize)]
+struct Record<'a, T: Serialize> {
+    endpoint: &'a str,
+    #[serde(serialize_with = "serialize_system_time")]
+    timestamp: SystemTime,
+    body: T,
+}
+
+impl Capture {
+    pub(crate) fn open(path: &Path, max_bytes: u64) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Capture {
+            path: path.to_path_buf(),
+            max_bytes,
+            written: file.metadata()?.len(),
+            file,
+        })
+    }
+
+    pub(crate) fn write<T: Serialize>(&mut self, endpoint: &str, body: T) -> io::Result<()> {
+        self.append(&Record {
+            endpoint,
+            timestamp: now(),
+            body,
+        })
+    }
+
+    /// Append a value as a line of JSON, rotating the file beforehand if needed
+    pub(crate) fn append<T: Serialize>(&mut self, value: &T) -> io::Result<()> {
+        let mut line = serde_json::to_vec(value)?;
+        line.push(b'\n');
+
+        if self.written > 0 && self.written + line.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+
+        self.file.write_all(&line)?;
+        self.written += line.len() as u64;
+
+        Ok(())
+    }
+
+    /// Move the current file to `<path>.1`, replacing the previous one, and start a new file
+    fn rotate(&mut self) -> io::Result<()> {
+        let mut rotated = self.path.clone().into_os_string();
+        rotated.push(".1");
+
+        fs::rename(&self.path, rotated)?;
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.written = 0;
+
+        Ok(())
+    }
+}
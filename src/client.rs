@@ -0,0 +1,103 @@
+//! Reqwest middleware creating a client span for each outgoing request, linking the trace of
+//! the called service to the current one
+//!
+//! Client spans get `span.kind = client`, `http.method`, `http.url` without its query,
+//! `peer.service` and, once the response is received, `http.status_code`. Spans of `5xx`
+//! responses and failed requests, e.g. timed out, get `otel.status_code = ERROR`.
+//!
+//! ```rust,no_run
+//! use reqwest_middleware::ClientBuilder;
+//! use tracing_newrelic::client::ClientSpans;
+//!
+//! let client = ClientBuilder::new(reqwest::Client::new())
+//!     .with(ClientSpans::new().with_peer_service("billing"))
+//!     .build();
+//! ```
+
+use reqwest::header::HeaderValue;
+use reqwest::{Request, Response};
+use reqwest_middleware::{Middleware, Next, Result};
+use task_local_extensions::Extensions;
+use tracing::{field::Empty, Instrument};
+
+use crate::propagation::{traceparent, TRACEPARENT};
+
+/// Wrap each request of a `reqwest_middleware` client into a client span, see the
+/// [module documentation](self)
+#[derive(Clone, Debug, Default)]
+pub struct ClientSpans {
+    peer_service: Option<String>,
+}
+
+impl ClientSpans {
+    /// A middleware naming the called service after the host of the url, see
+    /// [`with_peer_service`](ClientSpans::with_peer_service)
+    pub fn new() -> Self {
+        ClientSpans::default()
+    }
+
+    /// Name of the called service, recorded as `peer.service`
+    pub fn with_peer_service(mut self, name: impl Into<String>) -> Self {
+        self.peer_service = Some(name.into());
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for ClientSpans {
+    async fn handle(
+        &self,
+        mut request: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> Result<Response> {
+        let mut url = request.url().clone();
+        url.set_query(None);
+        url.set_fragment(None);
+        let _ = url.set_username("");
+        let _ = url.set_password(None);
+
+        let host = url.host_str().unwrap_or_default().to_string();
+        let method = request.method().as_str().to_string();
+
+        let span = tracing::info_span!(
+            "request",
+            span.kind = "client",
+            name = %format_args!("{} {}", method, host),
+            http.method = method.as_str(),
+            http.url = url.as_str(),
+            peer.service = self.peer_service.as_deref().unwrap_or(&host),
+            http.status_code = Empty,
+            otel.status_code = Empty,
+            otel.status_description = Empty,
+        );
+
+        if let Some(value) = traceparent(&span).and_then(|value| HeaderValue::from_str(&value).ok())
+        {
+            request.headers_mut().insert(TRACEPARENT, value);
+        }
+
+        let result = next.run(request, extensions).instrument(span.clone()).await;
+
+        match &result {
+            Ok(response) => {
+                let status = response.status();
+                span.record("http.status_code", status.as_u16());
+
+                if status.is_server_error() {
+                    span.record("otel.status_code", "ERROR");
+                    span.record(
+                        "otel.status_description",
+                        status.canonical_reason().unwrap_or(status.as_str()),
+                    );
+                }
+            }
+            Err(error) => {
+                span.record("otel.status_code", "ERROR");
+                span.record("otel.status_description", error.to_string().as_str());
+            }
+        }
+
+        result
+    }
+}
@@ -0,0 +1,149 @@
+use flate2::{Compress, Crc, FlushCompress, Status};
+use serde::Serialize;
+use std::io;
+use std::sync::Mutex;
+
+/// Header of the gzip members, without file name nor modification time, see RFC 1952
+const GZIP_HEADER: [u8; 8] = [0x1f, 0x8b, 8, 0, 0, 0, 0, 0];
+/// Header, then CRC32 and length of the uncompressed data
+const GZIP_OVERHEAD: usize = 18;
+
+/// How request bodies are compressed, see [`Api::with_compression`]
+///
+/// [`Api::with_compression`]: crate::Api::with_compression
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    /// Gzip with the given level, from `0` (no compression) to `9` (best compression)
+    Gzip(u32),
+    /// Plain JSON, sent without a `Content-Encoding` header
+    None,
+}
+
+impl Default for Compression {
+    /// Gzip with level `1`, the fastest
+    fn default() -> Self {
+        Compression::Gzip(1)
+    }
+}
+
+impl Compression {
+    /// Value of the `Content-Encoding` header
+    pub(crate) fn content_encoding(&self) -> Option<&'static str> {
+        match self {
+            Compression::Gzip(_) => Some("gzip"),
+            Compression::None => None,
+        }
+    }
+}
+
+/// Buffers reused from one request body to the next, one per body being encoded at once
+pub(crate) struct Encoders {
+    compression: Compression,
+    pool: Mutex<Vec<Scratch>>,
+    // capacity a buffer shrinks back to after an unusually large body
+    max_capacity: usize,
+}
+
+impl Encoders {
+    pub(crate) fn new(compression: Compression, max_capacity: usize) -> Self {
+        Encoders {
+            compression,
+            pool: Mutex::default(),
+            max_capacity,
+        }
+    }
+
+    pub(crate) fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Serialize `data` to JSON and compress it
+    pub(crate) fn encode<T: Serialize>(&self, data: T) -> io::Result<Vec<u8>> {
+        let mut scratch = self.pool.lock().unwrap().pop().unwrap_or_default();
+
+        let body = scratch.encode(self.compression, data);
+
+        if scratch.json.capacity() > self.max_capacity {
+            scratch.json.clear();
+            scratch.json.shrink_to(self.max_capacity);
+        }
+
+        self.pool.lock().unwrap().push(scratch);
+
+        body
+    }
+}
+
+/// Serialized JSON and compressor state, kept between bodies
+#[derive(Default)]
+struct Scratch {
+    json: Vec<u8>,
+    // raw deflate state, along with its level
+    deflate: Option<(u32, Compress)>,
+}
+
+impl Scratch {
+    fn encode<T: Serialize>(&mut self, compression: Compression, data: T) -> io::Result<Vec<u8>> {
+        self.json.clear();
+        serde_json::to_writer(&mut self.json, &data)?;
+
+        let level = match compression {
+            Compression::Gzip(level) => level.min(9),
+            // copied rather than taken, an exact copy allocates less than regrowing the buffer
+            Compression::None => return Ok(self.json.clone()),
+        };
+
+        let deflate = match &mut self.deflate {
+            Some((current, deflate)) if *current == level => {
+                deflate.reset();
+                deflate
+            }
+            deflate => {
+                let compress = Compress::new(flate2::Compression::new(level), false);
+                &mut deflate.insert((level, compress)).1
+            }
+        };
+
+        gzip(deflate, level, &self.json)
+    }
+}
+
+/// Compress `json` into a single gzip member
+fn gzip(deflate: &mut Compress, level: u32, json: &[u8]) -> io::Result<Vec<u8>> {
+    // same extra flags as `flate2`, then an unknown OS
+    let xfl = match level {
+        9 => 2,
+        1 => 4,
+        _ => 0,
+    };
+
+    let mut body = Vec::with_capacity(json.len() / 4 + GZIP_OVERHEAD);
+    body.extend_from_slice(&GZIP_HEADER);
+    body.extend_from_slice(&[xfl, 255]);
+
+    let mut input = json;
+
+    loop {
+        let before = deflate.total_in();
+
+        let status = deflate
+            .compress_vec(input, &mut body, FlushCompress::Finish)
+            .map_err(io::Error::other)?;
+
+        input = &input[(deflate.total_in() - before) as usize..];
+
+        if status == Status::StreamEnd {
+            break;
+        }
+
+        body.reserve(json.len() / 4 + GZIP_OVERHEAD);
+    }
+
+    let mut crc = Crc::new();
+    crc.update(json);
+
+    body.extend_from_slice(&crc.sum().to_le_bytes());
+    body.extend_from_slice(&(json.len() as u32).to_le_bytes());
+
+    Ok(body)
+}
@@ -0,0 +1,179 @@
+//! Database span conventions, see [`NewRelicLayer::with_db_statement_handling`]
+//!
+//! New Relic groups database spans by `db.system` and `db.operation`, which are always exported
+//! as they were recorded. Statements, recorded as `db.statement` or `db.query.text`, often inline
+//! their parameters and can be shortened or obfuscated before being exported.
+//!
+//! [`NewRelicLayer::with_db_statement_handling`]: crate::NewRelicLayer::with_db_statement_handling
+
+use crate::types::{NewrAttributes, Value};
+
+/// Attributes holding the statement of a database span
+const STATEMENT_KEYS: &[&str] = &["db.statement", "db.query.text"];
+
+/// Decide what happens to the statements of database spans
+#[derive(Clone, Debug, PartialEq)]
+pub enum DbStatementPolicy {
+    /// Drop them
+    Omit,
+    /// Keep their first bytes, at most the given number
+    Truncate(usize),
+    /// Replace their literals with `?`, see [`obfuscate`]
+    Obfuscate,
+}
+
+impl DbStatementPolicy {
+    pub(crate) fn apply(&self, attributes: &mut NewrAttributes) {
+        for key in STATEMENT_KEYS.iter() {
+            let statement = match attributes.get(key) {
+                Some(Value::String(statement)) => statement,
+                _ => continue,
+            };
+
+            let statement = match self {
+                DbStatementPolicy::Omit => {
+                    attributes.remove(key);
+                    continue;
+                }
+                DbStatementPolicy::Truncate(len) => truncate(statement, *len).to_string(),
+                DbStatementPolicy::Obfuscate => obfuscate(statement),
+            };
+
+            attributes.insert(*key, statement);
+        }
+    }
+}
+
+fn truncate(statement: &str, len: usize) -> &str {
+    if statement.len() <= len {
+        return statement;
+    }
+
+    let mut len = len;
+
+    while !statement.is_char_boundary(len) {
+        len -= 1;
+    }
+
+    &statement[..len]
+}
+
+/// Replace the string and numeric literals of a SQL statement with `?`, collapsing `IN` lists
+///
+/// ```rust
+/// use tracing_newrelic::db::obfuscate;
+///
+/// assert_eq!(
+///     obfuscate("SELECT * FROM users WHERE name = 'ferris' AND id IN (1, 2, 3)"),
+///     "SELECT * FROM users WHERE name = ? AND id IN (?)"
+/// );
+/// ```
+///
+/// Quoted identifiers, e.g. `"users"`, and placeholders, e.g. `$1`, are kept.
+pub fn obfuscate(statement: &str) -> String {
+    let mut obfuscated = String::with_capacity(statement.len());
+    let mut chars = statement.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            // `''` and `\'` escape a quote
+            '\'' => {
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '\'' if chars.peek() == Some(&'\'') => {
+                            chars.next();
+                        }
+                        '\'' => break,
+                        _ => {}
+                    }
+                }
+
+                obfuscated.push('?');
+            }
+            '"' | '`' => {
+                obfuscated.push(c);
+
+                for quoted in chars.by_ref() {
+                    obfuscated.push(quoted);
+
+                    if quoted == c {
+                        break;
+                    }
+                }
+            }
+            // digits ending an identifier, e.g. `t1` or `$1`, aren't literals
+            c if c.is_ascii_digit() && !obfuscated.ends_with(is_identifier) => {
+                // decimals, exponents and hexadecimals alike
+                while let Some(c) = chars.peek() {
+                    if !c.is_ascii_alphanumeric() && *c != '.' {
+                        break;
+                    }
+
+                    chars.next();
+                }
+
+                obfuscated.push('?');
+            }
+            c => obfuscated.push(c),
+        }
+    }
+
+    collapse_in_lists(&obfuscated)
+}
+
+fn is_identifier(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// Replace `IN (?, ?, ?)` with `IN (?)`, so that statements don't differ by their number of values
+fn collapse_in_lists(statement: &str) -> String {
+    let bytes = statement.as_bytes();
+    let mut collapsed = String::with_capacity(statement.len());
+    let mut copied = 0;
+    let mut i = 0;
+
+    while i + 2 <= bytes.len() {
+        let is_in = bytes[i..i + 2].eq_ignore_ascii_case(b"in")
+            && (i == 0 || !is_identifier(bytes[i - 1] as char))
+            && !bytes.get(i + 2).is_some_and(|b| is_identifier(*b as char));
+
+        if !is_in {
+            i += 1;
+            continue;
+        }
+
+        let open = i
+            + 2
+            + bytes[i + 2..]
+                .iter()
+                .take_while(|b| b.is_ascii_whitespace())
+                .count();
+
+        if bytes.get(open) != Some(&b'(') {
+            i += 2;
+            continue;
+        }
+
+        let close = open
+            + 1
+            + bytes[open + 1..]
+                .iter()
+                .take_while(|b| **b == b'?' || **b == b',' || b.is_ascii_whitespace())
+                .count();
+
+        if bytes.get(close) == Some(&b')') && bytes[open + 1..close].contains(&b'?') {
+            collapsed.push_str(&statement[copied..=open]);
+            collapsed.push_str("?)");
+            copied = close + 1;
+            i = copied;
+        } else {
+            i = open;
+        }
+    }
+
+    collapsed.push_str(&statement[copied..]);
+    collapsed
+}
@@ -0,0 +1,136 @@
+use std::error::Error;
+use std::fmt;
+
+/// Invalid configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// Api key is empty
+    EmptyApiKey,
+    /// Api key contains whitespace, even after trimming
+    ApiKeyWhitespace,
+    /// Custom endpoint doesn't start with `http://` or `https://`
+    EndpointMissingScheme {
+        /// Name of the misconfigured field
+        field: &'static str,
+        /// The endpoint as configured
+        endpoint: String,
+    },
+    /// Proxy url can't be parsed
+    InvalidProxy {
+        /// The proxy as configured
+        proxy: String,
+    },
+    /// CA certificate isn't a valid PEM-encoded certificate
+    InvalidCertificate,
+    /// Regular expression of a PII rule can't be compiled
+    InvalidPattern {
+        /// The pattern as given
+        pattern: String,
+    },
+    /// No http transport is available without the `reqwest` feature, see [`Api::with_transport`]
+    ///
+    /// [`Api::with_transport`]: crate::Api::with_transport
+    MissingTransport,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::EmptyApiKey => write!(f, "api key is empty"),
+            ConfigError::ApiKeyWhitespace => write!(f, "api key contains whitespace"),
+            ConfigError::EndpointMissingScheme { field, endpoint } => write!(
+                f,
+                "{}: custom endpoint missing scheme, got '{}'",
+                field, endpoint
+            ),
+            ConfigError::InvalidProxy { proxy } => write!(f, "invalid proxy url '{}'", proxy),
+            ConfigError::InvalidCertificate => write!(f, "invalid CA certificate"),
+            ConfigError::InvalidPattern { pattern } => {
+                write!(f, "invalid regular expression '{}'", pattern)
+            }
+            ConfigError::MissingTransport => write!(f, "no http transport to send requests"),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+/// Invalid configuration read from the environment, see [`layer_from_env`]
+///
+/// [`layer_from_env`]: crate::layer_from_env
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnvError {
+    /// Neither `NEW_RELIC_API_KEY` nor `NEW_RELIC_LICENSE_KEY` is set
+    MissingKey,
+    /// A variable is set to an unexpected value
+    Malformed {
+        /// Name of the variable
+        variable: &'static str,
+        /// The value as set
+        value: String,
+        /// What was expected instead
+        expected: &'static str,
+    },
+    /// The configuration read is invalid
+    Config(ConfigError),
+}
+
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvError::MissingKey => write!(
+                f,
+                "missing NEW_RELIC_API_KEY or NEW_RELIC_LICENSE_KEY environment variable"
+            ),
+            EnvError::Malformed {
+                variable,
+                value,
+                expected,
+            } => write!(f, "{}: expected {}, got '{}'", variable, expected, value),
+            EnvError::Config(err) => err.fmt(f),
+        }
+    }
+}
+
+impl Error for EnvError {}
+
+/// Data dropped for good while exporting, see [`Api::with_error_handler`]
+///
+/// [`Api::with_error_handler`]: crate::Api::with_error_handler
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportError {
+    /// Url of the request
+    pub endpoint: String,
+    /// Status of the last response, `None` if there was no response
+    pub status: Option<u16>,
+    /// Beginning of the last response body, or why no request was sent
+    pub body: String,
+    /// `requestId` of the last response, to be given to New Relic support
+    pub request_id: Option<String>,
+    /// Number of spans dropped
+    pub spans_dropped: usize,
+    /// Number of logs dropped
+    pub logs_dropped: usize,
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "dropped {} spans and {} logs sent to {}",
+            self.spans_dropped, self.logs_dropped, self.endpoint
+        )?;
+
+        if let Some(status) = self.status {
+            write!(f, ", got {} response", status)?;
+        }
+
+        if let Some(request_id) = &self.request_id {
+            write!(f, ", request id {}", request_id)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for ExportError {}
@@ -0,0 +1,280 @@
+use futures_util::future::{join_all, BoxFuture};
+use serde::Serialize;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::api::Api;
+use crate::capture::Capture;
+use crate::types::{NewrLogs, NewrSpans};
+
+/// Destination of the data collected by [`NewRelicLayer`]
+///
+/// The exporter is driven by the worker thread, inside a single-threaded Tokio runtime, or by a
+/// task on the runtime given to [`layer_on_runtime`](crate::layer_on_runtime).
+///
+/// [`NewRelicLayer`]: crate::NewRelicLayer
+pub trait Exporter: Send + 'static {
+    /// Export the logs and spans of a completed trace
+    fn export(&mut self, logs: NewrLogs, spans: NewrSpans) -> BoxFuture<'_, ()>;
+
+    /// Called once when the layer is dropped, after the last `export`
+    fn shutdown(&mut self) -> BoxFuture<'_, ()>;
+
+    /// How often the worker calls [`tick`](Exporter::tick), `None` means never. Default to `None`.
+    fn tick_interval(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Called periodically by the worker, e.g. to flush data on a timer
+    fn tick(&mut self) -> BoxFuture<'_, ()> {
+        Box::pin(async {})
+    }
+
+    /// Export everything buffered so far, returning whether it all succeeded. Default to `true`.
+    ///
+    /// Called when [`Handle::flush`](crate::Handle::flush) is requested.
+    fn flush(&mut self) -> BoxFuture<'_, bool> {
+        Box::pin(async { true })
+    }
+
+    /// Number of traces buffered and not exported yet, reported when shutdown times out. Default to `0`.
+    fn pending(&self) -> usize {
+        0
+    }
+}
+
+impl Exporter for Api {
+    fn export(&mut self, logs: NewrLogs, spans: NewrSpans) -> BoxFuture<'_, ()> {
+        self.push(logs, spans);
+        Box::pin(async {})
+    }
+
+    fn shutdown(&mut self) -> BoxFuture<'_, ()> {
+        Box::pin(Api::shutdown(self))
+    }
+
+    fn tick_interval(&self) -> Option<Duration> {
+        Some(self.metrics_interval())
+    }
+
+    fn tick(&mut self) -> BoxFuture<'_, ()> {
+        self.push_metrics();
+        self.close_idle_routes();
+        Box::pin(async {})
+    }
+
+    fn flush(&mut self) -> BoxFuture<'_, bool> {
+        Box::pin(Api::flush(self))
+    }
+
+    fn pending(&self) -> usize {
+        Api::pending(self)
+    }
+}
+
+/// An [`Exporter`] pretty-printing every payload as JSON instead of sending it, for local development
+///
+/// Each trace is printed as one JSON object, `{ "logs": [...], "spans": [...] }`, mirroring the
+/// request bodies sent to the Log and Trace APIs, so the output can be piped into `jq`.
+pub struct ConsoleExporter {
+    writer: Box<dyn Write + Send>,
+}
+
+/// A trace as printed by [`ConsoleExporter`] and written by [`FileExporter`]
+#[derive(Serialize)]
+struct Payload<'a> {
+    logs: [&'a NewrLogs; 1],
+    spans: [&'a NewrSpans; 1],
+}
+
+impl ConsoleExporter {
+    /// Create an exporter printing to stderr
+    pub fn new() -> Self {
+        ConsoleExporter::with_writer(io::stderr())
+    }
+
+    /// Create an exporter printing to the given writer
+    pub fn with_writer(writer: impl Write + Send + 'static) -> Self {
+        ConsoleExporter {
+            writer: Box::new(writer),
+        }
+    }
+}
+
+impl Default for ConsoleExporter {
+    fn default() -> Self {
+        ConsoleExporter::new()
+    }
+}
+
+impl Exporter for ConsoleExporter {
+    fn export(&mut self, logs: NewrLogs, spans: NewrSpans) -> BoxFuture<'_, ()> {
+        let payload = Payload {
+            logs: [&logs],
+            spans: [&spans],
+        };
+
+        let result = serde_json::to_writer_pretty(&mut self.writer, &payload)
+            .map_err(io::Error::from)
+            .and_then(|_| writeln!(self.writer));
+
+        if let Err(err) = result {
+            log::warn!("failed to print payload: {}", err);
+        }
+
+        Box::pin(async {})
+    }
+
+    fn shutdown(&mut self) -> BoxFuture<'_, ()> {
+        let _ = self.writer.flush();
+        Box::pin(async {})
+    }
+}
+
+/// An [`Exporter`] appending every payload to a local file, to be uploaded later with [`Api::replay_file`]
+///
+/// Each trace is written as one line of JSON, `{ "logs": [...], "spans": [...] }`. When the file
+/// would grow beyond `max_bytes`, it's moved to `<path>.1` and a new one is started.
+pub struct FileExporter {
+    file: Capture,
+}
+
+impl FileExporter {
+    /// Create an exporter appending to the file at `path`, creating it if needed
+    pub fn open(path: impl AsRef<Path>, max_bytes: u64) -> io::Result<Self> {
+        Ok(FileExporter {
+            file: Capture::open(path.as_ref(), max_bytes)?,
+        })
+    }
+}
+
+impl Exporter for FileExporter {
+    fn export(&mut self, logs: NewrLogs, spans: NewrSpans) -> BoxFuture<'_, ()> {
+        let payload = Payload {
+            logs: [&logs],
+            spans: [&spans],
+        };
+
+        if let Err(err) = self.file.append(&payload) {
+            log::warn!("failed to write payload: {}", err);
+        }
+
+        Box::pin(async {})
+    }
+
+    fn shutdown(&mut self) -> BoxFuture<'_, ()> {
+        Box::pin(async {})
+    }
+}
+
+/// An [`Exporter`] sending every trace to several exporters, e.g. to mirror the data into another
+/// New Relic account
+///
+/// Each exporter works on its own: an [`Api`] keeps its own queues, retries and [`Stats`], so a
+/// failing destination doesn't hold back or drop the data of the others. Span duration metrics
+/// aren't collected, see [`NewRelicLayer::with_metrics`].
+///
+/// ```no_run
+/// use tracing_newrelic::{Api, TeeExporter};
+///
+/// let production = Api::from("PRODUCTION-KEY");
+/// let sandbox = Api::from("SANDBOX-KEY");
+/// let sandbox_stats = sandbox.stats();
+///
+/// let layer = tracing_newrelic::layer_with_exporter(TeeExporter::new().with(production).with(sandbox));
+/// ```
+///
+/// [`Stats`]: crate::Stats
+/// [`NewRelicLayer::with_metrics`]: crate::NewRelicLayer::with_metrics
+#[derive(Default)]
+pub struct TeeExporter {
+    exporters: Vec<Tee>,
+}
+
+/// An exporter of a [`TeeExporter`], and when it was last ticked
+struct Tee {
+    exporter: Box<dyn Exporter>,
+    last_tick: Instant,
+}
+
+impl TeeExporter {
+    /// Create an exporter sending to nothing yet
+    pub fn new() -> Self {
+        TeeExporter::default()
+    }
+
+    /// Send every trace to the given exporter as well
+    pub fn with(mut self, exporter: impl Exporter) -> Self {
+        self.exporters.push(Tee {
+            exporter: Box::new(exporter),
+            last_tick: Instant::now(),
+        });
+        self
+    }
+}
+
+impl Exporter for TeeExporter {
+    fn export(&mut self, logs: NewrLogs, spans: NewrSpans) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            join_all(
+                self.exporters
+                    .iter_mut()
+                    .map(|tee| tee.exporter.export(logs.clone(), spans.clone())),
+            )
+            .await;
+        })
+    }
+
+    fn shutdown(&mut self) -> BoxFuture<'_, ()> {
+        Box::pin(async move {
+            join_all(self.exporters.iter_mut().map(|tee| tee.exporter.shutdown())).await;
+        })
+    }
+
+    fn tick_interval(&self) -> Option<Duration> {
+        self.exporters
+            .iter()
+            .filter_map(|tee| tee.exporter.tick_interval())
+            .min()
+    }
+
+    /// Tick each exporter at its own interval
+    fn tick(&mut self) -> BoxFuture<'_, ()> {
+        let now = Instant::now();
+        // ticks run late by up to a period, don't wait for the next one because of that
+        let slack = self.tick_interval().unwrap_or_default() / 2;
+
+        let mut ticks = Vec::new();
+
+        for tee in &mut self.exporters {
+            match tee.exporter.tick_interval() {
+                Some(interval) if now.duration_since(tee.last_tick) + slack >= interval => {
+                    tee.last_tick = now;
+                    ticks.push(tee.exporter.tick());
+                }
+                _ => {}
+            }
+        }
+
+        Box::pin(async move {
+            join_all(ticks).await;
+        })
+    }
+
+    fn flush(&mut self) -> BoxFuture<'_, bool> {
+        Box::pin(async move {
+            join_all(self.exporters.iter_mut().map(|tee| tee.exporter.flush()))
+                .await
+                .into_iter()
+                .all(|flushed| flushed)
+        })
+    }
+
+    fn pending(&self) -> usize {
+        self.exporters
+            .iter()
+            .map(|tee| tee.exporter.pending())
+            .sum()
+    }
+}
@@ -0,0 +1,206 @@
+//! Tower middleware creating a New Relic friendly span for each gRPC call served by tonic
+//!
+//! Call spans get `span.kind = server`, a `name` made of the service and the method, e.g.
+//! `demo.Greeter/SayHello`, `rpc.system = grpc`, `rpc.service`, `rpc.method` and, once the
+//! response is ready, `rpc.grpc.status_code`. Calls failing with `UNKNOWN`, `DEADLINE_EXCEEDED`,
+//! `UNIMPLEMENTED`, `INTERNAL`, `UNAVAILABLE` or `DATA_LOSS` get `otel.status_code = ERROR`.
+//!
+//! Incoming `traceparent` metadata continues the trace of the caller, and [`inject`] adds it
+//! to outgoing calls, see [`propagation`](crate::propagation).
+//!
+//! ```rust,ignore
+//! use tonic::transport::Server;
+//! use tracing_newrelic::grpc::GrpcSpanLayer;
+//!
+//! Server::builder()
+//!     .layer(GrpcSpanLayer::new())
+//!     .add_service(greeter)
+//!     .serve(addr)
+//!     .await?;
+//! ```
+//!
+//! Statuses sent in trailers, after a streamed response, aren't recorded.
+
+use http::header::HeaderMap;
+use http::{Request, Response};
+use pin_project_lite::pin_project;
+use std::convert::TryFrom;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tonic::metadata::MetadataValue;
+use tonic::{Code, Status};
+use tower_layer::Layer;
+use tower_service::Service;
+use tracing::{field::Empty, Span};
+
+use crate::propagation::{traceparent, TRACEPARENT};
+
+const GRPC_STATUS: &str = "grpc-status";
+const GRPC_MESSAGE: &str = "grpc-message";
+
+/// Wrap tonic services into [`GrpcSpan`]
+#[derive(Clone, Debug, Default)]
+pub struct GrpcSpanLayer {}
+
+impl GrpcSpanLayer {
+    /// A layer creating a span for each call
+    pub fn new() -> Self {
+        GrpcSpanLayer::default()
+    }
+}
+
+impl<S> Layer<S> for GrpcSpanLayer {
+    type Service = GrpcSpan<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcSpan { inner }
+    }
+}
+
+/// Service running each call inside its own span, see [`GrpcSpanLayer`]
+#[derive(Clone, Debug)]
+pub struct GrpcSpan<S> {
+    inner: S,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for GrpcSpan<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Error: fmt::Display,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        // `/{service}/{method}`
+        let path = request.uri().path();
+        let (service, method) = path
+            .trim_start_matches('/')
+            .split_once('/')
+            .unwrap_or((path, ""));
+
+        let span = tracing::info_span!(
+            "call",
+            span.kind = "server",
+            name = %format_args!("{}/{}", service, method),
+            rpc.system = "grpc",
+            rpc.service = service,
+            rpc.method = method,
+            rpc.grpc.status_code = Empty,
+            otel.status_code = Empty,
+            otel.status_description = Empty,
+            traceparent = request
+                .headers()
+                .get(TRACEPARENT)
+                .and_then(|value| value.to_str().ok()),
+        );
+
+        let inner = span.in_scope(|| self.inner.call(request));
+
+        ResponseFuture { inner, span }
+    }
+}
+
+pin_project! {
+    /// Response of a [`GrpcSpan`], recording its status onto the span
+    pub struct ResponseFuture<F> {
+        #[pin]
+        inner: F,
+        span: Span,
+    }
+}
+
+impl<F, ResBody, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<Response<ResBody>, E>>,
+    E: fmt::Display,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let _enter = this.span.enter();
+
+        let result = match this.inner.poll(cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        match &result {
+            Ok(response) => {
+                // sent in trailers when the call succeeds
+                let status = status(response.headers()).unwrap_or_else(|| Status::ok(""));
+
+                this.span
+                    .record("rpc.grpc.status_code", status.code() as i64);
+
+                if is_error(status.code()) {
+                    let message = match status.message() {
+                        "" => status.code().description(),
+                        message => message,
+                    };
+
+                    this.span.record("otel.status_code", "ERROR");
+                    this.span.record("otel.status_description", message);
+                }
+            }
+            Err(error) => {
+                this.span.record("otel.status_code", "ERROR");
+                this.span
+                    .record("otel.status_description", error.to_string().as_str());
+            }
+        }
+
+        Poll::Ready(result)
+    }
+}
+
+/// The status sent in the headers of a response, e.g. a call failing right away
+fn status(headers: &HeaderMap) -> Option<Status> {
+    let mut status = HeaderMap::new();
+
+    for name in [GRPC_STATUS, GRPC_MESSAGE].iter() {
+        if let Some(value) = headers.get(*name) {
+            status.insert(*name, value.clone());
+        }
+    }
+
+    Status::from_header_map(&status)
+}
+
+/// Whether a code is a failure of the server, following OpenTelemetry
+fn is_error(code: Code) -> bool {
+    matches!(
+        code,
+        Code::Unknown
+            | Code::DeadlineExceeded
+            | Code::Unimplemented
+            | Code::Internal
+            | Code::Unavailable
+            | Code::DataLoss
+    )
+}
+
+/// Interceptor adding the `traceparent` of the current span to the metadata of outgoing calls
+///
+/// ```rust,ignore
+/// let client = GreeterClient::with_interceptor(channel, tracing_newrelic::grpc::inject);
+/// ```
+// the signature of tonic interceptors
+#[allow(clippy::result_large_err)]
+pub fn inject(mut request: tonic::Request<()>) -> Result<tonic::Request<()>, Status> {
+    if let Some(value) = traceparent(&Span::current()) {
+        if let Ok(value) = MetadataValue::try_from(value.as_str()) {
+            request.metadata_mut().insert(TRACEPARENT, value);
+        }
+    }
+
+    Ok(request)
+}
@@ -0,0 +1,137 @@
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::oneshot;
+
+use crate::capture::Capture;
+use crate::stats::Stats;
+
+/// A command sent to the worker thread
+pub(crate) enum Command {
+    /// Export everything buffered, then report whether it succeeded
+    Flush(Box<dyn FnOnce(bool) + Send>),
+}
+
+/// A clonable handle controlling a running [`NewRelicLayer`] and its worker thread
+///
+/// [`NewRelicLayer`]: crate::NewRelicLayer
+#[derive(Clone, Default)]
+pub struct Handle {
+    pub(crate) capture: Arc<Mutex<Option<Capture>>>,
+    pub(crate) api_key: Arc<RwLock<Option<String>>>,
+    pub(crate) commands: Option<UnboundedSender<Command>>,
+    pub(crate) stats: Arc<Stats>,
+    pub(crate) disabled: Arc<AtomicBool>,
+}
+
+impl Handle {
+    /// Start appending a copy of every outgoing request body to the file at `path`
+    ///
+    /// Each line is a JSON object containing the `endpoint`, the `timestamp` and the decompressed
+    /// `body`. When the file would grow beyond `max_bytes`, it's moved to `<path>.1` and a new one
+    /// is started. Failures to write the file never affect sending.
+    ///
+    /// Only request bodies sent by [`Api`] are captured.
+    ///
+    /// [`Api`]: crate::Api
+    pub fn start_capture(&self, path: impl AsRef<Path>, max_bytes: u64) -> io::Result<()> {
+        let capture = Capture::open(path.as_ref(), max_bytes)?;
+
+        *self.capture.lock().unwrap() = Some(capture);
+
+        Ok(())
+    }
+
+    /// Turn the layer on or off, e.g. as a kill switch. Enabled by default.
+    ///
+    /// While disabled, new spans, events and recorded values are ignored, and traces whose root
+    /// span closes are dropped instead of being queued for export. Spans already open still close
+    /// as usual.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.disabled.store(!enabled, Ordering::Relaxed);
+    }
+
+    /// Whether the layer is enabled, see [`set_enabled`](Handle::set_enabled)
+    pub fn is_enabled(&self) -> bool {
+        !self.disabled.load(Ordering::Relaxed)
+    }
+
+    /// Replace the key sent with every request from now on, e.g. once it has been rotated
+    ///
+    /// Queued data and requests being retried are sent with the new key. Data rejected with the
+    /// old key, e.g. by a `403` response, is reported to the error handler, see
+    /// [`Api::with_error_handler`], which can trigger the rotation. Blank keys are ignored.
+    /// Traces routed to another account keep their own key, see [`Api::with_account_router`].
+    ///
+    /// Only affects [`Api`].
+    ///
+    /// [`Api`]: crate::Api
+    /// [`Api::with_error_handler`]: crate::Api::with_error_handler
+    /// [`Api::with_account_router`]: crate::Api::with_account_router
+    pub fn set_api_key(&self, key: impl Into<String>) {
+        let key = key.into();
+        let key = key.trim();
+
+        if key.is_empty() {
+            log::warn!("ignoring blank New Relic key");
+            return;
+        }
+
+        *self.api_key.write().unwrap() = Some(key.to_string());
+    }
+
+    /// Stop capturing request bodies
+    pub fn stop_capture(&self) {
+        *self.capture.lock().unwrap() = None;
+    }
+
+    /// Counters of the data exported so far, e.g. for a health check endpoint
+    ///
+    /// Only [`Api`] updates the sending counters.
+    ///
+    /// [`Api`]: crate::Api
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Export every trace completed so far and wait for it, returning whether everything was accepted
+    ///
+    /// Useful before the process is frozen, e.g. at the end of an AWS Lambda invocation.
+    /// Returns `false` if the worker thread isn't running anymore.
+    pub async fn flush(&self) -> bool {
+        let (tx, rx) = oneshot::channel();
+
+        if !self.send(Command::Flush(Box::new(move |succeeded| {
+            let _ = tx.send(succeeded);
+        }))) {
+            return false;
+        }
+
+        rx.await.unwrap_or(false)
+    }
+
+    /// Same as [`flush`](Handle::flush), but blocks the current thread for at most `timeout`
+    ///
+    /// Returns `false` if the timeout elapsed first, the flush keeps going in the background.
+    pub fn flush_timeout(&self, timeout: Duration) -> bool {
+        let (tx, rx) = mpsc::channel();
+
+        if !self.send(Command::Flush(Box::new(move |succeeded| {
+            let _ = tx.send(succeeded);
+        }))) {
+            return false;
+        }
+
+        rx.recv_timeout(timeout).unwrap_or(false)
+    }
+
+    fn send(&self, command: Command) -> bool {
+        match &self.commands {
+            Some(commands) => commands.send(command).is_ok(),
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! Tower middleware creating a New Relic friendly span for each HTTP request, usable with axum
+//! and hyper
+//!
+//! Request spans get `span.kind = server`, a `name` made of the method and the route, e.g.
+//! `GET /users/:id`, `http.method`, `http.route` and, once the response is ready,
+//! `http.status_code`. Spans of `5xx` responses and failed requests get `otel.status_code = ERROR`.
+//!
+//! Requests with a `traceparent` header continue the trace of the caller, see
+//! [`propagation`](crate::propagation).
+//!
+//! ```rust,ignore
+//! use axum::{extract::MatchedPath, routing::get, Router};
+//! use tracing_newrelic::http::RequestSpanLayer;
+//!
+//! let app = Router::new()
+//!     .route("/users/:id", get(user))
+//!     .layer(RequestSpanLayer::new().with_route(|extensions| {
+//!         extensions
+//!             .get::<MatchedPath>()
+//!             .map(|path| path.as_str().to_string())
+//!     }));
+//! ```
+
+use http::{Extensions, Request, Response};
+use pin_project_lite::pin_project;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower_layer::Layer;
+use tower_service::Service;
+use tracing::{field::Empty, Span};
+
+use crate::propagation::TRACEPARENT;
+
+type Route = dyn Fn(&Extensions) -> Option<String> + Send + Sync;
+
+/// Wrap services into [`RequestSpan`]
+#[derive(Clone, Default)]
+pub struct RequestSpanLayer {
+    route: Option<Arc<Route>>,
+}
+
+impl RequestSpanLayer {
+    /// A layer naming spans after the request path, see
+    /// [`with_route`](RequestSpanLayer::with_route)
+    pub fn new() -> Self {
+        RequestSpanLayer::default()
+    }
+
+    /// Find the route template matched by a request in its extensions, e.g. axum's `MatchedPath`
+    ///
+    /// Spans of requests without one are named after their path, pair it with
+    /// [`numeric_path_segments`](crate::normalizers::numeric_path_segments) to keep the number
+    /// of transactions low.
+    pub fn with_route<F>(mut self, route: F) -> Self
+    where
+        F: Fn(&Extensions) -> Option<String> + Send + Sync + 'static,
+    {
+        self.route = Some(Arc::new(route));
+        self
+    }
+}
+
+impl<S> Layer<S> for RequestSpanLayer {
+    type Service = RequestSpan<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestSpan {
+            inner,
+            route: self.route.clone(),
+        }
+    }
+}
+
+/// Service running each request inside its own span, see [`RequestSpanLayer`]
+#[derive(Clone)]
+pub struct RequestSpan<S> {
+    inner: S,
+    route: Option<Arc<Route>>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for RequestSpan<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>>,
+    S::Error: fmt::Display,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let route = self
+            .route
+            .as_ref()
+            .and_then(|route| route(request.extensions()));
+
+        let method = request.method().as_str();
+
+        let span = tracing::info_span!(
+            "request",
+            span.kind = "server",
+            name = %format_args!("{} {}", method, route.as_deref().unwrap_or(request.uri().path())),
+            http.method = method,
+            http.route = route.as_deref(),
+            http.status_code = Empty,
+            otel.status_code = Empty,
+            otel.status_description = Empty,
+            traceparent = request
+                .headers()
+                .get(TRACEPARENT)
+                .and_then(|value| value.to_str().ok()),
+        );
+
+        let inner = span.in_scope(|| self.inner.call(request));
+
+        ResponseFuture { inner, span }
+    }
+}
+
+pin_project! {
+    /// Response of a [`RequestSpan`], recording its status code onto the span
+    pub struct ResponseFuture<F> {
+        #[pin]
+        inner: F,
+        span: Span,
+    }
+}
+
+impl<F, ResBody, E> Future for ResponseFuture<F>
+where
+    F: Future<Output = Result<Response<ResBody>, E>>,
+    E: fmt::Display,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let _enter = this.span.enter();
+
+        let result = match this.inner.poll(cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        match &result {
+            Ok(response) => {
+                let status = response.status();
+                this.span.record("http.status_code", status.as_u16());
+
+                if status.is_server_error() {
+                    this.span.record("otel.status_code", "ERROR");
+                    this.span.record(
+                        "otel.status_description",
+                        status.canonical_reason().unwrap_or(status.as_str()),
+                    );
+                }
+            }
+            Err(error) => {
+                this.span.record("otel.status_code", "ERROR");
+                this.span
+                    .record("otel.status_description", error.to_string().as_str());
+            }
+        }
+
+        Poll::Ready(result)
+    }
+}
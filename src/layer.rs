@@ -1,74 +1,1349 @@
-use std::thread::JoinHandle;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
+use tokio::runtime::{self, RuntimeFlavor};
 use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::oneshot;
+use tokio::task;
 use tracing_core::span::{Attributes, Id, Record};
-use tracing_core::{Event, Subscriber};
-use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+use tracing_core::{Event, LevelFilter, Metadata, Subscriber};
+use tracing_subscriber::{
+    layer::Context,
+    registry::{ExtensionsMut, LookupSpan, SpanRef},
+    Layer,
+};
 
-use crate::types::{NewrAttributes, NewrCommon, NewrLog, NewrLogs, NewrSpan, NewrSpans, Value};
-use crate::utils::next_trace_id;
+use crate::api::Api;
+use crate::backlog::{Backlog, DropPolicy};
+use crate::budget::{Budget, Governor};
+use crate::db::DbStatementPolicy;
+use crate::handle::Handle;
+use crate::linking::LinkingDefaults;
+use crate::metrics::Aggregator;
+use crate::normalizers::Normalizer;
+use crate::otel;
+use crate::pii::PiiRules;
+use crate::policy::{self, EmptyValuePolicy, EmptyValues, ExportPolicy, ReservedKeyPolicy};
+use crate::propagation::{self, TRACEPARENT};
+use crate::semconv;
+use crate::spawn::OrphanDetector;
+use crate::stats;
+use crate::summary::TraceSummary;
+use crate::synthetic::Detector;
+use crate::types::{
+    AttributeKeys, NewrAttributes, NewrCommon, NewrLog, NewrLogs, NewrSpan, NewrSpans,
+    TimestampPrecision, Value,
+};
+use crate::utils::{millis, sample, BoundedCache, Generator};
 
 /// A [`Layer`] that collects newrelic-compatible data from `tracing` span/event.
 ///
+/// Span and event fields prefixed with `trace.`, e.g. `trace.customer.tier`, apply to the whole
+/// trace: they are moved into the common attributes of its payloads, without the prefix. When
+/// several spans set the same one, the last recorded value wins.
+///
+/// Span and event fields suffixed with `.json`, e.g. `response.json`, holding a JSON object or
+/// array are recorded as structured values, without the suffix, see [`Json`](crate::Json).
+///
+/// The layer can be cloned, e.g. to be installed in several subscribers: clones share the same
+/// worker thread, queue and stats. The worker is shut down once the last clone is dropped.
+/// Configure the layer before cloning it, clones don't see each other's configuration.
+///
+/// The `newrelic.account` field of a root span routes its trace to another account, see
+/// [`Api::with_account_router`](crate::Api::with_account_router).
+///
+/// Events are linked to the nearest span in scope the layer recorded. Spans it didn't record,
+/// e.g. created before it was installed with [`reload`](tracing_subscriber::reload), are
+/// skipped, so the `span.id` of their events is the one of an ancestor: the linkage is only
+/// approximate.
+///
+/// If the worker thread stops, e.g. the exporter panicked, an error is logged once and the layer
+/// stops collecting data, as if it was disabled with [`Handle::set_enabled`].
+///
 /// [`Layer`]: tracing_subscriber::layer::Layer
+#[derive(Clone)]
 pub struct NewRelicLayer {
     pub(crate) channel: Option<UnboundedSender<(NewrLogs, NewrSpans)>>,
-    pub(crate) handle: Option<JoinHandle<()>>,
+    pub(crate) worker: Option<Arc<Worker>>,
+    pub(crate) shutdown_timeout: Duration,
+    pub(crate) control: Handle,
+    pub(crate) sampling_ratio: f64,
+    pub(crate) export_policy: Arc<ExportPolicy>,
+    pub(crate) budget: Option<Arc<Governor>>,
+    pub(crate) dropped_traces: Arc<AtomicU64>,
+    pub(crate) min_span_duration: Duration,
+    pub(crate) eager_export: Option<Duration>,
+    pub(crate) generator: Arc<Generator>,
+    // common blocks sent recently, reused as long as the attributes stay the same
+    pub(crate) common: Arc<Mutex<BoundedCache<String, NewrCommon>>>,
+    pub(crate) synthetic_detector: Option<Arc<Detector>>,
+    pub(crate) empty_values: EmptyValues,
+    pub(crate) backlog: Arc<Backlog>,
+    pub(crate) capacity: usize,
+    pub(crate) drop_policy: DropPolicy,
+    pub(crate) last_overflow_warning: Arc<Mutex<Option<Instant>>>,
+    pub(crate) service_name: Option<String>,
+    pub(crate) service_version: Option<String>,
+    // `vcs.ref` and `deployment.timestamp`
+    pub(crate) deployment: Option<(String, String)>,
+    pub(crate) entity_guid: Option<String>,
+    pub(crate) entity_name: Option<String>,
+    pub(crate) entity_type: Option<String>,
+    pub(crate) global_attributes: NewrAttributes,
+    pub(crate) common_log_attributes: NewrAttributes,
+    pub(crate) common_span_attributes: NewrAttributes,
+    pub(crate) span_lifecycle_logs: LevelFilter,
+    // whether the worker thread has been found dead already
+    pub(crate) worker_gone: Arc<AtomicBool>,
+    // span durations summarized for the worker, only when exporting to New Relic
+    pub(crate) metrics: Option<Arc<Aggregator>>,
+    pub(crate) metrics_enabled: bool,
+    pub(crate) timestamp_precision: TimestampPrecision,
+    pub(crate) thread_info: bool,
+    pub(crate) log_dedup_window: Option<Duration>,
+    // gathered once when the layer is created, see `process_metadata`
+    pub(crate) process_metadata: Option<NewrAttributes>,
+    pub(crate) default_span_kind: Option<String>,
+    pub(crate) inherited_attributes: Vec<String>,
+    pub(crate) span_processor: Option<Arc<SpanProcessor>>,
+    pub(crate) log_processor: Option<Arc<LogProcessor>>,
+    pub(crate) trace_completed: Option<Arc<TraceCompleted>>,
+    pub(crate) metadata_fields: bool,
+    pub(crate) source_attribute: bool,
+    pub(crate) attribute_keys: AttributeKeys,
+    pub(crate) name_normalizer: Option<Arc<Normalizer>>,
+    pub(crate) pii_rules: Option<PiiRules>,
+    pub(crate) otel_compat: bool,
+    pub(crate) otel_keep_original: bool,
+    pub(crate) db_statement: Option<DbStatementPolicy>,
+    pub(crate) orphan_roots: Option<Arc<OrphanDetector>>,
+}
+
+/// The worker of a layer, shared by its clones
+pub(crate) struct Worker {
+    pub(crate) running: Running,
+    pub(crate) shutdown: oneshot::Sender<Duration>,
+}
+
+/// How to wait for the worker to finish
+pub(crate) enum Running {
+    /// A dedicated thread, joined
+    Thread(JoinHandle<()>),
+    /// A task on an existing runtime, disconnected once it's finished
+    Task(oneshot::Receiver<()>),
+}
+
+impl Running {
+    fn wait(self) {
+        match self {
+            Running::Thread(thread) => {
+                let _ = thread.join();
+            }
+            Running::Task(done) => match runtime::Handle::try_current() {
+                Err(_) => {
+                    let _ = done.blocking_recv();
+                }
+                Ok(current) if current.runtime_flavor() == RuntimeFlavor::MultiThread => {
+                    let _ = task::block_in_place(|| done.blocking_recv());
+                }
+                // blocking would prevent the task from ever running
+                Ok(_) => {}
+            },
+        }
+    }
+}
+
+pub(crate) type SpanProcessor = dyn Fn(&mut NewrSpan) -> bool + Send + Sync;
+
+pub(crate) type LogProcessor = dyn Fn(&mut NewrLog) -> bool + Send + Sync;
+
+pub(crate) type TraceCompleted = dyn Fn(TraceSummary) + Send + Sync;
+
+/// Prefix of the span and event fields applying to the whole trace.
+const TRACE_PREFIX: &str = "trace.";
+
+/// Attributes a span or a log usually gets on top of its fields, e.g. `code.*` or `duration.ms`.
+const LAYER_ATTRIBUTES: usize = 12;
+
+/// Attribute counting the identical logs collapsed into one, see `with_log_dedup`
+const REPEAT_COUNT: &str = "log.repeat_count";
+
+/// Fields of the events standing for `log` records, superseded by their normalized metadata
+#[cfg(feature = "log-compat")]
+const LOG_FIELDS: [&str; 4] = ["log.target", "log.module_path", "log.file", "log.line"];
+
+/// Marker stored in the extensions of every span belonging to an unsampled trace, with the id of
+/// the trace to keep propagating it.
+pub(crate) struct Unsampled {
+    pub(crate) trace_id: Option<String>,
+}
+
+/// Last time an in-progress snapshot of a root span was exported.
+struct LastExport(Instant);
+
+/// Open children of a span, which receive the inherited attributes it records later.
+struct Children(HashSet<Id>);
+
+/// Keys of the attributes a span has inherited from its parent rather than recorded itself.
+struct Inherited(Vec<Cow<'static, str>>);
+
+/// Attributes applying to the whole trace, stored in the extensions of its root span.
+struct TraceAttributes(NewrAttributes);
+
+/// Closed children of a span, assembled once it closes itself or is exported in progress, so that
+/// closing a child only pushes onto them while holding the lock of its parent.
+#[derive(Default)]
+struct Closed(Vec<Bundle>);
+
+/// Spans and logs of a closed span and its descendants.
+struct Bundle {
+    spans: Vec<NewrSpan>,
+    logs: Vec<NewrLog>,
+    // id of the span if it was too short to be kept, its children and logs moving to its parent
+    dropped: Option<Value>,
+    // number of logs the parent had recorded itself when the span closed
+    logs_before: usize,
+}
+
+impl Closed {
+    /// The spans and logs of the closed children of the span `parent_id`, interleaving its own
+    /// `logs` in the order they were recorded
+    fn assemble(self, parent_id: &str, logs: Vec<NewrLog>) -> (Vec<NewrSpan>, Vec<NewrLog>) {
+        if self.0.is_empty() {
+            return (Vec::new(), logs);
+        }
+
+        let mut spans = Vec::new();
+        let mut all_logs = Vec::with_capacity(logs.len());
+        let mut own = logs.into_iter();
+        // number of `logs` moved into `all_logs` so far
+        let mut taken = 0;
+
+        for mut bundle in self.0 {
+            let before = bundle.logs_before.saturating_sub(taken);
+            all_logs.extend(own.by_ref().take(before));
+            taken += before;
+
+            match &bundle.dropped {
+                Some(id) => {
+                    for child in &mut bundle.spans {
+                        if child.attributes.get("parent.id") == Some(id) {
+                            child.attributes.insert("parent.id", parent_id);
+                        }
+                    }
+
+                    for log in &mut bundle.logs {
+                        if log.attributes.get("span.id") == Some(id) {
+                            log.attributes.insert("span.id", parent_id);
+                        }
+                    }
+                }
+                None => bundle.spans[0].attributes.insert("parent.id", parent_id),
+            }
+
+            spans.append(&mut bundle.spans);
+            all_logs.append(&mut bundle.logs);
+        }
+
+        all_logs.extend(own);
+
+        (spans, all_logs)
+    }
+}
+
+/// Time a span has spent entered, on any thread.
+#[derive(Default)]
+struct Timings {
+    busy: Duration,
+    // number of threads currently inside the span, and since when it's been entered
+    depth: usize,
+    entered_at: Option<Instant>,
+}
+
+impl Timings {
+    fn enter(&mut self) {
+        if self.depth == 0 {
+            self.entered_at = Some(Instant::now());
+        }
+        self.depth += 1;
+    }
+
+    fn exit(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+
+        if self.depth == 0 {
+            if let Some(entered_at) = self.entered_at.take() {
+                self.busy += entered_at.elapsed();
+            }
+        }
+    }
+
+    /// Time spent entered so far, including the current interval if still entered
+    fn busy(&self) -> Duration {
+        self.busy + self.entered_at.map_or(Duration::ZERO, |at| at.elapsed())
+    }
+}
+
+impl NewRelicLayer {
+    /// Get a handle controlling this layer and its worker thread
+    pub fn handle(&self) -> Handle {
+        self.control.clone()
+    }
+
+    /// Set the ratio of traces to be sampled, between `0.0` and `1.0`. Default to `1.0`.
+    ///
+    /// The decision is made once when the root span is created,
+    /// and all of its descendant spans and events follow it.
+    ///
+    /// The decision is derived from the trace id rather than a random number: services
+    /// configured with the same ratio keep or drop a distributed trace together, as long
+    /// as they share its trace id. Traces continued from a caller that sampled them, see
+    /// [`propagation`](crate::propagation), are always kept.
+    pub fn with_sampling_ratio(mut self, ratio: f64) -> Self {
+        self.sampling_ratio = ratio.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Set the policy deciding whether a completed trace should be exported. Default to [`ExportPolicy::All`].
+    pub fn with_export_policy(mut self, policy: ExportPolicy) -> Self {
+        self.export_policy = Arc::new(policy);
+        self
+    }
+
+    /// Drop non-root spans lasting less than the given duration. Default to zero.
+    ///
+    /// The children and logs of a dropped span are re-parented onto its parent,
+    /// so the trace stays connected. Root spans are always kept.
+    pub fn with_min_span_duration(mut self, duration: Duration) -> Self {
+        self.min_span_duration = duration;
+        self
+    }
+
+    /// Export a snapshot of root spans that have been open longer than the given duration. Disabled by default.
+    ///
+    /// The snapshot carries the attributes recorded so far and an `in_progress = true` attribute,
+    /// along with the child spans and logs buffered in the root span at that point. It is
+    /// exported at most once per `duration` when a descendant span closes or an event is recorded.
+    /// Snapshots are not subject to the export policy.
+    ///
+    /// The final span is still exported on close with the real `duration.ms`.
+    pub fn with_eager_export(mut self, duration: Duration) -> Self {
+        self.eager_export = Some(duration);
+        self
+    }
+
+    /// Set how long dropping the layer waits for the remaining data to be exported. Default to 10 seconds.
+    ///
+    /// Once elapsed, in-flight requests and retries are abandoned, and the number of traces
+    /// left unsent is logged.
+    pub fn with_shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Set how attributes whose value is an empty string, `"null"` or `"undefined"` are handled.
+    /// Default to [`EmptyValuePolicy::Keep`].
+    ///
+    /// The policy applies to the attributes of spans, logs and common blocks alike.
+    pub fn with_empty_value_policy(mut self, policy: EmptyValuePolicy) -> Self {
+        self.empty_values.default = policy;
+        self
+    }
+
+    /// Same as [`with_empty_value_policy`](NewRelicLayer::with_empty_value_policy), for the given key only
+    pub fn with_empty_value_policy_for(mut self, key: &str, policy: EmptyValuePolicy) -> Self {
+        self.empty_values.per_key.insert(key.to_string(), policy);
+        self
+    }
+
+    /// Mark traces whose root span matches `detector` as synthetic traffic, e.g. health checks
+    ///
+    /// Instead of being dropped, matching traces get a `traffic.synthetic = true` common
+    /// attribute, shared by all of their spans and logs, so they can be filtered out of
+    /// dashboards. See [`synthetic`](crate::synthetic) for built-in detectors.
+    pub fn with_synthetic_detector<F>(mut self, detector: F) -> Self
+    where
+        F: Fn(&NewrSpan) -> bool + Send + Sync + 'static,
+    {
+        self.synthetic_detector = Some(Arc::new(detector));
+        self
+    }
+
+    /// Modify each span right before it's queued for export, dropping it if `processor` returns `false`
+    ///
+    /// Spans are processed once their trace is complete, after the export policy and the empty
+    /// value policy, e.g. to rename attributes or to drop noisy spans. The common attributes of the
+    /// trace are derived from its root span beforehand. Dropping a span doesn't re-parent its
+    /// children, see [`with_min_span_duration`](NewRelicLayer::with_min_span_duration) for that.
+    ///
+    /// The processor runs on the application thread closing the root span, so keep it cheap.
+    pub fn with_span_processor<F>(mut self, processor: F) -> Self
+    where
+        F: Fn(&mut NewrSpan) -> bool + Send + Sync + 'static,
+    {
+        self.span_processor = Some(Arc::new(processor));
+        self
+    }
+
+    /// Modify each log right before it's queued for export, dropping it if `processor` returns `false`
+    ///
+    /// Same as [`with_span_processor`](NewRelicLayer::with_span_processor), for logs.
+    pub fn with_log_processor<F>(mut self, processor: F) -> Self
+    where
+        F: Fn(&mut NewrLog) -> bool + Send + Sync + 'static,
+    {
+        self.log_processor = Some(Arc::new(processor));
+        self
+    }
+
+    /// Call `callback` with a [`TraceSummary`] of each trace once its root span is closed, e.g. to
+    /// feed an in-process latency histogram from the same spans New Relic sees
+    ///
+    /// Traces are summarized after their root span is renamed by the
+    /// [name normalizer](NewRelicLayer::with_name_normalizer), and before the export policy, the
+    /// ingest budget and the processors, so dropped traces are summarized too.
+    ///
+    /// The callback runs on the application thread closing the root span, so it must be fast.
+    pub fn with_trace_completed<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(TraceSummary) + Send + Sync + 'static,
+    {
+        self.trace_completed = Some(Arc::new(callback));
+        self
+    }
+
+    /// Rewrite the `name` of root spans once they're closed, e.g. to strip ids out of URL paths,
+    /// keeping the number of transactions low. See [`normalizers`](crate::normalizers) for
+    /// built-in normalizers.
+    ///
+    /// Other spans and attributes are left alone.
+    pub fn with_name_normalizer<F>(mut self, normalizer: F) -> Self
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        self.name_normalizer = Some(Arc::new(normalizer));
+        self
+    }
+
+    /// Mask personal data, e.g. email addresses, in the string values of attributes. Disabled by default.
+    ///
+    /// Values are scrubbed once their trace is complete, on the application thread, before
+    /// the span and log processors run.
+    pub fn with_pii_scrubbing(mut self, rules: PiiRules) -> Self {
+        self.pii_rules = Some(rules);
+        self
+    }
+
+    /// Use sequential ids (`trace_1`, `span_1`, ...) and zero timestamps, for deterministic output
+    #[cfg(feature = "testing")]
+    pub fn with_deterministic_ids(mut self) -> Self {
+        self.generator = Arc::new(Generator::sequential());
+        self
+    }
+
+    /// Set how many completed traces can wait to be exported. Default to `1024`.
+    ///
+    /// With [`Api`](crate::Api), a trace is waiting until both its logs and spans have been sent.
+    /// When the exporter can't keep up, e.g. New Relic is slow or down, traces beyond the capacity
+    /// are handled by the [`DropPolicy`], so memory usage stays bounded.
+    pub fn with_queue_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Set what happens to a completed trace when the queue is full. Default to [`DropPolicy::DropNewest`].
+    pub fn with_drop_policy(mut self, policy: DropPolicy) -> Self {
+        self.drop_policy = policy;
+        self
+    }
+
+    /// Set how many entries each internal cache can hold, e.g. serialized common blocks. Default to `256`.
+    ///
+    /// Evictions are counted in [`StatsSnapshot::cache_evictions`](crate::StatsSnapshot::cache_evictions).
+    pub fn with_cache_capacity(mut self, capacity: usize) -> Self {
+        self.common = Arc::new(Mutex::new(BoundedCache::new(capacity)));
+        self
+    }
+
+    /// Set the `service.name` of every trace, over the one recorded by their root span if any
+    ///
+    /// It's also their `entity.name`, unless one is set with [`with_entity`](NewRelicLayer::with_entity).
+    pub fn with_service_name(mut self, name: impl Into<String>) -> Self {
+        self.service_name = Some(name.into());
+        self
+    }
+
+    /// Set the `service.version` of every trace, e.g. [`service_version_from_cargo!`](crate::service_version_from_cargo)
+    pub fn with_service_version(mut self, version: &str) -> Self {
+        self.service_version = Some(version.to_string());
+        self
+    }
+
+    /// Set the `vcs.ref` and `deployment.timestamp` of every trace, to correlate them with deploys
+    pub fn with_deployment_attributes(
+        mut self,
+        commit_sha: impl Into<String>,
+        build_time: impl Into<String>,
+    ) -> Self {
+        self.deployment = Some((commit_sha.into(), build_time.into()));
+        self
+    }
+
+    /// Set the `entity.guid`, `entity.name` and `entity.type` of every trace, relating them to an
+    /// existing New Relic entity, e.g. an APM application
+    ///
+    /// Without an entity name, the `service.name` of the trace is used, see
+    /// [`with_service_name`](NewRelicLayer::with_service_name).
+    pub fn with_entity(
+        mut self,
+        guid: Option<String>,
+        name: Option<String>,
+        entity_type: Option<String>,
+    ) -> Self {
+        self.entity_guid = guid;
+        self.entity_name = name;
+        self.entity_type = entity_type;
+        self
+    }
+
+    /// Add attributes to the common block of every payload, overriding the ones set by the layer,
+    /// e.g. `service.name` or `hostname`
+    pub fn with_global_attributes(mut self, attributes: NewrAttributes) -> Self {
+        self.global_attributes = attributes;
+        self
+    }
+
+    /// Add attributes to the common block of log payloads only, e.g. `logtype`, overriding the
+    /// global ones, see [`with_global_attributes`](NewRelicLayer::with_global_attributes)
+    pub fn with_common_log_attributes(mut self, attributes: NewrAttributes) -> Self {
+        self.common_log_attributes = attributes;
+        self
+    }
+
+    /// Add attributes to the common block of span payloads only, overriding the global ones, see
+    /// [`with_global_attributes`](NewRelicLayer::with_global_attributes)
+    pub fn with_common_span_attributes(mut self, attributes: NewrAttributes) -> Self {
+        self.common_span_attributes = attributes;
+        self
+    }
+
+    /// Set the `span.kind` of root spans not recording one, e.g. `server`. Default to none.
+    ///
+    /// New Relic only builds throughput and response time views for spans with a `span.kind`.
+    /// Root spans are also marked with `nr.entryPoint = true`, so that they show up as transactions.
+    pub fn with_default_span_kind(mut self, kind: impl Into<String>) -> Self {
+        self.default_span_kind = Some(kind.into());
+        self
+    }
+
+    /// Copy the given attributes from parent spans to their child spans. Default to none.
+    ///
+    /// Values recorded on a parent after its children are created are passed down to the
+    /// children still open. Values recorded on a child itself are never overwritten.
+    pub fn with_inherited_attributes(mut self, keys: &[&str]) -> Self {
+        self.inherited_attributes = keys.iter().map(|key| key.to_string()).collect();
+        self
+    }
+
+    /// Prefix the keys of the fields recorded on spans and events, e.g. `checkout.` turns
+    /// `cart_size` into `checkout.cart_size`. Default to none.
+    ///
+    /// Well-known attributes, e.g. `name`, `service.name`, `span.kind` or `otel.status_code`, and
+    /// the ones set by the layer itself are left alone. Keys given to
+    /// [`with_inherited_attributes`](NewRelicLayer::with_inherited_attributes) are prefixed ones.
+    pub fn with_attribute_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.attribute_keys.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Rename the keys of the fields recorded on spans and events, e.g. from `http.status` to
+    /// `http.status_code`. Default to none.
+    ///
+    /// Renamed fields don't overwrite the ones recorded under their new key at the same time, and
+    /// are prefixed afterwards, see [`with_attribute_prefix`](NewRelicLayer::with_attribute_prefix).
+    pub fn with_attribute_mapping(mut self, mapping: HashMap<String, String>) -> Self {
+        self.attribute_keys.mapping = mapping;
+        self
+    }
+
+    /// Translate the OpenTelemetry attributes of spans and logs into the ones New Relic keys off,
+    /// e.g. `otel.kind` into `span.kind` or `exception.message` into `error.message`. Default to `false`.
+    ///
+    /// Spans recording an `exception.*` attribute, or logging one, get `otel.status_code = ERROR`.
+    /// See [`semconv::OTEL_ATTRIBUTES`](crate::semconv::OTEL_ATTRIBUTES) for the whole table.
+    pub fn with_otel_compat(mut self, enabled: bool) -> Self {
+        self.otel_compat = enabled;
+        self
+    }
+
+    /// Keep the OpenTelemetry attributes next to their translation, see
+    /// [`with_otel_compat`](NewRelicLayer::with_otel_compat). Default to `false`.
+    pub fn with_otel_original_keys(mut self, keep: bool) -> Self {
+        self.otel_keep_original = keep;
+        self
+    }
+
+    /// Set what happens to the `db.statement` and `db.query.text` of spans, e.g. to obfuscate
+    /// the parameters inlined in them. Default to exporting them as they were recorded.
+    ///
+    /// See the [`db`](crate::db) module.
+    pub fn with_db_statement_handling(mut self, policy: DbStatementPolicy) -> Self {
+        self.db_statement = Some(policy);
+        self
+    }
+
+    /// Warn about root spans likely created by a tokio task spawned inside another trace without
+    /// being instrumented with its span, which splits the trace in two. Default to `false`.
+    ///
+    /// A root span is suspicious when it's created by a task on a worker thread that just ran the
+    /// spans of another trace, still open. Concurrent traces sharing worker threads, e.g. requests
+    /// of a busy server, can be reported as well, so it's meant for debugging. See
+    /// [`spawn_traced`](crate::spawn_traced) to fix the reported tasks.
+    pub fn with_orphan_root_warning(mut self, enabled: bool) -> Self {
+        self.orphan_roots = match enabled {
+            true => Some(Arc::default()),
+            false => None,
+        };
+        self
+    }
+
+    /// Log `span started: {name}` when a span at the given level or above opens, and
+    /// `span closed: {name}, {duration}ms` when it closes. Default to [`LevelFilter::OFF`].
+    ///
+    /// These logs carry the `span.id` and `span.name` of the span, and are exported with the trace
+    /// like the ones of its events, giving a timeline of its spans in New Relic Logs. Traces left
+    /// out by sampling don't have any.
+    pub fn with_span_lifecycle_logs(mut self, level: LevelFilter) -> Self {
+        self.span_lifecycle_logs = level;
+        self
+    }
+
+    /// Set what happens to span and event fields recorded with a key the layer sets itself, e.g.
+    /// `id`, `trace.id`, `span.id`, `parent.id`, `duration.ms` or `timestamp`. Default to
+    /// [`ReservedKeyPolicy::Rename`].
+    ///
+    /// Such fields would otherwise overwrite the values set by the layer, breaking the trace.
+    pub fn with_reserved_key_policy(mut self, policy: ReservedKeyPolicy) -> Self {
+        self.attribute_keys.reserved = policy;
+        self
+    }
+
+    /// Replace the characters of field keys outside of `[A-Za-z0-9_.:/-]` with `_`, New Relic
+    /// dropping the attributes with other ones. Default to `true`.
+    ///
+    /// Keys are cut to 255 bytes either way. A sanitized key equal to the one of another field
+    /// replaces it.
+    pub fn with_key_sanitization(mut self, sanitize: bool) -> Self {
+        self.attribute_keys.sanitize = sanitize;
+        self
+    }
+
+    /// Record the `target`, `code.namespace`, `code.filepath` and `code.lineno` of spans, and the
+    /// same for logs with the target as `logger.name`. Default to `true`.
+    ///
+    /// Spans also get the name of their metadata as `code.function`, since a `name` or
+    /// `otel.name` field recorded on them replaces their `name`.
+    pub fn with_metadata_fields(mut self, enabled: bool) -> Self {
+        self.metadata_fields = enabled;
+        self
+    }
+
+    /// Also record the location of spans and logs as a single `source` attribute, e.g.
+    /// `src/main.rs:42`, as done by previous versions. Default to `false`.
+    pub fn with_source_attribute(mut self, enabled: bool) -> Self {
+        self.source_attribute = enabled;
+        self
+    }
+
+    /// Stamp every payload with `process.pid`, `process.executable.name`, `os.type`, `host.arch`,
+    /// `instrumentation.provider` and `instrumentation.version`. Default to `true`.
+    ///
+    /// The same attributes recorded on a root span take precedence.
+    pub fn with_process_metadata(mut self, enabled: bool) -> Self {
+        self.process_metadata = if enabled {
+            Some(process_metadata())
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Collapse identical consecutive logs of a span into the first one, counting them in its
+    /// `log.repeat_count` attribute, until `window` has elapsed since the first one. Default to
+    /// `None`, keeping every log.
+    ///
+    /// Logs are identical if they have the same level and attributes, e.g. the same message
+    /// logged from the same place with the same fields.
+    pub fn with_log_dedup(mut self, window: Option<Duration>) -> Self {
+        self.log_dedup_window = window;
+        self
+    }
+
+    /// Record the `thread.name` and `thread.id` of the thread creating each span and log, along with
+    /// the `tokio.task.id` of the task if any. Default to `false`.
+    pub fn with_thread_info(mut self, enabled: bool) -> Self {
+        self.thread_info = enabled;
+        self
+    }
+
+    /// Set the precision of the timestamps of logs. Default to [`TimestampPrecision::Millis`].
+    ///
+    /// Spans are always timestamped in whole milliseconds, as required by the Trace API, but
+    /// their durations carry microseconds either way.
+    pub fn with_timestamp_precision(mut self, precision: TimestampPrecision) -> Self {
+        self.timestamp_precision = precision;
+        self
+    }
+
+    /// Send the count, sum, min and max of span durations, by `name` and `span.kind`, as
+    /// `span.duration` summary metrics to the Metric API. Default to `false`.
+    ///
+    /// Durations are summarized over [`Api::with_metrics_interval`], including the traces dropped
+    /// by the export policy, but not the unsampled ones. Only takes effect when exporting to New
+    /// Relic, not with [`layer_with_exporter`](crate::layer_with_exporter).
+    pub fn with_metrics(mut self, enabled: bool) -> Self {
+        self.metrics_enabled = enabled;
+        self
+    }
+
+    /// Drop whole traces once the given budget is exceeded, e.g. to keep the ingest costs under
+    /// control. Default to no budget.
+    ///
+    /// The budget is a token bucket per kind of data, refilled continuously and holding up to one
+    /// minute of budget, checked once the root span closes and the export policy has kept the
+    /// trace. The last tenth of the budget is kept for traces containing an error. Traces
+    /// dropped are counted by [`StatsSnapshot::budget_drops`] and summarized in a warning at
+    /// most once a minute.
+    ///
+    /// [`StatsSnapshot::budget_drops`]: crate::StatsSnapshot::budget_drops
+    pub fn with_ingest_budget(mut self, budget: Budget) -> Self {
+        self.budget = Some(Arc::new(Governor::new(budget)));
+        self
+    }
+
+    /// Number of traces dropped by the export policy
+    pub fn dropped_traces(&self) -> u64 {
+        self.dropped_traces.load(Ordering::Relaxed)
+    }
+
+    /// Number of traces dropped because the queue was full
+    pub fn overflowed_traces(&self) -> u64 {
+        self.control.stats.queue_drops.load(Ordering::Relaxed)
+    }
+}
+
+impl<S> Layer<S> for NewRelicLayer
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if !self.active() {
+            return;
+        }
+
+        let span = ctx.span(id).expect("span not found");
+
+        // ids of a `tracing-opentelemetry` layer installed below this one, if any
+        let otel_ids = otel::ids(&span);
+
+        // children follow the sampling decision and the trace id of their root span
+        let (sampled, trace_id, remote_parent) = match span.parent() {
+            Some(parent) => {
+                let extensions = parent.extensions();
+                let unsampled = extensions.get::<Unsampled>();
+
+                (
+                    unsampled.is_none(),
+                    extensions
+                        .get::<NewrSpan>()
+                        .and_then(|s| s.trace_id.clone())
+                        .or_else(|| unsampled.and_then(|u| u.trace_id.clone()))
+                        .or_else(|| otel_ids.as_ref().map(|ids| ids.trace_id.clone())),
+                    None,
+                )
+            }
+            None => {
+                // root spans continue the trace of a remote caller, if any
+                let (trace_id, remote_parent, remote_sampled) =
+                    match (&otel_ids, propagation::remote_parent(attrs)) {
+                        (Some(ids), _) => (ids.trace_id.clone(), ids.parent_id.clone(), false),
+                        (None, Some((trace_id, parent_id, sampled))) => {
+                            (trace_id, Some(parent_id), sampled)
+                        }
+                        (None, None) => (self.generator.trace_id(), None, false),
+                    };
+
+                // traces sampled by the caller are always kept, otherwise the decision depends
+                // on the trace id only, so that every service sharing a trace makes the same one
+                (
+                    remote_sampled || sample(&trace_id, self.sampling_ratio),
+                    Some(trace_id),
+                    remote_parent,
+                )
+            }
+        };
+
+        if !sampled {
+            span.extensions_mut().insert(Unsampled { trace_id });
+            return;
+        }
+
+        let metadata = span.metadata();
+
+        // create a new span
+        let mut nr_span = NewrSpan::new(
+            metadata.name().to_string(),
+            match otel_ids {
+                Some(ids) => ids.span_id,
+                None => self.generator.span_id(),
+            },
+            self.generator.now(),
+            metadata.fields().len() + LAYER_ATTRIBUTES,
+        );
+
+        nr_span.trace_id = Some(trace_id.unwrap_or_else(|| self.generator.trace_id()));
+
+        self.record_metadata(&mut nr_span.attributes, metadata, "target");
+
+        // kept for debugging, `name` is replaced by the one recorded on the span if any
+        if self.metadata_fields {
+            nr_span.attributes.insert("code.function", metadata.name());
+        }
+
+        if self.thread_info {
+            record_thread_info(&mut nr_span.attributes);
+        }
+
+        // record span attributes
+        self.attribute_keys.record(&mut nr_span.attributes, attrs);
+
+        let trace_attributes = take_trace_attributes(&mut nr_span.attributes);
+
+        record_name(&mut nr_span.attributes);
+        record_error(&mut nr_span.attributes);
+
+        // root spans are the entry points of transactions, unless told otherwise
+        if span.parent().is_none() {
+            let attributes = &mut nr_span.attributes;
+
+            attributes.remove(TRACEPARENT);
+
+            if let Some(parent_id) = remote_parent {
+                attributes.insert("parent.id", parent_id);
+            }
+
+            if let (Some(detector), Some(trace_id)) = (&self.orphan_roots, &nr_span.trace_id) {
+                detector.opened(trace_id, metadata);
+            }
+
+            if let Some(kind) = &self.default_span_kind {
+                // `otel.kind` is translated on close
+                let otel_kind = self.otel_compat && attributes.get("otel.kind").is_some();
+
+                if attributes.get("span.kind").is_none() && !otel_kind {
+                    attributes.insert("span.kind", kind.as_str());
+                }
+            }
+
+            if attributes.get("nr.entryPoint").is_none() {
+                attributes.insert("nr.entryPoint", true);
+            }
+        }
+
+        if !self.inherited_attributes.is_empty() {
+            if let Some(parent) = span.parent() {
+                let mut parent_extensions = parent.extensions_mut();
+                let mut inherited = Vec::new();
+
+                if let Some(parent_span) = parent_extensions.get_mut::<NewrSpan>() {
+                    for key in &self.inherited_attributes {
+                        if nr_span.attributes.get(key).is_some() {
+                            continue;
+                        }
+
+                        if let Some(value) = parent_span.attributes.get(key) {
+                            nr_span.attributes.insert(key.clone(), value.clone());
+                            inherited.push(Cow::Owned(key.clone()));
+                        }
+                    }
+
+                    match parent_extensions.get_mut::<Children>() {
+                        Some(children) => {
+                            children.0.insert(id.clone());
+                        }
+                        None => parent_extensions.insert(Children(HashSet::from([id.clone()]))),
+                    }
+                }
+
+                drop(parent_extensions);
+
+                span.extensions_mut().insert(Inherited(inherited));
+            }
+        }
+
+        let started = self.lifecycle_log(&nr_span, metadata, None);
+
+        // insert into extensions
+        let mut extensions = span.extensions_mut();
+        extensions.insert(nr_span);
+
+        if span.parent().is_none() {
+            if let Some(defaults) = self.linking_defaults() {
+                extensions.insert(defaults);
+            }
+        }
+
+        if let Some(started) = started {
+            extensions.insert(vec![started]);
+        }
+
+        drop(extensions);
+
+        self.stash_trace_attributes(&span, trace_attributes);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if !self.active() {
+            return;
+        }
+
+        let span = ctx.span(id).expect("span not found");
+        let mut extensions = span.extensions_mut();
+
+        let trace_attributes = match extensions.get_mut::<NewrSpan>() {
+            Some(nr_span) => {
+                self.attribute_keys.record(&mut nr_span.attributes, values);
+                record_name(&mut nr_span.attributes);
+                record_error(&mut nr_span.attributes);
+                take_trace_attributes(&mut nr_span.attributes)
+            }
+            None => return,
+        };
+
+        let inherited = self.recorded_inherited(values, &mut extensions);
+
+        // children lock their parent on close, so the parent is never locked while a child is
+        drop(extensions);
+
+        self.stash_trace_attributes(&span, trace_attributes);
+
+        if let Some((children, attributes)) = inherited {
+            self.pass_down(&ctx, children, &attributes);
+        }
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        let span = ctx.span(id).expect("span not found");
+        let mut extensions = span.extensions_mut();
+
+        match extensions.get_mut::<NewrSpan>() {
+            Some(nr_span) => {
+                if let (Some(detector), Some(trace_id)) = (&self.orphan_roots, &nr_span.trace_id) {
+                    detector.entered(trace_id);
+                }
+            }
+            None => return,
+        }
+
+        match extensions.get_mut::<Timings>() {
+            Some(timings) => timings.enter(),
+            None => {
+                let mut timings = Timings::default();
+                timings.enter();
+                extensions.insert(timings);
+            }
+        }
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        let span = ctx.span(id).expect("span not found");
+        let mut extensions = span.extensions_mut();
+
+        if let Some(timings) = extensions.get_mut::<Timings>() {
+            timings.exit();
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if !self.active() {
+            return;
+        }
+
+        // ignore event that is out of any span
+        let scope = match ctx.event_scope(event) {
+            Some(scope) => scope,
+            None => return,
+        };
+
+        // attach the event to the nearest span that has a `NewrSpan`. if the
+        // current span lacks one (e.g. it was created before this layer was
+        // installed), the linkage to one of its ancestors is only approximate.
+        for span in scope {
+            let mut extensions = span.extensions_mut();
+
+            // ignore event that is inside an unsampled trace
+            if extensions.get_mut::<Unsampled>().is_some() {
+                return;
+            }
+
+            let (span_id, trace_id) = match extensions.get_mut::<NewrSpan>() {
+                Some(nr_span) => (nr_span.id.clone(), nr_span.trace_id.clone()),
+                None => continue,
+            };
+
+            // records of the `log` crate carry their own metadata, the one of the event
+            // points at the `tracing-log` shim
+            #[cfg(feature = "log-compat")]
+            let normalized = tracing_log::NormalizeEvent::normalized_metadata(event);
+            #[cfg(feature = "log-compat")]
+            let metadata = normalized.as_ref().unwrap_or_else(|| event.metadata());
+            #[cfg(not(feature = "log-compat"))]
+            let metadata = event.metadata();
+
+            // create a log
+            let mut nr_log = NewrLog::new(
+                metadata.level(),
+                self.generator.now(),
+                metadata.fields().len() + LAYER_ATTRIBUTES,
+            );
+            nr_log.precision = self.timestamp_precision;
+
+            // add linking metadata
+            // https://github.com/newrelic/node-newrelic/blob/91967dd5cd997aa283b8aa0b2fdacc2a5f10a628/api.js#L132
+            nr_log.attributes.insert("span.id", span_id);
+
+            if let Some(trace_id) = trace_id {
+                nr_log.attributes.insert("trace.id", trace_id);
+            }
+
+            // named after the attribute set by New Relic log forwarders
+            self.record_metadata(&mut nr_log.attributes, metadata, "logger.name");
+
+            if self.thread_info {
+                record_thread_info(&mut nr_log.attributes);
+            }
+
+            // record event attributes
+            self.attribute_keys.record(&mut nr_log.attributes, event);
+
+            #[cfg(feature = "log-compat")]
+            if normalized.is_some() {
+                for key in LOG_FIELDS.iter() {
+                    nr_log.attributes.remove(key);
+                }
+            }
+
+            let exception = match self.otel_compat {
+                true => semconv::translate(&mut nr_log.attributes, self.otel_keep_original),
+                false => None,
+            };
+
+            let trace_attributes = take_trace_attributes(&mut nr_log.attributes);
+
+            if let Some(error) = exception {
+                if let Some(nr_span) = extensions.get_mut::<NewrSpan>() {
+                    fail(&mut nr_span.attributes, &error);
+                }
+            }
+
+            // an error logged inside a span, e.g. by `#[instrument(err)]`, fails the span
+            if let Some(Value::String(error)) = nr_log.attributes.get("error") {
+                let error = error.clone();
+
+                if nr_log.attributes.get("error.message").is_none() {
+                    nr_log.attributes.insert("error.message", error.as_str());
+                }
+
+                if let Some(nr_span) = extensions.get_mut::<NewrSpan>() {
+                    fail(&mut nr_span.attributes, &error);
+                }
+            }
+
+            // insert into extensions
+            if let Some(nr_logs) = extensions.get_mut::<Vec<NewrLog>>() {
+                let repeated = match (self.log_dedup_window, nr_logs.last_mut()) {
+                    (Some(window), Some(last)) => repeat(last, &nr_log, window),
+                    _ => false,
+                };
+
+                if !repeated {
+                    nr_logs.push(nr_log);
+                }
+            } else {
+                extensions.insert(vec![nr_log]);
+            }
+
+            drop(extensions);
+
+            self.stash_trace_attributes(&span, trace_attributes);
+
+            self.export_in_progress(&span);
+
+            return;
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let span = ctx.span(&id).expect("span not found");
+        let mut extensions = span.extensions_mut();
+
+        if let Some(mut nr_span) = extensions.remove::<NewrSpan>() {
+            // update duration
+            let duration = nr_span.update_duration();
+
+            if self.otel_compat {
+                if let Some(error) =
+                    semconv::translate(&mut nr_span.attributes, self.otel_keep_original)
+                {
+                    fail(&mut nr_span.attributes, &error);
+                }
+            }
+
+            if let Some(policy) = &self.db_statement {
+                policy.apply(&mut nr_span.attributes);
+            }
+
+            // split it into time spent entered and time spent waiting, e.g. on I/O
+            let busy = extensions
+                .remove::<Timings>()
+                .map_or(Duration::ZERO, |timings| timings.busy())
+                .min(duration);
+
+            nr_span.attributes.insert("busy.ms", millis(busy));
+            nr_span
+                .attributes
+                .insert("idle.ms", millis(duration - busy));
+
+            let mut logs = extensions.remove::<Vec<NewrLog>>().unwrap_or_default();
+
+            if let Some(closed) = self.lifecycle_log(&nr_span, span.metadata(), Some(duration)) {
+                logs.push(closed);
+            }
+
+            let trace_attributes = extensions
+                .remove::<TraceAttributes>()
+                .map(|trace| trace.0)
+                .unwrap_or_default();
+
+            let closed = extensions.remove::<Closed>().unwrap_or_default();
+
+            drop(extensions);
+
+            // assembled without holding any lock, the children have all closed already
+            let (mut children, logs) = closed.assemble(&nr_span.id, logs);
+
+            let mut spans = Vec::with_capacity(children.len() + 1);
+            spans.push(nr_span);
+            spans.append(&mut children);
+
+            if let Some(parent) = span.parent() {
+                let dropped = if duration < self.min_span_duration {
+                    // drop this span and move its children and logs onto the parent
+                    Some(Value::String(spans.remove(0).id))
+                } else {
+                    None
+                };
+
+                let mut bundle = Bundle {
+                    spans,
+                    logs,
+                    dropped,
+                    logs_before: 0,
+                };
+
+                let mut parent_extensions = parent.extensions_mut();
+
+                if let Some(children) = parent_extensions.get_mut::<Children>() {
+                    children.0.remove(&id);
+                }
+
+                if parent_extensions.get_mut::<NewrSpan>().is_some() {
+                    bundle.logs_before = parent_extensions
+                        .get_mut::<Vec<NewrLog>>()
+                        .map_or(0, |logs| logs.len());
+
+                    match parent_extensions.get_mut::<Closed>() {
+                        Some(closed) => closed.0.push(bundle),
+                        None => parent_extensions.insert(Closed(vec![bundle])),
+                    }
+
+                    drop(parent_extensions);
+
+                    self.export_in_progress(&parent);
+                }
+
+                return;
+            }
+
+            if let (Some(detector), Some(trace_id)) = (&self.orphan_roots, &spans[0].trace_id) {
+                detector.closed(trace_id);
+            }
+
+            // the trace is dropped as a whole, open spans are closed as usual until then
+            if !self.active() {
+                return;
+            }
+
+            if let Some(normalizer) = &self.name_normalizer {
+                if let Some(Value::String(name)) = spans[0].attributes.get("name") {
+                    let name = normalizer(name);
+                    spans[0].attributes.insert("name", name);
+                }
+            }
+
+            if self.metrics_enabled {
+                if let Some(metrics) = &self.metrics {
+                    metrics.record(self.service_name_of(&spans[0]), &spans);
+                }
+            }
+
+            if let Some(callback) = &self.trace_completed {
+                callback(TraceSummary::new(&spans, &logs, duration));
+            }
+
+            if !self.export_policy.should_export(&spans, &logs) {
+                self.dropped_traces.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+
+            if let Some(budget) = &self.budget {
+                let error = policy::has_error(&spans, &logs);
+
+                if !budget.admit(spans.len(), logs.len(), error) {
+                    stats::add(&self.control.stats.budget_drops, 1);
+                    return;
+                }
+            }
+
+            self.send(spans, logs, trace_attributes);
+        }
+    }
 }
 
-impl<S> Layer<S> for NewRelicLayer
-where
-    S: Subscriber + for<'span> LookupSpan<'span>,
-{
-    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
-        let span = ctx.span(id).expect("span not found");
-        let metadata = span.metadata();
+impl NewRelicLayer {
+    /// Export a snapshot of the root span of `span` if it has been open long enough
+    fn export_in_progress<S>(&self, span: &SpanRef<'_, S>)
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let threshold = match self.eager_export {
+            Some(threshold) => threshold,
+            None => return,
+        };
 
-        // create a new span
-        let mut nr_span = NewrSpan::new(metadata.name().to_string());
-
-        nr_span.attributes.insert(
-            "source",
-            format!(
-                "{}:{}",
-                metadata.file().unwrap_or_default(),
-                metadata.line().unwrap_or_default()
-            ),
-        );
+        let root = match span.scope().from_root().next() {
+            Some(root) => root,
+            None => return,
+        };
 
-        // record span attributes
-        attrs.record(&mut nr_span.attributes);
+        let mut extensions = root.extensions_mut();
 
-        // insert into extensions
-        span.extensions_mut().insert(nr_span);
+        let mut snapshot = match extensions.get_mut::<NewrSpan>() {
+            Some(nr_span) => nr_span.clone(),
+            None => return,
+        };
+
+        let since = extensions
+            .get_mut::<LastExport>()
+            .map_or(snapshot.instant, |last| last.0);
+
+        if since.elapsed() < threshold {
+            return;
+        }
+
+        extensions.insert(LastExport(Instant::now()));
+
+        snapshot.update_duration();
+        snapshot.attributes.insert("in_progress", true);
+
+        let logs = extensions.remove::<Vec<NewrLog>>().unwrap_or_default();
+
+        let trace_attributes = extensions
+            .get_mut::<TraceAttributes>()
+            .map(|trace| trace.0.clone())
+            .unwrap_or_default();
+
+        let closed = extensions.remove::<Closed>().unwrap_or_default();
+
+        drop(extensions);
+
+        let (mut children, logs) = closed.assemble(&snapshot.id, logs);
+
+        let mut spans = vec![snapshot];
+        spans.append(&mut children);
+
+        self.send(spans, logs, trace_attributes);
     }
 
-    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
-        let span = ctx.span(id).expect("span not found");
-        let mut extensions = span.extensions_mut();
+    /// The open children of a span along with the inherited attributes just recorded on it, if any
+    fn recorded_inherited(
+        &self,
+        values: &Record<'_>,
+        extensions: &mut ExtensionsMut<'_>,
+    ) -> Option<(Vec<Id>, NewrAttributes)> {
+        if self.inherited_attributes.is_empty() {
+            return None;
+        }
 
-        if let Some(nr_span) = extensions.get_mut::<NewrSpan>() {
-            values.record(&mut nr_span.attributes);
+        let mut recorded = NewrAttributes::default();
+        self.attribute_keys.record(&mut recorded, values);
+
+        recorded.0.retain(|key, _| {
+            self.inherited_attributes
+                .iter()
+                .any(|inherited| inherited == key)
+        });
+
+        if recorded.0.is_empty() {
+            return None;
+        }
+
+        // recorded by the span itself from now on
+        if let Some(inherited) = extensions.get_mut::<Inherited>() {
+            inherited.0.retain(|key| recorded.get(key).is_none());
         }
+
+        let children = extensions
+            .get_mut::<Children>()?
+            .0
+            .iter()
+            .cloned()
+            .collect();
+
+        Some((children, recorded))
     }
 
-    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
-        // ignore event that is out of current span
-        if let Some(id) = ctx.current_span().id() {
-            let span = ctx.span(id).expect("span not found");
-            let mut extensions = span.extensions_mut();
-            let metadata = event.metadata();
+    /// Record where a span or a log comes from, `target_key` being the key of its target
+    fn record_metadata(
+        &self,
+        attributes: &mut NewrAttributes,
+        metadata: &Metadata<'_>,
+        target_key: &'static str,
+    ) {
+        // https://opentelemetry.io/docs/specs/semconv/general/attributes/#source-code-attributes
+        if self.metadata_fields {
+            attributes.insert(target_key, metadata.target());
 
-            // create a log
-            let mut nr_log = NewrLog::new(metadata.level());
+            if let Some(module_path) = metadata.module_path() {
+                attributes.insert("code.namespace", module_path);
+            }
+
+            if let Some(file) = metadata.file() {
+                attributes.insert("code.filepath", file);
+            }
 
-            if let Some(span_id) = extensions.get_mut::<NewrSpan>().map(|s| s.id.clone()) {
-                // add linking metadata
-                // https://github.com/newrelic/node-newrelic/blob/91967dd5cd997aa283b8aa0b2fdacc2a5f10a628/api.js#L132
-                nr_log.attributes.insert("span.id", span_id);
+            if let Some(line) = metadata.line() {
+                attributes.insert("code.lineno", line as u64);
             }
+        }
 
-            nr_log.attributes.insert(
+        if self.source_attribute && (metadata.file().is_some() || metadata.line().is_some()) {
+            attributes.insert(
                 "source",
                 format!(
                     "{}:{}",
@@ -76,110 +1351,555 @@ where
                     metadata.line().unwrap_or_default()
                 ),
             );
-
-            // record event attributes
-            event.record(&mut nr_log.attributes);
-
-            // insert into extensions
-            if let Some(nr_logs) = extensions.get_mut::<Vec<NewrLog>>() {
-                nr_logs.push(nr_log);
-            } else {
-                extensions.insert(vec![nr_log]);
-            }
         }
     }
 
-    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
-        let span = ctx.span(&id).expect("span not found");
-        let mut extensions = span.extensions_mut();
+    /// Merge trace attributes into the ones stored in the root span of `span`
+    fn stash_trace_attributes<S>(&self, span: &SpanRef<'_, S>, attributes: NewrAttributes)
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        if attributes.0.is_empty() {
+            return;
+        }
 
-        if let Some(mut nr_span) = extensions.remove::<NewrSpan>() {
-            // update duration
-            nr_span.update_duration();
+        let root = match span.scope().from_root().next() {
+            Some(root) => root,
+            None => return,
+        };
 
-            let mut logs = extensions.remove::<Vec<NewrLog>>().unwrap_or_default();
+        let mut extensions = root.extensions_mut();
 
-            let mut spans = vec![nr_span];
+        match extensions.get_mut::<TraceAttributes>() {
+            Some(trace) => trace.0 .0.extend(attributes.0),
+            None => extensions.insert(TraceAttributes(attributes)),
+        }
+    }
 
-            if let Some(mut children) = extensions.remove::<Vec<NewrSpan>>() {
-                spans.append(&mut children);
+    /// Set inherited attributes on open `children`, and on their own children in turn, unless they
+    /// recorded them themselves
+    fn pass_down<S>(&self, ctx: &Context<'_, S>, children: Vec<Id>, attributes: &NewrAttributes)
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        for id in children {
+            let child = match ctx.span(&id) {
+                Some(child) => child,
+                None => continue,
             };
 
-            if let Some(parent) = span.parent() {
-                let mut parent_extensions = parent.extensions_mut();
+            let mut extensions = child.extensions_mut();
 
-                if let Some(parent_span) = parent_extensions.get_mut::<NewrSpan>() {
-                    spans[0]
-                        .attributes
-                        .insert("parent.id", parent_span.id.clone());
-
-                    if let Some(siblings) = parent_extensions.get_mut::<Vec<NewrSpan>>() {
-                        siblings.append(&mut spans);
-                    } else {
-                        parent_extensions.insert(spans);
-                    }
+            let inherited = match extensions.remove::<Inherited>() {
+                Some(inherited) => inherited,
+                None => continue,
+            };
 
-                    if !logs.is_empty() {
-                        if let Some(parent_logs) = parent_extensions.get_mut::<Vec<NewrLog>>() {
-                            parent_logs.append(&mut logs);
-                        } else {
-                            parent_extensions.insert(logs);
+            let Inherited(mut keys) = inherited;
+            let mut passed = NewrAttributes::default();
+
+            if let Some(nr_span) = extensions.get_mut::<NewrSpan>() {
+                for (key, value) in &attributes.0 {
+                    let own = nr_span.attributes.get(key).is_some() && !keys.contains(key);
+
+                    if !own {
+                        nr_span.attributes.0.insert(key.clone(), value.clone());
+                        passed.0.insert(key.clone(), value.clone());
+
+                        if !keys.contains(key) {
+                            keys.push(key.clone());
                         }
                     }
                 }
+            }
 
-                return;
+            extensions.insert(Inherited(keys));
+
+            let grandchildren: Vec<Id> = extensions
+                .get_mut::<Children>()
+                .map(|children| children.0.iter().cloned().collect())
+                .unwrap_or_default();
+
+            drop(extensions);
+
+            if !passed.0.is_empty() && !grandchildren.is_empty() {
+                self.pass_down(ctx, grandchildren, &passed);
+            }
+        }
+    }
+
+    /// The `service.name` of the trace of a root span, the configured one wins
+    fn service_name_of<'a>(&'a self, root: &'a NewrSpan) -> Option<&'a str> {
+        match (&self.service_name, root.attributes.get("service.name")) {
+            (Some(service_name), _) | (None, Some(Value::String(service_name))) => {
+                Some(service_name)
             }
+            _ => None,
+        }
+    }
 
-            let trace_id = next_trace_id();
+    fn send(&self, mut spans: Vec<NewrSpan>, mut logs: Vec<NewrLog>, trace: NewrAttributes) {
+        if let Some(channel) = &self.channel {
+            if !self.backlog.acquire(self.capacity, self.drop_policy) {
+                self.overflow();
+                return;
+            }
 
             for span in &mut spans {
-                span.trace_id = Some(trace_id.clone());
+                self.empty_values.apply(&mut span.attributes);
             }
 
             for log in &mut logs {
-                log.attributes.insert("trace.id", trace_id.clone());
+                self.empty_values.apply(&mut log.attributes);
             }
 
-            if let Some(channel) = &self.channel {
-                let mut attributes = NewrAttributes::default();
+            // routing of the trace, not sent
+            let account = match spans[0].attributes.remove("newrelic.account") {
+                Some(Value::String(account)) => Some(account),
+                _ => None,
+            };
 
-                if let Some(Value::String(service_name)) =
-                    &spans[0].attributes.0.get("service.name")
-                {
-                    attributes.insert("service.name", service_name.as_str());
+            if let Some(rules) = &self.pii_rules {
+                let attributes = spans.iter_mut().map(|span| &mut span.attributes);
+                let attributes = attributes.chain(logs.iter_mut().map(|log| &mut log.attributes));
+
+                for attributes in attributes {
+                    rules.scrub(attributes);
+                }
+            }
+
+            let mut attributes = NewrAttributes::default();
+
+            if let Some(metadata) = &self.process_metadata {
+                for (key, value) in &metadata.0 {
+                    let value = spans[0].attributes.get(key).unwrap_or(value);
+                    attributes.0.insert(key.clone(), value.clone());
+                }
+            }
+
+            attributes.0.extend(trace.0);
+
+            let service_name = self.service_name_of(&spans[0]);
+
+            if let Some(service_name) = service_name {
+                attributes.insert("service.name", service_name);
+            }
+
+            if let Some(guid) = &self.entity_guid {
+                attributes.insert("entity.guid", guid.as_str());
+            }
+
+            if let Some(name) = self.entity_name.as_deref().or(service_name) {
+                attributes.insert("entity.name", name);
+            }
+
+            if let Some(entity_type) = &self.entity_type {
+                attributes.insert("entity.type", entity_type.as_str());
+            }
+
+            if let Some(version) = &self.service_version {
+                attributes.insert("service.version", version.as_str());
+            }
+
+            if let Some((commit_sha, build_time)) = &self.deployment {
+                attributes.insert("vcs.ref", commit_sha.as_str());
+                attributes.insert("deployment.timestamp", build_time.as_str());
+            }
+
+            if let Some(Value::String(hostname)) = &spans[0].attributes.0.get("hostname") {
+                attributes.insert("hostname", hostname.as_str());
+            }
+
+            if let Some(detector) = &self.synthetic_detector {
+                if detector(&spans[0]) {
+                    attributes.insert("traffic.synthetic", true);
                 }
+            }
+
+            let mut attributes = merge(attributes, &self.global_attributes);
+
+            self.empty_values.apply(&mut attributes);
+
+            if let Some(rules) = &self.pii_rules {
+                rules.scrub(&mut attributes);
+            }
+
+            if let Some(processor) = &self.span_processor {
+                spans.retain_mut(|span| processor(span));
+            }
+
+            if let Some(processor) = &self.log_processor {
+                logs.retain_mut(|log| processor(log));
+            }
+
+            if spans.is_empty() && logs.is_empty() {
+                self.backlog.release();
+                return;
+            }
+
+            // shared by both payloads, unless some attributes are specific to one of them
+            let (mut logs_common, mut spans_common) = if self.common_log_attributes.0.is_empty()
+                && self.common_span_attributes.0.is_empty()
+            {
+                let common = self.common_block(attributes);
+                (common.clone(), common)
+            } else {
+                (
+                    self.common_block(merge(attributes.clone(), &self.common_log_attributes)),
+                    self.common_block(merge(attributes, &self.common_span_attributes)),
+                )
+            };
+
+            let priority = policy::has_error(&spans, &logs);
+
+            for common in [&mut logs_common, &mut spans_common] {
+                common.account = account.clone();
+                common.priority = priority;
+            }
+
+            let sent = channel.send((
+                NewrLogs {
+                    logs,
+                    common: logs_common,
+                },
+                NewrSpans {
+                    spans,
+                    common: spans_common,
+                },
+            ));
+
+            if sent.is_err() {
+                self.backlog.release();
+                self.worker_stopped();
+            }
+        }
+    }
+
+    /// A log of a span opening, or closing after `duration`, see `with_span_lifecycle_logs`
+    fn lifecycle_log(
+        &self,
+        nr_span: &NewrSpan,
+        metadata: &Metadata<'_>,
+        duration: Option<Duration>,
+    ) -> Option<NewrLog> {
+        if metadata.level() > &self.span_lifecycle_logs {
+            return None;
+        }
+
+        let name = match nr_span.attributes.get("name") {
+            Some(Value::String(name)) => name.as_str(),
+            _ => metadata.name(),
+        };
 
-                if let Some(Value::String(hostname)) = &spans[0].attributes.0.get("hostname") {
-                    attributes.insert("hostname", hostname.as_str());
+        let mut nr_log = NewrLog::new(metadata.level(), self.generator.now(), LAYER_ATTRIBUTES);
+        nr_log.precision = self.timestamp_precision;
+
+        nr_log.attributes.insert("span.id", nr_span.id.as_str());
+
+        if let Some(trace_id) = &nr_span.trace_id {
+            nr_log.attributes.insert("trace.id", trace_id.as_str());
+        }
+
+        nr_log.attributes.insert("span.name", name);
+
+        match duration {
+            Some(duration) => {
+                let message = format!("span closed: {}, {}ms", name, millis(duration));
+                nr_log.attributes.insert("message", message);
+                nr_log.attributes.insert("duration.ms", millis(duration));
+            }
+            None => {
+                let message = format!("span started: {}", name);
+                nr_log.attributes.insert("message", message);
+            }
+        }
+
+        Some(nr_log)
+    }
+
+    /// The entity guid and hostname of the traces, if set, see `linking_metadata`
+    fn linking_defaults(&self) -> Option<LinkingDefaults> {
+        let global = |key| match self.global_attributes.get(key) {
+            Some(Value::String(value)) => Some(value.clone()),
+            _ => None,
+        };
+
+        let entity_guid = global("entity.guid").or_else(|| self.entity_guid.clone());
+        let hostname = global("hostname");
+
+        if entity_guid.is_none() && hostname.is_none() {
+            return None;
+        }
+
+        Some(LinkingDefaults {
+            entity_guid,
+            hostname,
+        })
+    }
+
+    /// The common block of the given attributes, serialized once as long as they stay the same
+    fn common_block(&self, attributes: NewrAttributes) -> NewrCommon {
+        let key = cache_key(&attributes);
+        let mut cache = self.common.lock().unwrap();
+
+        match cache.get(&key) {
+            Some(common) => common.clone(),
+            None => {
+                let common = NewrCommon::cached(attributes);
+
+                if cache.insert(key, common.clone()) {
+                    stats::add(&self.control.stats.cache_evictions, 1);
                 }
 
-                // TODO: error handling
-                let _ = channel.send((
-                    NewrLogs {
-                        logs,
-                        common: NewrCommon {
-                            attributes: attributes.clone(),
-                        },
-                    },
-                    NewrSpans {
-                        spans,
-                        common: NewrCommon { attributes },
-                    },
-                ));
+                common
+            }
+        }
+    }
+
+    /// Whether the layer is enabled and its worker thread still running to export the traces
+    fn active(&self) -> bool {
+        if !self.control.is_enabled() || self.worker_gone.load(Ordering::Relaxed) {
+            return false;
+        }
+
+        // the receiver is dropped once the worker exits, e.g. it panicked
+        match &self.channel {
+            Some(channel) if channel.is_closed() => {
+                self.worker_stopped();
+                false
             }
+            _ => true,
+        }
+    }
+
+    fn worker_stopped(&self) {
+        if !self.worker_gone.swap(true, Ordering::Relaxed) {
+            log::error!(
+                "the worker thread has stopped, traces are no longer collected nor exported"
+            );
+        }
+    }
+
+    fn overflow(&self) {
+        let overflowed = self
+            .control
+            .stats
+            .queue_drops
+            .fetch_add(1, Ordering::Relaxed)
+            + 1;
+
+        let mut last_warning = self.last_overflow_warning.lock().unwrap();
+
+        // warn at most once every 10 seconds
+        if last_warning.is_none_or(|instant| instant.elapsed() >= Duration::from_secs(10)) {
+            *last_warning = Some(Instant::now());
+
+            log::warn!(
+                "queue is full, dropping traces, capacity={}, overflowed_traces={}",
+                self.capacity,
+                overflowed
+            );
+        }
+    }
+}
+
+/// Count `log` in the `log.repeat_count` of `last` if they're identical, and `window` hasn't
+/// elapsed since `last`
+fn repeat(last: &mut NewrLog, log: &NewrLog, window: Duration) -> bool {
+    let count = match last.attributes.get(REPEAT_COUNT) {
+        Some(Value::U64(count)) => *count,
+        _ => 1,
+    };
+
+    let own = usize::from(count > 1);
+
+    let identical = last.level == log.level
+        && last.logtype == log.logtype
+        && last.attributes.0.len() == log.attributes.0.len() + own
+        && log
+            .attributes
+            .0
+            .iter()
+            .all(|(key, value)| key != REPEAT_COUNT && last.attributes.get(key) == Some(value));
+
+    let within = log.recorded.duration_since(last.recorded) < window;
+
+    if identical && within {
+        last.attributes.insert(REPEAT_COUNT, count + 1);
+    }
+
+    identical && within
+}
+
+/// Name a span after its `otel.name` field, an alias of `name` following OpenTelemetry
+fn record_name(attributes: &mut NewrAttributes) {
+    if let Some(name) = attributes.remove("otel.name") {
+        attributes.insert("name", name);
+    }
+}
+
+/// Fail a span recording an `error` field
+fn record_error(attributes: &mut NewrAttributes) {
+    if let Some(Value::String(error)) = attributes.get("error") {
+        let error = error.clone();
+        fail(attributes, &error);
+    }
+}
+
+/// Set the status of a span to `ERROR`, along with its `error.message`, unless they are set already
+fn fail(attributes: &mut NewrAttributes, error: &str) {
+    for (key, value) in [
+        ("otel.status_code", "ERROR"),
+        ("otel.status_description", error),
+        ("error.message", error),
+    ] {
+        if attributes.get(key).is_none() {
+            attributes.insert(key, value);
+        }
+    }
+}
+
+/// Remove the attributes prefixed with `trace.`, returning them without the prefix
+///
+/// `trace.id` is left alone, it links logs to their trace.
+fn take_trace_attributes(attributes: &mut NewrAttributes) -> NewrAttributes {
+    let keys: Vec<Cow<'static, str>> = attributes
+        .0
+        .keys()
+        .filter(|key| key.starts_with(TRACE_PREFIX) && key.len() > TRACE_PREFIX.len())
+        .filter(|key| *key != "trace.id")
+        .cloned()
+        .collect();
+
+    let mut trace = NewrAttributes::default();
+
+    for key in keys {
+        if let Some(value) = attributes.0.shift_remove(&key) {
+            let key = match key {
+                Cow::Borrowed(key) => Cow::Borrowed(&key[TRACE_PREFIX.len()..]),
+                Cow::Owned(key) => Cow::Owned(key[TRACE_PREFIX.len()..].to_string()),
+            };
+
+            trace.0.insert(key, value);
         }
     }
+
+    trace
+}
+
+/// Attributes overridden by the given ones
+fn merge(mut attributes: NewrAttributes, overrides: &NewrAttributes) -> NewrAttributes {
+    for (key, value) in &overrides.0 {
+        attributes.0.insert(key.clone(), value.clone());
+    }
+
+    attributes
+}
+
+/// Attributes as a string, independent of their order
+fn cache_key(attributes: &NewrAttributes) -> String {
+    let mut pairs: Vec<_> = attributes.0.iter().collect();
+    pairs.sort_by_key(|(key, _)| *key);
+
+    pairs
+        .iter()
+        .map(|(key, value)| format!("{}={:?}", key, value))
+        .collect::<Vec<_>>()
+        .join("\u{1f}")
+}
+
+/// Same as [`layer`], prefer [`try_layer`] to handle an invalid configuration
+///
+/// [`layer`]: crate::layer
+/// [`try_layer`]: crate::try_layer
+impl From<Api> for NewRelicLayer {
+    fn from(api: Api) -> Self {
+        crate::layer(api)
+    }
 }
 
 impl Drop for NewRelicLayer {
     fn drop(&mut self) {
+        // the worker stops once every clone has dropped its sender
         if let Some(channel) = self.channel.take() {
             drop(channel);
         }
 
-        if let Some(handle) = self.handle.take() {
-            let _ = handle.join();
+        // shut down by the last clone only
+        if let Some(worker) = self.worker.take().and_then(Arc::into_inner) {
+            let _ = worker.shutdown.send(self.shutdown_timeout);
+            worker.running.wait();
+        }
+    }
+}
+
+/// Record the current thread, and the current tokio task if any
+fn record_thread_info(attributes: &mut NewrAttributes) {
+    thread_local! {
+        // name and id of the current thread, looked up once per thread
+        static THREAD: (Option<String>, Option<u64>) = {
+            let thread = thread::current();
+
+            // `ThreadId` only exposes its number through `Debug`, e.g. `ThreadId(3)`
+            let id = format!("{:?}", thread.id())
+                .trim_start_matches("ThreadId(")
+                .trim_end_matches(')')
+                .parse()
+                .ok();
+
+            (thread.name().map(String::from), id)
+        };
+    }
+
+    THREAD.with(|(name, id)| {
+        if let Some(name) = name {
+            attributes.insert("thread.name", name.as_str());
+        }
+
+        if let Some(id) = id {
+            attributes.insert("thread.id", *id);
         }
+    });
+
+    if let Some(id) = tokio::task::try_id() {
+        attributes.insert("tokio.task.id", id.to_string());
+    }
+}
+
+/// Attributes of the current process, named after the OpenTelemetry resource conventions
+pub(crate) fn process_metadata() -> NewrAttributes {
+    let mut attributes = NewrAttributes::default();
+
+    attributes.insert("process.pid", std::process::id() as u64);
+
+    let executable = env::current_exe().ok().and_then(|path| {
+        path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+    });
+
+    if let Some(executable) = executable {
+        attributes.insert("process.executable.name", executable);
     }
+
+    // https://opentelemetry.io/docs/specs/semconv/resource/os/
+    let os = match env::consts::OS {
+        "macos" => "darwin",
+        os => os,
+    };
+    attributes.insert("os.type", os);
+
+    // https://opentelemetry.io/docs/specs/semconv/resource/host/
+    let arch = match env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "arm" => "arm32",
+        "powerpc64" => "ppc64",
+        arch => arch,
+    };
+    attributes.insert("host.arch", arch);
+
+    attributes.insert("instrumentation.provider", env!("CARGO_PKG_NAME"));
+    attributes.insert("instrumentation.version", env!("CARGO_PKG_VERSION"));
+
+    attributes
 }
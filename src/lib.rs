@@ -102,49 +102,391 @@
 #![warn(missing_docs)]
 
 mod api;
+mod backlog;
+mod breaker;
+mod budget;
+mod capture;
+#[cfg(feature = "reqwest-middleware")]
+pub mod client;
+mod compression;
+pub mod db;
+mod error;
+mod exporter;
+#[cfg(feature = "tonic")]
+pub mod grpc;
+mod handle;
+#[cfg(feature = "tower")]
+pub mod http;
 mod layer;
+mod linking;
+mod metrics;
+pub mod normalizers;
+mod otel;
+#[cfg(feature = "otlp")]
+mod otlp;
+mod panic_hook;
+mod pii;
+mod policy;
+pub mod propagation;
+mod retry;
+pub mod semconv;
+mod spawn;
+mod stats;
+mod stream;
+#[cfg(all(tracing_unstable, feature = "valuable"))]
+mod structured;
+mod summary;
+pub mod synthetic;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod transport;
 mod types;
 mod utils;
 
-pub use api::{Api, ApiEndpoint};
+pub use api::{Api, ApiEndpoint, ApiKeyRoute, Credential, ExportFormat};
+pub use backlog::DropPolicy;
+pub use breaker::{CircuitBreaker, CircuitState};
+pub use budget::Budget;
+pub use compression::Compression;
+pub use error::{ConfigError, EnvError, ExportError};
+pub use exporter::{ConsoleExporter, Exporter, FileExporter, TeeExporter};
+pub use handle::Handle;
 pub use layer::NewRelicLayer;
+pub use linking::{linking_metadata, LinkingMetadata};
+pub use panic_hook::install_panic_hook;
+pub use pii::PiiRules;
+pub use policy::{EmptyValuePolicy, ExportPolicy, ReservedKeyPolicy};
+pub use retry::RetryPolicy;
+pub use spawn::{spawn_blocking_traced, spawn_traced};
+pub use stats::{Stats, StatsSnapshot};
+pub use summary::TraceSummary;
+pub use transport::{HttpTransport, TelemetryRequest, TelemetryResponse, TransportError};
+pub use types::{
+    Json, NewrAttributes, NewrCommon, NewrEvent, NewrLog, NewrLogs, NewrMetric, NewrMetrics,
+    NewrSpan, NewrSpans, NewrSummary, TimestampPrecision, Value,
+};
 
+use backlog::Backlog;
+use futures_util::future;
+use handle::Command;
+use layer::{Running, Worker};
+use policy::EmptyValues;
+use std::env;
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Duration;
 use tokio::runtime;
-use tokio::sync::mpsc::unbounded_channel;
-use types::{NewrLogs, NewrSpans};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tokio::sync::oneshot;
+use tokio::time::{self, Interval, MissedTickBehavior};
+use types::AttributeKeys;
+use utils::BoundedCache;
+
+/// The version of the calling crate, for [`NewRelicLayer::with_service_version`]
+///
+/// ```rust
+/// let layer = tracing_newrelic::layer_with_exporter(tracing_newrelic::ConsoleExporter::new())
+///     .with_service_version(tracing_newrelic::service_version_from_cargo!());
+/// ```
+#[macro_export]
+macro_rules! service_version_from_cargo {
+    () => {
+        env!("CARGO_PKG_VERSION")
+    };
+}
 
 /// Create a new NewRelic layer and spawn a thread for sending data
+///
+/// If the `NEWRELIC_DRY_RUN` environment variable is set to `1` or `true`, data is printed to
+/// stderr by a [`ConsoleExporter`] instead of being sent.
+///
+/// An invalid configuration is only reported with `log::error!`, use [`try_layer`] to handle it.
 pub fn layer(api: impl Into<Api>) -> NewRelicLayer {
     let mut api = api.into();
 
+    if let Err(err) = api.validate() {
+        log::error!("invalid New Relic configuration: {}", err);
+    }
+
+    spawn_api(api, Spawner::Thread)
+}
+
+/// Create a new NewRelic layer sending data from a task spawned on the given Tokio runtime
+///
+/// Same as [`layer`], but no thread is spawned, e.g. in an application already running a
+/// multi-threaded runtime. When the layer is dropped, it waits for the final flush by blocking
+/// the current thread, except on a current-thread runtime, where the task finishes in the
+/// background. Use [`Handle::flush`] beforehand in that case.
+pub fn layer_on_runtime(api: impl Into<Api>, runtime: runtime::Handle) -> NewRelicLayer {
+    let mut api = api.into();
+
+    if let Err(err) = api.validate() {
+        log::error!("invalid New Relic configuration: {}", err);
+    }
+
+    spawn_api(api, Spawner::Runtime(runtime))
+}
+
+/// Create a new NewRelic layer and spawn a thread for sending data, failing on invalid configuration
+///
+/// The api key is trimmed before validation.
+pub fn try_layer(api: impl Into<Api>) -> Result<NewRelicLayer, ConfigError> {
+    let mut api = api.into();
+
+    api.validate()?;
+
+    Ok(spawn_api(api, Spawner::Thread))
+}
+
+/// Create a new NewRelic layer configured by the standard New Relic environment variables
+///
+/// On top of the variables read by [`Api::from_env`], `NEW_RELIC_APP_NAME` sets the service name,
+/// see [`NewRelicLayer::with_service_name`].
+pub fn layer_from_env() -> Result<NewRelicLayer, EnvError> {
+    let api = Api::from_env()?;
+
+    let layer = try_layer(api).map_err(EnvError::Config)?;
+
+    Ok(match env::var("NEW_RELIC_APP_NAME") {
+        Ok(name) if !name.trim().is_empty() => layer.with_service_name(name.trim()),
+        _ => layer,
+    })
+}
+
+fn spawn_api(mut api: Api, spawner: Spawner) -> NewRelicLayer {
+    if matches!(env::var("NEWRELIC_DRY_RUN").as_deref(), Ok("1" | "true")) {
+        let exporter = Box::new(ConsoleExporter::new());
+        return spawn(exporter, Handle::default(), Arc::default(), true, spawner);
+    }
+
+    let handle = Handle {
+        capture: api.capture.clone(),
+        api_key: api.rotated_key.clone(),
+        commands: None,
+        stats: api.stats.clone(),
+        disabled: Arc::default(),
+    };
+
+    // the api frees the slot of a trace once it has been sent, not when it's received
+    let backlog = Arc::new(Backlog::default());
+    api.backlog = Some(backlog.clone());
+
+    let metrics = api.metrics.clone();
+
+    let mut layer = spawn(Box::new(api), handle, backlog, false, spawner);
+    layer.metrics = Some(metrics);
+    layer
+}
+
+/// Create a new NewRelic layer and spawn a thread for sending data through the given exporter
+pub fn layer_with_exporter(exporter: impl Exporter) -> NewRelicLayer {
+    spawn(
+        Box::new(exporter),
+        Handle::default(),
+        Arc::default(),
+        true,
+        Spawner::Thread,
+    )
+}
+
+/// Where the worker runs
+enum Spawner {
+    /// A dedicated thread with its own current-thread runtime
+    Thread,
+    /// A task on an existing runtime
+    Runtime(runtime::Handle),
+}
+
+/// Spawn the worker, freeing the slot of each trace in `backlog` once received if `release_on_receive`
+fn spawn(
+    mut exporter: Box<dyn Exporter>,
+    mut handle: Handle,
+    backlog: Arc<Backlog>,
+    release_on_receive: bool,
+    spawner: Spawner,
+) -> NewRelicLayer {
     let (tx, mut rx) = unbounded_channel::<(NewrLogs, NewrSpans)>();
+    let (commands_tx, mut commands) = unbounded_channel::<Command>();
+    let worker_backlog = Some(backlog.clone()).filter(|_| release_on_receive);
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<Duration>();
 
-    let handle = thread::Builder::new()
-        .name("newrelic-report".into())
-        .spawn(move || {
-            let rt = match runtime::Builder::new_current_thread().enable_all().build() {
-                Err(e) => {
-                    eprintln!("Failed to communicate runtime creation failure: {:?}", e);
-                    return;
+    let work = async move {
+        // resolves once the layer is dropped and its shutdown timeout has elapsed
+        let deadline = async {
+            match shutdown_rx.await {
+                Ok(timeout) => {
+                    time::sleep(timeout).await;
+                    timeout
                 }
-                Ok(v) => v,
-            };
+                Err(_) => future::pending().await,
+            }
+        };
 
-            rt.block_on(async move {
-                while let Some((logs, spans)) = rx.recv().await {
-                    api.push(logs, spans).await
-                }
+        let timed_out = tokio::select! {
+            _ = run(&mut *exporter, &mut rx, &mut commands, worker_backlog.as_deref()) => None,
+            timeout = deadline => Some(timeout),
+        };
+
+        if let Some(timeout) = timed_out {
+            let mut abandoned = exporter.pending();
+
+            while rx.try_recv().is_ok() {
+                abandoned += 1;
+            }
 
-                api.flush().await;
+            log::warn!(
+                "shutdown timed out after {:?}, abandoned {} traces",
+                timeout,
+                abandoned
+            );
+        }
+    };
+
+    let running = match spawner {
+        Spawner::Thread => {
+            let thread = thread::Builder::new()
+                .name("newrelic-report".into())
+                .spawn(move || {
+                    let rt = match runtime::Builder::new_current_thread().enable_all().build() {
+                        Err(e) => {
+                            eprintln!("Failed to communicate runtime creation failure: {:?}", e);
+                            return;
+                        }
+                        Ok(v) => v,
+                    };
+
+                    rt.block_on(work);
+
+                    drop(rt);
+                })
+                .expect("failed to spawn thread");
+
+            Running::Thread(thread)
+        }
+        Spawner::Runtime(runtime) => {
+            // dropped along with the task, even if it panics
+            let (done_tx, done_rx) = oneshot::channel::<()>();
+
+            runtime.spawn(async move {
+                work.await;
+                drop(done_tx);
             });
 
-            drop(rt);
-        })
-        .expect("failed to spawn thread");
+            Running::Task(done_rx)
+        }
+    };
+
+    handle.commands = Some(commands_tx);
 
     NewRelicLayer {
-        handle: Some(handle),
+        worker: Some(Arc::new(Worker {
+            running,
+            shutdown: shutdown_tx,
+        })),
+        control: handle,
         channel: Some(tx),
+        shutdown_timeout: Duration::from_secs(10),
+        sampling_ratio: 1.0,
+        export_policy: Arc::default(),
+        budget: None,
+        dropped_traces: Arc::default(),
+        min_span_duration: Duration::from_secs(0),
+        eager_export: None,
+        generator: Arc::default(),
+        common: Arc::new(Mutex::new(BoundedCache::new(256))),
+        synthetic_detector: None,
+        empty_values: EmptyValues::default(),
+        backlog,
+        capacity: 1024,
+        drop_policy: DropPolicy::default(),
+        last_overflow_warning: Arc::default(),
+        service_name: None,
+        service_version: None,
+        deployment: None,
+        entity_guid: None,
+        entity_name: None,
+        entity_type: None,
+        global_attributes: NewrAttributes::default(),
+        common_log_attributes: NewrAttributes::default(),
+        common_span_attributes: NewrAttributes::default(),
+        span_lifecycle_logs: tracing_core::LevelFilter::OFF,
+        worker_gone: Arc::default(),
+        metrics: None,
+        metrics_enabled: false,
+        timestamp_precision: TimestampPrecision::default(),
+        thread_info: false,
+        log_dedup_window: None,
+        process_metadata: Some(layer::process_metadata()),
+        default_span_kind: None,
+        inherited_attributes: Vec::new(),
+        span_processor: None,
+        trace_completed: None,
+        log_processor: None,
+        metadata_fields: true,
+        source_attribute: false,
+        attribute_keys: AttributeKeys::default(),
+        name_normalizer: None,
+        pii_rules: None,
+        otel_compat: false,
+        otel_keep_original: false,
+        db_statement: None,
+        orphan_roots: None,
+    }
+}
+
+/// Export the received traces until the channel is closed, then shut the exporter down
+async fn run(
+    exporter: &mut dyn Exporter,
+    rx: &mut UnboundedReceiver<(NewrLogs, NewrSpans)>,
+    commands: &mut UnboundedReceiver<Command>,
+    backlog: Option<&Backlog>,
+) {
+    let mut interval = exporter.tick_interval().map(|period| {
+        // the first tick is a period away, not immediate
+        let mut interval = time::interval_at(time::Instant::now() + period, period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        interval
+    });
+
+    loop {
+        tokio::select! {
+            message = rx.recv() => match message {
+                Some((logs, spans)) => {
+                    release(backlog);
+                    exporter.export(logs, spans).await;
+                }
+                None => break,
+            },
+            Some(command) = commands.recv() => match command {
+                Command::Flush(done) => {
+                    // export the traces completed before the command was sent
+                    while let Ok((logs, spans)) = rx.try_recv() {
+                        release(backlog);
+                        exporter.export(logs, spans).await;
+                    }
+
+                    done(exporter.flush().await);
+                }
+            },
+            _ = tick(&mut interval) => exporter.tick().await,
+        }
+    }
+
+    exporter.shutdown().await;
+}
+
+/// Free the slot of a received trace, if the worker releases them on receive
+fn release(backlog: Option<&Backlog>) {
+    if let Some(backlog) = backlog {
+        backlog.release();
+    }
+}
+
+async fn tick(interval: &mut Option<Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => future::pending().await,
     }
 }
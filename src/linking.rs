@@ -0,0 +1,82 @@
+//! Linking metadata of the current span, for logs-in-context with other log pipelines
+
+use serde::Serialize;
+use tracing::Span;
+use tracing_subscriber::registry::{LookupSpan, Registry, SpanRef};
+
+use crate::types::{NewrSpan, Value};
+
+/// What New Relic needs on a log line to link it to its trace and entity, see [`linking_metadata`]
+///
+/// Serialized with the keys New Relic expects, e.g. `trace.id`, to be merged into JSON logs.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct LinkingMetadata {
+    /// Id of the trace, as exported
+    #[serde(rename = "trace.id")]
+    pub trace_id: String,
+    /// Id of the span, as exported
+    #[serde(rename = "span.id")]
+    pub span_id: String,
+    /// See [`NewRelicLayer::with_entity`](crate::NewRelicLayer::with_entity)
+    #[serde(rename = "entity.guid", skip_serializing_if = "Option::is_none")]
+    pub entity_guid: Option<String>,
+    /// The `hostname` set as a global attribute, or recorded on the root span
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+}
+
+/// Entity guid and hostname set on the layer, stored in the extensions of root spans
+pub(crate) struct LinkingDefaults {
+    pub(crate) entity_guid: Option<String>,
+    pub(crate) hostname: Option<String>,
+}
+
+impl LinkingMetadata {
+    /// The linking metadata of a span, e.g. the current one of a `fmt` formatter
+    ///
+    /// `None` if the span isn't recorded by a [`NewRelicLayer`](crate::NewRelicLayer), e.g. when
+    /// it's disabled or not sampled.
+    pub fn of<'a, R: LookupSpan<'a>>(span: &SpanRef<'a, R>) -> Option<Self> {
+        let (trace_id, span_id) = {
+            let extensions = span.extensions();
+            let nr_span = extensions.get::<NewrSpan>()?;
+            (nr_span.trace_id.clone()?, nr_span.id.clone())
+        };
+
+        let root = span.scope().from_root().next()?;
+        let extensions = root.extensions();
+        let defaults = extensions.get::<LinkingDefaults>();
+
+        // the global attribute wins, like in the common block
+        let hostname = match (
+            defaults.and_then(|defaults| defaults.hostname.as_ref()),
+            extensions
+                .get::<NewrSpan>()
+                .and_then(|root| root.attributes.get("hostname")),
+        ) {
+            (Some(hostname), _) | (None, Some(Value::String(hostname))) => Some(hostname.clone()),
+            _ => None,
+        };
+
+        Some(LinkingMetadata {
+            trace_id,
+            span_id,
+            entity_guid: defaults.and_then(|defaults| defaults.entity_guid.clone()),
+            hostname,
+        })
+    }
+}
+
+/// The linking metadata of the current span, to add to log lines shipped by another pipeline
+///
+/// Requires the [`NewRelicLayer`](crate::NewRelicLayer) to be on top of a `Registry`. Within a
+/// subscriber, e.g. in a `fmt` formatter, the current span isn't available this way, use
+/// [`LinkingMetadata::of`] instead.
+pub fn linking_metadata() -> Option<LinkingMetadata> {
+    Span::current()
+        .with_subscriber(|(id, dispatch)| {
+            let registry = dispatch.downcast_ref::<Registry>()?;
+            LinkingMetadata::of(&registry.span(id)?)
+        })
+        .flatten()
+}
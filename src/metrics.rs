@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::mem;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::types::{
+    NewrAttributes, NewrCommon, NewrMetric, NewrMetrics, NewrSpan, NewrSummary, Value,
+};
+use crate::utils;
+
+/// Name of the metric summarizing span durations
+pub(crate) const SPAN_DURATION: &str = "span.duration";
+
+/// Summaries of span durations over the current window, shared by the layer and the worker
+#[derive(Default)]
+pub(crate) struct Aggregator {
+    window: Mutex<Window>,
+}
+
+#[derive(Default)]
+struct Window {
+    // when the first span of the window was recorded
+    start: Option<SystemTime>,
+    buckets: HashMap<Bucket, NewrSummary>,
+}
+
+/// Spans are summarized by service, name and kind
+#[derive(Hash, PartialEq, Eq, PartialOrd, Ord)]
+struct Bucket {
+    service: Option<String>,
+    name: String,
+    kind: Option<String>,
+}
+
+impl Aggregator {
+    /// Add the durations of closed spans to the current window
+    pub(crate) fn record(&self, service: Option<&str>, spans: &[NewrSpan]) {
+        let mut window = self.window.lock().unwrap();
+
+        window.start.get_or_insert_with(utils::now);
+
+        for span in spans {
+            let duration = match span.attributes.get("duration.ms") {
+                Some(Value::F64(duration)) => *duration,
+                _ => continue,
+            };
+
+            let bucket = Bucket {
+                service: service.map(String::from),
+                name: string(span.attributes.get("name")).unwrap_or_default(),
+                kind: string(span.attributes.get("span.kind")),
+            };
+
+            window
+                .buckets
+                .entry(bucket)
+                .and_modify(|summary| summary.add(duration))
+                .or_insert_with(|| NewrSummary::new(duration));
+        }
+    }
+
+    /// Close the current window, returning one payload per service, if any span was recorded
+    pub(crate) fn take(&self) -> Vec<NewrMetrics> {
+        let Window { start, buckets } = mem::take(&mut *self.window.lock().unwrap());
+
+        let Some(start) = start else {
+            return Vec::new();
+        };
+
+        let interval = utils::now().duration_since(start).unwrap_or_default();
+
+        // sorted for a stable output
+        let mut buckets: Vec<_> = buckets.into_iter().collect();
+        buckets.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut payloads: Vec<(Option<String>, NewrMetrics)> = Vec::new();
+
+        for (
+            Bucket {
+                service,
+                name,
+                kind,
+            },
+            summary,
+        ) in buckets
+        {
+            let mut attributes = NewrAttributes::default();
+            attributes.insert("span.name", name);
+
+            if let Some(kind) = kind {
+                attributes.insert("span.kind", kind);
+            }
+
+            let metric = NewrMetric {
+                name: SPAN_DURATION.to_string(),
+                kind: "summary",
+                value: summary,
+                attributes,
+            };
+
+            match payloads.iter_mut().find(|(other, _)| *other == service) {
+                Some((_, payload)) => payload.metrics.push(metric),
+                None => {
+                    let mut attributes = NewrAttributes::default();
+
+                    if let Some(service) = &service {
+                        attributes.insert("service.name", service.as_str());
+                    }
+
+                    payloads.push((
+                        service,
+                        NewrMetrics {
+                            metrics: vec![metric],
+                            timestamp: start,
+                            interval,
+                            common: NewrCommon::new(attributes),
+                        },
+                    ));
+                }
+            }
+        }
+
+        payloads.into_iter().map(|(_, payload)| payload).collect()
+    }
+}
+
+fn string(value: Option<&Value>) -> Option<String> {
+    match value {
+        Some(Value::String(s)) => Some(s.clone()),
+        _ => None,
+    }
+}
@@ -0,0 +1,36 @@
+//! Built-in normalizers of transaction names, see [`NewRelicLayer::with_name_normalizer`]
+//!
+//! [`NewRelicLayer::with_name_normalizer`]: crate::NewRelicLayer::with_name_normalizer
+
+pub(crate) type Normalizer = dyn Fn(&str) -> String + Send + Sync;
+
+/// Replace the path segments made of digits only or looking like a UUID with `:id`,
+/// e.g. `GET /users/48121/orders` becomes `GET /users/:id/orders`
+pub fn numeric_path_segments() -> impl Fn(&str) -> String + Send + Sync + 'static {
+    |name| {
+        name.split('/')
+            .map(|segment| {
+                if is_numeric(segment) || is_uuid(segment) {
+                    ":id"
+                } else {
+                    segment
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+fn is_numeric(segment: &str) -> bool {
+    !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// In the `8-4-4-4-12` hex digits form
+fn is_uuid(segment: &str) -> bool {
+    let groups: Vec<_> = segment.split('-').collect();
+
+    groups.iter().map(|group| group.len()).eq([8, 4, 4, 4, 12])
+        && groups
+            .iter()
+            .all(|group| group.bytes().all(|b| b.is_ascii_hexdigit()))
+}
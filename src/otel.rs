@@ -0,0 +1,51 @@
+//! Ids shared with `tracing-opentelemetry`, see the `otel-ids` feature
+
+use tracing_subscriber::registry::{LookupSpan, SpanRef};
+
+/// The ids `tracing-opentelemetry` gave a span, as hex
+pub(crate) struct OtelIds {
+    pub(crate) trace_id: String,
+    pub(crate) span_id: String,
+    // the span of the OpenTelemetry context the span was created in, e.g. a remote caller
+    pub(crate) parent_id: Option<String>,
+}
+
+/// The ids of a span recorded by a `tracing-opentelemetry` layer installed below this one
+#[cfg(feature = "otel-ids")]
+pub(crate) fn ids<'a, R: LookupSpan<'a>>(span: &SpanRef<'a, R>) -> Option<OtelIds> {
+    use opentelemetry::trace::{SpanId, TraceContextExt, TraceId};
+    use tracing_opentelemetry::OtelData;
+
+    let extensions = span.extensions();
+    let data = extensions.get::<OtelData>()?;
+
+    let parent = data.parent_cx.span();
+    let parent = parent.span_context();
+
+    // only root spans get a trace id of their own
+    let trace_id = match data.builder.trace_id {
+        Some(trace_id) => trace_id,
+        None if parent.is_valid() => parent.trace_id(),
+        None => return None,
+    };
+    let span_id = data.builder.span_id?;
+
+    // e.g. a no-op tracer
+    if trace_id == TraceId::INVALID || span_id == SpanId::INVALID {
+        return None;
+    }
+
+    Some(OtelIds {
+        trace_id: trace_id.to_string(),
+        span_id: span_id.to_string(),
+        parent_id: match parent.is_valid() {
+            true => Some(parent.span_id().to_string()),
+            false => None,
+        },
+    })
+}
+
+#[cfg(not(feature = "otel-ids"))]
+pub(crate) fn ids<'a, R: LookupSpan<'a>>(_: &SpanRef<'a, R>) -> Option<OtelIds> {
+    None
+}
@@ -0,0 +1,288 @@
+//! Conversion of the payloads into OTLP/JSON
+//!
+//! https://opentelemetry.io/docs/specs/otlp/#json-protobuf-encoding
+
+use serde::Serialize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::types::{NewrAttributes, NewrCommon, NewrLog, NewrLogs, NewrSpan, NewrSpans, Value};
+use crate::utils::hex_id;
+
+/// Attributes of a span mapped onto fields of the OTLP span
+const SPAN_FIELDS: &[&str] = &[
+    "name",
+    "parent.id",
+    "duration.ms",
+    "span.kind",
+    "service.name",
+    "otel.status_code",
+    "otel.status_description",
+];
+
+/// Attributes of a log mapped onto fields of the OTLP log record
+const LOG_FIELDS: &[&str] = &["message", "trace.id", "span.id"];
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Traces {
+    resource_spans: Vec<ResourceSpans>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ResourceSpans {
+    resource: Resource,
+    scope_spans: Vec<ScopeSpans>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScopeSpans {
+    scope: Scope,
+    spans: Vec<Span>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Span {
+    trace_id: String,
+    span_id: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    parent_span_id: String,
+    name: String,
+    kind: u8,
+    start_time_unix_nano: String,
+    end_time_unix_nano: String,
+    attributes: Vec<KeyValue>,
+    status: Status,
+}
+
+#[derive(Serialize)]
+struct Status {
+    code: u8,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    message: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Logs {
+    resource_logs: Vec<ResourceLogs>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ResourceLogs {
+    resource: Resource,
+    scope_logs: Vec<ScopeLogs>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScopeLogs {
+    scope: Scope,
+    log_records: Vec<LogRecord>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LogRecord {
+    time_unix_nano: String,
+    severity_number: u8,
+    severity_text: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<AnyValue>,
+    attributes: Vec<KeyValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trace_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    span_id: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Resource {
+    attributes: Vec<KeyValue>,
+}
+
+#[derive(Serialize)]
+struct Scope {
+    name: &'static str,
+    version: &'static str,
+}
+
+impl Default for Scope {
+    fn default() -> Self {
+        Scope {
+            name: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct KeyValue {
+    key: String,
+    value: AnyValue,
+}
+
+#[derive(Serialize)]
+enum AnyValue {
+    #[serde(rename = "stringValue")]
+    String(String),
+    // 64-bit integers are strings in OTLP/JSON
+    #[serde(rename = "intValue")]
+    Int(String),
+    #[serde(rename = "doubleValue")]
+    Double(f64),
+    #[serde(rename = "boolValue")]
+    Bool(bool),
+}
+
+impl From<&Value> for AnyValue {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::I64(i) => AnyValue::Int(i.to_string()),
+            Value::U64(u) => AnyValue::Int(u.to_string()),
+            Value::F64(f) => AnyValue::Double(*f),
+            Value::Bool(b) => AnyValue::Bool(*b),
+            Value::String(s) => AnyValue::String(s.clone()),
+            Value::Json(json) => AnyValue::String(json.to_string()),
+        }
+    }
+}
+
+/// Convert spans payloads into OTLP resource spans, one per payload
+pub(crate) fn traces(data: &[NewrSpans]) -> Traces {
+    Traces {
+        resource_spans: data
+            .iter()
+            .map(|payload| ResourceSpans {
+                resource: resource(&payload.common),
+                scope_spans: vec![ScopeSpans {
+                    scope: Scope::default(),
+                    spans: payload.spans.iter().map(span).collect(),
+                }],
+            })
+            .collect(),
+    }
+}
+
+/// Convert logs payloads into OTLP resource logs, one per payload
+pub(crate) fn logs(data: &[NewrLogs]) -> Logs {
+    Logs {
+        resource_logs: data
+            .iter()
+            .map(|payload| ResourceLogs {
+                resource: resource(&payload.common),
+                scope_logs: vec![ScopeLogs {
+                    scope: Scope::default(),
+                    log_records: payload.logs.iter().map(log_record).collect(),
+                }],
+            })
+            .collect(),
+    }
+}
+
+fn resource(common: &NewrCommon) -> Resource {
+    Resource {
+        attributes: key_values(common.attributes(), &[]),
+    }
+}
+
+fn span(span: &NewrSpan) -> Span {
+    let attributes = &span.attributes;
+
+    let duration = match attributes.get("duration.ms") {
+        Some(Value::F64(ms)) => Duration::from_secs_f64(ms.max(0.0) / 1000.0),
+        _ => Duration::default(),
+    };
+
+    // https://opentelemetry.io/docs/specs/otel/trace/api/#spankind
+    let kind = match string(attributes.get("span.kind")) {
+        Some("server") => 2,
+        Some("client") => 3,
+        Some("producer") => 4,
+        Some("consumer") => 5,
+        _ => 1,
+    };
+
+    let code = match string(attributes.get("otel.status_code")) {
+        Some(code) if code.eq_ignore_ascii_case("ok") => 1,
+        Some(code) if code.eq_ignore_ascii_case("error") => 2,
+        _ => 0,
+    };
+
+    Span {
+        trace_id: hex_id(span.trace_id.as_deref().unwrap_or_default(), 32),
+        span_id: hex_id(&span.id, 16),
+        parent_span_id: string(attributes.get("parent.id"))
+            .map(|id| hex_id(id, 16))
+            .unwrap_or_default(),
+        name: string(attributes.get("name"))
+            .unwrap_or_default()
+            .to_string(),
+        kind,
+        start_time_unix_nano: unix_nano(span.timestamp),
+        end_time_unix_nano: unix_nano(span.timestamp + duration),
+        attributes: key_values(attributes, SPAN_FIELDS),
+        status: Status {
+            code,
+            message: string(attributes.get("otel.status_description"))
+                .unwrap_or_default()
+                .to_string(),
+        },
+    }
+}
+
+fn log_record(log: &NewrLog) -> LogRecord {
+    let attributes = &log.attributes;
+
+    // https://opentelemetry.io/docs/specs/otel/logs/data-model/#field-severitynumber
+    let severity_number = match log.level {
+        "TRACE" => 1,
+        "DEBUG" => 5,
+        "INFO" => 9,
+        "WARN" => 13,
+        _ => 17,
+    };
+
+    LogRecord {
+        time_unix_nano: unix_nano(log.timestamp),
+        severity_number,
+        severity_text: log.level,
+        body: attributes.get("message").map(AnyValue::from),
+        attributes: key_values(attributes, LOG_FIELDS),
+        trace_id: string(attributes.get("trace.id")).map(|id| hex_id(id, 32)),
+        span_id: string(attributes.get("span.id")).map(|id| hex_id(id, 16)),
+    }
+}
+
+/// Attributes sorted by key, leaving out the ones mapped onto fields
+fn key_values(attributes: &NewrAttributes, fields: &[&str]) -> Vec<KeyValue> {
+    let mut key_values: Vec<KeyValue> = attributes
+        .0
+        .iter()
+        .filter(|(key, _)| !fields.contains(&key.as_ref()))
+        .map(|(key, value)| KeyValue {
+            key: key.to_string(),
+            value: value.into(),
+        })
+        .collect();
+
+    key_values.sort_by(|a, b| a.key.cmp(&b.key));
+    key_values
+}
+
+fn string(value: Option<&Value>) -> Option<&str> {
+    match value {
+        Some(Value::String(s)) => Some(s),
+        _ => None,
+    }
+}
+
+fn unix_nano(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string()
+}
@@ -0,0 +1,58 @@
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::panic::{self, PanicHookInfo};
+use std::sync::Once;
+
+/// Record panics as `ERROR` logs of the current span, before running the previous panic hook
+///
+/// The log carries `panic.message`, `panic.file`, `panic.line`, and `panic.backtrace` when
+/// backtraces are enabled by `RUST_BACKTRACE`. It also records the message as `error`, so the
+/// span is marked as failed. Calling it more than once installs the hook only once.
+pub fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+
+    INSTALL.call_once(|| {
+        let previous = panic::take_hook();
+
+        panic::set_hook(Box::new(move |info| {
+            record(info);
+            previous(info);
+        }));
+    });
+}
+
+fn record(info: &PanicHookInfo<'_>) {
+    let payload = info.payload();
+
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("Box<dyn Any>");
+
+    let (file, line) = match info.location() {
+        Some(location) => (location.file(), location.line()),
+        None => ("", 0),
+    };
+
+    // only captured if enabled by `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE`
+    let backtrace = Backtrace::capture();
+
+    if backtrace.status() == BacktraceStatus::Captured {
+        tracing::error!(
+            panic.message = message,
+            panic.file = file,
+            panic.line = line,
+            panic.backtrace = %backtrace,
+            error = message,
+            "panicked"
+        );
+    } else {
+        tracing::error!(
+            panic.message = message,
+            panic.file = file,
+            panic.line = line,
+            error = message,
+            "panicked"
+        );
+    }
+}
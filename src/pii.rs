@@ -0,0 +1,153 @@
+use std::borrow::Cow;
+
+use regex::{Captures, Regex, RegexSet};
+
+use crate::error::ConfigError;
+use crate::types::{NewrAttributes, Value};
+
+/// Rules masking personal data in attribute values, see [`NewRelicLayer::with_pii_scrubbing`]
+///
+/// The default rules mask email addresses, card numbers, i.e. 13 to 19 digits passing the Luhn
+/// check, and bearer tokens.
+///
+/// [`NewRelicLayer::with_pii_scrubbing`]: crate::NewRelicLayer::with_pii_scrubbing
+#[derive(Clone)]
+pub struct PiiRules {
+    rules: Vec<Rule>,
+    // every pattern, to skip values matching none of them in a single pass
+    set: RegexSet,
+    min_len: usize,
+}
+
+#[derive(Clone)]
+struct Rule {
+    regex: Regex,
+    replacement: String,
+    // only replace matches passing the Luhn check
+    luhn: bool,
+}
+
+impl PiiRules {
+    /// Rules masking nothing, to be completed with [`with_rule`](PiiRules::with_rule)
+    pub fn empty() -> Self {
+        PiiRules {
+            rules: Vec::new(),
+            set: RegexSet::empty(),
+            min_len: 6,
+        }
+    }
+
+    /// Replace the matches of the regular expression `pattern` with `replacement`
+    ///
+    /// `replacement` may refer to capture groups, e.g. `$1`.
+    pub fn with_rule(self, pattern: &str, replacement: &str) -> Result<Self, ConfigError> {
+        let regex = Regex::new(pattern).map_err(|_| ConfigError::InvalidPattern {
+            pattern: pattern.to_string(),
+        })?;
+
+        Ok(self.push(regex, replacement, false))
+    }
+
+    /// Leave values shorter than `len` bytes alone. Default to `6`.
+    pub fn with_min_len(mut self, len: usize) -> Self {
+        self.min_len = len;
+        self
+    }
+
+    fn push(mut self, regex: Regex, replacement: &str, luhn: bool) -> Self {
+        self.rules.push(Rule {
+            regex,
+            replacement: replacement.to_string(),
+            luhn,
+        });
+
+        self.set = RegexSet::new(self.rules.iter().map(|rule| rule.regex.as_str()))
+            .expect("patterns compiled already");
+
+        self
+    }
+
+    /// Mask the string values of `attributes`
+    pub(crate) fn scrub(&self, attributes: &mut NewrAttributes) {
+        for value in attributes.0.values_mut() {
+            if let Value::String(s) = value {
+                if let Cow::Owned(scrubbed) = self.scrub_str(s) {
+                    *s = scrubbed;
+                }
+            }
+        }
+    }
+
+    fn scrub_str<'a>(&self, s: &'a str) -> Cow<'a, str> {
+        if s.len() < self.min_len {
+            return Cow::Borrowed(s);
+        }
+
+        let matches = self.set.matches(s);
+
+        if !matches.matched_any() {
+            return Cow::Borrowed(s);
+        }
+
+        let mut scrubbed = Cow::Borrowed(s);
+
+        for index in matches.iter() {
+            let rule = &self.rules[index];
+
+            let replaced = if rule.luhn {
+                rule.regex
+                    .replace_all(&scrubbed, |captures: &Captures<'_>| {
+                        if luhn(&captures[0]) {
+                            rule.replacement.clone()
+                        } else {
+                            captures[0].to_string()
+                        }
+                    })
+            } else {
+                rule.regex.replace_all(&scrubbed, rule.replacement.as_str())
+            };
+
+            if let Cow::Owned(replaced) = replaced {
+                scrubbed = Cow::Owned(replaced);
+            }
+        }
+
+        scrubbed
+    }
+}
+
+impl Default for PiiRules {
+    fn default() -> Self {
+        let regex = |pattern| Regex::new(pattern).expect("valid built-in pattern");
+
+        PiiRules::empty()
+            .push(
+                regex(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}"),
+                "<email>",
+                false,
+            )
+            .push(regex(r"\b[0-9]{13,19}\b"), "<card>", true)
+            .push(
+                regex(r"(?i)\b(bearer)\s+[A-Za-z0-9._~+/-]+=*"),
+                "$1 <token>",
+                false,
+            )
+    }
+}
+
+/// Whether a number passes the Luhn check, like card numbers do
+fn luhn(digits: &str) -> bool {
+    let sum: u32 = digits
+        .bytes()
+        .rev()
+        .map(|b| u32::from(b - b'0'))
+        .enumerate()
+        .map(|(i, digit)| match (i % 2, digit * 2) {
+            (0, _) => digit,
+            (_, doubled) if doubled > 9 => doubled - 9,
+            (_, doubled) => doubled,
+        })
+        .sum();
+
+    sum.is_multiple_of(10)
+}
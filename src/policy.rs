@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::types::{NewrAttributes, NewrLog, NewrSpan, Value};
+
+type Predicate = dyn Fn(&[NewrSpan], &[NewrLog]) -> bool + Send + Sync;
+
+/// Decide whether a completed trace should be exported
+///
+/// The policy is evaluated when the root span closes, once the whole trace has been buffered.
+#[derive(Default)]
+pub enum ExportPolicy {
+    /// Export every trace, Default
+    #[default]
+    All,
+    /// Export traces containing an error, or whose root span lasted longer than the given duration
+    ///
+    /// A trace contains an error if any of its spans has `otel.status_code` set to `ERROR`,
+    /// or any of its logs has `ERROR` level.
+    ErrorsOrSlowerThan(Duration),
+    /// Export traces for which the given predicate returns `true`
+    ///
+    /// The first span is always the root span.
+    Custom(Box<Predicate>),
+}
+
+impl ExportPolicy {
+    pub(crate) fn should_export(&self, spans: &[NewrSpan], logs: &[NewrLog]) -> bool {
+        match self {
+            ExportPolicy::All => true,
+            ExportPolicy::ErrorsOrSlowerThan(threshold) => {
+                has_error(spans, logs) || root_duration_ms(spans) > threshold.as_secs_f64() * 1000.0
+            }
+            ExportPolicy::Custom(predicate) => predicate(spans, logs),
+        }
+    }
+}
+
+/// Whether any span has `otel.status_code` set to `ERROR`, or any log has `ERROR` level
+pub(crate) fn has_error(spans: &[NewrSpan], logs: &[NewrLog]) -> bool {
+    spans.iter().any(|span| {
+        matches!(
+            span.attributes.get("otel.status_code"),
+            Some(Value::String(code)) if code == "ERROR"
+        )
+    }) || logs.iter().any(|log| log.level == "ERROR")
+}
+
+fn root_duration_ms(spans: &[NewrSpan]) -> f64 {
+    match spans
+        .first()
+        .and_then(|span| span.attributes.get("duration.ms"))
+    {
+        Some(Value::F64(ms)) => *ms,
+        _ => 0.0,
+    }
+}
+
+/// Decide what happens to attributes whose value is an empty string, `"null"` or `"undefined"`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum EmptyValuePolicy {
+    /// Keep the attribute as is, Default
+    #[default]
+    Keep,
+    /// Remove the attribute
+    Drop,
+    /// Replace the value with the given placeholder, e.g. `"<empty>"`
+    Replace(String),
+}
+
+/// Decide what happens to fields recorded with a key reserved to the layer, e.g. `trace.id`,
+/// `parent.id` or `duration.ms`, see [`NewRelicLayer::with_reserved_key_policy`]
+///
+/// [`NewRelicLayer::with_reserved_key_policy`]: crate::NewRelicLayer::with_reserved_key_policy
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReservedKeyPolicy {
+    /// Prefix the key with `custom.`, e.g. `custom.trace.id`, Default
+    #[default]
+    Rename,
+    /// Drop the field, warning about it the first time
+    Drop,
+}
+
+/// Empty value policies of a layer, a global one and per-key overrides
+#[derive(Clone, Default)]
+pub(crate) struct EmptyValues {
+    pub(crate) default: EmptyValuePolicy,
+    pub(crate) per_key: HashMap<String, EmptyValuePolicy>,
+}
+
+impl EmptyValues {
+    pub(crate) fn apply(&self, attributes: &mut NewrAttributes) {
+        if self.default == EmptyValuePolicy::Keep && self.per_key.is_empty() {
+            return;
+        }
+
+        attributes.0.retain(|key, value| {
+            let empty = matches!(
+                value,
+                Value::String(s) if s.is_empty()
+                    || s.eq_ignore_ascii_case("null")
+                    || s.eq_ignore_ascii_case("undefined")
+            );
+
+            if !empty {
+                return true;
+            }
+
+            match self.per_key.get(key.as_ref()).unwrap_or(&self.default) {
+                EmptyValuePolicy::Keep => true,
+                EmptyValuePolicy::Drop => false,
+                EmptyValuePolicy::Replace(placeholder) => {
+                    *value = Value::String(placeholder.clone());
+                    true
+                }
+            }
+        });
+    }
+}
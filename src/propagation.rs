@@ -0,0 +1,121 @@
+//! Continue traces across services with the W3C `traceparent` header
+//!
+//! Outgoing requests carry the header of the current span, see [`traceparent`]. Root spans
+//! recording an incoming header as a `traceparent` field continue the trace of the caller,
+//! instead of starting a new one:
+//!
+//! ```rust
+//! # let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+//! let span = tracing::info_span!("GET /users", traceparent = header);
+//! ```
+//!
+//! The field itself isn't exported, malformed headers are ignored. Traces the caller sampled are
+//! kept regardless of the sampling ratio.
+
+use tracing::field::{Field, Visit};
+use tracing::span::Attributes;
+use tracing::Span;
+use tracing_subscriber::registry::{LookupSpan, Registry};
+
+use crate::layer::Unsampled;
+use crate::types::NewrSpan;
+use crate::utils::hex_id;
+
+/// Name of the header, and of the field continuing a remote trace
+pub const TRACEPARENT: &str = "traceparent";
+
+/// The `traceparent` header of a span, to continue its trace in the called service
+///
+/// The sampled flag is set if the span is recorded, unset if its trace isn't sampled.
+///
+/// `None` if the span isn't seen by a [`NewRelicLayer`](crate::NewRelicLayer) on top of a
+/// `Registry`, e.g. when it's disabled.
+pub fn traceparent(span: &Span) -> Option<String> {
+    span.with_subscriber(|(id, dispatch)| {
+        let registry = dispatch.downcast_ref::<Registry>()?;
+        let span = registry.span(id)?;
+        let extensions = span.extensions();
+
+        if let Some(nr_span) = extensions.get::<NewrSpan>() {
+            let trace_id = nr_span.trace_id.as_deref()?;
+
+            return Some(format!(
+                "00-{}-{}-01",
+                hex_id(trace_id, 32),
+                hex_id(&nr_span.id, 16)
+            ));
+        }
+
+        // unsampled spans aren't exported, the id of the `tracing` span stands for theirs
+        let trace_id = extensions.get::<Unsampled>()?.trace_id.as_deref()?;
+
+        Some(format!(
+            "00-{}-{:016x}-00",
+            hex_id(trace_id, 32),
+            id.into_u64()
+        ))
+    })
+    .flatten()
+}
+
+/// The trace id, the parent id, and whether the caller sampled the trace, of a span recording
+/// a valid `traceparent` field
+pub(crate) fn remote_parent(attrs: &Attributes<'_>) -> Option<(String, String, bool)> {
+    attrs.metadata().fields().field(TRACEPARENT)?;
+
+    let mut visitor = Header(None);
+    attrs.record(&mut visitor);
+
+    parse(&visitor.0?)
+}
+
+/// `00-{trace id}-{parent id}-{flags}`, ids can't be all zeros
+fn parse(header: &str) -> Option<(String, String, bool)> {
+    let parts: Vec<&str> = header.trim().split('-').collect();
+
+    let (version, trace_id, parent_id, flags) = match parts[..] {
+        [version, trace_id, parent_id, flags] => (version, trace_id, parent_id, flags),
+        _ => return None,
+    };
+
+    let is_hex =
+        |part: &str, len: usize| part.len() == len && part.bytes().all(|b| b.is_ascii_hexdigit());
+    let is_zero = |part: &str| part.bytes().all(|b| b == b'0');
+
+    if !is_hex(version, 2)
+        || version.eq_ignore_ascii_case("ff")
+        || !is_hex(trace_id, 32)
+        || !is_hex(parent_id, 16)
+        || !is_hex(flags, 2)
+        || is_zero(trace_id)
+        || is_zero(parent_id)
+    {
+        return None;
+    }
+
+    // the lowest bit of the flags is the sampled one
+    let sampled = u8::from_str_radix(flags, 16).ok()? & 1 == 1;
+
+    Some((
+        trace_id.to_ascii_lowercase(),
+        parent_id.to_ascii_lowercase(),
+        sampled,
+    ))
+}
+
+/// Finds the `traceparent` field
+struct Header(Option<String>);
+
+impl Visit for Header {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == TRACEPARENT {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == TRACEPARENT {
+            self.0 = Some(format!("{:?}", value));
+        }
+    }
+}
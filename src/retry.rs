@@ -0,0 +1,69 @@
+use std::time::{Duration, SystemTime};
+
+use crate::utils::random;
+
+/// How failed requests are retried
+///
+/// The first retry happens immediately, the following ones wait `initial_backoff`, doubling
+/// every time up to `max_backoff`. A `retry-after` header on a `429` response is honored,
+/// but capped to `max_backoff` as well.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Number of requests sent for the same data before dropping it, including the first one
+    pub max_attempts: u32,
+    /// Delay before the second retry
+    pub initial_backoff: Duration,
+    /// Upper bound of every delay
+    pub max_backoff: Duration,
+    /// Wait a random duration between zero and the computed delay, so that many instances
+    /// failing at the same time don't retry in lockstep
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    /// 7 attempts, backoff from 1 to 30 seconds, with jitter
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 7,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before the given retry, starting from `1`
+    pub fn backoff(&self, retry: u32) -> Duration {
+        if retry <= 1 {
+            return Duration::from_secs(0);
+        }
+
+        let exponent = (retry - 2).min(31);
+
+        let delay = self
+            .initial_backoff
+            .checked_mul(1 << exponent)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff);
+
+        if self.jitter {
+            delay.mul_f64(random())
+        } else {
+            delay
+        }
+    }
+
+    /// Delay requested by a `retry-after` header, capped to `max_backoff`
+    pub(crate) fn retry_after(&self, value: &str) -> Option<Duration> {
+        let delay = match value.trim().parse::<u64>() {
+            Ok(seconds) => Duration::from_secs(seconds),
+            Err(_) => {
+                let date = httpdate::parse_http_date(value.trim()).ok()?;
+                date.duration_since(SystemTime::now()).unwrap_or_default()
+            }
+        };
+
+        Some(delay.min(self.max_backoff))
+    }
+}
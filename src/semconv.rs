@@ -0,0 +1,66 @@
+//! Translation of OpenTelemetry semantic conventions into the attributes New Relic keys off, see
+//! [`NewRelicLayer::with_otel_compat`]
+//!
+//! [`NewRelicLayer::with_otel_compat`]: crate::NewRelicLayer::with_otel_compat
+
+use crate::types::{NewrAttributes, Value};
+
+/// OpenTelemetry attribute keys, and the New Relic ones they are recorded under
+pub const OTEL_ATTRIBUTES: &[(&str, &str)] = &[
+    ("otel.kind", "span.kind"),
+    ("http.request.method", "http.method"),
+    ("http.response.status_code", "http.statusCode"),
+    ("url.full", "http.url"),
+    ("server.address", "peer.hostname"),
+    ("db.system.name", "db.system"),
+    ("db.query.text", "db.statement"),
+    ("db.collection.name", "db.collection"),
+    ("exception.message", "error.message"),
+    ("exception.type", "error.class"),
+    ("exception.stacktrace", "error.stack"),
+];
+
+/// Prefix of the attributes describing an exception
+const EXCEPTION_PREFIX: &str = "exception.";
+
+/// Rename the OpenTelemetry attributes, returning the error message if an exception is recorded
+///
+/// Attributes recorded under the New Relic key already win over the translated ones.
+pub(crate) fn translate(attributes: &mut NewrAttributes, keep_original: bool) -> Option<String> {
+    let exception = attributes
+        .0
+        .keys()
+        .any(|key| key.starts_with(EXCEPTION_PREFIX));
+
+    for (otel, newrelic) in OTEL_ATTRIBUTES.iter() {
+        let value = match keep_original {
+            true => attributes.get(otel).cloned(),
+            false => attributes.remove(otel),
+        };
+
+        let value = match value {
+            // `SERVER` and `server` alike
+            Some(Value::String(kind)) if *otel == "otel.kind" => {
+                Value::String(kind.to_ascii_lowercase())
+            }
+            Some(value) => value,
+            None => continue,
+        };
+
+        if attributes.get(newrelic).is_none() {
+            attributes.insert(*newrelic, value);
+        }
+    }
+
+    if !exception {
+        return None;
+    }
+
+    match attributes
+        .get("error.message")
+        .or_else(|| attributes.get("error.class"))
+    {
+        Some(Value::String(message)) => Some(message.clone()),
+        _ => Some("exception".to_string()),
+    }
+}
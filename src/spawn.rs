@@ -0,0 +1,96 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::Mutex;
+
+use tokio::task::{self, JoinHandle};
+use tracing::instrument::WithSubscriber;
+use tracing::{dispatcher, Instrument, Metadata, Span};
+
+/// Spawn a future on the current tokio runtime, inside the current span
+///
+/// Spans created by the future then belong to the current trace, instead of starting new ones.
+/// The future also keeps the current subscriber, even if it's not the global one.
+pub fn spawn_traced<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    task::spawn(future.instrument(Span::current()).with_current_subscriber())
+}
+
+/// Run a blocking closure on the current tokio runtime, inside the current span, see [`spawn_traced`]
+pub fn spawn_blocking_traced<F, R>(f: F) -> JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let span = Span::current();
+    let dispatch = dispatcher::get_default(|dispatch| dispatch.clone());
+
+    task::spawn_blocking(move || dispatcher::with_default(&dispatch, || span.in_scope(f)))
+}
+
+thread_local! {
+    // trace of the span entered last on this thread, and the task it was entered in
+    static LAST_ENTERED: RefCell<Option<(String, Option<task::Id>)>> = const { RefCell::new(None) };
+}
+
+/// Warns about root spans likely created by tasks spawned without their span, see
+/// [`NewRelicLayer::with_orphan_root_warning`](crate::NewRelicLayer::with_orphan_root_warning)
+#[derive(Default)]
+pub(crate) struct OrphanDetector {
+    // traces whose root span is still open
+    open: Mutex<HashSet<String>>,
+}
+
+impl OrphanDetector {
+    pub(crate) fn entered(&self, trace_id: &str) {
+        LAST_ENTERED.with(|last| {
+            let mut last = last.borrow_mut();
+
+            match &mut *last {
+                Some((last_trace_id, last_task)) if last_trace_id == trace_id => {
+                    *last_task = task::try_id();
+                }
+                _ => *last = Some((trace_id.to_string(), task::try_id())),
+            }
+        });
+    }
+
+    /// Check a new root span, then track its trace until it closes
+    pub(crate) fn opened(&self, trace_id: &str, metadata: &Metadata<'_>) {
+        let mut open = self.open.lock().unwrap();
+
+        // a root created by a task on the worker thread that just ran another trace, which is
+        // still open, most likely comes from a task spawned by that trace
+        let spawned_by = task::try_id().and_then(|task| {
+            LAST_ENTERED.with(|last| match &*last.borrow() {
+                Some((last_trace_id, last_task))
+                    if *last_task != Some(task)
+                        && last_trace_id != trace_id
+                        && open.contains(last_trace_id) =>
+                {
+                    Some(last_trace_id.clone())
+                }
+                _ => None,
+            })
+        });
+
+        if let Some(spawned_by) = spawned_by {
+            log::warn!(
+                "root span '{}' at {}:{} was likely created by a task spawned inside trace {}, instrument the task with its span, e.g. with `spawn_traced`",
+                metadata.name(),
+                metadata.file().unwrap_or("<unknown>"),
+                metadata.line().unwrap_or_default(),
+                spawned_by
+            );
+        }
+
+        open.insert(trace_id.to_string());
+    }
+
+    pub(crate) fn closed(&self, trace_id: &str) {
+        self.open.lock().unwrap().remove(trace_id);
+    }
+}
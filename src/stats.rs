@@ -0,0 +1,94 @@
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+use crate::breaker::CircuitState;
+
+/// Counters of the data exported by a layer, shared with its [`Handle`]
+///
+/// [`Handle`]: crate::Handle
+#[derive(Default, Debug)]
+pub struct Stats {
+    pub(crate) spans_sent: AtomicU64,
+    pub(crate) logs_sent: AtomicU64,
+    pub(crate) metrics_sent: AtomicU64,
+    pub(crate) events_sent: AtomicU64,
+    pub(crate) batches_sent: AtomicU64,
+    pub(crate) bytes_sent: AtomicU64,
+    pub(crate) send_failures: AtomicU64,
+    pub(crate) payloads_dropped: AtomicU64,
+    pub(crate) queue_drops: AtomicU64,
+    pub(crate) budget_drops: AtomicU64,
+    pub(crate) cache_evictions: AtomicU64,
+    pub(crate) logs_circuit: AtomicU8,
+    pub(crate) spans_circuit: AtomicU8,
+    pub(crate) metrics_circuit: AtomicU8,
+    pub(crate) events_circuit: AtomicU8,
+}
+
+/// A copy of the [`Stats`] counters at some point in time
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct StatsSnapshot {
+    /// Spans accepted by New Relic
+    pub spans_sent: u64,
+    /// Logs accepted by New Relic
+    pub logs_sent: u64,
+    /// Metrics accepted by New Relic, see [`NewRelicLayer::with_metrics`]
+    ///
+    /// [`NewRelicLayer::with_metrics`]: crate::NewRelicLayer::with_metrics
+    pub metrics_sent: u64,
+    /// Custom events accepted by New Relic, see [`Api::with_account_id`]
+    ///
+    /// [`Api::with_account_id`]: crate::Api::with_account_id
+    pub events_sent: u64,
+    /// Requests accepted by New Relic
+    pub batches_sent: u64,
+    /// Compressed bytes accepted by New Relic
+    pub bytes_sent: u64,
+    /// Requests that failed, including the ones retried afterwards
+    pub send_failures: u64,
+    /// Payloads given up on, rejected or out of retries
+    pub payloads_dropped: u64,
+    /// Traces dropped because the queue to the worker thread was full
+    pub queue_drops: u64,
+    /// Traces dropped over the ingest budget, see [`NewRelicLayer::with_ingest_budget`]
+    ///
+    /// [`NewRelicLayer::with_ingest_budget`]: crate::NewRelicLayer::with_ingest_budget
+    pub budget_drops: u64,
+    /// Entries evicted from internal caches, a steady increase means their capacity is too small
+    pub cache_evictions: u64,
+    /// State of the circuit breaker of the log endpoint
+    pub logs_circuit: CircuitState,
+    /// State of the circuit breaker of the trace endpoint
+    pub spans_circuit: CircuitState,
+    /// State of the circuit breaker of the metric endpoint
+    pub metrics_circuit: CircuitState,
+    /// State of the circuit breaker of the event endpoint
+    pub events_circuit: CircuitState,
+}
+
+impl Stats {
+    /// Read every counter
+    pub fn snapshot(&self) -> StatsSnapshot {
+        StatsSnapshot {
+            spans_sent: self.spans_sent.load(Ordering::Relaxed),
+            logs_sent: self.logs_sent.load(Ordering::Relaxed),
+            metrics_sent: self.metrics_sent.load(Ordering::Relaxed),
+            events_sent: self.events_sent.load(Ordering::Relaxed),
+            batches_sent: self.batches_sent.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            send_failures: self.send_failures.load(Ordering::Relaxed),
+            payloads_dropped: self.payloads_dropped.load(Ordering::Relaxed),
+            queue_drops: self.queue_drops.load(Ordering::Relaxed),
+            budget_drops: self.budget_drops.load(Ordering::Relaxed),
+            cache_evictions: self.cache_evictions.load(Ordering::Relaxed),
+            logs_circuit: CircuitState::load(&self.logs_circuit),
+            spans_circuit: CircuitState::load(&self.spans_circuit),
+            metrics_circuit: CircuitState::load(&self.metrics_circuit),
+            events_circuit: CircuitState::load(&self.events_circuit),
+        }
+    }
+}
+
+#[inline]
+pub(crate) fn add(counter: &AtomicU64, n: usize) {
+    counter.fetch_add(n as u64, Ordering::Relaxed);
+}
@@ -0,0 +1,502 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot;
+use tokio::time::{self, MissedTickBehavior};
+
+use super::api::{json_len, Outcome, Sendable, Transport};
+
+/// When a stream sends its queue
+pub(crate) struct Batching {
+    /// Send as soon as that many payloads are queued, and at most that many per batch
+    pub batch_size: usize,
+    /// Send at least once per interval, even if the batch isn't full
+    pub flush_interval: Option<Duration>,
+    /// Queue held payloads after this long, even if they haven't been released
+    pub hold_timeout: Option<Duration>,
+    /// Number of batches being sent at the same time
+    pub concurrency: usize,
+    /// Send payloads failing with a transient error again with the next batch, until they're that old
+    pub requeue_max_age: Option<Duration>,
+    /// Evict the oldest payloads once the queued ones take up more than that many bytes
+    pub max_queue_bytes: Option<usize>,
+}
+
+enum Message<T> {
+    /// Queue a payload, or hold it back until its token is released
+    Push {
+        token: u64,
+        item: T,
+        held: bool,
+        priority: bool,
+    },
+    /// Queue the held payloads with these tokens
+    Release(Vec<u64>),
+    /// Send the queue, after queueing every held payload if `release_held`
+    Flush {
+        release_held: bool,
+        done: oneshot::Sender<bool>,
+    },
+}
+
+/// A task on the worker runtime draining the queue of either logs or spans
+///
+/// Each stream has its own queue and retries, so a slow or rate-limited endpoint
+/// doesn't hold back the other one.
+pub(crate) struct Stream<T> {
+    sender: UnboundedSender<Message<T>>,
+    // payloads pushed and not sent yet, either accepted or dropped
+    pending: Arc<AtomicUsize>,
+}
+
+impl<T: Sendable + Send + Sync + 'static> Stream<T> {
+    /// Spawn the task, `on_sent` is called with the token of each payload sent and whether it was accepted
+    pub(crate) fn spawn<F>(transport: Arc<Transport>, batching: Batching, on_sent: F) -> Self
+    where
+        F: Fn(&[(u64, bool)]) + Send + Sync + 'static,
+    {
+        let (sender, receiver) = unbounded_channel();
+        let pending = Arc::new(AtomicUsize::new(0));
+
+        let queue = Queue {
+            items: Vec::with_capacity(batching.batch_size),
+            held: Vec::new(),
+            failed: Vec::new(),
+            last_flush: Instant::now(),
+            bytes: 0,
+            evicted: 0,
+            warned_at: None,
+        };
+
+        tokio::spawn(drain(
+            transport,
+            receiver,
+            batching,
+            queue,
+            pending.clone(),
+            on_sent,
+        ));
+
+        Stream { sender, pending }
+    }
+
+    /// Queue a payload, `priority` if it belongs to a trace containing an error, sent first
+    /// and evicted last
+    pub(crate) fn push(&self, token: u64, item: T, held: bool, priority: bool) {
+        self.pending.fetch_add(1, Ordering::Relaxed);
+        let _ = self.sender.send(Message::Push {
+            token,
+            item,
+            held,
+            priority,
+        });
+    }
+
+    /// Release the held payloads with the given tokens
+    pub(crate) fn release(&self, tokens: Vec<u64>) {
+        if !tokens.is_empty() {
+            let _ = self.sender.send(Message::Release(tokens));
+        }
+    }
+
+    /// Send the queue, returning whether every payload has been accepted
+    pub(crate) async fn flush(&self) -> bool {
+        self.send_flush(false).await
+    }
+
+    /// Queue every held payload and send the queue, returning whether every payload has been accepted
+    pub(crate) async fn release_all_and_flush(&self) -> bool {
+        self.send_flush(true).await
+    }
+
+    async fn send_flush(&self, release_held: bool) -> bool {
+        let (done, result) = oneshot::channel();
+
+        if self
+            .sender
+            .send(Message::Flush { release_held, done })
+            .is_err()
+        {
+            return false;
+        }
+
+        result.await.unwrap_or(false)
+    }
+
+    /// Number of payloads pushed and not sent yet
+    pub(crate) fn pending(&self) -> usize {
+        self.pending.load(Ordering::Relaxed)
+    }
+}
+
+/// How often evictions are warned about at most
+const EVICTION_WARNING_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A payload with its token
+struct Entry<T> {
+    token: u64,
+    pushed_at: Instant,
+    // approximate size, zero if the queue isn't capped
+    bytes: usize,
+    // whether its trace contains an error, sent first and evicted last
+    priority: bool,
+    item: T,
+}
+
+struct Queue<T> {
+    // payloads to be sent, the priority ones first
+    items: Vec<Entry<T>>,
+    // payloads waiting to be released
+    held: Vec<Entry<T>>,
+    // payloads that failed with a transient error, waiting for the next batch
+    failed: Vec<Entry<T>>,
+    last_flush: Instant,
+    // size of the payloads above, not counting the ones being sent
+    bytes: usize,
+    // payloads evicted since the last warning
+    evicted: usize,
+    warned_at: Option<Instant>,
+}
+
+impl<T> Queue<T> {
+    /// Move held payloads matching `predicate` into the queue
+    fn release(&mut self, predicate: impl Fn(u64, Instant) -> bool) {
+        let mut index = 0;
+
+        while index < self.held.len() {
+            let entry = &self.held[index];
+
+            if predicate(entry.token, entry.pushed_at) {
+                let entry = self.held.remove(index);
+                self.enqueue(entry);
+            } else {
+                index += 1;
+            }
+        }
+    }
+
+    /// Queue a payload after the ones with the same priority
+    fn enqueue(&mut self, entry: Entry<T>) {
+        if entry.priority {
+            let index = self.items.partition_point(|entry| entry.priority);
+            self.items.insert(index, entry);
+        } else {
+            self.items.push(entry);
+        }
+    }
+
+    /// Keep payloads to be sent again with the next batch
+    fn fail(&mut self, failed: Vec<Entry<T>>) {
+        self.bytes += failed.iter().map(|entry| entry.bytes).sum::<usize>();
+        self.failed.extend(failed);
+    }
+
+    /// Put the failed payloads back at the front of the queue, dropping the ones too old to be kept
+    ///
+    /// Returns the tokens of the dropped payloads.
+    fn requeue(&mut self, transport: &Transport, max_age: Option<Duration>) -> Vec<(u64, bool)>
+    where
+        T: Sendable,
+    {
+        let (kept, expired): (Vec<_>, Vec<_>) = self
+            .failed
+            .drain(..)
+            .partition(|entry| max_age.is_some_and(|age| entry.pushed_at.elapsed() < age));
+
+        // ahead of the payloads with the same priority
+        let (kept_priority, kept): (Vec<_>, Vec<_>) =
+            kept.into_iter().partition(|entry| entry.priority);
+
+        let index = self.items.partition_point(|entry| entry.priority);
+        self.items.splice(index..index, kept);
+        self.items.splice(0..0, kept_priority);
+
+        if expired.is_empty() {
+            return Vec::new();
+        }
+
+        self.bytes -= expired.iter().map(|entry| entry.bytes).sum::<usize>();
+
+        log::warn!(
+            "giving up on {} {} failed too many times",
+            expired.len(),
+            T::KIND.name()
+        );
+
+        self.drop_entries(transport, expired, "failed too many times")
+    }
+
+    /// Evict the oldest payloads until the queue takes up at most `max_bytes`, the priority
+    /// ones last
+    ///
+    /// Returns the tokens of the evicted payloads.
+    fn evict(&mut self, transport: &Transport, max_bytes: usize) -> Vec<(u64, bool)>
+    where
+        T: Sendable,
+    {
+        let mut evicted = Vec::new();
+
+        while self.bytes > max_bytes {
+            let oldest = [&self.items, &self.held, &self.failed]
+                .iter()
+                .enumerate()
+                .flat_map(|(list, entries)| {
+                    entries
+                        .iter()
+                        .enumerate()
+                        .map(move |(index, entry)| ((entry.priority, entry.pushed_at), list, index))
+                })
+                .min_by_key(|(key, _, _)| *key);
+
+            let Some((_, list, index)) = oldest else {
+                break;
+            };
+
+            let entry = match list {
+                0 => self.items.remove(index),
+                1 => self.held.remove(index),
+                _ => self.failed.remove(index),
+            };
+
+            self.bytes -= entry.bytes;
+            evicted.push(entry);
+        }
+
+        if evicted.is_empty() {
+            return Vec::new();
+        }
+
+        self.evicted += evicted.len();
+
+        if self
+            .warned_at
+            .is_none_or(|at| at.elapsed() >= EVICTION_WARNING_INTERVAL)
+        {
+            log::warn!(
+                "evicted {} {} from a queue over {} bytes",
+                self.evicted,
+                T::KIND.name(),
+                max_bytes
+            );
+
+            self.evicted = 0;
+            self.warned_at = Some(Instant::now());
+        }
+
+        self.drop_entries(transport, evicted, "evicted from a full queue")
+    }
+
+    /// Report payloads as dropped, returning their tokens
+    fn drop_entries(
+        &self,
+        transport: &Transport,
+        entries: Vec<Entry<T>>,
+        reason: &str,
+    ) -> Vec<(u64, bool)>
+    where
+        T: Sendable,
+    {
+        let (tokens, items): (Vec<u64>, Vec<T>) = entries
+            .into_iter()
+            .map(|entry| (entry.token, entry.item))
+            .unzip();
+
+        transport.report_dropped(&items, transport.url(&T::KIND), None, reason.into(), None);
+
+        tokens.into_iter().map(|token| (token, false)).collect()
+    }
+
+    /// Take the next batch of at most `batch_size` payloads
+    fn take(&mut self, batch_size: usize) -> Vec<Entry<T>> {
+        self.last_flush = Instant::now();
+        let len = self.items.len().min(batch_size.max(1));
+        let batch: Vec<_> = self.items.drain(..len).collect();
+        self.bytes -= batch.iter().map(|entry| entry.bytes).sum::<usize>();
+        batch
+    }
+}
+
+/// Send a batch, returning the token of each payload sent and whether it was accepted,
+/// along with the payloads to be sent again
+async fn send<T: Sendable>(
+    transport: Arc<Transport>,
+    batch: Vec<Entry<T>>,
+    requeue: bool,
+) -> (Vec<(u64, bool)>, Vec<Entry<T>>) {
+    let name = T::KIND.name();
+
+    log::debug!("flushing {}, batch_len={}", name, batch.len());
+
+    let items: Vec<T> = batch.iter().map(|entry| entry.item.clone()).collect();
+
+    let outcomes = transport.send_all(&items, requeue).await;
+
+    log::info!("flushed {}, batch_len={}", name, items.len());
+
+    let mut sent = Vec::with_capacity(batch.len());
+    let mut failed = Vec::new();
+
+    for (entry, outcome) in batch.into_iter().zip(outcomes) {
+        match outcome {
+            Outcome::Delivered => sent.push((entry.token, true)),
+            Outcome::Dropped => sent.push((entry.token, false)),
+            Outcome::Failed => failed.push(entry),
+        }
+    }
+
+    (sent, failed)
+}
+
+async fn drain<T: Sendable>(
+    transport: Arc<Transport>,
+    mut receiver: UnboundedReceiver<Message<T>>,
+    batching: Batching,
+    mut queue: Queue<T>,
+    pending: Arc<AtomicUsize>,
+    on_sent: impl Fn(&[(u64, bool)]),
+) {
+    let mut interval = [batching.flush_interval, batching.hold_timeout]
+        .iter()
+        .flatten()
+        .min()
+        .map(|period| {
+            let mut interval = time::interval(*period);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            interval
+        });
+
+    let mut in_flight = FuturesUnordered::new();
+
+    // flushes waiting for the queue to be sent, with whether everything has been accepted so far
+    let mut flushes: Vec<(oneshot::Sender<bool>, bool)> = Vec::new();
+
+    let mut closed = false;
+
+    let requeue = batching.requeue_max_age.is_some();
+
+    let finish = |sent: &[(u64, bool)]| {
+        if !sent.is_empty() {
+            pending.fetch_sub(sent.len(), Ordering::Relaxed);
+            on_sent(sent);
+        }
+    };
+
+    loop {
+        // whether to send the failed payloads again
+        let mut retry = false;
+
+        tokio::select! {
+            message = receiver.recv(), if !closed => match message {
+                Some(Message::Push { token, item, held, priority }) => {
+                    let bytes = if batching.max_queue_bytes.is_some() {
+                        json_len(&item)
+                    } else {
+                        0
+                    };
+
+                    queue.bytes += bytes;
+
+                    let entry = Entry {
+                        token,
+                        pushed_at: Instant::now(),
+                        bytes,
+                        priority,
+                        item,
+                    };
+
+                    if held {
+                        queue.held.push(entry);
+                    } else {
+                        queue.enqueue(entry);
+                        retry = queue.items.len() >= batching.batch_size;
+                    }
+
+                    if let Some(max_bytes) = batching.max_queue_bytes {
+                        let evicted = queue.evict(&transport, max_bytes);
+
+                        if !evicted.is_empty() {
+                            for (_, succeeded) in &mut flushes {
+                                *succeeded = false;
+                            }
+
+                            finish(&evicted);
+                        }
+                    }
+                }
+                Some(Message::Release(tokens)) => {
+                    queue.release(|token, _| tokens.contains(&token));
+                }
+                Some(Message::Flush { release_held, done }) => {
+                    if release_held {
+                        queue.release(|_, _| true);
+                    }
+
+                    flushes.push((done, true));
+                    retry = true;
+                }
+                None => {
+                    // send everything left before stopping
+                    queue.release(|_, _| true);
+                    closed = true;
+                    retry = true;
+                }
+            },
+            Some((sent, failed)) = in_flight.next() => {
+                let (sent, failed): (Vec<(u64, bool)>, Vec<Entry<T>>) = (sent, failed);
+
+                finish(&sent);
+
+                let accepted = failed.is_empty() && sent.iter().all(|(_, accepted)| *accepted);
+
+                queue.fail(failed);
+
+                for (_, succeeded) in &mut flushes {
+                    *succeeded &= accepted;
+                }
+            }
+            _ = crate::tick(&mut interval), if !closed => {}
+        }
+
+        if let Some(timeout) = batching.hold_timeout {
+            queue.release(|_, held_at| held_at.elapsed() >= timeout);
+        }
+
+        let interval_elapsed = batching
+            .flush_interval
+            .is_some_and(|interval| queue.last_flush.elapsed() >= interval);
+
+        let flushing = closed || !flushes.is_empty();
+
+        if retry || interval_elapsed {
+            finish(&queue.requeue(&transport, batching.requeue_max_age));
+        }
+
+        if interval_elapsed && queue.items.is_empty() {
+            queue.last_flush = Instant::now();
+        }
+
+        // start as many batches as allowed
+        while in_flight.len() < batching.concurrency.max(1)
+            && !queue.items.is_empty()
+            && (flushing || interval_elapsed || queue.items.len() >= batching.batch_size)
+        {
+            let batch = queue.take(batching.batch_size);
+            in_flight.push(send(transport.clone(), batch, requeue));
+        }
+
+        if in_flight.is_empty() && queue.items.is_empty() {
+            for (done, succeeded) in flushes.drain(..) {
+                let _ = done.send(succeeded);
+            }
+
+            if closed {
+                // failed again while shutting down
+                finish(&queue.requeue(&transport, None));
+                break;
+            }
+        }
+    }
+}
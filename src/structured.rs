@@ -0,0 +1,191 @@
+use serde_json::{Map, Value as Json};
+use std::convert::TryFrom;
+use valuable::{NamedValues, Value, Visit};
+
+use crate::types::{MAX_JSON_DEPTH, MAX_VALUE_LEN};
+
+/// The JSON of a value recorded with `valuable`, `None` if too long or too deep
+///
+/// Structs and maps become objects, lists and tuples arrays, and enums their variant name,
+/// or an object with the fields of the variant under its name.
+pub(crate) fn to_json(value: Value<'_>) -> Option<Json> {
+    let mut remaining = MAX_VALUE_LEN;
+    convert(value, 0, &mut remaining)
+}
+
+fn convert(value: Value<'_>, depth: usize, remaining: &mut usize) -> Option<Json> {
+    let json = match value {
+        Value::Bool(b) => Json::from(b),
+        Value::Char(c) => Json::from(c.to_string()),
+        Value::F32(f) => Json::from(f64::from(f)),
+        Value::F64(f) => Json::from(f),
+        Value::I8(i) => Json::from(i),
+        Value::I16(i) => Json::from(i),
+        Value::I32(i) => Json::from(i),
+        Value::I64(i) => Json::from(i),
+        Value::Isize(i) => Json::from(i),
+        Value::I128(i) => i64::try_from(i).map_or_else(|_| Json::from(i.to_string()), Json::from),
+        Value::U8(u) => Json::from(u),
+        Value::U16(u) => Json::from(u),
+        Value::U32(u) => Json::from(u),
+        Value::U64(u) => Json::from(u),
+        Value::Usize(u) => Json::from(u),
+        Value::U128(u) => u64::try_from(u).map_or_else(|_| Json::from(u.to_string()), Json::from),
+        Value::String(s) => Json::from(s),
+        Value::Path(path) => Json::from(path.display().to_string()),
+        Value::Error(error) => Json::from(error.to_string()),
+        Value::Listable(_)
+        | Value::Mappable(_)
+        | Value::Structable(_)
+        | Value::Enumerable(_)
+        | Value::Tuplable(_) => return nested(value, depth + 1, remaining),
+        _ => Json::Null,
+    };
+
+    spend(remaining, len(&json))?;
+
+    Some(json)
+}
+
+/// Convert a value made of other values
+fn nested(value: Value<'_>, depth: usize, remaining: &mut usize) -> Option<Json> {
+    if depth > MAX_JSON_DEPTH {
+        return None;
+    }
+
+    // brackets or braces
+    spend(remaining, 2)?;
+
+    let mut visitor = Visitor {
+        depth,
+        remaining,
+        values: Vec::new(),
+        fields: Map::new(),
+        named: false,
+        failed: false,
+    };
+
+    match value {
+        Value::Listable(listable) => listable.visit(&mut visitor),
+        Value::Mappable(mappable) => {
+            visitor.named = true;
+            mappable.visit(&mut visitor);
+        }
+        Value::Structable(structable) => structable.visit(&mut visitor),
+        Value::Enumerable(enumerable) => enumerable.visit(&mut visitor),
+        Value::Tuplable(tuplable) => tuplable.visit(&mut visitor),
+        _ => {}
+    }
+
+    if visitor.failed {
+        return None;
+    }
+
+    let fields = if visitor.named {
+        Json::Object(visitor.fields)
+    } else {
+        Json::Array(visitor.values)
+    };
+
+    match value {
+        Value::Enumerable(enumerable) => {
+            let name = enumerable.variant().name().to_string();
+            spend(visitor.remaining, name.len() + 2)?;
+
+            match fields {
+                Json::Array(values) if values.is_empty() => Some(Json::String(name)),
+                fields => {
+                    let mut variant = Map::new();
+                    variant.insert(name, fields);
+                    Some(Json::Object(variant))
+                }
+            }
+        }
+        // `()`
+        Value::Tuplable(_) if fields == Json::Array(Vec::new()) => Some(Json::Null),
+        _ => Some(fields),
+    }
+}
+
+/// Take `len` bytes out of the remaining ones, `None` if there aren't enough
+fn spend(remaining: &mut usize, len: usize) -> Option<()> {
+    *remaining = remaining.checked_sub(len)?;
+    Some(())
+}
+
+/// Approximate length of a scalar serialized
+fn len(json: &Json) -> usize {
+    match json {
+        Json::String(s) => s.len() + 2,
+        Json::Number(n) => n.to_string().len(),
+        Json::Bool(_) | Json::Null => 5,
+        _ => 0,
+    }
+}
+
+struct Visitor<'r> {
+    depth: usize,
+    remaining: &'r mut usize,
+    values: Vec<Json>,
+    fields: Map<String, Json>,
+    // whether the fields are named, i.e. an object
+    named: bool,
+    failed: bool,
+}
+
+impl Visitor<'_> {
+    fn convert(&mut self, value: Value<'_>) -> Option<Json> {
+        if self.failed {
+            return None;
+        }
+
+        let json = convert(value, self.depth, self.remaining);
+        self.failed = json.is_none();
+        json
+    }
+
+    fn field(&mut self, key: String, value: Value<'_>) {
+        if spend(self.remaining, key.len() + 3).is_none() {
+            self.failed = true;
+        }
+
+        if let Some(json) = self.convert(value) {
+            self.fields.insert(key, json);
+        }
+    }
+}
+
+impl Visit for Visitor<'_> {
+    fn visit_value(&mut self, value: Value<'_>) {
+        if let Some(json) = self.convert(value) {
+            self.values.push(json);
+        }
+    }
+
+    fn visit_named_fields(&mut self, named_values: &NamedValues<'_>) {
+        self.named = true;
+
+        for (field, value) in named_values {
+            self.field(field.name().to_string(), *value);
+        }
+    }
+
+    fn visit_unnamed_fields(&mut self, values: &[Value<'_>]) {
+        for value in values {
+            self.visit_value(*value);
+        }
+    }
+
+    fn visit_entry(&mut self, key: Value<'_>, value: Value<'_>) {
+        let key = match key {
+            Value::String(key) => key.to_string(),
+            key => match self.convert(key) {
+                Some(Json::String(key)) => key,
+                Some(key) => key.to_string(),
+                None => return,
+            },
+        };
+
+        self.field(key, value);
+    }
+}
@@ -0,0 +1,48 @@
+use std::time::Duration;
+
+use crate::types::{NewrLog, NewrSpan, Value};
+
+/// Summary of a completed trace, see [`NewRelicLayer::with_trace_completed`]
+///
+/// [`NewRelicLayer::with_trace_completed`]: crate::NewRelicLayer::with_trace_completed
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceSummary {
+    /// Identifier shared by all spans of the trace.
+    pub trace_id: String,
+    /// `name` of the root span, once normalized.
+    pub root_name: String,
+    /// How long the root span was open.
+    pub duration: Duration,
+    /// Number of spans, the root one included.
+    pub span_count: usize,
+    /// Number of logs.
+    pub log_count: usize,
+    /// Whether any span has `otel.status_code` set to `ERROR`.
+    pub error: bool,
+}
+
+impl TraceSummary {
+    /// Summarize a trace whose first span is the root one
+    pub(crate) fn new(spans: &[NewrSpan], logs: &[NewrLog], duration: Duration) -> Self {
+        let root = &spans[0];
+
+        let root_name = match root.attributes.get("name") {
+            Some(Value::String(name)) => name.clone(),
+            _ => String::new(),
+        };
+
+        TraceSummary {
+            trace_id: root.trace_id.clone().unwrap_or_default(),
+            root_name,
+            duration,
+            span_count: spans.len(),
+            log_count: logs.len(),
+            error: spans.iter().any(|span| {
+                matches!(
+                    span.attributes.get("otel.status_code"),
+                    Some(Value::String(code)) if code == "ERROR"
+                )
+            }),
+        }
+    }
+}
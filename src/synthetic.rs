@@ -0,0 +1,26 @@
+//! Built-in detectors for synthetic traffic, see [`NewRelicLayer::with_synthetic_detector`]
+//!
+//! [`NewRelicLayer::with_synthetic_detector`]: crate::NewRelicLayer::with_synthetic_detector
+
+use crate::types::{NewrSpan, Value};
+
+pub(crate) type Detector = dyn Fn(&NewrSpan) -> bool + Send + Sync;
+
+/// Detect health checks, root spans whose name ends with `/healthz`
+pub fn health_check(span: &NewrSpan) -> bool {
+    matches!(
+        span.attributes.get("name"),
+        Some(Value::String(name)) if name.ends_with("/healthz")
+    )
+}
+
+/// Detect synthetic monitors, root spans with a `user-agent` or `http.user_agent`
+/// attribute containing `Synthetic`
+pub fn synthetic_user_agent(span: &NewrSpan) -> bool {
+    ["user-agent", "http.user_agent"].iter().any(|key| {
+        matches!(
+            span.attributes.get(key),
+            Some(Value::String(agent)) if agent.contains("Synthetic")
+        )
+    })
+}
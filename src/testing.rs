@@ -0,0 +1,145 @@
+//! In-memory exporter and test harness
+//!
+//! Available with the `testing` feature.
+//!
+//! ```rust
+//! let captured = tracing_newrelic::testing::with_captured(|| {
+//!     let span = tracing::info_span!("root");
+//!     let _span = span.enter();
+//!     tracing::info!("hello");
+//! });
+//!
+//! assert_eq!(captured.spans()[0].id, "span_1");
+//! assert_eq!(captured.logs().len(), 1);
+//! ```
+
+use futures_util::future::BoxFuture;
+use serde::{ser::Error as _, Serialize, Serializer};
+use std::sync::{Arc, Mutex};
+use tracing_core::dispatcher::{self, Dispatch};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+use crate::api::{spans_request, Api, Kind, Sendable, Transport};
+use crate::exporter::Exporter;
+use crate::layer::NewRelicLayer;
+use crate::transport::TelemetryRequest;
+use crate::types::{NewrLog, NewrLogs, NewrSpan, NewrSpans};
+
+/// An [`Exporter`] keeping every payload in memory
+#[derive(Clone, Default)]
+pub struct CapturingExporter {
+    payloads: Arc<Mutex<Vec<(NewrLogs, NewrSpans)>>>,
+}
+
+impl CapturingExporter {
+    /// Create an empty exporter
+    pub fn new() -> Self {
+        CapturingExporter::default()
+    }
+
+    /// Payloads exported so far
+    pub fn captured(&self) -> Captured {
+        Captured {
+            payloads: self.payloads.lock().unwrap().clone(),
+        }
+    }
+}
+
+impl Exporter for CapturingExporter {
+    fn export(&mut self, logs: NewrLogs, spans: NewrSpans) -> BoxFuture<'_, ()> {
+        self.payloads.lock().unwrap().push((logs, spans));
+        Box::pin(async {})
+    }
+
+    fn shutdown(&mut self) -> BoxFuture<'_, ()> {
+        Box::pin(async {})
+    }
+}
+
+/// Payloads captured by a [`CapturingExporter`]
+#[derive(Clone, Debug)]
+pub struct Captured {
+    /// Logs and spans payloads, in the order they were exported
+    pub payloads: Vec<(NewrLogs, NewrSpans)>,
+}
+
+impl Captured {
+    /// All spans, across payloads
+    pub fn spans(&self) -> Vec<&NewrSpan> {
+        self.payloads
+            .iter()
+            .flat_map(|(_, spans)| &spans.spans)
+            .collect()
+    }
+
+    /// All logs, across payloads
+    pub fn logs(&self) -> Vec<&NewrLog> {
+        self.payloads
+            .iter()
+            .flat_map(|(logs, _)| &logs.logs)
+            .collect()
+    }
+
+    /// The first span with the given name
+    pub fn span(&self, name: &str) -> Option<&NewrSpan> {
+        self.spans().into_iter().find(|span| {
+            matches!(span.attributes.get("name"), Some(crate::Value::String(n)) if n == name)
+        })
+    }
+}
+
+/// Run `f` with a [`NewRelicLayer`] installed as the default subscriber, and return every exported payload
+///
+/// The layer uses deterministic ids and timestamps, see [`NewRelicLayer::with_deterministic_ids`].
+pub fn with_captured(f: impl FnOnce()) -> Captured {
+    with_captured_layer(|layer| layer, f)
+}
+
+/// Same as [`with_captured`], but `configure` can customize the layer before installing it
+pub fn with_captured_layer(
+    configure: impl FnOnce(NewRelicLayer) -> NewRelicLayer,
+    f: impl FnOnce(),
+) -> Captured {
+    let exporter = CapturingExporter::new();
+
+    let layer = configure(crate::layer_with_exporter(exporter.clone()).with_deterministic_ids());
+
+    // the layer is dropped at the end, waiting for the worker to export everything
+    dispatcher::with_default(&Dispatch::new(Registry::default().with(layer)), f);
+
+    exporter.captured()
+}
+
+/// Send a spans payload failing to serialize with `api`, to test how export errors are handled
+///
+/// The payload is dropped and reported to the error handler, like any payload failing to
+/// serialize. Must be called from within a tokio runtime.
+pub async fn send_unserializable_spans(api: &Api) {
+    api.transport().send_all(&[Unserializable], false).await;
+}
+
+/// A spans payload failing to serialize
+#[derive(Clone)]
+struct Unserializable;
+
+impl Serialize for Unserializable {
+    fn serialize<S: Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+        Err(S::Error::custom("unserializable payload"))
+    }
+}
+
+impl Sendable for Unserializable {
+    const KIND: Kind = Kind::Spans;
+
+    fn build_request(
+        data: &[Unserializable],
+        body: Vec<u8>,
+        transport: &Transport,
+    ) -> TelemetryRequest {
+        spans_request(data, body, transport)
+    }
+
+    fn count(data: &[Unserializable]) -> usize {
+        data.len()
+    }
+}
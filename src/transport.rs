@@ -0,0 +1,280 @@
+use futures_util::future::BoxFuture;
+use http::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_ENCODING, CONTENT_TYPE};
+use std::error::Error;
+use std::fmt;
+#[cfg(feature = "blocking")]
+use std::io::Read;
+use std::time::Duration;
+
+use crate::compression::Compression;
+
+/// Beginning of a rejected response body kept, about 1 KB
+const MAX_LEN: usize = 1024;
+
+/// Http stack sending the requests of [`Api`], see [`Api::with_transport`]
+///
+/// Implemented for `reqwest::Client` with the `reqwest` feature, enabled by default, and for
+/// `ureq::Agent` with the `blocking` feature.
+///
+/// [`Api`]: crate::Api
+/// [`Api::with_transport`]: crate::Api::with_transport
+pub trait HttpTransport: Send + Sync + 'static {
+    /// Send a `POST` request, failing only if no response was received
+    fn post(
+        &self,
+        request: TelemetryRequest,
+    ) -> BoxFuture<'_, Result<TelemetryResponse, TransportError>>;
+}
+
+/// A request to New Relic, see [`HttpTransport`]
+#[derive(Debug, Clone)]
+pub struct TelemetryRequest {
+    /// Url of the endpoint
+    pub url: String,
+    /// Headers with lowercase names, including the key and the `Content-Encoding` of the body
+    pub headers: Vec<(String, String)>,
+    /// Body, already compressed
+    pub body: Vec<u8>,
+    /// How long the request can take, see [`Api::with_request_timeout`]
+    ///
+    /// [`Api::with_request_timeout`]: crate::Api::with_request_timeout
+    pub timeout: Duration,
+}
+
+impl TelemetryRequest {
+    /// A JSON request, with the user headers set already
+    pub(crate) fn post(
+        url: String,
+        body: Vec<u8>,
+        compression: Compression,
+        headers: &HeaderMap,
+        timeout: Duration,
+    ) -> Self {
+        let mut request = TelemetryRequest {
+            url,
+            headers: Vec::new(),
+            body,
+            timeout,
+        };
+
+        for (name, value) in headers {
+            if let Ok(value) = value.to_str() {
+                request.headers.push((name.to_string(), value.to_string()));
+            }
+        }
+
+        request = request.header(CONTENT_TYPE.as_str(), "application/json");
+
+        if let Some(encoding) = compression.content_encoding() {
+            request = request.header(CONTENT_ENCODING.as_str(), encoding);
+        }
+
+        request
+    }
+
+    /// Set a header, replacing the one with the same name if any
+    pub(crate) fn header(mut self, name: &str, value: &str) -> Self {
+        let name = name.to_ascii_lowercase();
+
+        self.headers.retain(|(other, _)| *other != name);
+        self.headers.push((name, value.to_string()));
+        self
+    }
+
+    /// The headers, failing on an invalid name or value
+    pub(crate) fn header_map(&self) -> Result<HeaderMap, TransportError> {
+        let mut headers = HeaderMap::new();
+
+        for (name, value) in &self.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| TransportError::new(format!("invalid header name '{}'", name)))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|_| TransportError::new(format!("invalid value of header '{}'", name)))?;
+
+            headers.insert(name, value);
+        }
+
+        Ok(headers)
+    }
+}
+
+/// The response of New Relic to a [`TelemetryRequest`]
+#[derive(Debug, Clone, Default)]
+pub struct TelemetryResponse {
+    /// Status code
+    pub status: u16,
+    /// Value of the `Retry-After` header, if any
+    pub retry_after: Option<String>,
+    /// Beginning of the body, only read for responses other than `2xx`. About 1 KB is enough.
+    pub body_snippet: String,
+}
+
+/// Failure to get a response from New Relic, e.g. timed out or couldn't connect
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransportError {
+    message: String,
+}
+
+impl TransportError {
+    /// Create an error with the given message
+    pub fn new(message: impl Into<String>) -> Self {
+        TransportError {
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for TransportError {}
+
+/// A response other than `2xx`
+pub(crate) struct Rejection {
+    pub(crate) status: u16,
+    // beginning of the body
+    pub(crate) body: String,
+    // id of the request, asked by New Relic support
+    pub(crate) request_id: Option<String>,
+}
+
+impl Rejection {
+    pub(crate) fn new(response: &TelemetryResponse) -> Self {
+        let mut body = response.body_snippet.clone();
+
+        // looked up without parsing, the body may have been cut short
+        let request_id = body
+            .split_once(r#""requestId":""#)
+            .and_then(|(_, rest)| rest.split_once('"'))
+            .map(|(request_id, _)| request_id.to_string());
+
+        if body.len() > MAX_LEN {
+            let mut end = MAX_LEN;
+
+            while !body.is_char_boundary(end) {
+                end -= 1;
+            }
+
+            body.truncate(end);
+        }
+
+        Rejection {
+            status: response.status,
+            body,
+            request_id,
+        }
+    }
+}
+
+/// Sent when no other transport is available, see [`Api::with_transport`]
+///
+/// [`Api::with_transport`]: crate::Api::with_transport
+#[cfg(not(feature = "reqwest"))]
+pub(crate) struct NoTransport;
+
+#[cfg(not(feature = "reqwest"))]
+impl HttpTransport for NoTransport {
+    fn post(
+        &self,
+        _: TelemetryRequest,
+    ) -> BoxFuture<'_, Result<TelemetryResponse, TransportError>> {
+        Box::pin(async { Err(TransportError::new("no http transport configured")) })
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl HttpTransport for reqwest::Client {
+    fn post(
+        &self,
+        request: TelemetryRequest,
+    ) -> BoxFuture<'_, Result<TelemetryResponse, TransportError>> {
+        Box::pin(async move {
+            let headers = request.header_map()?;
+
+            let mut res = reqwest::Client::post(self, request.url)
+                .headers(headers)
+                .timeout(request.timeout)
+                .body(request.body)
+                .send()
+                .await
+                .map_err(|err| TransportError::new(err.to_string()))?;
+
+            let status = res.status().as_u16();
+
+            let retry_after = res
+                .headers()
+                .get("retry-after")
+                .and_then(|val| val.to_str().ok())
+                .map(String::from);
+
+            // read the body right away, instead of holding the response while waiting to retry
+            let mut bytes = Vec::new();
+
+            if !res.status().is_success() {
+                while bytes.len() < MAX_LEN {
+                    match res.chunk().await {
+                        Ok(Some(chunk)) => bytes.extend_from_slice(&chunk),
+                        _ => break,
+                    }
+                }
+            }
+
+            Ok(TelemetryResponse {
+                status,
+                retry_after,
+                body_snippet: String::from_utf8_lossy(&bytes).into_owned(),
+            })
+        })
+    }
+}
+
+/// Sends from the worker thread synchronously, blocking its runtime meanwhile
+#[cfg(feature = "blocking")]
+impl HttpTransport for ureq::Agent {
+    fn post(
+        &self,
+        request: TelemetryRequest,
+    ) -> BoxFuture<'_, Result<TelemetryResponse, TransportError>> {
+        let result = (|| {
+            let headers = request.header_map()?;
+
+            let mut call = ureq::Agent::post(self, &request.url).timeout(request.timeout);
+
+            for (name, value) in &headers {
+                if let Ok(value) = value.to_str() {
+                    call = call.set(name.as_str(), value);
+                }
+            }
+
+            let res = match call.send_bytes(&request.body) {
+                Ok(res) => res,
+                Err(ureq::Error::Status(_, res)) => res,
+                Err(err) => return Err(TransportError::new(err.to_string())),
+            };
+
+            let status = res.status();
+
+            let retry_after = res.header("retry-after").map(String::from);
+
+            let mut bytes = Vec::new();
+
+            if !(200..300).contains(&status) {
+                let _ = res
+                    .into_reader()
+                    .take(2 * MAX_LEN as u64)
+                    .read_to_end(&mut bytes);
+            }
+
+            Ok(TelemetryResponse {
+                status,
+                retry_after,
+                body_snippet: String::from_utf8_lossy(&bytes).into_owned(),
+            })
+        })();
+
+        Box::pin(async move { result })
+    }
+}
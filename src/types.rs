@@ -1,20 +1,40 @@
-use serde::Serialize;
+use indexmap::IndexMap;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use serde_json::value::RawValue;
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
 use std::fmt::Debug;
-use std::time::{Instant, SystemTime};
+use std::fmt::Write;
+use std::iter;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 use tracing_core::field::{Field, Visit};
 use tracing_core::Level;
+use tracing_subscriber::field::RecordFields;
 
-use crate::utils::{next_span_id, now, serialize_system_time};
+use crate::policy::ReservedKeyPolicy;
+use crate::utils::{millis, serialize_system_time, serialize_system_time_micros};
 
-#[derive(Serialize, Clone, Debug)]
+/// Attribute value
+#[derive(Serialize, Clone, Debug, PartialEq)]
 #[serde(untagged)]
 pub enum Value {
+    /// Signed integer
     I64(i64),
+    /// Unsigned integer
     U64(u64),
+    /// Floating point number
     F64(f64),
+    /// Boolean
     Bool(bool),
+    /// String
     String(String),
+    /// Structured value, e.g. an object or an array, see [`Json`]
+    Json(serde_json::Value),
 }
 
 impl From<i64> for Value {
@@ -29,12 +49,36 @@ impl From<u64> for Value {
     }
 }
 
+impl From<i32> for Value {
+    fn from(i: i32) -> Self {
+        Value::I64(i.into())
+    }
+}
+
+impl From<u32> for Value {
+    fn from(i: u32) -> Self {
+        Value::U64(i.into())
+    }
+}
+
+impl From<usize> for Value {
+    fn from(i: usize) -> Self {
+        Value::U64(i as u64)
+    }
+}
+
 impl From<f64> for Value {
     fn from(i: f64) -> Self {
         Value::F64(i)
     }
 }
 
+impl From<f32> for Value {
+    fn from(i: f32) -> Self {
+        Value::F64(i.into())
+    }
+}
+
 impl From<bool> for Value {
     fn from(i: bool) -> Self {
         Value::Bool(i)
@@ -53,12 +97,380 @@ impl From<&str> for Value {
     }
 }
 
-#[derive(Serialize, Default, Clone, Debug)]
-pub struct NewrAttributes(pub HashMap<String, Value>);
+impl From<serde_json::Value> for Value {
+    fn from(i: serde_json::Value) -> Self {
+        Value::Json(i)
+    }
+}
+
+/// Suffix of the fields recorded as structured values
+const JSON_SUFFIX: &str = ".json";
+
+/// Longest attribute value kept by New Relic, in bytes
+pub(crate) const MAX_VALUE_LEN: usize = 4094;
+
+/// Deepest JSON recorded as a structured value
+pub(crate) const MAX_JSON_DEPTH: usize = 16;
+
+/// Formats a value as JSON, to be recorded as a structured value
+///
+/// Fields named with a `.json` suffix whose value is a JSON object or array are recorded
+/// as structured values, without the suffix. Values longer than 4094 bytes or nested more
+/// than 16 levels deep are recorded as strings.
+///
+/// ```rust
+/// use tracing_newrelic::Json;
+///
+/// #[derive(serde::Serialize)]
+/// struct Summary {
+///     status: u16,
+///     items: Vec<u32>,
+/// }
+///
+/// let summary = Summary {
+///     status: 200,
+///     items: vec![1, 2],
+/// };
+///
+/// // recorded as `response = {"status":200,"items":[1,2]}`
+/// tracing::info!(response.json = %Json(&summary));
+/// ```
+pub struct Json<T>(pub T);
+
+impl<T: Serialize> std::fmt::Display for Json<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match serde_json::to_string(&self.0) {
+            Ok(json) => f.write_str(&json),
+            Err(_) => Err(std::fmt::Error),
+        }
+    }
+}
+
+impl<T: Serialize> Debug for Json<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(self, f)
+    }
+}
+
+/// The structured value of a field named with the `.json` suffix, along with its name
+/// without the suffix
+fn json_field(name: &'static str, value: &str) -> Option<(&'static str, serde_json::Value)> {
+    let name = name.strip_suffix(JSON_SUFFIX)?;
+
+    let value = value.trim();
+
+    if value.len() > MAX_VALUE_LEN || !(value.starts_with('{') || value.starts_with('[')) {
+        return None;
+    }
+
+    let json: serde_json::Value = serde_json::from_str(value).ok()?;
+
+    if depth(&json) > MAX_JSON_DEPTH {
+        return None;
+    }
+
+    Some((name, json))
+}
+
+/// Levels of nesting of a JSON value, zero for scalars
+fn depth(value: &serde_json::Value) -> usize {
+    match value {
+        serde_json::Value::Array(values) => 1 + values.iter().map(depth).max().unwrap_or(0),
+        serde_json::Value::Object(map) => 1 + map.values().map(depth).max().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+/// Attributes managed by this crate or given a meaning by New Relic, e.g. never prefixed
+pub(crate) const WELL_KNOWN_ATTRIBUTES: &[&str] = &[
+    "name",
+    "message",
+    "service.name",
+    "span.kind",
+    "span.id",
+    "trace.id",
+    "parent.id",
+    "duration.ms",
+    "source",
+    "hostname",
+    "nr.entryPoint",
+    "traceparent",
+    "error",
+    "error.message",
+    "error.cause",
+    "db.system",
+    "db.operation",
+    "db.statement",
+    NewrEvent::TYPE_FIELD,
+];
+
+/// Attributes set by the layer itself, fields recorded with these keys are renamed or dropped,
+/// see [`NewRelicLayer::with_reserved_key_policy`]
+///
+/// [`NewRelicLayer::with_reserved_key_policy`]: crate::NewRelicLayer::with_reserved_key_policy
+pub(crate) const RESERVED_ATTRIBUTES: &[&str] = &[
+    "id",
+    "trace.id",
+    "span.id",
+    "parent.id",
+    "duration.ms",
+    "timestamp",
+];
+
+/// Prefix of the fields renamed for their reserved key
+const RESERVED_PREFIX: &str = "custom.";
+
+/// Longest attribute key New Relic accepts, in bytes
+pub(crate) const MAX_KEY_LEN: usize = 255;
+
+/// Whether a character is safe in an attribute key
+fn is_key_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | ':' | '/' | '-')
+}
+
+fn is_sanitized(key: &str) -> bool {
+    key.len() <= MAX_KEY_LEN && key.chars().all(is_key_char)
+}
+
+/// Replace the unsafe characters of a key with `_`, and cut it short
+fn sanitize_key(key: &str) -> String {
+    key.chars()
+        .map(|c| if is_key_char(c) { c } else { '_' })
+        .take(MAX_KEY_LEN)
+        .collect()
+}
+
+/// Cut a key to [`MAX_KEY_LEN`] bytes, on a character boundary
+fn truncate_key(key: Cow<'static, str>) -> Cow<'static, str> {
+    if key.len() <= MAX_KEY_LEN {
+        return key;
+    }
+
+    let mut len = MAX_KEY_LEN;
+
+    while !key.is_char_boundary(len) {
+        len -= 1;
+    }
+
+    match key {
+        Cow::Borrowed(key) => Cow::Borrowed(&key[..len]),
+        Cow::Owned(mut key) => {
+            key.truncate(len);
+            Cow::Owned(key)
+        }
+    }
+}
+
+/// Prefixes of the well-known attributes, `trace.` being the one of trace attributes
+pub(crate) const WELL_KNOWN_PREFIXES: &[&str] = &["otel.", "trace."];
+
+/// Whether an attribute is well-known, see [`WELL_KNOWN_ATTRIBUTES`]
+pub(crate) fn is_well_known(key: &str) -> bool {
+    WELL_KNOWN_ATTRIBUTES.contains(&key)
+        || WELL_KNOWN_PREFIXES
+            .iter()
+            .any(|prefix| key.starts_with(prefix))
+}
+
+/// How the keys of recorded fields are rewritten, see [`NewRelicLayer::with_attribute_mapping`]
+/// and [`NewRelicLayer::with_attribute_prefix`]
+///
+/// [`NewRelicLayer::with_attribute_mapping`]: crate::NewRelicLayer::with_attribute_mapping
+/// [`NewRelicLayer::with_attribute_prefix`]: crate::NewRelicLayer::with_attribute_prefix
+#[derive(Clone)]
+pub(crate) struct AttributeKeys {
+    pub(crate) mapping: HashMap<String, String>,
+    pub(crate) prefix: Option<String>,
+    pub(crate) reserved: ReservedKeyPolicy,
+    // whether a field has been dropped for its reserved key already
+    pub(crate) warned_reserved: Arc<AtomicBool>,
+    pub(crate) sanitize: bool,
+}
+
+impl Default for AttributeKeys {
+    fn default() -> Self {
+        AttributeKeys {
+            mapping: HashMap::new(),
+            prefix: None,
+            reserved: ReservedKeyPolicy::default(),
+            warned_reserved: Arc::default(),
+            sanitize: true,
+        }
+    }
+}
+
+impl AttributeKeys {
+    /// Record the fields of a span or an event, renaming then prefixing their keys
+    pub(crate) fn record(&self, attributes: &mut NewrAttributes, fields: &impl RecordFields) {
+        if self.mapping.is_empty() && self.prefix.is_none() {
+            return fields.record(&mut Guard {
+                attributes,
+                keys: self,
+            });
+        }
+
+        let mut recorded = NewrAttributes::default();
+        fields.record(&mut recorded);
+
+        for (key, value) in &recorded.0 {
+            let key = match self.mapping.get(key.as_ref()) {
+                // the field recorded under the new key wins
+                Some(renamed) if recorded.0.contains_key(renamed.as_str()) => continue,
+                Some(renamed) => Cow::Owned(renamed.clone()),
+                None => key.clone(),
+            };
+
+            let key = match &self.prefix {
+                Some(prefix) if !is_well_known(&key) => Cow::Owned(format!("{}{}", prefix, key)),
+                _ => key,
+            };
+
+            self.insert(attributes, key, value.clone());
+        }
+    }
+
+    /// Insert a recorded field, sanitizing its key, unless it's reserved
+    fn insert(&self, attributes: &mut NewrAttributes, key: Cow<'static, str>, value: Value) {
+        let key = match self.sanitize && !is_sanitized(&key) {
+            true => {
+                let sanitized = sanitize_key(&key);
+
+                if attributes.0.contains_key(sanitized.as_str()) {
+                    log::debug!(
+                        "field '{}' sanitized to '{}' replaces the attribute with the same key",
+                        key,
+                        sanitized
+                    );
+                }
+
+                Cow::Owned(sanitized)
+            }
+            false => key,
+        };
+
+        if !RESERVED_ATTRIBUTES.contains(&key.as_ref()) {
+            attributes.insert(key, value);
+            return;
+        }
+
+        match self.reserved {
+            ReservedKeyPolicy::Rename => {
+                attributes.insert(format!("{}{}", RESERVED_PREFIX, key), value);
+            }
+            ReservedKeyPolicy::Drop => {
+                if !self.warned_reserved.swap(true, Ordering::Relaxed) {
+                    log::warn!(
+                        "dropping field '{}', its key is reserved to the layer, further ones are dropped silently",
+                        key
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Records fields into attributes, sanitizing their keys and keeping the reserved ones out
+struct Guard<'a> {
+    attributes: &'a mut NewrAttributes,
+    keys: &'a AttributeKeys,
+}
+
+impl Guard<'_> {
+    fn guard(&mut self, field: &Field, record: impl FnOnce(&mut NewrAttributes)) {
+        let name = field.name();
+        let name = name.strip_suffix(JSON_SUFFIX).unwrap_or(name);
+
+        if !RESERVED_ATTRIBUTES.contains(&name)
+            && !RESERVED_ATTRIBUTES.contains(&field.name())
+            && (!self.keys.sanitize || is_sanitized(field.name()))
+        {
+            return record(self.attributes);
+        }
+
+        let mut recorded = NewrAttributes::default();
+        record(&mut recorded);
+
+        for (key, value) in recorded.0 {
+            self.keys.insert(self.attributes, key, value);
+        }
+    }
+}
+
+impl Visit for Guard<'_> {
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.guard(field, |attributes| attributes.record_bool(field, value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.guard(field, |attributes| attributes.record_i64(field, value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.guard(field, |attributes| attributes.record_u64(field, value));
+    }
+
+    fn record_i128(&mut self, field: &Field, value: i128) {
+        self.guard(field, |attributes| attributes.record_i128(field, value));
+    }
+
+    fn record_u128(&mut self, field: &Field, value: u128) {
+        self.guard(field, |attributes| attributes.record_u128(field, value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.guard(field, |attributes| attributes.record_f64(field, value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.guard(field, |attributes| attributes.record_str(field, value));
+    }
+
+    fn record_bytes(&mut self, field: &Field, value: &[u8]) {
+        self.guard(field, |attributes| attributes.record_bytes(field, value));
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn Error + 'static)) {
+        self.guard(field, |attributes| attributes.record_error(field, value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.guard(field, |attributes| attributes.record_debug(field, value));
+    }
+
+    #[cfg(all(tracing_unstable, feature = "valuable"))]
+    fn record_value(&mut self, field: &Field, value: valuable::Value<'_>) {
+        self.guard(field, |attributes| attributes.record_value(field, value));
+    }
+}
+
+/// Custom attributes of a span, a log or a common block
+///
+/// Keys are borrowed when static, e.g. field names, instead of being copied. Attributes are
+/// serialized in the order they were first inserted.
+#[derive(Serialize, Default, Clone, Debug, PartialEq)]
+pub struct NewrAttributes(pub IndexMap<Cow<'static, str>, Value>);
 
 impl NewrAttributes {
-    pub fn insert<V: Into<Value>>(&mut self, key: &str, val: V) {
-        self.0.insert(key.into(), val.into());
+    /// Empty attributes with room for `capacity` of them
+    pub fn with_capacity(capacity: usize) -> Self {
+        NewrAttributes(IndexMap::with_capacity(capacity))
+    }
+
+    /// Insert an attribute, replacing the existing one
+    ///
+    /// Keys longer than 255 bytes, dropped by New Relic, are cut short.
+    pub fn insert<K: Into<Cow<'static, str>>, V: Into<Value>>(&mut self, key: K, val: V) {
+        self.0.insert(truncate_key(key.into()), val.into());
+    }
+
+    /// Get an attribute by its key
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.0.get(key)
+    }
+
+    /// Remove an attribute by its key, keeping the order of the others
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        self.0.shift_remove(key)
     }
 }
 
@@ -79,16 +491,95 @@ impl Visit for NewrAttributes {
         self.insert(field.name(), value);
     }
 
+    // numbers too big for 64 bits are recorded as decimal strings
+    fn record_i128(&mut self, field: &Field, value: i128) {
+        if let Ok(value) = i64::try_from(value) {
+            self.insert(field.name(), value);
+        } else if let Ok(value) = u64::try_from(value) {
+            self.insert(field.name(), value);
+        } else {
+            self.insert(field.name(), value.to_string());
+        }
+    }
+
+    fn record_u128(&mut self, field: &Field, value: u128) {
+        match u64::try_from(value) {
+            Ok(value) => self.insert(field.name(), value),
+            Err(_) => self.insert(field.name(), value.to_string()),
+        }
+    }
+
+    // lowercase hex, cut short to fit in an attribute value
+    fn record_bytes(&mut self, field: &Field, value: &[u8]) {
+        let value = &value[..value.len().min(MAX_VALUE_LEN / 2)];
+        let mut hex = String::with_capacity(value.len() * 2);
+
+        for byte in value {
+            let _ = write!(hex, "{:02x}", byte);
+        }
+
+        self.insert(field.name(), hex);
+    }
+
     fn record_str(&mut self, field: &Field, value: &str) {
-        self.insert(field.name(), value.to_string());
+        match json_field(field.name(), value) {
+            Some((name, json)) => self.insert(name, json),
+            None => self.insert(field.name(), value.to_string()),
+        }
     }
 
     fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
-        self.insert(field.name(), format!("{:?}", value));
+        let value = format!("{:?}", value);
+
+        match json_field(field.name(), &value) {
+            Some((name, json)) => self.insert(name, json),
+            None => self.insert(field.name(), value),
+        }
+    }
+
+    // structs, maps, lists and enums become structured values, unless too long or too deep
+    #[cfg(all(tracing_unstable, feature = "valuable"))]
+    fn record_value(&mut self, field: &Field, value: valuable::Value<'_>) {
+        match crate::structured::to_json(value) {
+            Some(serde_json::Value::Bool(b)) => self.insert(field.name(), b),
+            Some(serde_json::Value::String(s)) => self.insert(field.name(), s),
+            Some(serde_json::Value::Number(n)) if n.is_i64() => {
+                self.insert(field.name(), n.as_i64().unwrap_or_default())
+            }
+            Some(serde_json::Value::Number(n)) if n.is_u64() => {
+                self.insert(field.name(), n.as_u64().unwrap_or_default())
+            }
+            Some(serde_json::Value::Number(n)) => {
+                self.insert(field.name(), n.as_f64().unwrap_or_default())
+            }
+            Some(json) => self.insert(field.name(), json),
+            None => self.insert(field.name(), format!("{:?}", value)),
+        }
+    }
+
+    // an `error` field also sets `error.message`, and `error.cause` if it has sources, e.g.
+    // `outer: inner`
+    fn record_error(&mut self, field: &Field, value: &(dyn Error + 'static)) {
+        self.insert(field.name(), value.to_string());
+
+        if field.name() != "error" {
+            return;
+        }
+
+        self.insert("error.message", value.to_string());
+
+        let causes: Vec<String> = iter::successors(value.source(), |error| (*error).source())
+            .map(|error| error.to_string())
+            .collect();
+
+        if !causes.is_empty() {
+            self.insert("error.cause", causes.join(": "));
+        }
     }
 }
 
-#[derive(Serialize, Debug)]
+/// A span of the New Relic Trace API
+#[derive(Serialize, Clone, Debug)]
 pub struct NewrSpan {
     /// Unique identifier for this span.
     pub id: String,
@@ -100,69 +591,309 @@ pub struct NewrSpan {
     pub timestamp: SystemTime,
     /// Instant the span was created.
     #[serde(skip)]
-    pub instant: Instant,
+    pub(crate) instant: Instant,
     /// Any set of key: value pairs that add more details about a span.
     pub attributes: NewrAttributes,
 }
 
 impl NewrSpan {
-    pub fn new(name: String) -> Self {
-        let mut attributes = NewrAttributes::default();
+    /// A span with room for `capacity` attributes
+    pub(crate) fn new(name: String, id: String, timestamp: SystemTime, capacity: usize) -> Self {
+        let mut attributes = NewrAttributes::with_capacity(capacity);
         attributes.insert("name", name);
 
         NewrSpan {
-            id: next_span_id(),
+            id,
             trace_id: None,
-            timestamp: now(),
+            timestamp,
             instant: Instant::now(),
             attributes,
         }
     }
 
-    pub fn update_duration(&mut self) {
+    pub(crate) fn update_duration(&mut self) -> Duration {
         let duration = self.instant.elapsed();
-        let duration_ms = duration.as_secs_f64() * 1000.0;
-        self.attributes.insert("duration.ms", duration_ms);
+        self.attributes.insert("duration.ms", millis(duration));
+        duration
     }
 }
 
-#[derive(Serialize, Debug)]
+/// Precision of the timestamps of logs, see [`NewRelicLayer::with_timestamp_precision`]
+///
+/// [`NewRelicLayer::with_timestamp_precision`]: crate::NewRelicLayer::with_timestamp_precision
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum TimestampPrecision {
+    /// Whole milliseconds, Default
+    #[default]
+    Millis,
+    /// Milliseconds with a fractional part of microseconds, e.g. `1700000000000.237`
+    Micros,
+}
+
+/// A log of the New Relic Log API
+#[derive(Clone, Debug)]
 pub struct NewrLog {
-    #[serde(serialize_with = "serialize_system_time")]
+    /// Log time since the Unix epoch, in milliseconds.
     pub timestamp: SystemTime,
     // event contains a field named message
     // pub message: String,
     /// parsing rules
     // https://docs.newrelic.com/docs/logs/ui-data/parsing#logtype
     pub logtype: &'static str,
+    /// Any set of key: value pairs that add more details about a log.
     pub attributes: NewrAttributes,
+    /// Log level, e.g. `INFO`.
     pub level: &'static str,
+    /// Precision of the serialized `timestamp`.
+    pub(crate) precision: TimestampPrecision,
+    /// When the log was recorded, on a monotonic clock.
+    pub(crate) recorded: Instant,
 }
 
 impl NewrLog {
-    pub fn new(level: &Level) -> Self {
+    /// A log with room for `capacity` attributes
+    pub(crate) fn new(level: &Level, timestamp: SystemTime, capacity: usize) -> Self {
         NewrLog {
-            timestamp: now(),
+            timestamp,
             logtype: "accesslogs",
-            attributes: NewrAttributes::default(),
+            attributes: NewrAttributes::with_capacity(capacity),
             level: level.as_str(),
+            precision: TimestampPrecision::default(),
+            recorded: Instant::now(),
+        }
+    }
+}
+
+impl Serialize for NewrLog {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        struct Timestamp<'a>(&'a SystemTime, TimestampPrecision);
+
+        impl Serialize for Timestamp<'_> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                match self.1 {
+                    TimestampPrecision::Millis => serialize_system_time(self.0, serializer),
+                    TimestampPrecision::Micros => serialize_system_time_micros(self.0, serializer),
+                }
+            }
         }
+
+        let mut state = serializer.serialize_struct("NewrLog", 4)?;
+
+        state.serialize_field("timestamp", &Timestamp(&self.timestamp, self.precision))?;
+        state.serialize_field("logtype", &self.logtype)?;
+        state.serialize_field("attributes", &self.attributes)?;
+        state.serialize_field("level", &self.level)?;
+
+        state.end()
     }
 }
 
-#[derive(Serialize)]
+/// Attributes shared by every span or log of a payload
+#[derive(Clone, Debug)]
 pub struct NewrCommon {
-    pub attributes: NewrAttributes,
+    attributes: NewrAttributes,
+    // serialized `attributes`, shared between payloads with the same attributes, dropped once
+    // they're changed
+    serialized: Option<Arc<RawValue>>,
+    // account the trace is routed to, see `Api::with_account_router`
+    pub(crate) account: Option<String>,
+    // whether the trace contains an error, sent ahead of the others
+    pub(crate) priority: bool,
+}
+
+impl NewrCommon {
+    /// Create a common block from its attributes
+    pub fn new(attributes: NewrAttributes) -> Self {
+        NewrCommon {
+            attributes,
+            serialized: None,
+            account: None,
+            priority: false,
+        }
+    }
+
+    /// Common attributes
+    pub fn attributes(&self) -> &NewrAttributes {
+        &self.attributes
+    }
+
+    /// Common attributes, to be changed before the payload is exported
+    pub fn attributes_mut(&mut self) -> &mut NewrAttributes {
+        self.serialized = None;
+        &mut self.attributes
+    }
+
+    /// Create a common block whose attributes are serialized once and for all
+    pub(crate) fn cached(attributes: NewrAttributes) -> Self {
+        let serialized = serde_json::value::to_raw_value(&attributes)
+            .ok()
+            .map(Arc::from);
+
+        NewrCommon {
+            attributes,
+            serialized,
+            account: None,
+            priority: false,
+        }
+    }
 }
 
-#[derive(Serialize)]
+impl Serialize for NewrCommon {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("NewrCommon", 1)?;
+
+        match &self.serialized {
+            Some(raw) => state.serialize_field("attributes", &**raw)?,
+            None => state.serialize_field("attributes", &self.attributes)?,
+        }
+
+        state.end()
+    }
+}
+
+/// A payload of the New Relic Log API
+#[derive(Serialize, Clone, Debug)]
 pub struct NewrLogs {
+    /// Logs of a trace
     pub logs: Vec<NewrLog>,
+    /// Attributes shared by every log
     pub common: NewrCommon,
 }
 
-#[derive(Serialize)]
+/// A payload of the New Relic Trace API
+#[derive(Serialize, Clone, Debug)]
 pub struct NewrSpans {
+    /// Spans of a trace, the root span comes first
     pub spans: Vec<NewrSpan>,
+    /// Attributes shared by every span
     pub common: NewrCommon,
 }
+
+/// Count, sum, min and max of the values of a summary metric
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+pub struct NewrSummary {
+    /// Number of values
+    pub count: u64,
+    /// Sum of the values
+    pub sum: f64,
+    /// Smallest value
+    pub min: f64,
+    /// Largest value
+    pub max: f64,
+}
+
+impl NewrSummary {
+    pub(crate) fn new(value: f64) -> Self {
+        NewrSummary {
+            count: 1,
+            sum: value,
+            min: value,
+            max: value,
+        }
+    }
+
+    pub(crate) fn add(&mut self, value: f64) {
+        self.count += 1;
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+}
+
+/// A metric of the New Relic Metric API
+#[derive(Serialize, Clone, Debug)]
+pub struct NewrMetric {
+    /// Name of the metric, e.g. `span.duration`
+    pub name: String,
+    /// Type of the metric, always `summary`
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    /// Summary of the values over the interval
+    pub value: NewrSummary,
+    /// Dimensions of the metric
+    pub attributes: NewrAttributes,
+}
+
+/// A payload of the New Relic Metric API
+#[derive(Clone, Debug)]
+pub struct NewrMetrics {
+    /// Metrics of an interval
+    pub metrics: Vec<NewrMetric>,
+    /// Start of the interval
+    pub timestamp: SystemTime,
+    /// Length of the interval
+    pub interval: Duration,
+    /// Attributes shared by every metric
+    pub common: NewrCommon,
+}
+
+impl Serialize for NewrMetrics {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Common<'a> {
+            #[serde(serialize_with = "serialize_system_time")]
+            timestamp: &'a SystemTime,
+            #[serde(rename = "interval.ms")]
+            interval_ms: u64,
+            attributes: &'a NewrAttributes,
+        }
+
+        let mut state = serializer.serialize_struct("NewrMetrics", 2)?;
+
+        state.serialize_field(
+            "common",
+            &Common {
+                timestamp: &self.timestamp,
+                interval_ms: self.interval.as_millis() as u64,
+                attributes: &self.common.attributes,
+            },
+        )?;
+        state.serialize_field("metrics", &self.metrics)?;
+
+        state.end()
+    }
+}
+
+/// A custom event of the New Relic Event API
+///
+/// Recorded as an event with a `newrelic.event_type` field, e.g.
+/// `tracing::info!(newrelic.event_type = "OrderPlaced", order_id = 123)`.
+#[derive(Serialize, Clone, Debug)]
+pub struct NewrEvent {
+    /// Type of the event, queried with `FROM <eventType>`
+    #[serde(rename = "eventType")]
+    pub event_type: String,
+    /// Event time in milliseconds since the Unix epoch.
+    #[serde(serialize_with = "serialize_system_time")]
+    pub timestamp: SystemTime,
+    /// Any set of key: value pairs, sent alongside `eventType` and `timestamp`.
+    #[serde(flatten)]
+    pub attributes: NewrAttributes,
+}
+
+impl NewrEvent {
+    /// Field of an event holding its custom event type
+    pub(crate) const TYPE_FIELD: &'static str = "newrelic.event_type";
+
+    /// The custom event recorded as `log`, if it has an event type, along with the common attributes
+    pub(crate) fn from_log(log: &NewrLog, common: &NewrCommon) -> Option<Self> {
+        let event_type = match log.attributes.get(Self::TYPE_FIELD) {
+            Some(Value::String(event_type)) if !event_type.is_empty() => event_type.clone(),
+            _ => return None,
+        };
+
+        let mut attributes = common.attributes.clone();
+
+        for (key, value) in &log.attributes.0 {
+            if key != Self::TYPE_FIELD {
+                attributes.0.insert(key.clone(), value.clone());
+            }
+        }
+
+        Some(NewrEvent {
+            event_type,
+            timestamp: log.timestamp,
+            attributes,
+        })
+    }
+}
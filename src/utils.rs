@@ -1,9 +1,13 @@
 use serde::Serializer;
 use std::{
+    borrow::Borrow,
+    collections::HashMap,
     convert::TryInto as _,
-    time::{SystemTime, UNIX_EPOCH},
+    hash::Hash,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
-use uuid::Uuid;
+use uuid::{adapter::Simple, Uuid};
 
 #[inline]
 pub fn next_trace_id() -> String {
@@ -11,7 +15,7 @@ pub fn next_trace_id() -> String {
         use std::cell::RefCell;
 
         thread_local! {
-            static COUNT: RefCell<i32> = RefCell::new(0);
+            static COUNT: RefCell<i32> = const { RefCell::new(0) };
         }
 
         COUNT.with(|count| {
@@ -19,7 +23,7 @@ pub fn next_trace_id() -> String {
             format!("trace_{}", count.borrow())
         })
     } else {
-        Uuid::new_v4().to_string()
+        random_id(32)
     }
 }
 
@@ -29,7 +33,7 @@ pub fn next_span_id() -> String {
         use std::cell::RefCell;
 
         thread_local! {
-            static COUNT: RefCell<i32> = RefCell::new(0);
+            static COUNT: RefCell<i32> = const { RefCell::new(0) };
         }
 
         COUNT.with(|count| {
@@ -37,10 +41,181 @@ pub fn next_span_id() -> String {
             format!("span_{}", count.borrow())
         })
     } else {
-        Uuid::new_v4().to_string()
+        random_id(16)
     }
 }
 
+/// `len` random lowercase hex digits, at most 32, as in W3C trace context ids
+///
+/// Taken from a UUID encoded on the stack, then allocated once.
+#[inline]
+fn random_id(len: usize) -> String {
+    let mut buffer = [0; Simple::LENGTH];
+    Uuid::new_v4().to_simple().encode_lower(&mut buffer)[..len].to_owned()
+}
+
+/// Generates ids and timestamps for a layer
+#[derive(Default)]
+pub struct Generator {
+    // sequential trace and span counters, for deterministic output
+    sequential: Option<(AtomicU64, AtomicU64)>,
+}
+
+impl Generator {
+    #[cfg(feature = "testing")]
+    pub fn sequential() -> Self {
+        Generator {
+            sequential: Some((AtomicU64::new(0), AtomicU64::new(0))),
+        }
+    }
+
+    pub fn trace_id(&self) -> String {
+        match &self.sequential {
+            Some((trace, _)) => format!("trace_{}", trace.fetch_add(1, Ordering::Relaxed) + 1),
+            None => next_trace_id(),
+        }
+    }
+
+    pub fn span_id(&self) -> String {
+        match &self.sequential {
+            Some((_, span)) => format!("span_{}", span.fetch_add(1, Ordering::Relaxed) + 1),
+            None => next_span_id(),
+        }
+    }
+
+    pub fn now(&self) -> SystemTime {
+        match &self.sequential {
+            Some(_) => UNIX_EPOCH,
+            None => now(),
+        }
+    }
+}
+
+/// Decide whether the trace with the given id is sampled, consistently for the same id
+#[inline]
+pub fn sample(trace_id: &str, ratio: f64) -> bool {
+    if ratio >= 1.0 {
+        true
+    } else if ratio <= 0.0 {
+        false
+    } else {
+        // 53 bits of the hashed trace id mapped into [0, 1)
+        let bits = hash(trace_id) >> 11;
+        (bits as f64 / (1_u64 << 53) as f64) < ratio
+    }
+}
+
+/// FNV-1a followed by the splitmix64 finalizer, stable across processes and platforms
+pub fn hash(s: &str) -> u64 {
+    let mut hash = 0xcbf29ce484222325_u64;
+
+    for byte in s.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d049bb133111eb);
+    hash ^ (hash >> 31)
+}
+
+/// A map holding at most `capacity` entries, evicting with the CLOCK algorithm
+///
+/// Every lookup marks the entry as recently used, the eviction hand sweeps the entries
+/// and evicts the first one that hasn't been used since its last pass.
+pub struct BoundedCache<K, V> {
+    capacity: usize,
+    index: HashMap<K, usize>,
+    entries: Vec<(K, V, bool)>,
+    hand: usize,
+}
+
+impl<K: Hash + Eq + Clone, V> BoundedCache<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        BoundedCache {
+            capacity: capacity.max(1),
+            index: HashMap::new(),
+            entries: Vec::new(),
+            hand: 0,
+        }
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let entry = &mut self.entries[*self.index.get(key)?];
+        entry.2 = true;
+        Some(&entry.1)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let entry = &mut self.entries[*self.index.get(key)?];
+        entry.2 = true;
+        Some(&mut entry.1)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.entries.iter().map(|(_, value, _)| value)
+    }
+
+    /// Keep only the entries for which `f` returns `true`
+    pub fn retain(&mut self, mut f: impl FnMut(&K, &mut V) -> bool) {
+        self.entries.retain_mut(|(key, value, _)| f(key, value));
+
+        self.index.clear();
+        for (i, (key, _, _)) in self.entries.iter().enumerate() {
+            self.index.insert(key.clone(), i);
+        }
+
+        self.hand = 0;
+    }
+
+    pub fn clear(&mut self) {
+        self.retain(|_, _| false);
+    }
+
+    /// Insert an entry, returning `true` if another one was evicted to make room
+    pub fn insert(&mut self, key: K, value: V) -> bool {
+        if let Some(&i) = self.index.get(&key) {
+            self.entries[i] = (key, value, true);
+            return false;
+        }
+
+        if self.entries.len() < self.capacity {
+            self.index.insert(key.clone(), self.entries.len());
+            self.entries.push((key, value, false));
+            return false;
+        }
+
+        // give a second chance to recently used entries
+        while self.entries[self.hand].2 {
+            self.entries[self.hand].2 = false;
+            self.hand = (self.hand + 1) % self.entries.len();
+        }
+
+        let evicted = std::mem::replace(&mut self.entries[self.hand], (key.clone(), value, false));
+        self.index.remove(&evicted.0);
+        self.index.insert(key, self.hand);
+        self.hand = (self.hand + 1) % self.entries.len();
+
+        true
+    }
+}
+
+/// A random number in `[0, 1)`
+#[inline]
+pub fn random() -> f64 {
+    // 53 random bits mapped into [0, 1)
+    let bits = Uuid::new_v4().as_u128() as u64 >> 11;
+    bits as f64 / (1_u64 << 53) as f64
+}
+
 #[inline]
 pub fn now() -> SystemTime {
     if cfg!(feature = "__testing") {
@@ -50,6 +225,12 @@ pub fn now() -> SystemTime {
     }
 }
 
+/// Milliseconds of a duration, with microsecond precision
+#[inline]
+pub fn millis(duration: Duration) -> f64 {
+    duration.as_micros() as f64 / 1000.0
+}
+
 #[inline]
 pub fn serialize_system_time<S>(time: &SystemTime, s: S) -> Result<S::Ok, S::Error>
 where
@@ -63,3 +244,36 @@ where
         s.serialize_none()
     }
 }
+
+/// Milliseconds since the Unix epoch, with microsecond precision
+#[inline]
+pub fn serialize_system_time_micros<S>(time: &SystemTime, s: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => s.serialize_f64(millis(duration)),
+        Err(_) => s.serialize_none(),
+    }
+}
+
+/// An id as `len` hex digits, taken from the id itself if it's hex already, e.g. a uuid,
+/// derived from its hash otherwise
+pub fn hex_id(id: &str, len: usize) -> String {
+    let digits: String = id.chars().filter(|c| *c != '-').collect();
+
+    if digits.len() >= len && digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return digits[digits.len() - len..].to_ascii_lowercase();
+    }
+
+    let mut hex = String::with_capacity(len);
+    let mut seed = id.to_string();
+
+    while hex.len() < len {
+        hex.push_str(&format!("{:016x}", hash(&seed)));
+        seed.push('\'');
+    }
+
+    hex.truncate(len);
+    hex
+}
@@ -0,0 +1,123 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+use tracing_newrelic::{
+    Api, Compression, Exporter, HttpTransport, NewRelicLayer, NewrLogs, NewrSpans,
+    TelemetryRequest, TelemetryResponse, TransportError,
+};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+/// Counts the bytes allocated by every thread
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATED.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// Accepts every request without sending it
+struct Accept;
+
+impl HttpTransport for Accept {
+    fn post(
+        &self,
+        _: TelemetryRequest,
+    ) -> BoxFuture<'_, Result<TelemetryResponse, TransportError>> {
+        Box::pin(async {
+            Ok(TelemetryResponse {
+                status: 202,
+                ..TelemetryResponse::default()
+            })
+        })
+    }
+}
+
+/// Drops every trace, to count the bytes allocated by the layer alone
+struct Discard;
+
+impl Exporter for Discard {
+    fn export(&mut self, _: NewrLogs, _: NewrSpans) -> BoxFuture<'_, ()> {
+        Box::pin(async {})
+    }
+
+    fn shutdown(&mut self) -> BoxFuture<'_, ()> {
+        Box::pin(async {})
+    }
+}
+
+/// Bytes allocated to record and export `traces` traces of 100 spans, one at a time
+fn allocated(layer: NewRelicLayer, traces: usize) -> usize {
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let start = ALLOCATED.load(Ordering::Relaxed);
+
+        for _ in 0..traces {
+            tracing::info_span!("root").in_scope(|| {
+                for n in 0..100 {
+                    tracing::info_span!("child", n).in_scope(|| {});
+                }
+            });
+
+            assert!(handle.flush_timeout(Duration::from_secs(5)));
+        }
+
+        ALLOCATED.load(Ordering::Relaxed) - start
+    })
+}
+
+/// Bytes allocated for each trace past the first ones
+fn per_trace(layer: impl Fn() -> NewRelicLayer) -> usize {
+    let few = allocated(layer(), 4);
+    let many = allocated(layer(), 24);
+
+    (many - few) / 20
+}
+
+/// Bytes allocated for each request past the first ones
+fn per_request(compression: Compression) -> usize {
+    let sent = per_trace(|| {
+        let api = Api::from("key")
+            .with_trace_batch_size(1)
+            .with_compression(compression)
+            .with_transport(Accept);
+
+        tracing_newrelic::layer(api)
+    });
+    let recorded = per_trace(|| tracing_newrelic::layer_with_exporter(Discard));
+
+    sent - recorded
+}
+
+#[test]
+fn buffers_are_reused_across_requests() {
+    // a compressor alone allocates several hundred KB, the payload is cloned once merged, and
+    // an uncompressed body is copied once out of the reused JSON buffer
+    for compression in [Compression::default(), Compression::None].iter() {
+        let sending = per_request(*compression);
+        assert!(
+            sending < 512 * 1024,
+            "{} bytes allocated per {:?} request",
+            sending,
+            compression
+        );
+    }
+}
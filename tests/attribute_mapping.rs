@@ -0,0 +1,73 @@
+mod common;
+
+use std::collections::HashMap;
+
+use common::MockServer;
+use serde_json::Value;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn run(f: impl FnOnce()) -> (Vec<Value>, Vec<Value>) {
+    let server = MockServer::start();
+
+    let mapping: HashMap<String, String> = [
+        ("http.status", "http.status_code"),
+        ("peer.service", "server.address"),
+    ]
+    .iter()
+    .map(|(from, to)| (from.to_string(), to.to_string()))
+    .collect();
+
+    let layer = tracing_newrelic::layer(server.api()).with_attribute_mapping(mapping);
+
+    tracing::subscriber::with_default(Registry::default().with(layer), f);
+
+    (server.spans(), server.logs())
+}
+
+#[test]
+fn renames_span_attributes() {
+    let (spans, _) = run(|| {
+        let span = tracing::info_span!(
+            "request",
+            peer.service = "billing",
+            http.status = tracing::field::Empty
+        );
+
+        span.record("http.status", 200);
+    });
+
+    let attributes = &spans[0]["attributes"];
+
+    assert_eq!(attributes["server.address"], "billing");
+    assert_eq!(attributes["http.status_code"], 200);
+    assert_eq!(attributes.get("peer.service"), None);
+    assert_eq!(attributes.get("http.status"), None);
+}
+
+#[test]
+fn renames_log_attributes() {
+    let (_, logs) = run(|| {
+        let _span = tracing::info_span!("request").entered();
+
+        tracing::warn!(http.status = 503, "unavailable");
+    });
+
+    let attributes = &logs[0]["attributes"];
+
+    assert_eq!(attributes["http.status_code"], 503);
+    assert_eq!(attributes.get("http.status"), None);
+    assert_eq!(attributes["message"], "unavailable");
+}
+
+#[test]
+fn new_key_wins_over_the_renamed_one() {
+    let (spans, logs) = run(|| {
+        let _span =
+            tracing::info_span!("request", http.status = 500, http.status_code = 200).entered();
+
+        tracing::info!(http.status_code = 201, http.status = 404);
+    });
+
+    assert_eq!(spans[0]["attributes"]["http.status_code"], 200);
+    assert_eq!(logs[0]["attributes"]["http.status_code"], 201);
+}
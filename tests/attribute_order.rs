@@ -0,0 +1,59 @@
+use std::sync::{Arc, Mutex};
+
+use futures_util::future::BoxFuture;
+use tracing_newrelic::{Exporter, NewrLogs, NewrSpans};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+/// Keeps the serialized attributes of every span
+#[derive(Clone, Default)]
+struct Serialized(Arc<Mutex<Vec<String>>>);
+
+impl Exporter for Serialized {
+    fn export(&mut self, _: NewrLogs, spans: NewrSpans) -> BoxFuture<'_, ()> {
+        let mut serialized = self.0.lock().unwrap();
+
+        for span in &spans.spans {
+            serialized.push(serde_json::to_string(&span.attributes).unwrap());
+        }
+
+        Box::pin(async {})
+    }
+
+    fn shutdown(&mut self) -> BoxFuture<'_, ()> {
+        Box::pin(async {})
+    }
+}
+
+/// The attributes of a span recording the same fields every time, without its timings
+fn record() -> String {
+    let exporter = Serialized::default();
+
+    let layer = tracing_newrelic::layer_with_exporter(exporter.clone())
+        .with_metadata_fields(false)
+        .with_span_processor(|span| {
+            for key in ["duration.ms", "busy.ms", "idle.ms"] {
+                span.attributes.remove(key);
+            }
+            true
+        });
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let span = tracing::info_span!("root", zeta = 1, alpha = "a", mu = true, omega = 2.5);
+        span.in_scope(|| {});
+    });
+
+    let serialized = exporter.0.lock().unwrap();
+    assert_eq!(serialized.len(), 1);
+    serialized[0].clone()
+}
+
+#[test]
+fn serialized_in_recording_order() {
+    let first = record();
+
+    assert_eq!(
+        first,
+        r#"{"name":"root","zeta":1,"alpha":"a","mu":true,"omega":2.5,"nr.entryPoint":true}"#
+    );
+    assert_eq!(record(), first);
+}
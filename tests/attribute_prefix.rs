@@ -0,0 +1,55 @@
+mod common;
+
+use common::MockServer;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+#[test]
+fn prefixes_custom_attributes_only() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api())
+        .with_service_name("checkout")
+        .with_attribute_prefix("checkout.");
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let root = tracing::info_span!(
+            "root",
+            name = "POST /cart",
+            span.kind = "server",
+            otel.status_code = "OK",
+            cart_size = 3,
+            user.id = tracing::field::Empty,
+        );
+        let _root = root.enter();
+
+        root.record("user.id", "u-1");
+
+        tracing::info!(items = 2, "added to cart");
+    });
+
+    let spans = server.spans();
+    let root = &spans[0]["attributes"];
+
+    assert_eq!(root["checkout.cart_size"], 3);
+    assert_eq!(root["checkout.user.id"], "u-1");
+    assert_eq!(root.get("cart_size"), None);
+
+    assert_eq!(root["name"], "POST /cart");
+    assert_eq!(root["span.kind"], "server");
+    assert_eq!(root["otel.status_code"], "OK");
+    assert!(root["duration.ms"].is_number());
+    assert!(root["code.namespace"].is_string());
+
+    let logs = server.logs();
+    let log = &logs[0]["attributes"];
+
+    assert_eq!(log["checkout.items"], 2);
+    assert_eq!(log["message"], "added to cart");
+    assert!(log["trace.id"].is_string());
+    assert!(log["span.id"].is_string());
+
+    assert_eq!(
+        server.trace_requests()[0].body[0]["common"]["attributes"]["service.name"],
+        "checkout"
+    );
+}
@@ -0,0 +1,106 @@
+#![cfg(feature = "blocking")]
+
+mod common;
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use common::{MockResponse, MockServer};
+use tracing_newrelic::{Api, RetryPolicy, StatsSnapshot};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+/// Respond with `script` to trace requests in turn, then with 202
+fn scripted(script: Vec<MockResponse>) -> MockServer {
+    let script = Mutex::new(script.into_iter());
+
+    MockServer::start_with(move |request| {
+        if request.path.contains("trace") {
+            script.lock().unwrap().next()
+        } else {
+            None
+        }
+        .unwrap_or_else(|| MockResponse::status(202))
+    })
+}
+
+fn run(api: Api, spans: usize) -> StatsSnapshot {
+    let layer = tracing_newrelic::layer(api.with_blocking(true));
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let _root = tracing::info_span!("root").entered();
+
+        for _ in 1..spans {
+            tracing::info_span!("child").in_scope(|| tracing::info!("log"));
+        }
+    });
+
+    handle.stats().snapshot()
+}
+
+#[test]
+fn sends_synchronously() {
+    let server = MockServer::start();
+
+    let stats = run(server.api(), 1);
+
+    let request = &server.trace_requests()[0];
+    assert_eq!(request.headers["api-key"], "key");
+    assert_eq!(request.headers["data-format"], "newrelic");
+    assert_eq!(request.headers["content-encoding"], "gzip");
+    assert_eq!(server.spans().len(), 1);
+    assert_eq!(stats.spans_sent, 1);
+}
+
+#[test]
+fn retries_and_honors_retry_after() {
+    let server = scripted(vec![
+        MockResponse::status(500),
+        MockResponse::status(429).header("retry-after", "0"),
+    ]);
+
+    let api = server.api().with_retry_policy(RetryPolicy {
+        max_attempts: 3,
+        initial_backoff: Duration::from_millis(20),
+        max_backoff: Duration::from_millis(50),
+        jitter: false,
+    });
+
+    let stats = run(api, 1);
+
+    assert_eq!(server.trace_requests().len(), 3);
+    assert_eq!(stats.spans_sent, 1);
+    assert_eq!(stats.send_failures, 2);
+}
+
+#[test]
+fn drops_too_large_payloads() {
+    let server = scripted(vec![MockResponse::status(413)]);
+
+    let stats = run(server.api(), 4);
+
+    // a single payload can't be split any further
+    assert_eq!(server.trace_requests().len(), 1);
+    assert_eq!(stats.payloads_dropped, 1);
+    assert_eq!(stats.spans_sent, 0);
+}
+
+#[test]
+fn reports_rejections() {
+    let server = scripted(vec![
+        MockResponse::status(403).body(r#"{"requestId":"abc-123","error":"forbidden"}"#)
+    ]);
+
+    let (tx, rx) = std::sync::mpsc::channel();
+
+    let api = server.api().with_error_handler(move |err| {
+        let _ = tx.send(err);
+    });
+
+    let stats = run(api, 1);
+
+    let err = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    assert_eq!(err.status, Some(403));
+    assert_eq!(err.request_id.as_deref(), Some("abc-123"));
+    assert_eq!(stats.payloads_dropped, 1);
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use common::MockServer;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn ms(span: &serde_json::Value, key: &str) -> f64 {
+    span["attributes"][key].as_f64().unwrap()
+}
+
+#[test]
+fn time_spent_exited_is_idle() {
+    let server = MockServer::start();
+
+    let subscriber = Registry::default().with(tracing_newrelic::layer(server.api()));
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("request");
+
+        span.in_scope(|| sleep(Duration::from_millis(20)));
+
+        // e.g. parked waiting on I/O
+        sleep(Duration::from_millis(100));
+
+        span.in_scope(|| {});
+    });
+
+    let span = &server.spans()[0];
+
+    assert!(ms(span, "idle.ms") >= 100.0);
+    assert!(ms(span, "busy.ms") >= 20.0);
+    assert!(ms(span, "busy.ms") < 100.0);
+    assert!(ms(span, "busy.ms") + ms(span, "idle.ms") <= ms(span, "duration.ms") + 0.001);
+}
+
+#[test]
+fn nested_enters_are_counted_once() {
+    let server = MockServer::start();
+
+    let subscriber = Registry::default().with(tracing_newrelic::layer(server.api()));
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("request");
+
+        let _outer = span.enter();
+        {
+            let _inner = span.enter();
+            sleep(Duration::from_millis(50));
+        }
+        sleep(Duration::from_millis(50));
+    });
+
+    let span = &server.spans()[0];
+
+    assert!(ms(span, "busy.ms") >= 100.0);
+    assert!(ms(span, "idle.ms") < 50.0);
+}
+
+#[test]
+fn never_entered_is_idle() {
+    let server = MockServer::start();
+
+    let subscriber = Registry::default().with(tracing_newrelic::layer(server.api()));
+
+    tracing::subscriber::with_default(subscriber, || {
+        let _span = tracing::info_span!("request");
+        sleep(Duration::from_millis(20));
+    });
+
+    let span = &server.spans()[0];
+
+    assert_eq!(ms(span, "busy.ms"), 0.0);
+    assert!(ms(span, "idle.ms") >= 20.0);
+}
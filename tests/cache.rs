@@ -0,0 +1,56 @@
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+use tracing_newrelic::{DropPolicy, Exporter, NewrLogs, NewrSpans, StatsSnapshot};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+struct Discard;
+
+impl Exporter for Discard {
+    fn export(&mut self, _: NewrLogs, _: NewrSpans) -> BoxFuture<'_, ()> {
+        Box::pin(async {})
+    }
+
+    fn shutdown(&mut self) -> BoxFuture<'_, ()> {
+        Box::pin(async {})
+    }
+}
+
+fn run(capacity: usize, services: impl Iterator<Item = String>) -> StatsSnapshot {
+    let layer = tracing_newrelic::layer_with_exporter(Discard)
+        .with_cache_capacity(capacity)
+        .with_drop_policy(DropPolicy::Block(Duration::from_secs(5)));
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        for service in services {
+            let _span = tracing::info_span!("root", service.name = service.as_str()).entered();
+        }
+    });
+
+    handle.stats().snapshot()
+}
+
+#[test]
+fn recently_used_entries_survive_eviction() {
+    let services = ["a", "b", "a", "c", "a", "b"].iter().map(|s| s.to_string());
+
+    // `c` evicts `b` which wasn't used since, then `b` evicts `c`
+    assert_eq!(run(2, services).cache_evictions, 2);
+}
+
+#[test]
+fn hits_do_not_evict() {
+    let services = ["a", "b", "a", "b", "a"].iter().map(|s| s.to_string());
+
+    assert_eq!(run(2, services).cache_evictions, 0);
+}
+
+#[test]
+fn stays_bounded_with_distinct_keys() {
+    let stats = run(16, (0..100_000).map(|i| format!("service-{}", i)));
+
+    assert_eq!(stats.queue_drops, 0);
+    // every insertion beyond the capacity replaced an existing entry
+    assert_eq!(stats.cache_evictions, 100_000 - 16);
+}
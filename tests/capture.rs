@@ -0,0 +1,99 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use common::MockServer;
+use serde_json::Value;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn temp_path() -> PathBuf {
+    std::env::temp_dir().join(format!("tracing-newrelic-{}.ndjson", uuid::Uuid::new_v4()))
+}
+
+fn read_lines(path: &PathBuf) -> Vec<Value> {
+    fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn capture_and_rotate() {
+    let server = MockServer::start();
+    let path = temp_path();
+    let rotated = PathBuf::from(format!("{}.1", path.display()));
+
+    let layer = tracing_newrelic::layer(server.api());
+    let handle = layer.handle();
+
+    // every record exceeds the limit, so each one starts a new file
+    handle.start_capture(&path, 1).unwrap();
+
+    let subscriber = Registry::default().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("captured");
+        let _span = span.enter();
+        tracing::info!("captured log");
+    });
+
+    handle.stop_capture();
+
+    let mut records = read_lines(&rotated);
+    records.append(&mut read_lines(&path));
+
+    assert_eq!(records.len(), 2);
+    assert_eq!(server.requests().len(), 2);
+
+    let mut endpoints: Vec<_> = records
+        .iter()
+        .map(|record| record["endpoint"].as_str().unwrap().to_string())
+        .collect();
+    endpoints.sort();
+
+    assert_eq!(
+        endpoints,
+        [
+            format!("{}/log/v1", server.url()),
+            format!("{}/trace/v1", server.url())
+        ]
+    );
+
+    for record in &records {
+        let body = &record["body"][0];
+
+        if let Some(spans) = body.get("spans") {
+            assert_eq!(spans[0]["attributes"]["name"], "captured");
+        } else {
+            assert_eq!(body["logs"][0]["attributes"]["message"], "captured log");
+        }
+    }
+
+    fs::remove_file(path).unwrap();
+    fs::remove_file(rotated).unwrap();
+}
+
+#[test]
+fn stopped_capture_writes_nothing() {
+    let server = MockServer::start();
+    let path = temp_path();
+
+    let layer = tracing_newrelic::layer(server.api());
+    let handle = layer.handle();
+
+    handle.start_capture(&path, 1024 * 1024).unwrap();
+    handle.stop_capture();
+
+    let subscriber = Registry::default().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        let _span = tracing::info_span!("not captured").entered();
+    });
+
+    assert_eq!(server.spans().len(), 1);
+    assert!(read_lines(&path).is_empty());
+
+    fs::remove_file(path).unwrap();
+}
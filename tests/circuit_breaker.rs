@@ -0,0 +1,148 @@
+mod common;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::Duration;
+
+use common::{MockResponse, MockServer};
+use tracing_newrelic::{CircuitBreaker, CircuitState, RetryPolicy};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn trace() {
+    let _span = tracing::info_span!("root").entered();
+}
+
+#[test]
+fn open_half_open_closed() {
+    let healthy = Arc::new(AtomicBool::new(false));
+
+    let server = MockServer::start_with({
+        let healthy = healthy.clone();
+        move |request| {
+            if !request.path.contains("trace") {
+                MockResponse::status(202)
+            } else if healthy.load(Ordering::SeqCst) {
+                MockResponse::status(202).delay(Duration::from_millis(300))
+            } else {
+                MockResponse::status(503)
+            }
+        }
+    });
+
+    let api = server
+        .api()
+        .with_trace_batch_size(1)
+        .with_retry_policy(RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        })
+        .with_circuit_breaker(Some(CircuitBreaker {
+            failure_threshold: 2,
+            cooldown: Duration::from_millis(500),
+        }));
+
+    let layer = tracing_newrelic::layer(api);
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let state = || handle.stats().snapshot().spans_circuit;
+
+        trace();
+        handle.flush_timeout(Duration::from_secs(5));
+        assert_eq!(state(), CircuitState::Closed);
+
+        trace();
+        handle.flush_timeout(Duration::from_secs(5));
+        assert_eq!(state(), CircuitState::Open);
+
+        // dropped without being sent
+        trace();
+        handle.flush_timeout(Duration::from_secs(5));
+        assert_eq!(server.trace_requests().len(), 2);
+        assert_eq!(handle.stats().snapshot().payloads_dropped, 3);
+
+        healthy.store(true, Ordering::SeqCst);
+        sleep(Duration::from_millis(600));
+
+        trace();
+        sleep(Duration::from_millis(100));
+        assert_eq!(state(), CircuitState::HalfOpen);
+
+        assert!(handle.flush_timeout(Duration::from_secs(5)));
+        assert_eq!(state(), CircuitState::Closed);
+        assert_eq!(server.trace_requests().len(), 3);
+    });
+
+    assert_eq!(handle.stats().snapshot().logs_circuit, CircuitState::Closed);
+}
+
+#[test]
+fn failed_probe_opens_again() {
+    let server = MockServer::start_with(|request| {
+        if request.path.contains("trace") {
+            MockResponse::status(503)
+        } else {
+            MockResponse::status(202)
+        }
+    });
+
+    let api = server
+        .api()
+        .with_trace_batch_size(1)
+        .with_retry_policy(RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        })
+        .with_circuit_breaker(Some(CircuitBreaker {
+            failure_threshold: 1,
+            cooldown: Duration::from_millis(300),
+        }));
+
+    let layer = tracing_newrelic::layer(api);
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        trace();
+        handle.flush_timeout(Duration::from_secs(5));
+
+        sleep(Duration::from_millis(400));
+
+        trace();
+        handle.flush_timeout(Duration::from_secs(5));
+        assert_eq!(server.trace_requests().len(), 2);
+
+        // the cooldown started over
+        trace();
+        handle.flush_timeout(Duration::from_secs(5));
+        assert_eq!(server.trace_requests().len(), 2);
+    });
+
+    assert_eq!(handle.stats().snapshot().spans_circuit, CircuitState::Open);
+}
+
+#[test]
+fn disabled() {
+    let server = MockServer::start_with(|_| MockResponse::status(403));
+
+    let api = server
+        .api()
+        .with_trace_batch_size(1)
+        .with_circuit_breaker(None);
+
+    let layer = tracing_newrelic::layer(api);
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        for _ in 0..8 {
+            trace();
+            handle.flush_timeout(Duration::from_secs(5));
+        }
+    });
+
+    assert_eq!(server.trace_requests().len(), 8);
+    assert_eq!(
+        handle.stats().snapshot().spans_circuit,
+        CircuitState::Closed
+    );
+}
@@ -0,0 +1,169 @@
+#![cfg(all(feature = "reqwest-middleware", feature = "testing"))]
+
+mod common;
+
+use std::future::Future;
+use std::net::TcpListener;
+use std::time::Duration;
+
+use common::{MockResponse, MockServer};
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
+use tracing::Instrument;
+use tracing_newrelic::client::ClientSpans;
+use tracing_newrelic::testing::{with_captured, Captured};
+use tracing_newrelic::{NewrSpan, Value};
+
+fn client() -> ClientWithMiddleware {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_millis(200))
+        .build()
+        .unwrap();
+
+    ClientBuilder::new(client)
+        .with(ClientSpans::new().with_peer_service("billing"))
+        .build()
+}
+
+/// Run `f` under a server span
+fn serve<F: Future>(f: F) -> Captured {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    with_captured(|| {
+        let span = tracing::info_span!("GET /checkout", span.kind = "server");
+
+        runtime.block_on(f.instrument(span));
+    })
+}
+
+fn client_span(captured: &Captured) -> &NewrSpan {
+    captured
+        .spans()
+        .into_iter()
+        .find(|span| span.attributes.get("span.kind") == Some(&Value::from("client")))
+        .unwrap()
+}
+
+#[test]
+fn records_client_spans() {
+    let server = MockServer::start();
+    let url = format!("{}/charge?card=4242", server.url());
+
+    let captured = serve(async {
+        client().post(&url).body("{}").send().await.unwrap();
+    });
+
+    let server_span = captured.span("GET /checkout").unwrap();
+    let span = client_span(&captured);
+    let host = server.addr.ip().to_string();
+
+    // spans of hyper itself may show up below the client one
+    assert_eq!(server_span.attributes.get("parent.id"), None);
+    assert_eq!(
+        span.attributes.get("parent.id"),
+        Some(&Value::from(server_span.id.as_str()))
+    );
+    assert_eq!(span.trace_id, server_span.trace_id);
+    assert_eq!(
+        span.attributes.get("name"),
+        Some(&Value::from(format!("POST {}", host)))
+    );
+    assert_eq!(
+        span.attributes.get("http.method"),
+        Some(&Value::from("POST"))
+    );
+    assert_eq!(
+        span.attributes.get("http.url"),
+        Some(&Value::from(format!("{}/charge", server.url())))
+    );
+    assert_eq!(
+        span.attributes.get("peer.service"),
+        Some(&Value::from("billing"))
+    );
+    assert_eq!(
+        span.attributes.get("http.status_code"),
+        Some(&Value::from(202u64))
+    );
+    assert_eq!(span.attributes.get("otel.status_code"), None);
+
+    // the called service continues the trace
+    let traceparent = server.requests()[0].headers["traceparent"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let parts: Vec<&str> = traceparent.split('-').collect();
+
+    assert_eq!(parts.len(), 4);
+    assert_eq!(parts[0], "00");
+    assert_eq!(parts[1].len(), 32);
+    assert_eq!(parts[2].len(), 16);
+    assert_eq!(parts[3], "01");
+}
+
+#[test]
+fn fails_server_errors() {
+    let server = MockServer::start_with(|_| MockResponse::status(503));
+    let url = server.url();
+
+    let captured = serve(async {
+        client().post(&url).send().await.unwrap();
+    });
+
+    let span = client_span(&captured);
+    assert_eq!(
+        span.attributes.get("http.status_code"),
+        Some(&Value::from(503u64))
+    );
+    assert_eq!(
+        span.attributes.get("otel.status_code"),
+        Some(&Value::from("ERROR"))
+    );
+}
+
+#[test]
+fn fails_timeouts() {
+    let server =
+        MockServer::start_with(|_| MockResponse::status(202).delay(Duration::from_secs(1)));
+    let url = server.url();
+
+    let captured = serve(async {
+        assert!(client().post(&url).send().await.is_err());
+    });
+
+    let span = client_span(&captured);
+    assert_eq!(
+        span.attributes.get("otel.status_code"),
+        Some(&Value::from("ERROR"))
+    );
+    assert!(matches!(
+        span.attributes.get("otel.status_description"),
+        Some(Value::String(message)) if message.contains("timed out")
+    ));
+}
+
+#[test]
+fn fails_connection_errors() {
+    // a port nothing listens on
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let url = format!("http://{}", addr);
+
+    let captured = serve(async {
+        assert!(client().get(&url).send().await.is_err());
+    });
+
+    let span = client_span(&captured);
+    assert_eq!(
+        span.attributes.get("otel.status_code"),
+        Some(&Value::from("ERROR"))
+    );
+    assert!(matches!(
+        span.attributes.get("otel.status_description"),
+        Some(Value::String(message)) if !message.is_empty()
+    ));
+    assert_eq!(span.attributes.get("http.status_code"), None);
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use std::thread;
+use std::time::Duration;
+
+use common::MockServer;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn names(server: &MockServer) -> Vec<String> {
+    let mut names: Vec<_> = server
+        .spans()
+        .iter()
+        .map(|span| span["attributes"]["name"].as_str().unwrap().to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn clones_share_the_worker() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api()).with_service_name("shared");
+    let handle = layer.handle();
+
+    let server_mode = layer.clone();
+
+    let cli = thread::spawn(move || {
+        tracing::subscriber::with_default(Registry::default().with(layer), || {
+            tracing::info_span!("cli").in_scope(|| {});
+        });
+    });
+
+    tracing::subscriber::with_default(Registry::default().with(server_mode), || {
+        tracing::info_span!("server").in_scope(|| {});
+    });
+
+    cli.join().unwrap();
+
+    assert_eq!(names(&server), ["cli", "server"]);
+
+    for request in server.trace_requests() {
+        for payload in request.body.as_array().unwrap() {
+            assert_eq!(payload["common"]["attributes"]["service.name"], "shared");
+        }
+    }
+
+    assert_eq!(handle.stats().snapshot().spans_sent, 2);
+}
+
+#[test]
+fn worker_outlives_the_original_layer() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api());
+    let clone = layer.clone();
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        tracing::info_span!("first").in_scope(|| {});
+    });
+
+    tracing::subscriber::with_default(Registry::default().with(clone), || {
+        tracing::info_span!("second").in_scope(|| {});
+
+        assert!(handle.flush_timeout(Duration::from_secs(5)));
+    });
+
+    assert_eq!(names(&server), ["first", "second"]);
+}
@@ -0,0 +1,167 @@
+//! Known-good payload fixtures, and the certificates of the TLS mock server
+//!
+//! Set `REGENERATE_FIXTURES=1` to rewrite the fixtures from the current code
+//! instead of comparing against them.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use pretty_assertions::assert_eq;
+use serde_json::{json, Value};
+
+use super::MockServer;
+
+/// Payload format version, bump it when the shape changes on purpose
+const VERSION: &str = "v2";
+
+/// Root certificate issuing [`TLS_CERT`]
+pub const TLS_CA: &[u8] = include_bytes!("../fixtures/tls/ca.pem");
+
+/// Certificate and key of the TLS mock server, for `localhost`
+pub const TLS_CERT: &[u8] = include_bytes!("../fixtures/tls/server.pem");
+pub const TLS_KEY: &[u8] = include_bytes!("../fixtures/tls/server.key");
+
+/// Attributes whose values depend on the wall clock
+const VOLATILE_ATTRIBUTES: &[&str] = &["duration.ms", "busy.ms", "idle.ms"];
+
+/// Common attributes whose values depend on the process, the platform or the crate version
+const VOLATILE_COMMON_ATTRIBUTES: &[&str] = &[
+    "process.pid",
+    "process.executable.name",
+    "os.type",
+    "host.arch",
+    "instrumentation.version",
+];
+
+/// Compare every payload received by `server` against the named fixture
+pub fn assert_fixture(name: &str, server: &MockServer) {
+    compare(name, render(server));
+}
+
+/// Compare every OTLP payload received by `server` against the named fixture
+pub fn assert_otlp_fixture(name: &str, server: &MockServer) {
+    compare(name, render_otlp(server));
+}
+
+fn compare(name: &str, actual: String) {
+    let path = fixture_path(name);
+
+    if env::var_os("REGENERATE_FIXTURES").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).unwrap_or_else(|err| {
+        panic!(
+            "failed to read fixture {}: {}, run with REGENERATE_FIXTURES=1 to create it",
+            path.display(),
+            err
+        )
+    });
+
+    assert_eq!(expected, actual, "payload differs from fixture {}", name);
+}
+
+fn fixture_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(VERSION)
+        .join(format!("{}.json", name))
+}
+
+fn render(server: &MockServer) -> String {
+    let mut traces: Vec<Value> = server
+        .trace_requests()
+        .into_iter()
+        .map(|r| r.body)
+        .collect();
+    let mut logs: Vec<Value> = server.log_requests().into_iter().map(|r| r.body).collect();
+
+    for payload in traces
+        .iter_mut()
+        .chain(&mut logs)
+        .filter_map(Value::as_array_mut)
+        .flatten()
+    {
+        for key in VOLATILE_COMMON_ATTRIBUTES {
+            if let Some(value) = payload["common"]["attributes"].get_mut(*key) {
+                *value = Value::String("<volatile>".into());
+            }
+        }
+    }
+
+    for span in traces
+        .iter_mut()
+        .filter_map(Value::as_array_mut)
+        .flatten()
+        .filter_map(|payload| payload["spans"].as_array_mut())
+        .flatten()
+    {
+        for key in VOLATILE_ATTRIBUTES {
+            if let Some(value) = span["attributes"].get_mut(*key) {
+                *value = Value::String("<volatile>".into());
+            }
+        }
+    }
+
+    to_string(json!({ "traces": traces, "logs": logs }))
+}
+
+fn render_otlp(server: &MockServer) -> String {
+    let mut traces = server.requests_to("/v1/traces");
+    let mut logs = server.requests_to("/v1/logs");
+
+    for resource in traces
+        .iter_mut()
+        .filter_map(|r| r.body["resourceSpans"].as_array_mut())
+        .chain(
+            logs.iter_mut()
+                .filter_map(|r| r.body["resourceLogs"].as_array_mut()),
+        )
+        .flatten()
+    {
+        mask(
+            &mut resource["resource"]["attributes"],
+            VOLATILE_COMMON_ATTRIBUTES,
+        );
+    }
+
+    for span in traces
+        .iter_mut()
+        .filter_map(|r| r.body["resourceSpans"].as_array_mut())
+        .flatten()
+        .filter_map(|resource| resource["scopeSpans"].as_array_mut())
+        .flatten()
+        .filter_map(|scope| scope["spans"].as_array_mut())
+        .flatten()
+    {
+        // derived from the duration
+        span["endTimeUnixNano"] = Value::String("<volatile>".into());
+
+        mask(&mut span["attributes"], VOLATILE_ATTRIBUTES);
+    }
+
+    let traces: Vec<Value> = traces.into_iter().map(|r| r.body).collect();
+    let logs: Vec<Value> = logs.into_iter().map(|r| r.body).collect();
+
+    to_string(json!({ "traces": traces, "logs": logs }))
+}
+
+/// Mask the values of OTLP key-value pairs
+fn mask(attributes: &mut Value, keys: &[&str]) {
+    for attribute in attributes.as_array_mut().into_iter().flatten() {
+        if keys.contains(&attribute["key"].as_str().unwrap_or_default()) {
+            attribute["value"] = Value::String("<volatile>".into());
+        }
+    }
+}
+
+fn to_string(value: Value) -> String {
+    // `serde_json::Value` sorts object keys, so the output is stable
+    let mut output = serde_json::to_string_pretty(&value).unwrap();
+    output.push('\n');
+    output
+}
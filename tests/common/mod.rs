@@ -0,0 +1,284 @@
+#![allow(dead_code)]
+
+pub mod fixtures;
+
+use std::io::Read;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use flate2::read::GzDecoder;
+use serde_json::Value;
+use tokio::sync::oneshot;
+use tracing_newrelic::{Api, ApiEndpoint};
+use warp::http::{HeaderMap, Response};
+use warp::hyper::body::Bytes;
+use warp::Filter;
+
+/// A request received by the mock server
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub path: String,
+    pub headers: HeaderMap,
+    pub body: Value,
+    pub received: Instant,
+}
+
+/// A response the mock server replies with
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: String,
+    pub delay: Duration,
+}
+
+impl MockResponse {
+    pub fn status(status: u16) -> Self {
+        MockResponse {
+            status,
+            headers: Vec::new(),
+            body: String::new(),
+            delay: Duration::from_secs(0),
+        }
+    }
+
+    pub fn header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<String>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+type Responder = dyn Fn(&Request) -> MockResponse + Send + Sync;
+
+/// A fake New Relic ingest server, recording every request it receives
+pub struct MockServer {
+    pub addr: SocketAddr,
+    tls: bool,
+    requests: Arc<Mutex<Vec<Request>>>,
+    shutdown: Option<oneshot::Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl MockServer {
+    /// Start a server accepting every request with `202 Accepted`
+    pub fn start() -> Self {
+        MockServer::start_with(|_| MockResponse::status(202))
+    }
+
+    /// Start a server replying with the given responder
+    pub fn start_with<F>(responder: F) -> Self
+    where
+        F: Fn(&Request) -> MockResponse + Send + Sync + 'static,
+    {
+        MockServer::spawn(responder, false)
+    }
+
+    /// Start a server accepting every request with `202 Accepted` over TLS, see [`fixtures::TLS_CA`]
+    pub fn start_tls() -> Self {
+        MockServer::spawn(|_| MockResponse::status(202), true)
+    }
+
+    fn spawn<F>(responder: F, tls: bool) -> Self
+    where
+        F: Fn(&Request) -> MockResponse + Send + Sync + 'static,
+    {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let responder: Arc<Responder> = Arc::new(responder);
+
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let (addr_tx, addr_rx) = std::sync::mpsc::channel();
+
+        let route = {
+            let requests = requests.clone();
+
+            warp::post()
+                .and(warp::path::full())
+                .and(warp::header::headers_cloned())
+                .and(warp::body::bytes())
+                .and_then(
+                    move |path: warp::path::FullPath, headers: HeaderMap, body: Bytes| {
+                        let requests = requests.clone();
+                        let responder = responder.clone();
+
+                        async move {
+                            let request = Request {
+                                path: path.as_str().to_string(),
+                                body: decode_body(&headers, &body),
+                                headers,
+                                received: Instant::now(),
+                            };
+
+                            let response = responder(&request);
+
+                            requests.lock().unwrap().push(request);
+
+                            if !response.delay.is_zero() {
+                                tokio::time::sleep(response.delay).await;
+                            }
+
+                            let mut builder = Response::builder().status(response.status);
+
+                            for (name, value) in response.headers {
+                                builder = builder.header(name, value);
+                            }
+
+                            Ok::<_, warp::Rejection>(builder.body(response.body).unwrap())
+                        }
+                    },
+                )
+        };
+
+        let handle = thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+
+            rt.block_on(async move {
+                let shutdown = async move {
+                    let _ = shutdown_rx.await;
+                };
+
+                if tls {
+                    let (addr, server) = warp::serve(route)
+                        .tls()
+                        .cert(fixtures::TLS_CERT)
+                        .key(fixtures::TLS_KEY)
+                        .bind_with_graceful_shutdown(([127, 0, 0, 1], 0), shutdown);
+
+                    addr_tx.send(addr).unwrap();
+
+                    server.await;
+                } else {
+                    let (addr, server) = warp::serve(route)
+                        .bind_with_graceful_shutdown(([127, 0, 0, 1], 0), shutdown);
+
+                    addr_tx.send(addr).unwrap();
+
+                    server.await;
+                }
+            });
+        });
+
+        MockServer {
+            addr: addr_rx.recv().unwrap(),
+            tls,
+            requests,
+            shutdown: Some(shutdown_tx),
+            handle: Some(handle),
+        }
+    }
+
+    pub fn url(&self) -> String {
+        if self.tls {
+            // the name the certificate is issued for
+            format!("https://localhost:{}", self.addr.port())
+        } else {
+            format!("http://{}", self.addr)
+        }
+    }
+
+    /// An `Api` sending logs, traces, metrics and events to this server
+    pub fn api(&self) -> Api {
+        Api::from(("key".to_string(), ApiEndpoint::Custom(self.url())))
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    pub fn trace_requests(&self) -> Vec<Request> {
+        self.requests_to("/trace/v1")
+    }
+
+    pub fn log_requests(&self) -> Vec<Request> {
+        self.requests_to("/log/v1")
+    }
+
+    pub fn metric_requests(&self) -> Vec<Request> {
+        self.requests_to("/metric/v1")
+    }
+
+    /// Requests to the Event API, for any account
+    pub fn event_requests(&self) -> Vec<Request> {
+        self.requests()
+            .into_iter()
+            .filter(|req| req.path.ends_with("/events"))
+            .collect()
+    }
+
+    pub fn requests_to(&self, path: &str) -> Vec<Request> {
+        self.requests()
+            .into_iter()
+            .filter(|req| req.path == path)
+            .collect()
+    }
+
+    /// All spans received, flattened across requests and payloads
+    pub fn spans(&self) -> Vec<Value> {
+        flatten(&self.trace_requests(), "spans")
+    }
+
+    /// All logs received, flattened across requests and payloads
+    pub fn logs(&self) -> Vec<Value> {
+        flatten(&self.log_requests(), "logs")
+    }
+
+    /// All events received, flattened across requests
+    pub fn events(&self) -> Vec<Value> {
+        self.event_requests()
+            .iter()
+            .flat_map(|req| req.body.as_array().cloned().unwrap_or_default())
+            .collect()
+    }
+
+    /// All metrics received, flattened across requests and payloads
+    pub fn metrics(&self) -> Vec<Value> {
+        flatten(&self.metric_requests(), "metrics")
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn decode_body(headers: &HeaderMap, body: &[u8]) -> Value {
+    let gzipped = headers
+        .get("content-encoding")
+        .is_some_and(|val| val == "gzip");
+
+    if gzipped {
+        let mut json = String::new();
+        GzDecoder::new(body).read_to_string(&mut json).unwrap();
+        serde_json::from_str(&json).unwrap()
+    } else {
+        serde_json::from_slice(body).unwrap_or(Value::Null)
+    }
+}
+
+fn flatten(requests: &[Request], key: &str) -> Vec<Value> {
+    requests
+        .iter()
+        .flat_map(|req| req.body.as_array().cloned().unwrap_or_default())
+        .flat_map(|payload| payload[key].as_array().cloned().unwrap_or_default())
+        .collect()
+}
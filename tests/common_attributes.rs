@@ -0,0 +1,95 @@
+#![cfg(feature = "testing")]
+
+use tracing_newrelic::testing::with_captured_layer;
+use tracing_newrelic::{NewRelicLayer, NewrAttributes, Value};
+
+fn attributes(pairs: &[(&'static str, &'static str)]) -> NewrAttributes {
+    let mut attributes = NewrAttributes::default();
+
+    for (key, value) in pairs {
+        attributes.insert(*key, *value);
+    }
+
+    attributes
+}
+
+/// Common attributes of the logs and spans of a trace
+fn common(
+    configure: impl FnOnce(NewRelicLayer) -> NewRelicLayer,
+) -> (NewrAttributes, NewrAttributes) {
+    let captured = with_captured_layer(configure, || {
+        let _root = tracing::info_span!("root").entered();
+
+        tracing::info!("in root");
+    });
+
+    let (logs, spans) = &captured.payloads[0];
+
+    (
+        logs.common.attributes().clone(),
+        spans.common.attributes().clone(),
+    )
+}
+
+#[test]
+fn specific_attributes_stay_in_their_payload() {
+    let (logs, spans) = common(|layer| {
+        layer
+            .with_common_log_attributes(attributes(&[
+                ("logtype", "application"),
+                ("plugin.type", "tracing-newrelic"),
+            ]))
+            .with_common_span_attributes(attributes(&[("collector.name", "tracing-newrelic")]))
+    });
+
+    assert_eq!(logs.get("logtype"), Some(&Value::from("application")));
+    assert_eq!(
+        logs.get("plugin.type"),
+        Some(&Value::from("tracing-newrelic"))
+    );
+    assert_eq!(logs.get("collector.name"), None);
+
+    assert_eq!(
+        spans.get("collector.name"),
+        Some(&Value::from("tracing-newrelic"))
+    );
+    assert_eq!(spans.get("logtype"), None);
+    assert_eq!(spans.get("plugin.type"), None);
+}
+
+#[test]
+fn global_attributes_are_shared() {
+    let (logs, spans) =
+        common(|layer| layer.with_global_attributes(attributes(&[("environment", "staging")])));
+
+    assert_eq!(logs.get("environment"), Some(&Value::from("staging")));
+    assert_eq!(spans.get("environment"), Some(&Value::from("staging")));
+    assert_eq!(logs, spans);
+}
+
+#[test]
+fn precedence() {
+    let (logs, spans) = common(|layer| {
+        layer
+            .with_service_name("billing")
+            .with_global_attributes(attributes(&[
+                ("service.name", "billing-global"),
+                ("environment", "staging"),
+            ]))
+            .with_common_log_attributes(attributes(&[("environment", "logs")]))
+    });
+
+    // global over built-ins
+    assert_eq!(
+        logs.get("service.name"),
+        Some(&Value::from("billing-global"))
+    );
+    assert_eq!(
+        spans.get("service.name"),
+        Some(&Value::from("billing-global"))
+    );
+
+    // specific over global
+    assert_eq!(logs.get("environment"), Some(&Value::from("logs")));
+    assert_eq!(spans.get("environment"), Some(&Value::from("staging")));
+}
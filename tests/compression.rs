@@ -0,0 +1,85 @@
+#![cfg(feature = "testing")]
+
+mod common;
+
+use common::{MockServer, Request};
+use serde_json::Value;
+use tracing_newrelic::Compression;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn run(compression: Compression) -> Vec<Request> {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api().with_compression(compression))
+        .with_deterministic_ids();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let _root = tracing::info_span!("root", message = "a".repeat(1000)).entered();
+        tracing::info!(message = "b".repeat(1000).as_str());
+    });
+
+    server.requests()
+}
+
+fn header<'a>(request: &'a Request, name: &str) -> Option<&'a str> {
+    request.headers.get(name).map(|val| val.to_str().unwrap())
+}
+
+fn content_length(requests: &[Request]) -> usize {
+    requests
+        .iter()
+        .map(|request| {
+            header(request, "content-length")
+                .unwrap()
+                .parse::<usize>()
+                .unwrap()
+        })
+        .sum()
+}
+
+/// Decoded bodies by path, without timings
+fn bodies(requests: &[Request]) -> Vec<(String, Value)> {
+    let mut bodies: Vec<_> = requests
+        .iter()
+        .map(|request| {
+            let mut body = request.body.clone();
+            for span in body[0]["spans"].as_array_mut().into_iter().flatten() {
+                let attributes = span["attributes"].as_object_mut().unwrap();
+
+                for key in ["duration.ms", "busy.ms", "idle.ms"] {
+                    attributes.remove(key);
+                }
+            }
+            (request.path.clone(), body)
+        })
+        .collect();
+    bodies.sort_by(|a, b| a.0.cmp(&b.0));
+    bodies
+}
+
+#[test]
+fn every_mode_sends_the_same_json() {
+    let default = run(Compression::default());
+    let best = run(Compression::Gzip(9));
+    let stored = run(Compression::Gzip(0));
+    let plain = run(Compression::None);
+
+    assert_eq!(default.len(), 2);
+
+    for request in default.iter().chain(&best).chain(&stored) {
+        assert_eq!(header(request, "content-encoding"), Some("gzip"));
+    }
+
+    for request in &plain {
+        assert_eq!(header(request, "content-encoding"), None);
+        assert_eq!(header(request, "content-type"), Some("application/json"));
+    }
+
+    assert_ne!(bodies(&default)[0].1, Value::Null);
+    assert_eq!(bodies(&best), bodies(&default));
+    assert_eq!(bodies(&stored), bodies(&default));
+    assert_eq!(bodies(&plain), bodies(&default));
+
+    assert!(content_length(&best) < content_length(&stored));
+    assert!(content_length(&stored) > content_length(&plain));
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{MockResponse, MockServer};
+use tracing_newrelic::RetryPolicy;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+/// Send 8 traces one per batch to a server answering in 100ms, returning how long the flush took
+fn flush_duration(concurrency: usize) -> Duration {
+    let server =
+        MockServer::start_with(|_| MockResponse::status(202).delay(Duration::from_millis(100)));
+
+    let api = server
+        .api()
+        .with_log_batch_size(1)
+        .with_trace_batch_size(1)
+        .with_max_concurrent_requests(concurrency);
+
+    let layer = tracing_newrelic::layer(api);
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let start = Instant::now();
+
+        for n in 0..8 {
+            let _span = tracing::info_span!("root").entered();
+            tracing::info!(n, "inside root");
+        }
+
+        assert!(handle.flush_timeout(Duration::from_secs(10)));
+        assert_eq!(server.spans().len(), 8);
+        assert_eq!(server.logs().len(), 8);
+
+        start.elapsed()
+    })
+}
+
+#[test]
+fn concurrent_requests_improve_throughput() {
+    let sequential = flush_duration(1);
+    let concurrent = flush_duration(4);
+
+    assert!(sequential >= Duration::from_millis(800), "{:?}", sequential);
+    assert!(concurrent < sequential / 2, "{:?}", concurrent);
+}
+
+#[test]
+fn rate_limit_pauses_every_request_to_the_endpoint() {
+    let server = MockServer::start_with(|request| {
+        let first =
+            request.path.contains("trace") && request.body[0]["spans"][0]["attributes"]["n"] == 0;
+
+        if first {
+            MockResponse::status(429).header("retry-after", "1")
+        } else {
+            MockResponse::status(202).delay(Duration::from_millis(50))
+        }
+    });
+
+    let api = server
+        .api()
+        .with_trace_batch_size(1)
+        .with_max_concurrent_requests(4)
+        .with_retry_policy(RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        });
+
+    let layer = tracing_newrelic::layer(api);
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let _span = tracing::info_span!("root", n = 0).entered();
+        drop(_span);
+
+        // wait for the rate limited response
+        while server.trace_requests().is_empty() {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        std::thread::sleep(Duration::from_millis(50));
+
+        for n in 1..=4 {
+            let _span = tracing::info_span!("root", n).entered();
+        }
+
+        assert!(handle.flush_timeout(Duration::from_secs(10)));
+    });
+
+    let requests = server.trace_requests();
+    let limited = requests[0].received;
+
+    assert_eq!(requests.len(), 5);
+
+    for request in &requests[1..] {
+        assert!(request.received - limited >= Duration::from_millis(900));
+    }
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use common::MockServer;
+use tracing_newrelic::{Api, ApiEndpoint, ConfigError, NewRelicLayer};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn error_of(api: impl Into<Api>) -> ConfigError {
+    match tracing_newrelic::try_layer(api) {
+        Ok(_) => panic!("expected an invalid configuration"),
+        Err(err) => err,
+    }
+}
+
+#[test]
+fn empty_key() {
+    let err = error_of("  ");
+
+    assert_eq!(err, ConfigError::EmptyApiKey);
+    assert!(err.to_string().contains("api key is empty"));
+}
+
+#[test]
+fn key_with_whitespace() {
+    let err = error_of("abc def");
+
+    assert_eq!(err, ConfigError::ApiKeyWhitespace);
+    assert!(err.to_string().contains("api key contains whitespace"));
+}
+
+#[test]
+fn custom_endpoint_without_scheme() {
+    let endpoint = ApiEndpoint::Custom("trace-proxy.internal:8080".into());
+
+    let err = error_of(("key".to_string(), endpoint));
+
+    assert_eq!(
+        err,
+        ConfigError::EndpointMissingScheme {
+            field: "log_endpoint",
+            endpoint: "trace-proxy.internal:8080".into(),
+        }
+    );
+    assert!(err
+        .to_string()
+        .contains("custom endpoint missing scheme, got 'trace-proxy.internal:8080'"));
+}
+
+#[test]
+fn invalid_proxy() {
+    let err = error_of(Api::from("key").with_proxy("http://[oops"));
+
+    assert_eq!(
+        err,
+        ConfigError::InvalidProxy {
+            proxy: "http://[oops".into()
+        }
+    );
+    assert!(err.to_string().contains("invalid proxy url"));
+}
+
+#[test]
+fn invalid_ca_certificate() {
+    let err = error_of(Api::from("key").with_ca_certificate("not a certificate"));
+
+    assert_eq!(err, ConfigError::InvalidCertificate);
+}
+
+#[test]
+fn trace_endpoint_is_validated() {
+    let mut api = Api::from("key");
+    api.trace_endpoint = ApiEndpoint::Custom("localhost".into());
+
+    let err = error_of(api);
+
+    assert!(matches!(
+        err,
+        ConfigError::EndpointMissingScheme {
+            field: "trace_endpoint",
+            ..
+        }
+    ));
+    assert!(err.to_string().starts_with("trace_endpoint: "));
+}
+
+#[test]
+#[allow(deprecated)]
+fn key_is_trimmed() {
+    let server = MockServer::start();
+
+    let mut api = server.api();
+    api.key = " key\n".into();
+
+    let layer = tracing_newrelic::try_layer(api).unwrap();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let _span = tracing::info_span!("root").entered();
+    });
+
+    let requests = server.trace_requests();
+
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].headers["api-key"], "key");
+}
+
+#[test]
+fn layer_from_api() {
+    let server = MockServer::start();
+
+    let layer = NewRelicLayer::from(server.api());
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let _span = tracing::info_span!("root").entered();
+    });
+
+    assert_eq!(server.spans().len(), 1);
+}
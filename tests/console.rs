@@ -0,0 +1,58 @@
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use serde_json::Value;
+use tracing_newrelic::ConsoleExporter;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn prints_one_json_object_per_trace() {
+    let buffer = Buffer::default();
+
+    let layer = tracing_newrelic::layer_with_exporter(ConsoleExporter::with_writer(buffer.clone()));
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        for i in 0..2 {
+            let span = tracing::info_span!("root", service.name = "console", i);
+            let _span = span.enter();
+            tracing::info!("hello");
+        }
+    });
+
+    let output = buffer.0.lock().unwrap().clone();
+
+    let payloads: Vec<Value> = serde_json::Deserializer::from_slice(&output)
+        .into_iter()
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    assert_eq!(payloads.len(), 2);
+
+    for payload in payloads {
+        assert_eq!(
+            payload["spans"][0]["common"]["attributes"]["service.name"],
+            "console"
+        );
+        assert_eq!(
+            payload["spans"][0]["spans"][0]["attributes"]["name"],
+            "root"
+        );
+        assert_eq!(
+            payload["logs"][0]["logs"][0]["attributes"]["message"],
+            "hello"
+        );
+    }
+}
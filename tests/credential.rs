@@ -0,0 +1,68 @@
+mod common;
+
+use common::MockServer;
+use tracing_newrelic::{Api, ApiEndpoint, Credential};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn run(server: &MockServer, credential: Credential) {
+    let api = Api::from((credential, ApiEndpoint::Custom(server.url())));
+
+    let layer = tracing_newrelic::try_layer(api).unwrap();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let _span = tracing::info_span!("root").entered();
+        tracing::info!("inside root");
+    });
+
+    assert_eq!(server.trace_requests().len(), 1);
+    assert_eq!(server.log_requests().len(), 1);
+}
+
+#[test]
+fn api_key() {
+    let server = MockServer::start();
+
+    run(&server, Credential::ApiKey("insert-key".into()));
+
+    for request in server.requests() {
+        assert_eq!(request.headers["api-key"], "insert-key");
+        assert!(!request.headers.contains_key("x-license-key"));
+    }
+}
+
+#[test]
+fn license_key() {
+    let server = MockServer::start();
+
+    run(&server, Credential::LicenseKey(" license-key\n".into()));
+
+    for request in server.requests() {
+        assert_eq!(request.headers["x-license-key"], "license-key");
+        assert!(!request.headers.contains_key("api-key"));
+    }
+}
+
+#[test]
+#[allow(deprecated)]
+fn deprecated_key_is_an_api_key() {
+    let server = MockServer::start();
+
+    let mut api = Api::from((
+        Credential::LicenseKey("license-key".into()),
+        ApiEndpoint::Custom(server.url()),
+    ));
+    api.key = "insert-key".into();
+
+    tracing::subscriber::with_default(
+        Registry::default().with(tracing_newrelic::layer(api)),
+        || {
+            let _span = tracing::info_span!("root").entered();
+        },
+    );
+
+    let requests = server.trace_requests();
+
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].headers["api-key"], "insert-key");
+    assert!(!requests[0].headers.contains_key("x-license-key"));
+}
@@ -0,0 +1,156 @@
+#![cfg(feature = "testing")]
+
+use tracing_newrelic::db::{obfuscate, DbStatementPolicy};
+use tracing_newrelic::testing::with_captured_layer;
+use tracing_newrelic::{NewrSpan, Value};
+
+const STATEMENT: &str = "SELECT * FROM invoices WHERE customer = 'O''Brien' AND total > 10.5";
+
+fn query(policy: DbStatementPolicy) -> NewrSpan {
+    let captured = with_captured_layer(
+        |layer| {
+            layer
+                .with_db_statement_handling(policy)
+                .with_attribute_prefix("app.")
+        },
+        || {
+            let _span = tracing::info_span!(
+                "query",
+                db.system = "postgresql",
+                db.operation = "SELECT",
+                db.statement = STATEMENT,
+            )
+            .entered();
+        },
+    );
+
+    captured.span("query").unwrap().clone()
+}
+
+#[test]
+fn obfuscates_string_literals() {
+    assert_eq!(
+        obfuscate("SELECT * FROM users WHERE name = 'ferris'"),
+        "SELECT * FROM users WHERE name = ?"
+    );
+    assert_eq!(
+        obfuscate("UPDATE users SET bio = 'it''s me', motto = 'a \\' b' WHERE id = $1"),
+        "UPDATE users SET bio = ?, motto = ? WHERE id = $1"
+    );
+    assert_eq!(
+        obfuscate("SELECT \"name\", `email` FROM \"users\" WHERE note = ''"),
+        "SELECT \"name\", `email` FROM \"users\" WHERE note = ?"
+    );
+    // unterminated
+    assert_eq!(obfuscate("SELECT 'secret"), "SELECT ?");
+}
+
+#[test]
+fn obfuscates_numbers() {
+    assert_eq!(
+        obfuscate("SELECT * FROM t1 WHERE id = 42 AND ratio > 0.75 AND mask = 0xff"),
+        "SELECT * FROM t1 WHERE id = ? AND ratio > ? AND mask = ?"
+    );
+    assert_eq!(
+        obfuscate("SELECT * FROM logs WHERE delta = -3 LIMIT 10 OFFSET 20"),
+        "SELECT * FROM logs WHERE delta = -? LIMIT ? OFFSET ?"
+    );
+    assert_eq!(
+        obfuscate("INSERT INTO t VALUES ($1, $2)"),
+        "INSERT INTO t VALUES ($1, $2)"
+    );
+}
+
+#[test]
+fn obfuscates_in_lists() {
+    assert_eq!(
+        obfuscate("SELECT * FROM users WHERE id IN (1, 2, 3)"),
+        "SELECT * FROM users WHERE id IN (?)"
+    );
+    assert_eq!(
+        obfuscate("SELECT * FROM users WHERE name in ('a','b') AND id NOT IN(4)"),
+        "SELECT * FROM users WHERE name in (?) AND id NOT IN(?)"
+    );
+    // not lists of literals
+    assert_eq!(
+        obfuscate("SELECT * FROM users WHERE id IN (SELECT id FROM admins)"),
+        "SELECT * FROM users WHERE id IN (SELECT id FROM admins)"
+    );
+    assert_eq!(
+        obfuscate("SELECT * FROM users WHERE id IN ($1, $2)"),
+        "SELECT * FROM users WHERE id IN ($1, $2)"
+    );
+    assert_eq!(
+        obfuscate("SELECT login, (1, 2) FROM users"),
+        "SELECT login, (?, ?) FROM users"
+    );
+}
+
+#[test]
+fn obfuscates_statements() {
+    let span = query(DbStatementPolicy::Obfuscate);
+
+    assert_eq!(
+        span.attributes.get("db.statement"),
+        Some(&Value::from(
+            "SELECT * FROM invoices WHERE customer = ? AND total > ?"
+        ))
+    );
+    assert_eq!(
+        span.attributes.get("db.system"),
+        Some(&Value::from("postgresql"))
+    );
+    assert_eq!(
+        span.attributes.get("db.operation"),
+        Some(&Value::from("SELECT"))
+    );
+}
+
+#[test]
+fn truncates_statements() {
+    let span = query(DbStatementPolicy::Truncate(13));
+
+    assert_eq!(
+        span.attributes.get("db.statement"),
+        Some(&Value::from("SELECT * FROM"))
+    );
+
+    let span = query(DbStatementPolicy::Truncate(1024));
+
+    assert_eq!(
+        span.attributes.get("db.statement"),
+        Some(&Value::from(STATEMENT))
+    );
+}
+
+#[test]
+fn omits_statements() {
+    let span = query(DbStatementPolicy::Omit);
+
+    assert_eq!(span.attributes.get("db.statement"), None);
+    assert_eq!(span.attributes.get("app.db.statement"), None);
+    assert_eq!(
+        span.attributes.get("db.system"),
+        Some(&Value::from("postgresql"))
+    );
+}
+
+#[test]
+fn handles_query_text() {
+    let captured = with_captured_layer(
+        |layer| layer.with_db_statement_handling(DbStatementPolicy::Obfuscate),
+        || {
+            let _span = tracing::info_span!("query", db.query.text = "DELETE FROM t WHERE id = 7")
+                .entered();
+        },
+    );
+
+    assert_eq!(
+        captured
+            .span("query")
+            .unwrap()
+            .attributes
+            .get("db.query.text"),
+        Some(&Value::from("DELETE FROM t WHERE id = ?"))
+    );
+}
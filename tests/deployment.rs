@@ -0,0 +1,79 @@
+#![cfg(feature = "testing")]
+
+use tracing_newrelic::testing::with_captured_layer;
+use tracing_newrelic::Value;
+
+#[test]
+fn records_deployment_attributes() {
+    let captured = with_captured_layer(
+        |layer| {
+            layer
+                .with_service_name("billing")
+                .with_service_version("1.4.2")
+                .with_deployment_attributes("9fceb02d", "2024-05-01T12:00:00Z")
+        },
+        || {
+            let _root = tracing::info_span!("root").entered();
+
+            tracing::info!("deployed");
+        },
+    );
+
+    let (logs, spans) = &captured.payloads[0];
+
+    for common in [&logs.common, &spans.common].iter() {
+        assert_eq!(
+            common.attributes().get("service.name"),
+            Some(&Value::from("billing"))
+        );
+        assert_eq!(
+            common.attributes().get("service.version"),
+            Some(&Value::from("1.4.2"))
+        );
+        assert_eq!(
+            common.attributes().get("vcs.ref"),
+            Some(&Value::from("9fceb02d"))
+        );
+        assert_eq!(
+            common.attributes().get("deployment.timestamp"),
+            Some(&Value::from("2024-05-01T12:00:00Z"))
+        );
+    }
+}
+
+#[test]
+fn no_deployment_attributes_by_default() {
+    let captured = with_captured_layer(
+        |layer| layer,
+        || {
+            let _root = tracing::info_span!("root").entered();
+        },
+    );
+
+    let (_, spans) = &captured.payloads[0];
+
+    assert_eq!(spans.common.attributes().get("service.version"), None);
+    assert_eq!(spans.common.attributes().get("vcs.ref"), None);
+    assert_eq!(spans.common.attributes().get("deployment.timestamp"), None);
+}
+
+#[test]
+fn service_version_from_cargo() {
+    // expands in the calling crate, i.e. this one
+    let version: &'static str = tracing_newrelic::service_version_from_cargo!();
+    assert_eq!(version, env!("CARGO_PKG_VERSION"));
+
+    let captured = with_captured_layer(
+        |layer| layer.with_service_version(tracing_newrelic::service_version_from_cargo!()),
+        || {
+            let _root = tracing::info_span!("root").entered();
+        },
+    );
+
+    let (_, spans) = &captured.payloads[0];
+
+    assert_eq!(
+        spans.common.attributes().get("service.version"),
+        Some(&Value::from(env!("CARGO_PKG_VERSION")))
+    );
+}
@@ -0,0 +1,21 @@
+mod common;
+
+use common::MockServer;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+#[test]
+fn dry_run_sends_nothing() {
+    std::env::set_var("NEWRELIC_DRY_RUN", "1");
+
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api());
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let span = tracing::info_span!("root");
+        let _span = span.enter();
+        tracing::info!("not sent");
+    });
+
+    assert!(server.requests().is_empty());
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use common::MockServer;
+use serde_json::Value;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+#[test]
+fn long_lived_root_is_exported_in_progress() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api()).with_eager_export(Duration::from_millis(50));
+
+    let subscriber = Registry::default().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        let root = tracing::info_span!("consumer");
+        let _root = root.enter();
+
+        tracing::info_span!("message", n = 1).in_scope(|| tracing::info!("first"));
+
+        sleep(Duration::from_millis(60));
+
+        tracing::info_span!("message", n = 2).in_scope(|| tracing::info!("second"));
+
+        tracing::info_span!("message", n = 3).in_scope(|| tracing::info!("third"));
+    });
+
+    let spans = server.spans();
+    let logs = server.logs();
+
+    let roots: Vec<&Value> = spans
+        .iter()
+        .filter(|span| span["attributes"]["name"] == "consumer")
+        .collect();
+
+    // one snapshot and the final span, sharing the same id
+    assert_eq!(roots.len(), 2);
+    assert_eq!(roots[0]["id"], roots[1]["id"]);
+    assert_eq!(roots[0]["attributes"]["in_progress"], true);
+    assert_eq!(roots[1]["attributes"].get("in_progress"), None);
+
+    // every child and log is exported exactly once, under the same trace
+    assert_eq!(spans.len(), 5);
+    assert_eq!(logs.len(), 3);
+    assert!(spans
+        .iter()
+        .all(|span| span["trace.id"] == roots[0]["trace.id"]));
+    assert!(logs
+        .iter()
+        .all(|log| log["attributes"]["trace.id"] == roots[0]["trace.id"]));
+}
+
+#[test]
+fn short_lived_root_is_not_exported_early() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api()).with_eager_export(Duration::from_secs(10));
+
+    let subscriber = Registry::default().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        let root = tracing::info_span!("root");
+        let _root = root.enter();
+
+        tracing::info_span!("child").in_scope(|| tracing::info!("log"));
+    });
+
+    let spans = server.spans();
+
+    assert_eq!(spans.len(), 2);
+    assert!(spans
+        .iter()
+        .all(|span| span["attributes"].get("in_progress").is_none()));
+}
@@ -0,0 +1,98 @@
+#![cfg(feature = "testing")]
+
+use tracing_newrelic::testing::{with_captured_layer, Captured};
+use tracing_newrelic::{EmptyValuePolicy, NewRelicLayer, Value};
+
+const TROUBLESOME: [&str; 3] = ["", "null", "undefined"];
+
+fn run(configure: impl FnOnce(NewRelicLayer) -> NewRelicLayer, value: &str) -> Captured {
+    with_captured_layer(configure, || {
+        let span = tracing::info_span!("root", user.id = value, hostname = value);
+        let _span = span.enter();
+        tracing::info!(user.id = value, "log");
+    })
+}
+
+#[test]
+fn keep_by_default() {
+    for value in TROUBLESOME {
+        let captured = run(|layer| layer, value);
+
+        let (logs, spans) = &captured.payloads[0];
+        assert_eq!(
+            spans.spans[0].attributes.get("user.id"),
+            Some(&value.into())
+        );
+        assert_eq!(logs.logs[0].attributes.get("user.id"), Some(&value.into()));
+        assert_eq!(
+            spans.common.attributes().get("hostname"),
+            Some(&value.into())
+        );
+    }
+}
+
+#[test]
+fn drop() {
+    for value in TROUBLESOME {
+        let captured = run(
+            |layer| layer.with_empty_value_policy(EmptyValuePolicy::Drop),
+            value,
+        );
+
+        let (logs, spans) = &captured.payloads[0];
+        assert_eq!(spans.spans[0].attributes.get("user.id"), None);
+        assert_eq!(logs.logs[0].attributes.get("user.id"), None);
+        assert_eq!(spans.common.attributes().get("hostname"), None);
+    }
+}
+
+#[test]
+fn replace() {
+    let placeholder = Value::from("<empty>");
+
+    for value in TROUBLESOME {
+        let captured = run(
+            |layer| layer.with_empty_value_policy(EmptyValuePolicy::Replace("<empty>".into())),
+            value,
+        );
+
+        let (logs, spans) = &captured.payloads[0];
+        assert_eq!(spans.spans[0].attributes.get("user.id"), Some(&placeholder));
+        assert_eq!(logs.logs[0].attributes.get("user.id"), Some(&placeholder));
+        assert_eq!(logs.common.attributes().get("hostname"), Some(&placeholder));
+    }
+}
+
+#[test]
+fn per_key() {
+    for value in TROUBLESOME {
+        let captured = run(
+            |layer| {
+                layer
+                    .with_empty_value_policy(EmptyValuePolicy::Drop)
+                    .with_empty_value_policy_for("hostname", EmptyValuePolicy::Keep)
+            },
+            value,
+        );
+
+        let (_, spans) = &captured.payloads[0];
+        assert_eq!(spans.spans[0].attributes.get("user.id"), None);
+        assert_eq!(
+            spans.spans[0].attributes.get("hostname"),
+            Some(&value.into())
+        );
+    }
+}
+
+#[test]
+fn other_values_are_untouched() {
+    let captured = run(
+        |layer| layer.with_empty_value_policy(EmptyValuePolicy::Drop),
+        "nullable",
+    );
+
+    assert_eq!(
+        captured.spans()[0].attributes.get("user.id"),
+        Some(&"nullable".into())
+    );
+}
@@ -0,0 +1,57 @@
+use tracing_newrelic::ApiEndpoint;
+
+#[test]
+fn log_urls() {
+    assert_eq!(
+        ApiEndpoint::US.log_url(),
+        "https://log-api.newrelic.com/log/v1"
+    );
+    assert_eq!(
+        ApiEndpoint::EU.log_url(),
+        "https://log-api.eu.newrelic.com/log/v1"
+    );
+    assert_eq!(
+        ApiEndpoint::FedRamp.log_url(),
+        "https://gov-log-api.newrelic.com/log/v1"
+    );
+    assert_eq!(
+        ApiEndpoint::Custom("http://localhost:8080".into()).log_url(),
+        "http://localhost:8080/log/v1"
+    );
+}
+
+#[test]
+fn trace_urls() {
+    assert_eq!(
+        ApiEndpoint::US.trace_url(),
+        "https://trace-api.newrelic.com/trace/v1"
+    );
+    assert_eq!(
+        ApiEndpoint::EU.trace_url(),
+        "https://trace-api.eu.newrelic.com/trace/v1"
+    );
+    assert_eq!(
+        ApiEndpoint::FedRamp.trace_url(),
+        "https://gov-trace-api.newrelic.com/trace/v1"
+    );
+    assert_eq!(
+        ApiEndpoint::Custom("http://localhost:8080".into()).trace_url(),
+        "http://localhost:8080/trace/v1"
+    );
+}
+
+#[test]
+fn region_of_keys() {
+    assert_eq!(
+        ApiEndpoint::region_of("eu01xx0123456789abcdef0123456789abcdNRAL"),
+        ApiEndpoint::EU
+    );
+    assert_eq!(
+        ApiEndpoint::region_of("0123456789abcdef0123456789abcdefNRAL"),
+        ApiEndpoint::US
+    );
+    assert_eq!(ApiEndpoint::region_of("NRAK-0123456789"), ApiEndpoint::US);
+    assert_eq!(ApiEndpoint::region_of(""), ApiEndpoint::US);
+    assert_eq!(ApiEndpoint::region_of("eu"), ApiEndpoint::US);
+    assert_eq!(ApiEndpoint::region_of("garbage!"), ApiEndpoint::US);
+}
@@ -0,0 +1,93 @@
+#![cfg(feature = "testing")]
+
+use tracing_newrelic::testing::with_captured_layer;
+use tracing_newrelic::{NewRelicLayer, NewrAttributes, Value};
+
+/// Common attributes of the spans of a trace
+fn common(configure: impl FnOnce(NewRelicLayer) -> NewRelicLayer) -> NewrAttributes {
+    let captured = with_captured_layer(configure, || {
+        let _root = tracing::info_span!("root").entered();
+
+        tracing::info!("in root");
+    });
+
+    let (logs, spans) = &captured.payloads[0];
+    assert_eq!(logs.common.attributes(), spans.common.attributes());
+
+    spans.common.attributes().clone()
+}
+
+#[test]
+fn service_name_only() {
+    let attributes = common(|layer| layer.with_service_name("billing"));
+
+    assert_eq!(attributes.get("entity.name"), Some(&Value::from("billing")));
+    assert_eq!(attributes.get("entity.guid"), None);
+    assert_eq!(attributes.get("entity.type"), None);
+}
+
+#[test]
+fn explicit_entity() {
+    let attributes = common(|layer| {
+        layer.with_entity(
+            Some("MXxBUE18QVBQTElDQVRJT058MTIz".to_string()),
+            Some("billing-api".to_string()),
+            Some("SERVICE".to_string()),
+        )
+    });
+
+    assert_eq!(
+        attributes.get("entity.guid"),
+        Some(&Value::from("MXxBUE18QVBQTElDQVRJT058MTIz"))
+    );
+    assert_eq!(
+        attributes.get("entity.name"),
+        Some(&Value::from("billing-api"))
+    );
+    assert_eq!(attributes.get("entity.type"), Some(&Value::from("SERVICE")));
+    assert_eq!(attributes.get("service.name"), None);
+}
+
+#[test]
+fn service_name_and_entity() {
+    let attributes = common(|layer| {
+        layer.with_service_name("billing").with_entity(
+            Some("MXxBUE18QVBQTElDQVRJT058MTIz".to_string()),
+            Some("billing-api".to_string()),
+            None,
+        )
+    });
+
+    assert_eq!(
+        attributes.get("service.name"),
+        Some(&Value::from("billing"))
+    );
+    assert_eq!(
+        attributes.get("entity.name"),
+        Some(&Value::from("billing-api"))
+    );
+    assert_eq!(
+        attributes.get("entity.guid"),
+        Some(&Value::from("MXxBUE18QVBQTElDQVRJT058MTIz"))
+    );
+
+    // the entity name still defaults to the service name
+    let attributes = common(|layer| {
+        layer.with_service_name("billing").with_entity(
+            Some("MXxBUE18QVBQTElDQVRJT058MTIz".to_string()),
+            None,
+            None,
+        )
+    });
+
+    assert_eq!(attributes.get("entity.name"), Some(&Value::from("billing")));
+}
+
+#[test]
+fn no_entity_by_default() {
+    let attributes = common(|layer| layer);
+
+    assert_eq!(attributes.get("entity.name"), None);
+    assert_eq!(attributes.get("entity.guid"), None);
+    assert_eq!(attributes.get("entity.type"), None);
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use common::MockServer;
+use serde_json::{json, Value};
+use tracing_newrelic::NewRelicLayer;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn spans(layer: impl FnOnce(&MockServer) -> NewRelicLayer, f: impl FnOnce()) -> Vec<Value> {
+    let server = MockServer::start();
+
+    tracing::subscriber::with_default(Registry::default().with(layer(&server)), f);
+
+    server.spans()
+}
+
+fn nested() {
+    let _root = tracing::info_span!("root").entered();
+    let _child = tracing::info_span!("child").entered();
+}
+
+#[test]
+fn root_spans_are_entry_points() {
+    let spans = spans(|server| tracing_newrelic::layer(server.api()), nested);
+
+    let (root, child) = (&spans[0]["attributes"], &spans[1]["attributes"]);
+
+    assert_eq!(root["nr.entryPoint"], true);
+    assert_eq!(root.get("span.kind"), None);
+    assert_eq!(child.get("nr.entryPoint"), None);
+}
+
+#[test]
+fn default_span_kind_on_root_spans() {
+    let spans = spans(
+        |server| tracing_newrelic::layer(server.api()).with_default_span_kind("server"),
+        nested,
+    );
+
+    let (root, child) = (&spans[0]["attributes"], &spans[1]["attributes"]);
+
+    assert_eq!(root["span.kind"], "server");
+    assert_eq!(child.get("span.kind"), None);
+}
+
+#[test]
+fn explicit_values_win() {
+    let spans = spans(
+        |server| tracing_newrelic::layer(server.api()).with_default_span_kind("server"),
+        || {
+            let _root = tracing::info_span!("root", span.kind = "consumer", nr.entryPoint = false)
+                .entered();
+        },
+    );
+
+    assert_eq!(spans[0]["attributes"]["span.kind"], "consumer");
+    assert_eq!(spans[0]["attributes"]["nr.entryPoint"], json!(false));
+}
+
+#[test]
+fn recorded_later_values_win() {
+    let spans = spans(
+        |server| tracing_newrelic::layer(server.api()).with_default_span_kind("server"),
+        || {
+            let root = tracing::info_span!("root", span.kind = tracing::field::Empty);
+            root.record("span.kind", "producer");
+        },
+    );
+
+    assert_eq!(spans[0]["attributes"]["span.kind"], "producer");
+}
@@ -0,0 +1,177 @@
+mod common;
+
+use std::env;
+use std::sync::Mutex;
+
+use common::MockServer;
+use tracing_newrelic::{Api, ApiEndpoint, Credential, EnvError};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+// tests of this file share the environment
+static ENV: Mutex<()> = Mutex::new(());
+
+const VARIABLES: [&str; 6] = [
+    "NEW_RELIC_API_KEY",
+    "NEW_RELIC_LICENSE_KEY",
+    "NEW_RELIC_REGION",
+    "NEW_RELIC_APP_NAME",
+    "NEW_RELIC_LOG_ENDPOINT",
+    "NEW_RELIC_TRACE_ENDPOINT",
+];
+
+fn with_env<T>(vars: &[(&str, &str)], f: impl FnOnce() -> T) -> T {
+    let _guard = ENV.lock().unwrap_or_else(|err| err.into_inner());
+
+    for name in VARIABLES {
+        env::remove_var(name);
+    }
+
+    for (name, value) in vars {
+        env::set_var(name, value);
+    }
+
+    f()
+}
+
+fn api_from_env(vars: &[(&str, &str)]) -> Result<Api, EnvError> {
+    with_env(vars, Api::from_env)
+}
+
+fn error_of(vars: &[(&str, &str)]) -> EnvError {
+    match api_from_env(vars) {
+        Ok(_) => panic!("expected an invalid configuration"),
+        Err(err) => err,
+    }
+}
+
+#[test]
+fn api_key() {
+    let api = api_from_env(&[("NEW_RELIC_API_KEY", "insert-key")]).unwrap();
+
+    assert_eq!(api.credential, Credential::ApiKey("insert-key".into()));
+    assert_eq!(api.log_endpoint, ApiEndpoint::US);
+    assert_eq!(api.trace_endpoint, ApiEndpoint::US);
+}
+
+#[test]
+fn license_key() {
+    let api = api_from_env(&[("NEW_RELIC_LICENSE_KEY", "license-key")]).unwrap();
+
+    assert_eq!(api.credential, Credential::LicenseKey("license-key".into()));
+}
+
+#[test]
+fn api_key_takes_precedence() {
+    let api = api_from_env(&[
+        ("NEW_RELIC_API_KEY", "insert-key"),
+        ("NEW_RELIC_LICENSE_KEY", "license-key"),
+    ])
+    .unwrap();
+
+    assert_eq!(api.credential, Credential::ApiKey("insert-key".into()));
+}
+
+#[test]
+fn missing_key() {
+    let err = error_of(&[("NEW_RELIC_LICENSE_KEY", "  ")]);
+
+    assert_eq!(err, EnvError::MissingKey);
+    assert!(err.to_string().contains("NEW_RELIC_LICENSE_KEY"));
+}
+
+#[test]
+fn region() {
+    for (region, endpoint) in [
+        ("US", ApiEndpoint::US),
+        ("eu", ApiEndpoint::EU),
+        ("FedRAMP", ApiEndpoint::FedRamp),
+    ] {
+        let api = api_from_env(&[
+            ("NEW_RELIC_LICENSE_KEY", "license-key"),
+            ("NEW_RELIC_REGION", region),
+        ])
+        .unwrap();
+
+        assert_eq!(api.log_endpoint, endpoint);
+        assert_eq!(api.trace_endpoint, endpoint);
+    }
+}
+
+#[test]
+fn malformed_region() {
+    let err = error_of(&[
+        ("NEW_RELIC_LICENSE_KEY", "license-key"),
+        ("NEW_RELIC_REGION", "mars"),
+    ]);
+
+    assert_eq!(
+        err,
+        EnvError::Malformed {
+            variable: "NEW_RELIC_REGION",
+            value: "mars".into(),
+            expected: "US, EU or FedRAMP",
+        }
+    );
+    assert!(err.to_string().starts_with("NEW_RELIC_REGION: "));
+}
+
+#[test]
+fn endpoint_overrides() {
+    let api = api_from_env(&[
+        ("NEW_RELIC_LICENSE_KEY", "license-key"),
+        ("NEW_RELIC_REGION", "EU"),
+        ("NEW_RELIC_TRACE_ENDPOINT", "https://gateway.internal/"),
+    ])
+    .unwrap();
+
+    assert_eq!(api.log_endpoint, ApiEndpoint::EU);
+    assert_eq!(
+        api.trace_endpoint,
+        ApiEndpoint::Custom("https://gateway.internal".into())
+    );
+}
+
+#[test]
+fn malformed_endpoint() {
+    let err = error_of(&[
+        ("NEW_RELIC_LICENSE_KEY", "license-key"),
+        ("NEW_RELIC_LOG_ENDPOINT", "gateway.internal"),
+    ]);
+
+    assert!(matches!(
+        err,
+        EnvError::Malformed {
+            variable: "NEW_RELIC_LOG_ENDPOINT",
+            ..
+        }
+    ));
+}
+
+#[test]
+fn layer_from_env() {
+    let server = MockServer::start();
+
+    let layer = with_env(
+        &[
+            ("NEW_RELIC_LICENSE_KEY", "license-key"),
+            ("NEW_RELIC_APP_NAME", "checkout"),
+            ("NEW_RELIC_LOG_ENDPOINT", &server.url()),
+            ("NEW_RELIC_TRACE_ENDPOINT", &server.url()),
+        ],
+        tracing_newrelic::layer_from_env,
+    )
+    .unwrap();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let _span = tracing::info_span!("root").entered();
+    });
+
+    let requests = server.trace_requests();
+
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].headers["x-license-key"], "license-key");
+    assert_eq!(
+        requests[0].body[0]["common"]["attributes"]["service.name"],
+        "checkout"
+    );
+}
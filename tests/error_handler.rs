@@ -0,0 +1,84 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use common::{MockResponse, MockServer};
+use tracing_newrelic::ExportError;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+#[test]
+fn called_on_rejected_request() {
+    let server = MockServer::start_with(|request| {
+        if request.path.contains("trace") {
+            MockResponse::status(403).body(r#"{"error":"invalid key"}"#)
+        } else {
+            MockResponse::status(202)
+        }
+    });
+
+    let errors = Arc::new(Mutex::new(Vec::<ExportError>::new()));
+
+    let api = server.api().with_error_handler({
+        let errors = errors.clone();
+        move |error| errors.lock().unwrap().push(error)
+    });
+
+    tracing::subscriber::with_default(
+        Registry::default().with(tracing_newrelic::layer(api)),
+        || {
+            let root = tracing::info_span!("root");
+            let _root = root.enter();
+            tracing::info!("log");
+            let _child = tracing::info_span!("child").entered();
+        },
+    );
+
+    let errors = errors.lock().unwrap();
+
+    assert_eq!(
+        *errors,
+        [ExportError {
+            endpoint: format!("{}/trace/v1", server.url()),
+            status: Some(403),
+            body: r#"{"error":"invalid key"}"#.into(),
+            request_id: None,
+            spans_dropped: 2,
+            logs_dropped: 0,
+        }]
+    );
+}
+
+#[test]
+fn includes_request_id() {
+    let server = MockServer::start_with(|request| {
+        if request.path.contains("trace") {
+            MockResponse::status(400).body(format!(
+                r#"{{"requestId":"1b2c3d4e-5f60","error":"{}"}}"#,
+                "x".repeat(2000)
+            ))
+        } else {
+            MockResponse::status(202)
+        }
+    });
+
+    let errors = Arc::new(Mutex::new(Vec::<ExportError>::new()));
+
+    let api = server.api().with_error_handler({
+        let errors = errors.clone();
+        move |error| errors.lock().unwrap().push(error)
+    });
+
+    tracing::subscriber::with_default(
+        Registry::default().with(tracing_newrelic::layer(api)),
+        || {
+            let _root = tracing::info_span!("root").entered();
+        },
+    );
+
+    let errors = errors.lock().unwrap();
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].request_id.as_deref(), Some("1b2c3d4e-5f60"));
+    assert_eq!(errors[0].body.len(), 1024);
+    assert!(errors[0].to_string().contains("request id 1b2c3d4e-5f60"));
+}
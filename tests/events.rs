@@ -0,0 +1,93 @@
+mod common;
+
+use std::time::Duration;
+
+use common::MockServer;
+use serde_json::json;
+use tracing_newrelic::{Api, StatsSnapshot};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn run(api: Api) -> StatsSnapshot {
+    let layer = tracing_newrelic::layer(api).with_service_name("checkout");
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let _span = tracing::info_span!("request").entered();
+
+        tracing::info!(
+            newrelic.event_type = "OrderPlaced",
+            order_id = 123,
+            amount = 42.5
+        );
+        tracing::info!("order placed");
+
+        assert!(handle.flush_timeout(Duration::from_secs(5)));
+    });
+
+    handle.stats().snapshot()
+}
+
+#[test]
+fn sent_to_the_event_api() {
+    let server = MockServer::start();
+
+    let stats = run(server.api().with_account_id(42));
+
+    let requests = server.event_requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].path, "/v1/accounts/42/events");
+    assert_eq!(requests[0].headers["api-key"], "key");
+    assert_eq!(requests[0].headers["content-encoding"], "gzip");
+
+    let events = server.events();
+    assert_eq!(events.len(), 1);
+
+    let event = &events[0];
+    assert_eq!(event["eventType"], "OrderPlaced");
+    assert_eq!(event["order_id"], 123);
+    assert_eq!(event["amount"], 42.5);
+    assert_eq!(event["service.name"], "checkout");
+    assert_eq!(event["trace.id"], server.spans()[0]["trace.id"]);
+    assert!(event["timestamp"].is_u64());
+    assert_eq!(event.get("newrelic.event_type"), None);
+
+    assert_eq!(stats.events_sent, 1);
+}
+
+#[test]
+fn not_sent_as_logs() {
+    let server = MockServer::start();
+
+    run(server.api().with_account_id(42));
+
+    let logs = server.logs();
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0]["attributes"]["message"], "order placed");
+}
+
+#[test]
+fn sent_as_logs_if_requested() {
+    let server = MockServer::start();
+
+    run(server.api().with_account_id(42).with_events_as_logs(true));
+
+    assert_eq!(server.events().len(), 1);
+
+    let logs = server.logs();
+    assert_eq!(logs.len(), 2);
+    assert_eq!(
+        logs[0]["attributes"]["newrelic.event_type"],
+        json!("OrderPlaced")
+    );
+}
+
+#[test]
+fn sent_as_logs_without_account_id() {
+    let server = MockServer::start();
+
+    let stats = run(server.api());
+
+    assert!(server.event_requests().is_empty());
+    assert_eq!(server.logs().len(), 2);
+    assert_eq!(stats.events_sent, 0);
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use common::MockServer;
+use tracing_newrelic::{ExportPolicy, NewRelicLayer};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn run(server: &MockServer, policy: ExportPolicy, f: impl FnOnce()) -> u64 {
+    let layer = tracing_newrelic::layer(server.api()).with_export_policy(policy);
+
+    let subscriber = Registry::default().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        f();
+
+        tracing::dispatcher::get_default(|dispatch| {
+            dispatch
+                .downcast_ref::<NewRelicLayer>()
+                .unwrap()
+                .dropped_traces()
+        })
+    })
+}
+
+fn slow_errors() -> ExportPolicy {
+    ExportPolicy::ErrorsOrSlowerThan(Duration::from_millis(50))
+}
+
+#[test]
+fn fast_trace_is_dropped() {
+    let server = MockServer::start();
+
+    let dropped = run(&server, slow_errors(), || {
+        let span = tracing::info_span!("fast");
+        let _span = span.enter();
+        tracing::info!("ok");
+    });
+
+    assert_eq!(dropped, 1);
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn slow_trace_is_kept() {
+    let server = MockServer::start();
+
+    let dropped = run(&server, slow_errors(), || {
+        let span = tracing::info_span!("slow");
+        let _span = span.enter();
+        sleep(Duration::from_millis(60));
+    });
+
+    assert_eq!(dropped, 0);
+    assert_eq!(server.spans().len(), 1);
+}
+
+#[test]
+fn error_status_trace_is_kept() {
+    let server = MockServer::start();
+
+    let dropped = run(&server, slow_errors(), || {
+        let root = tracing::info_span!("root");
+        let _root = root.enter();
+
+        let child = tracing::info_span!("child", otel.status_code = "ERROR");
+        let _child = child.enter();
+    });
+
+    assert_eq!(dropped, 0);
+    assert_eq!(server.spans().len(), 2);
+}
+
+#[test]
+fn error_log_trace_is_kept() {
+    let server = MockServer::start();
+
+    let dropped = run(&server, slow_errors(), || {
+        let span = tracing::info_span!("root");
+        let _span = span.enter();
+        tracing::error!("failed");
+    });
+
+    assert_eq!(dropped, 0);
+    assert_eq!(server.logs().len(), 1);
+}
+
+#[test]
+fn custom_policy() {
+    let server = MockServer::start();
+
+    let policy = ExportPolicy::Custom(Box::new(|spans, logs| spans.len() > 1 && logs.is_empty()));
+
+    let dropped = run(&server, policy, || {
+        {
+            let root = tracing::info_span!("kept");
+            let _root = root.enter();
+            let _child = tracing::info_span!("child").entered();
+        }
+
+        {
+            let root = tracing::info_span!("dropped");
+            let _root = root.enter();
+            tracing::info!("log");
+        }
+    });
+
+    assert_eq!(dropped, 1);
+
+    let spans = server.spans();
+    assert_eq!(spans.len(), 2);
+    assert!(spans
+        .iter()
+        .all(|span| span["attributes"]["name"] != "dropped"));
+}
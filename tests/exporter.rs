@@ -0,0 +1,51 @@
+use std::sync::{Arc, Mutex};
+
+use futures_util::future::BoxFuture;
+use tracing_newrelic::{Exporter, NewrLogs, NewrSpans};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+#[derive(Clone, Default)]
+struct Collector {
+    payloads: Arc<Mutex<Vec<(NewrLogs, NewrSpans)>>>,
+    shutdown: Arc<Mutex<bool>>,
+}
+
+impl Exporter for Collector {
+    fn export(&mut self, logs: NewrLogs, spans: NewrSpans) -> BoxFuture<'_, ()> {
+        self.payloads.lock().unwrap().push((logs, spans));
+        Box::pin(async {})
+    }
+
+    fn shutdown(&mut self) -> BoxFuture<'_, ()> {
+        *self.shutdown.lock().unwrap() = true;
+        Box::pin(async {})
+    }
+}
+
+#[test]
+fn custom_exporter_receives_every_trace() {
+    let collector = Collector::default();
+
+    let subscriber =
+        Registry::default().with(tracing_newrelic::layer_with_exporter(collector.clone()));
+
+    tracing::subscriber::with_default(subscriber, || {
+        for _ in 0..3 {
+            let root = tracing::info_span!("root");
+            let _root = root.enter();
+
+            tracing::info_span!("child").in_scope(|| tracing::info!("log"));
+        }
+    });
+
+    let payloads = collector.payloads.lock().unwrap();
+
+    assert_eq!(payloads.len(), 3);
+    assert!(*collector.shutdown.lock().unwrap());
+
+    for (logs, spans) in payloads.iter() {
+        assert_eq!(logs.logs.len(), 1);
+        assert_eq!(spans.spans.len(), 2);
+        assert_eq!(spans.spans[0].trace_id, spans.spans[1].trace_id);
+    }
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use common::MockServer;
+use serde_json::Value;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn attribute<'a>(item: &'a Value, key: &str) -> &'a Value {
+    &item["attributes"][key]
+}
+
+// ids from the `__testing` feature are counted per thread, so they collide across threads
+#[cfg(not(feature = "__testing"))]
+#[test]
+fn children_closing_on_many_threads() {
+    let server = MockServer::start();
+
+    let dispatch =
+        tracing::Dispatch::new(Registry::default().with(tracing_newrelic::layer(server.api())));
+
+    tracing::dispatcher::with_default(&dispatch, || {
+        let root = tracing::info_span!("root");
+
+        std::thread::scope(|scope| {
+            for thread in 0..16 {
+                let root = &root;
+                let dispatch = &dispatch;
+
+                scope.spawn(move || {
+                    tracing::dispatcher::with_default(dispatch, || {
+                        for n in 0..8 {
+                            let child = tracing::info_span!(parent: root, "child", thread, n);
+                            child.in_scope(|| {
+                                tracing::info_span!("grandchild").in_scope(|| {});
+                                tracing::info!("inside child");
+                            });
+                        }
+                    });
+                });
+            }
+        });
+    });
+
+    drop(dispatch);
+
+    let spans = server.spans();
+    assert_eq!(spans.len(), 1 + 16 * 8 * 2);
+    assert_eq!(server.logs().len(), 16 * 8);
+
+    let root_id = &spans[0]["id"];
+    assert_eq!(attribute(&spans[0], "name"), "root");
+
+    for child in spans
+        .iter()
+        .filter(|span| attribute(span, "name") == "child")
+    {
+        assert_eq!(attribute(child, "parent.id"), root_id);
+
+        let grandchildren = spans
+            .iter()
+            .filter(|span| attribute(span, "parent.id") == &child["id"])
+            .count();
+        assert_eq!(grandchildren, 1);
+    }
+}
+
+#[test]
+fn logs_keep_their_order() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api());
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        tracing::info_span!("root").in_scope(|| {
+            tracing::info!("first");
+            tracing::info_span!("child").in_scope(|| tracing::info!("second"));
+            tracing::info!("third");
+            tracing::info_span!("child").in_scope(|| tracing::info!("fourth"));
+        });
+    });
+
+    let messages = server
+        .logs()
+        .iter()
+        .map(|log| attribute(log, "message").clone())
+        .collect::<Vec<_>>();
+    assert_eq!(messages, ["first", "second", "third", "fourth"]);
+}
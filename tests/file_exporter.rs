@@ -0,0 +1,101 @@
+mod common;
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use common::MockServer;
+use tracing_newrelic::FileExporter;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn temp_path() -> PathBuf {
+    std::env::temp_dir().join(format!("tracing-newrelic-{}.ndjson", uuid::Uuid::new_v4()))
+}
+
+fn write_traces(path: &PathBuf, max_bytes: u64) {
+    let layer = tracing_newrelic::layer_with_exporter(FileExporter::open(path, max_bytes).unwrap());
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        for i in 0..2 {
+            let span = tracing::info_span!("stored", i);
+            let _span = span.enter();
+            tracing::info!("stored log");
+        }
+    });
+}
+
+fn replay(server: &MockServer, path: &PathBuf) -> usize {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(server.api().replay_file(path))
+        .unwrap()
+}
+
+#[test]
+fn round_trip() {
+    let server = MockServer::start();
+    let path = temp_path();
+
+    write_traces(&path, u64::MAX);
+
+    assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+    assert_eq!(replay(&server, &path), 2);
+
+    let spans = server.spans();
+    assert_eq!(spans.len(), 2);
+    assert!(spans
+        .iter()
+        .all(|span| span["attributes"]["name"] == "stored"));
+    assert_eq!(server.logs().len(), 2);
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn corrupted_lines_are_skipped() {
+    let server = MockServer::start();
+    let path = temp_path();
+
+    write_traces(&path, u64::MAX);
+
+    let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+    writeln!(file, "{{\"logs\": [").unwrap();
+
+    assert_eq!(replay(&server, &path), 2);
+    assert_eq!(server.spans().len(), 2);
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn invalid_utf8_lines_are_skipped() {
+    let server = MockServer::start();
+    let path = temp_path();
+
+    write_traces(&path, u64::MAX);
+
+    let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(b"{\"logs\": [\xff\xfe]}\n").unwrap();
+
+    assert_eq!(replay(&server, &path), 2);
+    assert_eq!(server.spans().len(), 2);
+    assert_eq!(server.logs().len(), 2);
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn rotate_by_size() {
+    let path = temp_path();
+    let rotated = PathBuf::from(format!("{}.1", path.display()));
+
+    write_traces(&path, 1);
+
+    assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 1);
+    assert_eq!(fs::read_to_string(&rotated).unwrap().lines().count(), 1);
+
+    fs::remove_file(path).unwrap();
+    fs::remove_file(rotated).unwrap();
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{MockResponse, MockServer};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn trace() {
+    let span = tracing::info_span!("root");
+    let _span = span.enter();
+    tracing::info!("flushed");
+}
+
+#[test]
+fn flush_timeout_drains_queues() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api());
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        trace();
+
+        assert!(server.requests().is_empty());
+        assert!(handle.flush_timeout(Duration::from_secs(5)));
+        assert_eq!(server.spans().len(), 1);
+        assert_eq!(server.logs().len(), 1);
+    });
+}
+
+#[test]
+fn flush_reports_failure() {
+    let server = MockServer::start_with(|_| MockResponse::status(403));
+
+    let layer = tracing_newrelic::layer(server.api());
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        trace();
+
+        assert!(!handle.flush_timeout(Duration::from_secs(5)));
+    });
+}
+
+#[test]
+fn flush_times_out() {
+    let server =
+        MockServer::start_with(|_| MockResponse::status(202).delay(Duration::from_secs(1)));
+
+    let layer = tracing_newrelic::layer(server.api());
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        trace();
+
+        assert!(!handle.flush_timeout(Duration::from_millis(100)));
+    });
+}
+
+#[tokio::test]
+async fn async_flush() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api());
+    let handle = layer.handle();
+
+    let _guard = tracing::subscriber::set_default(Registry::default().with(layer));
+
+    trace();
+
+    assert!(handle.flush().await);
+    assert_eq!(server.spans().len(), 1);
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use common::MockServer;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+#[test]
+fn logs_are_flushed_before_traces() {
+    let server = MockServer::start();
+
+    let api = server
+        .api()
+        .with_log_batch_size(1000)
+        .with_trace_batch_size(1000)
+        .with_log_flush_interval(Duration::from_millis(50))
+        .with_trace_flush_interval(Duration::from_secs(60));
+
+    let subscriber = Registry::default().with(tracing_newrelic::layer(api));
+
+    tracing::subscriber::with_default(subscriber, || {
+        {
+            let span = tracing::info_span!("root");
+            let _span = span.enter();
+            tracing::info!("urgent");
+        }
+
+        sleep(Duration::from_millis(300));
+
+        // logs are delivered on their own cadence, traces are still queued
+        assert_eq!(server.logs().len(), 1);
+        assert!(server.trace_requests().is_empty());
+    });
+
+    assert_eq!(server.spans().len(), 1);
+}
+
+#[test]
+fn independent_batch_sizes() {
+    let server = MockServer::start();
+
+    let api = server
+        .api()
+        .with_log_batch_size(1)
+        .with_trace_batch_size(1000);
+
+    let layer = tracing_newrelic::layer(api);
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        for _ in 0..3 {
+            let span = tracing::info_span!("root");
+            let _span = span.enter();
+            tracing::info!("log");
+        }
+
+        // give the worker a chance to send the logs
+        sleep(Duration::from_millis(300));
+
+        assert_eq!(server.log_requests().len(), 3);
+        assert!(server.trace_requests().is_empty());
+    });
+
+    // remaining traces are flushed on shutdown
+    assert_eq!(server.trace_requests().len(), 1);
+    assert_eq!(server.spans().len(), 3);
+}
@@ -0,0 +1,123 @@
+#![cfg(all(feature = "tonic", feature = "testing"))]
+
+use std::convert::Infallible;
+use std::future::{ready, Ready};
+use std::task::{Context, Poll};
+
+use http::{Request, Response};
+use tonic::body::{empty_body, BoxBody};
+use tonic::Status;
+use tower_layer::Layer;
+use tower_service::Service;
+use tracing_newrelic::grpc::GrpcSpanLayer;
+use tracing_newrelic::testing::{with_captured, Captured};
+use tracing_newrelic::{NewrSpan, Value};
+
+const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+/// Fails calls to `Fail`, succeeds otherwise
+struct Dummy;
+
+impl Service<Request<()>> for Dummy {
+    type Response = Response<BoxBody>;
+    type Error = Infallible;
+    type Future = Ready<Result<Response<BoxBody>, Infallible>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<()>) -> Self::Future {
+        tracing::info!("handling");
+
+        let response = if request.uri().path().ends_with("/Fail") {
+            Status::internal("database is down").to_http()
+        } else {
+            Response::new(empty_body())
+        };
+
+        ready(Ok(response))
+    }
+}
+
+fn call(method: &str, traceparent: Option<&str>) -> Captured {
+    let mut request = Request::post(format!("/demo.Greeter/{}", method));
+
+    if let Some(traceparent) = traceparent {
+        request = request.header("traceparent", traceparent);
+    }
+
+    let request = request.body(()).unwrap();
+
+    with_captured(|| {
+        let response =
+            futures_util::FutureExt::now_or_never(GrpcSpanLayer::new().layer(Dummy).call(request));
+        assert!(response.is_some());
+    })
+}
+
+fn span(captured: &Captured) -> &NewrSpan {
+    captured.spans()[0]
+}
+
+#[test]
+fn records_successful_calls() {
+    let captured = call("SayHello", None);
+    let attributes = &span(&captured).attributes;
+
+    assert_eq!(captured.spans().len(), 1);
+    assert_eq!(captured.logs().len(), 1);
+    assert_eq!(
+        attributes.get("name"),
+        Some(&Value::from("demo.Greeter/SayHello"))
+    );
+    assert_eq!(attributes.get("span.kind"), Some(&Value::from("server")));
+    assert_eq!(attributes.get("rpc.system"), Some(&Value::from("grpc")));
+    assert_eq!(
+        attributes.get("rpc.service"),
+        Some(&Value::from("demo.Greeter"))
+    );
+    assert_eq!(attributes.get("rpc.method"), Some(&Value::from("SayHello")));
+    assert_eq!(
+        attributes.get("rpc.grpc.status_code"),
+        Some(&Value::from(0i64))
+    );
+    assert_eq!(attributes.get("otel.status_code"), None);
+    assert_eq!(attributes.get("parent.id"), None);
+}
+
+#[test]
+fn fails_internal_errors() {
+    let captured = call("Fail", None);
+    let attributes = &span(&captured).attributes;
+
+    assert_eq!(attributes.get("rpc.method"), Some(&Value::from("Fail")));
+    assert_eq!(
+        attributes.get("rpc.grpc.status_code"),
+        Some(&Value::from(13i64))
+    );
+    assert_eq!(
+        attributes.get("otel.status_code"),
+        Some(&Value::from("ERROR"))
+    );
+    assert_eq!(
+        attributes.get("otel.status_description"),
+        Some(&Value::from("database is down"))
+    );
+}
+
+#[test]
+fn continues_remote_traces() {
+    let captured = call("SayHello", Some(TRACEPARENT));
+    let span = span(&captured);
+
+    assert_eq!(
+        span.trace_id.as_deref(),
+        Some("4bf92f3577b34da6a3ce929d0e0e4736")
+    );
+    assert_eq!(
+        span.attributes.get("parent.id"),
+        Some(&Value::from("00f067aa0ba902b7"))
+    );
+    assert_eq!(span.attributes.get("traceparent"), None);
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use common::MockServer;
+use tracing_newrelic::Api;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn run(server: &MockServer, api: Api) {
+    tracing::subscriber::with_default(
+        Registry::default().with(tracing_newrelic::layer(api)),
+        || {
+            let _span = tracing::info_span!("root").entered();
+            tracing::info!("inside root");
+        },
+    );
+
+    assert_eq!(server.trace_requests().len(), 1);
+    assert_eq!(server.log_requests().len(), 1);
+}
+
+#[test]
+fn custom_headers_on_both_endpoints() {
+    let server = MockServer::start();
+
+    run(
+        &server,
+        server
+            .api()
+            .with_header("X-Tenant-Id", "acme")
+            .with_header("X-Team", "observability"),
+    );
+
+    for request in server.requests() {
+        assert_eq!(request.headers["x-tenant-id"], "acme");
+        assert_eq!(request.headers["x-team"], "observability");
+    }
+}
+
+#[test]
+fn default_user_agent() {
+    let server = MockServer::start();
+
+    run(&server, server.api());
+
+    let expected = format!("tracing-newrelic/{}", env!("CARGO_PKG_VERSION"));
+
+    for request in server.requests() {
+        assert_eq!(request.headers["user-agent"], expected.as_str());
+    }
+}
+
+#[test]
+fn custom_user_agent() {
+    let server = MockServer::start();
+
+    run(
+        &server,
+        server.api().with_header("User-Agent", "my-service/1.0"),
+    );
+
+    for request in server.requests() {
+        assert_eq!(request.headers["user-agent"], "my-service/1.0");
+    }
+}
+
+#[test]
+fn required_headers_cannot_be_overridden() {
+    let server = MockServer::start();
+
+    run(
+        &server,
+        server
+            .api()
+            .with_header("Api-Key", "other")
+            .with_header("content-type", "text/plain")
+            .with_header("Data-Format", "zipkin")
+            .with_header("X-Invalid", "line\nbreak"),
+    );
+
+    for request in server.requests() {
+        assert_eq!(request.headers["api-key"], "key");
+        assert_eq!(request.headers["content-type"], "application/json");
+        assert!(!request.headers.contains_key("x-invalid"));
+    }
+
+    for request in server.trace_requests() {
+        assert_eq!(request.headers["data-format"], "newrelic");
+    }
+}
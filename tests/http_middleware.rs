@@ -0,0 +1,118 @@
+#![cfg(all(feature = "tower", feature = "testing"))]
+
+use std::convert::Infallible;
+use std::future::{ready, Ready};
+use std::task::{Context, Poll};
+
+use http::{Request, Response, StatusCode};
+use tower_layer::Layer;
+use tower_service::Service;
+use tracing_newrelic::http::RequestSpanLayer;
+use tracing_newrelic::testing::{with_captured, Captured};
+use tracing_newrelic::Value;
+
+/// Route template put in the extensions by a router
+#[derive(Clone)]
+struct MatchedRoute(&'static str);
+
+/// Replies with the status of the path, e.g. `/status/503`
+struct Dummy;
+
+impl Service<Request<()>> for Dummy {
+    type Response = Response<()>;
+    type Error = Infallible;
+    type Future = Ready<Result<Response<()>, Infallible>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<()>) -> Self::Future {
+        tracing::info!("handling");
+
+        let status = request.uri().path().rsplit('/').next().unwrap();
+        let status = StatusCode::from_bytes(status.as_bytes()).unwrap();
+
+        ready(Ok(Response::builder().status(status).body(()).unwrap()))
+    }
+}
+
+fn serve(path: &str) -> Captured {
+    let layer = RequestSpanLayer::new().with_route(|extensions| {
+        extensions
+            .get::<MatchedRoute>()
+            .map(|route| route.0.to_string())
+    });
+
+    let mut request = Request::get(path).body(()).unwrap();
+    request
+        .extensions_mut()
+        .insert(MatchedRoute("/status/:code"));
+
+    with_captured(|| {
+        let response = futures_util::FutureExt::now_or_never(layer.layer(Dummy).call(request));
+        assert!(response.is_some());
+    })
+}
+
+fn attribute<'a>(captured: &'a Captured, key: &str) -> Option<&'a Value> {
+    captured.spans()[0].attributes.get(key)
+}
+
+#[test]
+fn records_request_spans() {
+    let captured = serve("/status/200");
+
+    assert_eq!(captured.spans().len(), 1);
+    assert_eq!(captured.logs().len(), 1);
+    assert_eq!(
+        attribute(&captured, "name"),
+        Some(&Value::from("GET /status/:code"))
+    );
+    assert_eq!(
+        attribute(&captured, "span.kind"),
+        Some(&Value::from("server"))
+    );
+    assert_eq!(
+        attribute(&captured, "http.method"),
+        Some(&Value::from("GET"))
+    );
+    assert_eq!(
+        attribute(&captured, "http.route"),
+        Some(&Value::from("/status/:code"))
+    );
+    assert_eq!(
+        attribute(&captured, "http.status_code"),
+        Some(&Value::from(200u64))
+    );
+    assert_eq!(attribute(&captured, "otel.status_code"), None);
+}
+
+#[test]
+fn fails_server_errors() {
+    let captured = serve("/status/503");
+
+    assert_eq!(
+        attribute(&captured, "http.status_code"),
+        Some(&Value::from(503u64))
+    );
+    assert_eq!(
+        attribute(&captured, "otel.status_code"),
+        Some(&Value::from("ERROR"))
+    );
+    assert_eq!(
+        attribute(&captured, "otel.status_description"),
+        Some(&Value::from("Service Unavailable"))
+    );
+}
+
+#[test]
+fn client_errors_are_not_failures() {
+    let captured = serve("/status/404");
+
+    assert_eq!(
+        attribute(&captured, "http.status_code"),
+        Some(&Value::from(404u64))
+    );
+    assert_eq!(attribute(&captured, "otel.status_code"), None);
+}
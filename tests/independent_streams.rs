@@ -0,0 +1,76 @@
+mod common;
+
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use common::{MockResponse, MockServer};
+use tracing_newrelic::RetryPolicy;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+#[test]
+fn logs_keep_flowing_while_traces_are_rate_limited() {
+    let server = MockServer::start_with(|request| {
+        if request.path.contains("trace") {
+            MockResponse::status(429).header("retry-after", "1")
+        } else {
+            MockResponse::status(202)
+        }
+    });
+
+    let api = server
+        .api()
+        .with_log_batch_size(1)
+        .with_trace_batch_size(1)
+        .with_retry_policy(RetryPolicy {
+            max_attempts: 2,
+            ..RetryPolicy::default()
+        });
+
+    let layer = tracing_newrelic::layer(api);
+    let handle = layer.handle();
+
+    let start = Instant::now();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        for n in 1..=3 {
+            let _span = tracing::info_span!("root").entered();
+            tracing::info!(n, "inside root");
+            drop(_span);
+
+            sleep(Duration::from_millis(100));
+
+            // the trace endpoint is waiting for its retry-after
+            assert_eq!(server.logs().len(), n);
+            assert!(start.elapsed() < Duration::from_secs(1));
+        }
+    });
+
+    let stats = handle.stats().snapshot();
+
+    assert_eq!(stats.logs_sent, 3);
+    assert_eq!(stats.spans_sent, 0);
+}
+
+#[test]
+fn a_failing_stream_does_not_keep_the_other_queued() {
+    let server = MockServer::start_with(|request| {
+        if request.path.contains("trace") {
+            MockResponse::status(403)
+        } else {
+            MockResponse::status(202)
+        }
+    });
+
+    let layer = tracing_newrelic::layer(server.api().with_log_batch_size(1));
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let _span = tracing::info_span!("root").entered();
+        tracing::info!("inside root");
+    });
+
+    let stats = handle.stats().snapshot();
+
+    assert_eq!(stats.logs_sent, 1);
+    assert_eq!(stats.payloads_dropped, 1);
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use std::collections::HashMap;
+
+use common::MockServer;
+use tracing_newrelic::Budget;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+const BUDGET: Budget = Budget {
+    spans_per_minute: 50,
+    logs_per_minute: 1_000,
+};
+
+fn trace() {
+    tracing::info_span!("root").in_scope(|| {
+        tracing::info_span!("child").in_scope(|| tracing::info!("inside"));
+    });
+}
+
+#[test]
+fn burst_over_budget() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api()).with_ingest_budget(BUDGET);
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        for _ in 0..200 {
+            trace();
+        }
+    });
+
+    let spans = server.spans();
+
+    // a tenth of the budget is kept for errors, plus the little refilled meanwhile
+    assert!(spans.len() >= 40, "{} spans exported", spans.len());
+    assert!(spans.len() <= 46, "{} spans exported", spans.len());
+
+    // whole traces are dropped
+    let mut traces = HashMap::new();
+
+    for span in &spans {
+        *traces.entry(span["trace.id"].to_string()).or_insert(0) += 1;
+    }
+
+    assert!(traces.values().all(|spans| *spans == 2));
+    assert_eq!(server.logs().len(), traces.len());
+
+    let stats = handle.stats().snapshot();
+    assert_eq!(stats.budget_drops as usize, 200 - traces.len());
+}
+
+#[test]
+fn errors_use_the_reserve() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api()).with_ingest_budget(BUDGET);
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        for _ in 0..200 {
+            trace();
+        }
+
+        tracing::info_span!("failed", otel.status_code = "ERROR").in_scope(|| {});
+    });
+
+    let spans = server.spans();
+    assert!(spans.len() <= 51, "{} spans exported", spans.len());
+    assert!(spans
+        .iter()
+        .any(|span| span["attributes"]["name"] == "failed"));
+}
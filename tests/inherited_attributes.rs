@@ -0,0 +1,105 @@
+mod common;
+
+use common::MockServer;
+use serde_json::Value;
+use tracing_newrelic::NewRelicLayer;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn spans(layer: impl FnOnce(&MockServer) -> NewRelicLayer, f: impl FnOnce()) -> Vec<Value> {
+    let server = MockServer::start();
+
+    tracing::subscriber::with_default(Registry::default().with(layer(&server)), f);
+
+    server.spans()
+}
+
+fn inheriting(server: &MockServer) -> NewRelicLayer {
+    tracing_newrelic::layer(server.api()).with_inherited_attributes(&["customer.id", "request_id"])
+}
+
+fn attributes<'a>(spans: &'a [Value], name: &str) -> &'a Value {
+    &spans
+        .iter()
+        .find(|span| span["attributes"]["name"] == name)
+        .unwrap_or_else(|| panic!("no span named {}", name))["attributes"]
+}
+
+#[test]
+fn grandchildren_inherit_from_the_root() {
+    let spans = spans(inheriting, || {
+        let _root = tracing::info_span!("root", request_id = "abc", other = 1).entered();
+        let _child = tracing::info_span!("child").entered();
+        let _grandchild = tracing::info_span!("grandchild").entered();
+    });
+
+    let grandchild = attributes(&spans, "grandchild");
+
+    assert_eq!(grandchild["request_id"], "abc");
+    assert_eq!(grandchild.get("customer.id"), None);
+    assert_eq!(grandchild.get("other"), None);
+    assert_eq!(attributes(&spans, "child")["request_id"], "abc");
+}
+
+#[test]
+fn disabled_by_default() {
+    let spans = spans(
+        |server| tracing_newrelic::layer(server.api()),
+        || {
+            let _root = tracing::info_span!("root", request_id = "abc").entered();
+            let _child = tracing::info_span!("child").entered();
+        },
+    );
+
+    assert_eq!(attributes(&spans, "child").get("request_id"), None);
+}
+
+#[test]
+fn explicit_values_are_kept() {
+    let spans = spans(inheriting, || {
+        let _root = tracing::info_span!("root", request_id = "abc").entered();
+        let _child = tracing::info_span!("child", request_id = "def").entered();
+        let _grandchild = tracing::info_span!("grandchild").entered();
+    });
+
+    assert_eq!(attributes(&spans, "child")["request_id"], "def");
+    assert_eq!(attributes(&spans, "grandchild")["request_id"], "def");
+}
+
+#[test]
+fn values_recorded_later_reach_open_children() {
+    let spans = spans(inheriting, || {
+        let root = tracing::info_span!("root", request_id = tracing::field::Empty);
+        let _root = root.clone().entered();
+
+        tracing::info_span!("closed").in_scope(|| {});
+
+        let _child = tracing::info_span!("child").entered();
+        let _grandchild = tracing::info_span!("grandchild").entered();
+
+        root.record("request_id", "abc");
+    });
+
+    assert_eq!(attributes(&spans, "root")["request_id"], "abc");
+    assert_eq!(attributes(&spans, "child")["request_id"], "abc");
+    assert_eq!(attributes(&spans, "grandchild")["request_id"], "abc");
+    assert_eq!(attributes(&spans, "closed").get("request_id"), None);
+}
+
+#[test]
+fn values_recorded_later_do_not_overwrite_explicit_ones() {
+    let spans = spans(inheriting, || {
+        let root = tracing::info_span!("root", customer.id = tracing::field::Empty);
+        let _root = root.clone().entered();
+
+        let child = tracing::info_span!("child", customer.id = tracing::field::Empty);
+        let _child = child.clone().entered();
+        let _grandchild = tracing::info_span!("grandchild").entered();
+
+        child.record("customer.id", 7);
+        root.record("customer.id", 1);
+    });
+
+    assert_eq!(attributes(&spans, "root")["customer.id"], 1);
+    assert_eq!(attributes(&spans, "child")["customer.id"], 7);
+    assert_eq!(attributes(&spans, "grandchild")["customer.id"], 7);
+}
@@ -0,0 +1,99 @@
+mod common;
+
+use anyhow::{anyhow, Context};
+use common::MockServer;
+use serde_json::Value;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+#[tracing::instrument(err)]
+fn charge(amount: u64) -> anyhow::Result<()> {
+    Err(anyhow!("card declined"))
+}
+
+fn run(f: impl FnOnce()) -> MockServer {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api());
+
+    tracing::subscriber::with_default(Registry::default().with(layer), f);
+
+    server
+}
+
+fn span<'a>(spans: &'a [Value], name: &str) -> &'a Value {
+    &spans
+        .iter()
+        .find(|span| span["attributes"]["name"] == name)
+        .unwrap_or_else(|| panic!("no span named {}", name))["attributes"]
+}
+
+#[test]
+fn instrumented_errors_fail_the_span() {
+    let server = run(|| {
+        let _root = tracing::info_span!("root").entered();
+
+        assert!(charge(42).is_err());
+    });
+
+    let spans = server.spans();
+
+    let charge = span(&spans, "charge");
+    assert_eq!(charge["otel.status_code"], "ERROR");
+    assert_eq!(charge["otel.status_description"], "card declined");
+    assert_eq!(charge["error.message"], "card declined");
+
+    // the parent didn't fail itself
+    assert_eq!(span(&spans, "root").get("otel.status_code"), None);
+
+    let logs = server.logs();
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0]["level"], "ERROR");
+    assert_eq!(logs[0]["attributes"]["error"], "card declined");
+    assert_eq!(logs[0]["attributes"]["error.message"], "card declined");
+}
+
+#[test]
+fn error_sources_are_recorded_as_the_cause() {
+    let server = run(|| {
+        let _root = tracing::info_span!("root").entered();
+
+        let err = Err::<(), _>(anyhow!("connection reset"))
+            .context("query failed")
+            .context("request failed")
+            .unwrap_err();
+
+        tracing::error!(error = err.as_ref() as &(dyn std::error::Error + 'static));
+    });
+
+    let logs = server.logs();
+    let log = &logs[0]["attributes"];
+
+    assert_eq!(log["error.message"], "request failed");
+    assert_eq!(log["error.cause"], "query failed: connection reset");
+
+    let spans = server.spans();
+    assert_eq!(
+        span(&spans, "root")["otel.status_description"],
+        "request failed"
+    );
+}
+
+#[test]
+fn explicit_status_is_kept() {
+    let server = run(|| {
+        let span = tracing::info_span!(
+            "root",
+            otel.status_code = "OK",
+            error = tracing::field::Empty
+        );
+
+        span.record("error", "ignored");
+    });
+
+    let spans = server.spans();
+    let root = span(&spans, "root");
+
+    assert_eq!(root["otel.status_code"], "OK");
+    assert_eq!(root["otel.status_description"], "ignored");
+    assert_eq!(root["error.message"], "ignored");
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use common::MockServer;
+use serde::Serialize;
+use serde_json::json;
+use tracing_newrelic::Json;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+#[derive(Serialize)]
+struct Summary {
+    status: u16,
+    user: User,
+}
+
+#[derive(Serialize)]
+struct User {
+    id: u64,
+    roles: Vec<&'static str>,
+}
+
+#[test]
+fn structured_values() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api()).with_span_processor(|span| {
+        span.attributes
+            .insert("inserted", json!({ "nested": [true] }));
+        true
+    });
+
+    let summary = Summary {
+        status: 200,
+        user: User {
+            id: 7,
+            roles: vec!["admin", "dev"],
+        },
+    };
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        tracing::info_span!("root", items.json = "[1, 2.5, \"three\", null]").in_scope(|| {
+            tracing::info!(response.json = %Json(&summary), "responded");
+        });
+    });
+
+    let spans = server.spans();
+    assert_eq!(
+        spans[0]["attributes"]["items"],
+        json!([1, 2.5, "three", null])
+    );
+    assert_eq!(
+        spans[0]["attributes"]["inserted"],
+        json!({ "nested": [true] })
+    );
+
+    let logs = server.logs();
+    assert_eq!(
+        logs[0]["attributes"]["response"],
+        json!({ "status": 200, "user": { "id": 7, "roles": ["admin", "dev"] } })
+    );
+    assert!(logs[0]["attributes"].get("response.json").is_none());
+}
+
+#[test]
+fn kept_as_strings_otherwise() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api());
+
+    let deep = "[".repeat(20) + &"]".repeat(20);
+    let long = format!("[\"{}\"]", "a".repeat(5000));
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        tracing::info_span!("root").in_scope(|| {
+            tracing::info!(
+                invalid.json = "{ not json",
+                scalar.json = "42",
+                deep.json = deep.as_str(),
+                long.json = long.as_str(),
+                plain = "[1, 2]",
+            );
+        });
+    });
+
+    let logs = server.logs();
+    let attributes = &logs[0]["attributes"];
+
+    assert_eq!(attributes["invalid.json"], "{ not json");
+    assert_eq!(attributes["scalar.json"], "42");
+    assert_eq!(attributes["deep.json"], deep.as_str());
+    assert_eq!(attributes["long.json"], long.as_str());
+    assert_eq!(attributes["plain"], "[1, 2]");
+}
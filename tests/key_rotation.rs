@@ -0,0 +1,94 @@
+mod common;
+
+use std::sync::{Arc, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use common::{MockResponse, MockServer, Request};
+use tracing_newrelic::{Api, ApiEndpoint, Handle, RetryPolicy};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+/// A server accepting only `new-key`, replying with `status` otherwise
+fn server(status: u16) -> MockServer {
+    MockServer::start_with(move |request| match request.headers.get("api-key") {
+        Some(key) if key == "new-key" => MockResponse::status(202),
+        _ => MockResponse::status(status),
+    })
+}
+
+fn api(server: &MockServer) -> Api {
+    Api::from(("old-key".to_string(), ApiEndpoint::Custom(server.url())))
+        .with_trace_batch_size(1)
+        .with_retry_policy(RetryPolicy {
+            max_attempts: 100,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_millis(50),
+            jitter: false,
+        })
+}
+
+fn accepted(server: &MockServer) -> Vec<Request> {
+    server
+        .trace_requests()
+        .into_iter()
+        .filter(|request| request.headers["api-key"] == "new-key")
+        .collect()
+}
+
+#[test]
+fn rotates_from_the_error_handler() {
+    let server = server(403);
+
+    let rotate = Arc::new(OnceLock::<Handle>::new());
+
+    let api = api(&server).with_error_handler({
+        let rotate = rotate.clone();
+        move |err| {
+            if err.status == Some(403) {
+                rotate.get().unwrap().set_api_key("new-key");
+            }
+        }
+    });
+
+    let layer = tracing_newrelic::layer(api);
+    let handle = layer.handle();
+    let _ = rotate.set(handle.clone());
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        tracing::info_span!("rejected").in_scope(|| {});
+        assert!(!handle.flush_timeout(Duration::from_secs(5)));
+
+        tracing::info_span!("accepted").in_scope(|| {});
+    });
+
+    let requests = accepted(&server);
+    assert_eq!(requests.len(), 1);
+    assert_eq!(
+        requests[0].body[0]["spans"][0]["attributes"]["name"],
+        "accepted"
+    );
+}
+
+#[test]
+fn retries_with_the_rotated_key() {
+    let server = server(503);
+
+    let layer = tracing_newrelic::layer(api(&server));
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        tracing::info_span!("root").in_scope(|| {});
+
+        let start = Instant::now();
+
+        while server.trace_requests().is_empty() {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        handle.set_api_key(" new-key ");
+    });
+
+    assert_eq!(server.trace_requests()[0].headers["api-key"], "old-key");
+    assert_eq!(accepted(&server).len(), 1);
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use common::MockServer;
+use serde_json::Value;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+// 300 characters
+const LONG_KEY: &str = concat!(
+    "kkkkkkkkkkkkkkkkkkkkkkkkkkkkkk",
+    "kkkkkkkkkkkkkkkkkkkkkkkkkkkkkk",
+    "kkkkkkkkkkkkkkkkkkkkkkkkkkkkkk",
+    "kkkkkkkkkkkkkkkkkkkkkkkkkkkkkk",
+    "kkkkkkkkkkkkkkkkkkkkkkkkkkkkkk",
+    "kkkkkkkkkkkkkkkkkkkkkkkkkkkkkk",
+    "kkkkkkkkkkkkkkkkkkkkkkkkkkkkkk",
+    "kkkkkkkkkkkkkkkkkkkkkkkkkkkkkk",
+    "kkkkkkkkkkkkkkkkkkkkkkkkkkkkkk",
+    "kkkkkkkkkkkkkkkkkkkkkkkkkkkkkk"
+);
+
+fn keys(attributes: &Value) -> Vec<&str> {
+    attributes
+        .as_object()
+        .unwrap()
+        .keys()
+        .map(|key| key.as_str())
+        .collect()
+}
+
+fn trace() {
+    tracing::info_span!(
+        "root",
+        { LONG_KEY } = 1,
+        "cart size" = 2,
+        "user 🚀" = "emoji",
+    )
+    .in_scope(|| tracing::info!("some key" = "value", "inside"));
+}
+
+#[test]
+fn sanitizes_keys() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api());
+
+    tracing::subscriber::with_default(Registry::default().with(layer), trace);
+
+    let spans = server.spans();
+    let keys = keys(&spans[0]["attributes"]);
+
+    assert!(keys.contains(&"k".repeat(255).as_str()));
+    assert!(keys.contains(&"cart_size"));
+    assert!(keys.contains(&"user__"));
+    assert!(keys.iter().all(|key| key.len() <= 255));
+
+    assert_eq!(server.logs()[0]["attributes"]["some_key"], "value");
+}
+
+#[test]
+fn truncates_keys_without_sanitization() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api()).with_key_sanitization(false);
+
+    tracing::subscriber::with_default(Registry::default().with(layer), trace);
+
+    let spans = server.spans();
+    let keys = keys(&spans[0]["attributes"]);
+
+    assert!(keys.contains(&"k".repeat(255).as_str()));
+    assert!(keys.contains(&"cart size"));
+    assert!(keys.contains(&"user 🚀"));
+}
+
+#[test]
+fn last_sanitized_key_wins() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api());
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        tracing::info_span!("root", cart_size = 1, "cart size" = 2).in_scope(|| {});
+    });
+
+    assert_eq!(server.spans()[0]["attributes"]["cart_size"], 2);
+}
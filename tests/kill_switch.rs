@@ -0,0 +1,78 @@
+mod common;
+
+use std::time::Duration;
+
+use common::MockServer;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn names(server: &MockServer) -> Vec<String> {
+    server
+        .spans()
+        .iter()
+        .map(|span| span["attributes"]["name"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn disabled_mid_trace() {
+    let server = MockServer::start();
+    let layer = tracing_newrelic::layer(server.api());
+    let handle = layer.handle();
+
+    assert!(handle.is_enabled());
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        tracing::info_span!("first").in_scope(|| tracing::info!("first"));
+
+        tracing::info_span!("second").in_scope(|| {
+            tracing::info_span!("second.child").in_scope(|| {});
+
+            handle.set_enabled(false);
+
+            tracing::info_span!("ignored").in_scope(|| tracing::info!("ignored"));
+        });
+
+        assert!(!handle.is_enabled());
+
+        tracing::info_span!("third").in_scope(|| {});
+
+        handle.set_enabled(true);
+
+        tracing::info_span!("fourth").in_scope(|| {
+            tracing::info_span!("fourth.child").in_scope(|| tracing::info!("fourth"));
+        });
+
+        assert!(handle.flush_timeout(Duration::from_secs(5)));
+    });
+
+    assert_eq!(names(&server), ["first", "fourth", "fourth.child"]);
+
+    let messages: Vec<_> = server
+        .logs()
+        .iter()
+        .map(|log| log["attributes"]["message"].clone())
+        .collect();
+
+    assert_eq!(messages, ["first", "fourth"]);
+}
+
+#[test]
+fn spans_opened_while_disabled_are_dropped() {
+    let server = MockServer::start();
+    let layer = tracing_newrelic::layer(server.api());
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        handle.set_enabled(false);
+
+        let root = tracing::info_span!("root").entered();
+
+        handle.set_enabled(true);
+
+        tracing::info_span!("child").in_scope(|| {});
+
+        drop(root);
+    });
+
+    assert!(server.trace_requests().is_empty());
+}
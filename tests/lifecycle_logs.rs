@@ -0,0 +1,114 @@
+#![cfg(feature = "testing")]
+
+use tracing::level_filters::LevelFilter;
+use tracing_newrelic::testing::{with_captured_layer, Captured};
+use tracing_newrelic::{NewrLog, Value};
+
+fn run(level: LevelFilter) -> Captured {
+    with_captured_layer(
+        |layer| layer.with_span_lifecycle_logs(level),
+        || {
+            let _root = tracing::info_span!("checkout").entered();
+
+            let _child = tracing::debug_span!("charge").entered();
+
+            tracing::info!("charged");
+        },
+    )
+}
+
+fn message(log: &NewrLog) -> &str {
+    match log.attributes.get("message") {
+        Some(Value::String(message)) => message,
+        _ => "",
+    }
+}
+
+#[test]
+fn logs_span_lifecycle() {
+    let captured = run(LevelFilter::DEBUG);
+
+    for span in captured.spans() {
+        let lifecycle: Vec<&NewrLog> = captured
+            .logs()
+            .into_iter()
+            .filter(|log| log.attributes.get("span.name").is_some())
+            .filter(|log| log.attributes.get("span.id") == Some(&Value::from(span.id.as_str())))
+            .collect();
+
+        assert_eq!(lifecycle.len(), 2);
+
+        let name = match span.attributes.get("name") {
+            Some(Value::String(name)) => name.as_str(),
+            _ => unreachable!(),
+        };
+
+        let (started, closed) = (lifecycle[0], lifecycle[1]);
+
+        assert_eq!(message(started), format!("span started: {}", name));
+        assert!(message(closed).starts_with(&format!("span closed: {}, ", name)));
+        assert!(message(closed).ends_with("ms"));
+
+        for log in [started, closed].iter() {
+            assert_eq!(log.attributes.get("span.name"), Some(&Value::from(name)));
+            assert_eq!(
+                log.attributes.get("trace.id"),
+                Some(&Value::from(span.trace_id.as_deref().unwrap()))
+            );
+        }
+
+        assert_eq!(started.attributes.get("duration.ms"), None);
+        assert!(matches!(
+            closed.attributes.get("duration.ms"),
+            Some(Value::F64(ms)) if *ms >= 0.0
+        ));
+        assert!(started.timestamp <= closed.timestamp);
+    }
+
+    // alongside the logs of events, at the level of the span
+    let charge = captured.span("charge").unwrap();
+    let charge_logs: Vec<&NewrLog> = captured
+        .logs()
+        .into_iter()
+        .filter(|log| log.attributes.get("span.id") == Some(&Value::from(charge.id.as_str())))
+        .collect();
+
+    let messages: Vec<&str> = charge_logs.iter().map(|log| message(log)).collect();
+    assert_eq!(messages.len(), 3);
+    assert_eq!(messages[0], "span started: charge");
+    assert_eq!(messages[1], "charged");
+
+    let levels: Vec<&str> = charge_logs.iter().map(|log| log.level).collect();
+    assert_eq!(levels, ["DEBUG", "INFO", "DEBUG"]);
+}
+
+#[test]
+fn filters_spans_by_level() {
+    let captured = run(LevelFilter::INFO);
+    let checkout = captured.span("checkout").unwrap();
+    let charge = captured.span("charge").unwrap();
+
+    let lifecycle = |id: &str| {
+        captured
+            .logs()
+            .into_iter()
+            .filter(|log| log.attributes.get("span.name").is_some())
+            .filter(|log| log.attributes.get("span.id") == Some(&Value::from(id)))
+            .count()
+    };
+
+    assert_eq!(lifecycle(&checkout.id), 2);
+    assert_eq!(lifecycle(&charge.id), 0);
+}
+
+#[test]
+fn disabled_by_default() {
+    let captured = with_captured_layer(
+        |layer| layer,
+        || {
+            let _root = tracing::info_span!("checkout").entered();
+        },
+    );
+
+    assert!(captured.logs().is_empty());
+}
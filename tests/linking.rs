@@ -0,0 +1,208 @@
+#![cfg(feature = "testing")]
+
+use std::sync::{Arc, Mutex};
+
+use tracing::{Event, Subscriber};
+use tracing_newrelic::testing::{with_captured_layer, CapturingExporter};
+use tracing_newrelic::{linking_metadata, LinkingMetadata, NewrAttributes, Value};
+use tracing_subscriber::filter::filter_fn;
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::{LookupSpan, Registry};
+use tracing_subscriber::reload;
+
+#[test]
+fn matches_exported_spans() {
+    let mut root_linking = None;
+    let mut child_linking = None;
+
+    let captured = with_captured_layer(
+        |layer| layer.with_entity(Some("MXxBUE18QVBQTElDQVRJT058MTIz".to_string()), None, None),
+        || {
+            let _root = tracing::info_span!("root", hostname = "web-1").entered();
+            root_linking = linking_metadata();
+
+            let _child = tracing::info_span!("child").entered();
+            child_linking = linking_metadata();
+        },
+    );
+
+    let root = captured.span("root").unwrap();
+    let child = captured.span("child").unwrap();
+    let (root_linking, child_linking) = (root_linking.unwrap(), child_linking.unwrap());
+
+    assert_eq!(root_linking.span_id, root.id);
+    assert_eq!(child_linking.span_id, child.id);
+
+    for linking in [&root_linking, &child_linking].iter() {
+        assert_eq!(Some(&linking.trace_id), child.trace_id.as_ref());
+        assert_eq!(
+            linking.entity_guid.as_deref(),
+            Some("MXxBUE18QVBQTElDQVRJT058MTIz")
+        );
+        assert_eq!(linking.hostname.as_deref(), Some("web-1"));
+    }
+
+    let (_, spans) = &captured.payloads[0];
+    assert_eq!(
+        spans.common.attributes().get("hostname"),
+        Some(&Value::from("web-1"))
+    );
+
+    assert_eq!(
+        serde_json::to_value(&child_linking).unwrap(),
+        serde_json::json!({
+            "trace.id": child.trace_id,
+            "span.id": child.id,
+            "entity.guid": "MXxBUE18QVBQTElDQVRJT058MTIz",
+            "hostname": "web-1",
+        })
+    );
+}
+
+#[test]
+fn global_hostname() {
+    let mut global = NewrAttributes::default();
+    global.insert("hostname", "web-2");
+
+    let mut linking = None;
+
+    with_captured_layer(
+        |layer| layer.with_global_attributes(global),
+        || {
+            let _root = tracing::info_span!("root", hostname = "web-1").entered();
+            linking = linking_metadata();
+        },
+    );
+
+    let linking = linking.unwrap();
+    assert_eq!(linking.hostname.as_deref(), Some("web-2"));
+    assert_eq!(linking.entity_guid, None);
+}
+
+#[test]
+fn none_outside_of_spans() {
+    let mut linking = Some(None);
+
+    let captured = with_captured_layer(
+        |layer| layer.with_sampling_ratio(0.0),
+        || {
+            assert_eq!(linking_metadata(), None);
+
+            let _root = tracing::info_span!("root").entered();
+            linking = Some(linking_metadata());
+        },
+    );
+
+    assert!(captured.spans().is_empty());
+    assert_eq!(linking, Some(None));
+}
+
+/// Collects the linking metadata of the events, like a `fmt` formatter would
+#[derive(Clone, Default)]
+struct Collector(Arc<Mutex<Vec<Option<LinkingMetadata>>>>);
+
+impl<S> Layer<S> for Collector
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, _: &Event<'_>, ctx: Context<'_, S>) {
+        // not available from within the subscriber
+        assert_eq!(linking_metadata(), None);
+
+        let linking = ctx.lookup_current().as_ref().and_then(LinkingMetadata::of);
+        self.0.lock().unwrap().push(linking);
+    }
+}
+
+#[test]
+fn from_other_layers() {
+    let exporter = CapturingExporter::new();
+    let collector = Collector::default();
+
+    let subscriber = Registry::default()
+        .with(tracing_newrelic::layer_with_exporter(exporter.clone()).with_deterministic_ids())
+        .with(collector.clone());
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!("outside of spans");
+
+        let _root = tracing::info_span!("root").entered();
+
+        tracing::info!("in root");
+    });
+
+    let captured = exporter.captured();
+    let root = captured.span("root").unwrap();
+    let collected = collector.0.lock().unwrap();
+
+    assert_eq!(collected.len(), 2);
+    assert_eq!(collected[0], None);
+
+    let linking = collected[1].as_ref().unwrap();
+    assert_eq!(linking.span_id, root.id);
+    assert_eq!(linking.trace_id.as_str(), root.trace_id.as_deref().unwrap());
+}
+
+#[test]
+fn event_in_filtered_span_links_to_ancestor() {
+    let exporter = CapturingExporter::new();
+
+    let layer = tracing_newrelic::layer_with_exporter(exporter.clone())
+        .with_deterministic_ids()
+        .with_filter(filter_fn(|metadata| metadata.name() != "filtered"));
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let _root = tracing::info_span!("root").entered();
+        let _filtered = tracing::info_span!("filtered").entered();
+
+        tracing::info!("inside filtered span");
+    });
+
+    let captured = exporter.captured();
+    let root = captured.span("root").unwrap();
+    let logs = captured.logs();
+
+    assert_eq!(captured.spans().len(), 1);
+    assert_eq!(logs.len(), 1);
+    assert_eq!(
+        logs[0].attributes.get("span.id"),
+        Some(&Value::from(root.id.as_str()))
+    );
+}
+
+#[test]
+fn event_in_span_created_without_layer_links_to_ancestor() {
+    let exporter = CapturingExporter::new();
+    let layer = tracing_newrelic::layer_with_exporter(exporter.clone()).with_deterministic_ids();
+
+    let (layer, handle) = reload::Layer::new(Some(layer));
+    let mut removed = None;
+
+    // another layer keeps the spans enabled while ours is swapped out
+    let subscriber = Registry::default().with(layer).with(Collector::default());
+
+    tracing::subscriber::with_default(subscriber, || {
+        let _grandparent = tracing::info_span!("grandparent").entered();
+
+        // spans created while the layer is swapped out don't get a `NewrSpan`
+        handle.modify(|layer| removed = layer.take()).unwrap();
+
+        let _parent = tracing::info_span!("parent").entered();
+        let _current = tracing::info_span!("current").entered();
+
+        handle.modify(|layer| *layer = removed.take()).unwrap();
+
+        tracing::info!("inside span created without the layer");
+    });
+
+    let captured = exporter.captured();
+    let grandparent = captured.span("grandparent").unwrap();
+    let logs = captured.logs();
+
+    assert_eq!(captured.spans().len(), 1);
+    assert_eq!(logs.len(), 1);
+    assert_eq!(
+        logs[0].attributes.get("span.id"),
+        Some(&Value::from(grandparent.id.as_str()))
+    );
+}
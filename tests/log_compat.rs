@@ -0,0 +1,62 @@
+#![cfg(all(feature = "log-compat", feature = "testing"))]
+
+use tracing_log::LogTracer;
+use tracing_newrelic::testing::with_captured_layer;
+use tracing_newrelic::Value;
+
+mod billing {
+    pub mod invoices {
+        pub fn create() -> u32 {
+            log::warn!("invoice without a due date");
+            line!() - 1
+        }
+    }
+}
+
+#[test]
+fn uses_metadata_of_log_records() {
+    // installed once for the whole test binary
+    let _ = LogTracer::init();
+
+    let mut line = 0;
+
+    let captured = with_captured_layer(
+        |layer| layer.with_source_attribute(true),
+        || {
+            let _root = tracing::info_span!("root").entered();
+
+            line = billing::invoices::create();
+        },
+    );
+
+    let logs = captured.logs();
+    assert_eq!(logs.len(), 1);
+
+    let log = &logs[0];
+    assert_eq!(log.level, "WARN");
+    assert_eq!(
+        log.attributes.get("logger.name"),
+        Some(&Value::from("log_compat::billing::invoices"))
+    );
+    assert_eq!(
+        log.attributes.get("code.namespace"),
+        Some(&Value::from("log_compat::billing::invoices"))
+    );
+    assert_eq!(
+        log.attributes.get("code.filepath"),
+        Some(&Value::from(file!()))
+    );
+    assert_eq!(
+        log.attributes.get("code.lineno"),
+        Some(&Value::from(line as u64))
+    );
+    assert_eq!(
+        log.attributes.get("source"),
+        Some(&Value::from(format!("{}:{}", file!(), line)))
+    );
+
+    // the fields of the shim aren't exported
+    for key in ["log.target", "log.module_path", "log.file", "log.line"].iter() {
+        assert_eq!(log.attributes.get(key), None);
+    }
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use common::MockServer;
+use serde_json::Value;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn attribute<'a>(log: &'a Value, key: &str) -> &'a Value {
+    &log["attributes"][key]
+}
+
+#[test]
+fn collapses_identical_logs() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api()).with_log_dedup(Some(Duration::from_secs(10)));
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        tracing::info_span!("root").in_scope(|| {
+            for _ in 0..1000 {
+                tracing::warn!(attempt = 1, "retrying");
+            }
+
+            tracing::warn!(attempt = 2, "retrying");
+            tracing::info!("succeeded");
+
+            for _ in 0..1000 {
+                tracing::warn!(attempt = 1, "retrying");
+            }
+        });
+    });
+
+    let logs = server.logs();
+    assert_eq!(logs.len(), 4);
+
+    assert_eq!(attribute(&logs[0], "attempt"), 1);
+    assert_eq!(attribute(&logs[0], "log.repeat_count"), 1000);
+
+    assert_eq!(attribute(&logs[1], "attempt"), 2);
+    assert!(attribute(&logs[1], "log.repeat_count").is_null());
+
+    assert_eq!(attribute(&logs[2], "message"), "succeeded");
+    assert!(attribute(&logs[2], "log.repeat_count").is_null());
+
+    // not consecutive to the first ones
+    assert_eq!(attribute(&logs[3], "log.repeat_count"), 1000);
+    assert!(logs[0]["timestamp"].as_u64() <= logs[1]["timestamp"].as_u64());
+}
+
+#[test]
+fn starts_again_after_the_window() {
+    let server = MockServer::start();
+
+    let layer =
+        tracing_newrelic::layer(server.api()).with_log_dedup(Some(Duration::from_millis(50)));
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        tracing::info_span!("root").in_scope(|| {
+            for n in 0..4 {
+                if n == 2 {
+                    sleep(Duration::from_millis(100));
+                }
+
+                tracing::warn!("retrying");
+            }
+        });
+    });
+
+    let logs = server.logs();
+    assert_eq!(logs.len(), 2);
+    assert!(logs
+        .iter()
+        .all(|log| attribute(log, "log.repeat_count") == 2));
+}
+
+#[test]
+fn keeps_every_log_by_default() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api());
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        tracing::info_span!("root").in_scope(|| {
+            for _ in 0..10 {
+                tracing::warn!("retrying");
+            }
+        });
+    });
+
+    assert_eq!(server.logs().len(), 10);
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use common::MockServer;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+#[test]
+fn traces_sharing_common_attributes_are_merged() {
+    let server = MockServer::start();
+
+    let api = server
+        .api()
+        .with_log_batch_size(50)
+        .with_trace_batch_size(50);
+
+    tracing::subscriber::with_default(
+        Registry::default().with(tracing_newrelic::layer(api)),
+        || {
+            for n in 0..50 {
+                let service = if n % 5 == 0 { "billing" } else { "shop" };
+                let _span = tracing::info_span!("root", service.name = service).entered();
+                tracing::info!(n, "inside root");
+            }
+        },
+    );
+
+    for request in server.requests() {
+        let payloads = request.body.as_array().unwrap();
+
+        // one payload per service, in order of appearance
+        assert_eq!(payloads.len(), 2, "{}", request.path);
+        assert_eq!(
+            payloads[0]["common"]["attributes"]["service.name"],
+            "billing"
+        );
+        assert_eq!(payloads[1]["common"]["attributes"]["service.name"], "shop");
+    }
+
+    assert_eq!(server.trace_requests().len(), 1);
+    assert_eq!(server.log_requests().len(), 1);
+    assert_eq!(server.spans().len(), 50);
+    assert_eq!(server.logs().len(), 50);
+
+    // logs keep their order within a payload
+    let logs = server.logs();
+    let shop: Vec<_> = logs[10..]
+        .iter()
+        .map(|log| log["attributes"]["n"].as_u64().unwrap())
+        .collect();
+
+    assert_eq!(shop, (0..50).filter(|n| n % 5 != 0).collect::<Vec<_>>());
+}
+
+#[test]
+fn merged_payloads_are_still_split() {
+    let server = MockServer::start();
+
+    let api = server
+        .api()
+        .with_trace_batch_size(50)
+        .with_max_payload_bytes(2_000);
+
+    tracing::subscriber::with_default(
+        Registry::default().with(tracing_newrelic::layer(api)),
+        || {
+            for _ in 0..50 {
+                let _span = tracing::info_span!("root").entered();
+            }
+        },
+    );
+
+    let requests = server.trace_requests();
+
+    assert!(requests.len() > 1);
+    assert_eq!(server.spans().len(), 50);
+
+    for request in &requests {
+        assert_eq!(request.body.as_array().unwrap().len(), 1);
+        assert!(request.body.to_string().len() <= 2_000);
+    }
+}
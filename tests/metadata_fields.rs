@@ -0,0 +1,88 @@
+mod common;
+
+use common::MockServer;
+use serde_json::Value;
+use tracing_newrelic::NewRelicLayer;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+mod billing {
+    pub mod invoices {
+        pub fn create() {
+            let _span = tracing::info_span!("create").entered();
+
+            tracing::info!("created");
+        }
+    }
+}
+
+fn run(layer: impl FnOnce(&MockServer) -> NewRelicLayer) -> (Value, Value) {
+    let server = MockServer::start();
+
+    tracing::subscriber::with_default(Registry::default().with(layer(&server)), || {
+        let _root = tracing::info_span!("root").entered();
+
+        billing::invoices::create();
+    });
+
+    let span = server
+        .spans()
+        .into_iter()
+        .find(|span| span["attributes"]["name"] == "create")
+        .unwrap();
+
+    (span, server.logs().remove(0))
+}
+
+#[test]
+fn spans_and_logs_record_their_metadata() {
+    let (span, log) = run(|server| tracing_newrelic::layer(server.api()));
+
+    let module = "metadata_fields::billing::invoices";
+
+    let span = &span["attributes"];
+    assert_eq!(span["target"], module);
+    assert_eq!(span["code.namespace"], module);
+    assert_eq!(span["code.filepath"], "tests/metadata_fields.rs");
+    assert_eq!(span["code.lineno"], 11);
+    assert_eq!(span.get("source"), None);
+
+    let log = &log["attributes"];
+    assert_eq!(log["logger.name"], module);
+    assert_eq!(log["code.namespace"], module);
+    assert_eq!(log["code.filepath"], "tests/metadata_fields.rs");
+    assert_eq!(log["code.lineno"], 13);
+    assert_eq!(log.get("target"), None);
+    assert_eq!(log.get("source"), None);
+}
+
+#[test]
+fn disabled() {
+    let (span, log) =
+        run(|server| tracing_newrelic::layer(server.api()).with_metadata_fields(false));
+
+    for attributes in [&span["attributes"], &log["attributes"]] {
+        for key in [
+            "target",
+            "logger.name",
+            "code.namespace",
+            "code.filepath",
+            "code.lineno",
+            "source",
+        ] {
+            assert_eq!(attributes.get(key), None, "{}", key);
+        }
+    }
+}
+
+#[test]
+fn source_attribute_kept_for_compatibility() {
+    let (span, log) = run(|server| {
+        tracing_newrelic::layer(server.api())
+            .with_metadata_fields(false)
+            .with_source_attribute(true)
+    });
+
+    assert_eq!(span["attributes"]["source"], "tests/metadata_fields.rs:11");
+    assert_eq!(log["attributes"]["source"], "tests/metadata_fields.rs:13");
+    assert_eq!(span["attributes"].get("code.lineno"), None);
+}
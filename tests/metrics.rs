@@ -0,0 +1,139 @@
+mod common;
+
+use std::time::Duration;
+
+use common::MockServer;
+use serde_json::{json, Value};
+use tracing_newrelic::{ExportPolicy, NewRelicLayer};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn request(path: &str) {
+    let _span = tracing::info_span!("request", name = path, span.kind = "server").entered();
+
+    tracing::info_span!("query").in_scope(|| {});
+}
+
+fn run(layer: NewRelicLayer) {
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        request("/users");
+        request("/users");
+        request("/orders");
+
+        assert!(handle.flush_timeout(Duration::from_secs(5)));
+    });
+}
+
+fn summary<'a>(metrics: &'a [Value], name: &str) -> &'a Value {
+    metrics
+        .iter()
+        .find(|metric| metric["attributes"]["span.name"] == name)
+        .unwrap_or_else(|| panic!("no summary of {}", name))
+}
+
+#[test]
+fn disabled_by_default() {
+    let server = MockServer::start();
+
+    run(tracing_newrelic::layer(server.api()));
+
+    assert!(server.metric_requests().is_empty());
+}
+
+#[test]
+fn summarizes_span_durations_by_name_and_kind() {
+    let server = MockServer::start();
+
+    // sent by the flush only
+    let api = server
+        .api()
+        .with_metrics_interval(Duration::from_secs(3600));
+
+    let layer = tracing_newrelic::layer(api)
+        .with_service_name("checkout")
+        .with_metrics(true);
+
+    run(layer);
+
+    let requests = server.metric_requests();
+    assert_eq!(requests.len(), 1);
+
+    let payload = &requests[0].body[0];
+    assert_eq!(
+        payload["common"]["attributes"],
+        json!({ "service.name": "checkout" })
+    );
+    assert!(payload["common"]["interval.ms"].is_u64());
+    assert!(payload["common"]["timestamp"].is_u64());
+
+    let metrics = server.metrics();
+    assert_eq!(metrics.len(), 3);
+
+    for metric in &metrics {
+        assert_eq!(metric["name"], "span.duration");
+        assert_eq!(metric["type"], "summary");
+    }
+
+    let users = summary(&metrics, "/users");
+    assert_eq!(users["attributes"]["span.kind"], "server");
+    assert_eq!(users["value"]["count"], 2);
+    assert!(users["value"]["min"].as_f64() <= users["value"]["max"].as_f64());
+
+    let orders = summary(&metrics, "/orders");
+    assert_eq!(orders["value"]["count"], 1);
+    assert_eq!(orders["value"]["min"], orders["value"]["max"]);
+    assert_eq!(orders["value"]["sum"], orders["value"]["max"]);
+
+    let query = summary(&metrics, "query");
+    assert_eq!(query["value"]["count"], 3);
+    assert_eq!(query["attributes"].get("span.kind"), None);
+}
+
+#[test]
+fn includes_traces_not_exported() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api())
+        .with_export_policy(ExportPolicy::ErrorsOrSlowerThan(Duration::from_secs(3600)))
+        .with_metrics(true);
+
+    run(layer);
+
+    assert!(server.trace_requests().is_empty());
+
+    let metrics = server.metrics();
+    assert_eq!(summary(&metrics, "/users")["value"]["count"], 2);
+    assert_eq!(summary(&metrics, "/orders")["value"]["count"], 1);
+}
+
+#[test]
+fn sent_every_interval() {
+    let server = MockServer::start();
+
+    let api = server
+        .api()
+        .with_metrics_interval(Duration::from_millis(100));
+
+    let layer = tracing_newrelic::layer(api).with_metrics(true);
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        request("/users");
+
+        std::thread::sleep(Duration::from_millis(500));
+
+        assert_eq!(server.metric_requests().len(), 1);
+
+        request("/users");
+
+        std::thread::sleep(Duration::from_millis(500));
+    });
+
+    // each window is sent on its own
+    let requests = server.metric_requests();
+    assert_eq!(requests.len(), 2);
+
+    for request in requests {
+        assert_eq!(request.body[0]["metrics"][0]["value"]["count"], 1);
+    }
+}
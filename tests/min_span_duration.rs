@@ -0,0 +1,63 @@
+mod common;
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use common::MockServer;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+#[test]
+fn fast_spans_are_dropped_and_reparented() {
+    let server = MockServer::start();
+
+    let layer =
+        tracing_newrelic::layer(server.api()).with_min_span_duration(Duration::from_millis(50));
+
+    let subscriber = Registry::default().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        let root = tracing::info_span!("root");
+        let _root = root.enter();
+
+        {
+            let slow = tracing::info_span!("slow");
+            let _slow = slow.enter();
+
+            {
+                let fast = tracing::info_span!("fast");
+                let _fast = fast.enter();
+
+                tracing::info!("inside fast span");
+            }
+
+            sleep(Duration::from_millis(60));
+        }
+    });
+
+    let spans = server.spans();
+    let logs = server.logs();
+
+    let names: Vec<_> = spans.iter().map(|s| &s["attributes"]["name"]).collect();
+    assert_eq!(names, ["root", "slow"]);
+
+    // the log of the dropped span now belongs to its parent
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0]["attributes"]["span.id"], spans[1]["id"]);
+    assert_eq!(spans[1]["attributes"]["parent.id"], spans[0]["id"]);
+}
+
+#[test]
+fn fast_root_span_is_kept() {
+    let server = MockServer::start();
+
+    let layer =
+        tracing_newrelic::layer(server.api()).with_min_span_duration(Duration::from_secs(10));
+
+    let subscriber = Registry::default().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        let _root = tracing::info_span!("root").entered();
+    });
+
+    assert_eq!(server.spans().len(), 1);
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use common::MockServer;
+use serde_json::Value;
+use tracing_newrelic::normalizers;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn run(path: &str) -> Vec<Value> {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api())
+        .with_name_normalizer(normalizers::numeric_path_segments());
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let _root = tracing::info_span!("request", name = path, path = path).entered();
+
+        tracing::info_span!("query", name = "GET /users/48121").in_scope(|| {});
+    });
+
+    server.spans()
+}
+
+fn root_name(path: &str) -> Value {
+    run(path)[0]["attributes"]["name"].clone()
+}
+
+#[test]
+fn integer_segments() {
+    assert_eq!(
+        root_name("GET /users/48121/orders/7"),
+        "GET /users/:id/orders/:id"
+    );
+}
+
+#[test]
+fn uuid_segments() {
+    assert_eq!(
+        root_name("DELETE /sessions/3F2504E0-4F89-11D3-9A0C-0305E82C3301"),
+        "DELETE /sessions/:id"
+    );
+}
+
+#[test]
+fn mixed_segments_are_kept() {
+    assert_eq!(
+        root_name("GET /v2/users/u48121/3f2504e0/48121.json"),
+        "GET /v2/users/u48121/3f2504e0/48121.json"
+    );
+}
+
+#[test]
+fn only_the_root_name_is_normalized() {
+    let spans = run("GET /users/48121");
+
+    let root = &spans[0]["attributes"];
+    assert_eq!(root["name"], "GET /users/:id");
+    assert_eq!(root["path"], "GET /users/48121");
+
+    assert_eq!(spans[1]["attributes"]["name"], "GET /users/48121");
+}
+
+#[test]
+fn custom_normalizer() {
+    let server = MockServer::start();
+
+    let layer =
+        tracing_newrelic::layer(server.api()).with_name_normalizer(|name| name.to_uppercase());
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let _root = tracing::info_span!("request").entered();
+    });
+
+    assert_eq!(server.spans()[0]["attributes"]["name"], "REQUEST");
+}
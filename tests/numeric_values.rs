@@ -0,0 +1,74 @@
+mod common;
+
+use common::MockServer;
+use serde_json::{json, Value};
+use tracing_newrelic::{NewrAttributes, Value as NewrValue};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn record(f: impl FnOnce()) -> Value {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api());
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        tracing::info_span!("root").in_scope(f);
+    });
+
+    server.logs()[0]["attributes"].clone()
+}
+
+#[test]
+fn wide_integers() {
+    let attributes = record(|| {
+        tracing::info!(
+            max_u64 = u64::MAX as u128,
+            over_u64 = u64::MAX as u128 + 1,
+            min_i64 = i64::MIN as i128,
+            under_i64 = i64::MIN as i128 - 1,
+            positive = u64::MAX as i128,
+            max_u128 = u128::MAX,
+        );
+    });
+
+    assert_eq!(attributes["max_u64"], json!(u64::MAX));
+    assert_eq!(attributes["over_u64"], "18446744073709551616");
+    assert_eq!(attributes["min_i64"], json!(i64::MIN));
+    assert_eq!(attributes["under_i64"], "-9223372036854775809");
+    assert_eq!(attributes["positive"], json!(u64::MAX));
+    assert_eq!(attributes["max_u128"], u128::MAX.to_string());
+}
+
+#[test]
+fn bytes_as_hex() {
+    let long = vec![0xab_u8; 5000];
+
+    let attributes = record(|| {
+        tracing::info!(
+            bytes = &b"\x00\x01\x7f\xff"[..],
+            empty = &b""[..],
+            long = &long[..],
+        );
+    });
+
+    assert_eq!(attributes["bytes"], "00017fff");
+    assert_eq!(attributes["empty"], "");
+    assert_eq!(attributes["long"], "ab".repeat(2047));
+}
+
+#[test]
+fn conversions() {
+    let mut attributes = NewrAttributes::default();
+
+    attributes.insert("i32", -1_i32);
+    attributes.insert("u32", u32::MAX);
+    attributes.insert("usize", 7_usize);
+    attributes.insert("f32", 0.5_f32);
+
+    assert_eq!(attributes.get("i32"), Some(&NewrValue::I64(-1)));
+    assert_eq!(
+        attributes.get("u32"),
+        Some(&NewrValue::U64(u32::MAX.into()))
+    );
+    assert_eq!(attributes.get("usize"), Some(&NewrValue::U64(7)));
+    assert_eq!(attributes.get("f32"), Some(&NewrValue::F64(0.5)));
+}
@@ -0,0 +1,134 @@
+#![cfg(feature = "testing")]
+
+use std::future::Future;
+use std::sync::{Mutex, Once};
+
+use log::{Log, Metadata, Record};
+use tracing::Instrument;
+use tracing_newrelic::testing::{with_captured_layer, Captured};
+use tracing_newrelic::{spawn_blocking_traced, spawn_traced, Value};
+
+static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+struct Warnings;
+
+impl Log for Warnings {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::Level::Warn
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            WARNINGS.lock().unwrap().push(record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Warnings logged about the given span
+fn warnings(name: &str) -> Vec<String> {
+    let pattern = format!("'{}'", name);
+
+    WARNINGS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|warning| warning.contains(&pattern))
+        .cloned()
+        .collect()
+}
+
+/// Run `f` on a single threaded runtime
+fn run<F: Future>(f: F) -> Captured {
+    static LOGGER: Once = Once::new();
+
+    LOGGER.call_once(|| {
+        log::set_logger(&Warnings).unwrap();
+        log::set_max_level(log::LevelFilter::Warn);
+    });
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    with_captured_layer(
+        |layer| layer.with_orphan_root_warning(true),
+        || {
+            runtime.block_on(f);
+        },
+    )
+}
+
+#[test]
+fn warns_about_uninstrumented_tasks() {
+    let captured = run(async {
+        let job = tokio::spawn(async {
+            let _span = tracing::info_span!("untraced_job").entered();
+        });
+
+        job.instrument(tracing::info_span!("untraced_request"))
+            .await
+            .unwrap();
+    });
+
+    // two traces instead of one
+    let job = captured.span("untraced_job").unwrap();
+    assert_eq!(job.attributes.get("parent.id"), None);
+
+    let warnings = warnings("untraced_job");
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains(file!()), "{}", warnings[0]);
+}
+
+#[test]
+fn spawns_traced_tasks() {
+    let captured = run(async {
+        async {
+            spawn_traced(async {
+                let _span = tracing::info_span!("traced_job").entered();
+            })
+            .await
+            .unwrap();
+
+            spawn_blocking_traced(|| {
+                let _span = tracing::info_span!("traced_blocking_job").entered();
+            })
+            .await
+            .unwrap();
+        }
+        .instrument(tracing::info_span!("traced_request"))
+        .await;
+    });
+
+    let request = captured.span("traced_request").unwrap();
+
+    for name in ["traced_job", "traced_blocking_job"].iter() {
+        let job = captured.span(name).unwrap();
+
+        assert_eq!(job.trace_id, request.trace_id);
+        assert_eq!(
+            job.attributes.get("parent.id"),
+            Some(&Value::from(request.id.as_str()))
+        );
+        assert!(warnings(name).is_empty());
+    }
+}
+
+#[test]
+fn ignores_tasks_outside_traces() {
+    run(async {
+        async {}
+            .instrument(tracing::info_span!("finished_request"))
+            .await;
+
+        tokio::spawn(async {
+            let _span = tracing::info_span!("scheduled_job").entered();
+        })
+        .await
+        .unwrap();
+    });
+
+    assert!(warnings("scheduled_job").is_empty());
+}
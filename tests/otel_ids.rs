@@ -0,0 +1,111 @@
+#![cfg(all(feature = "otel-ids", feature = "testing"))]
+
+use opentelemetry::trace::{
+    SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState, TracerProvider as _,
+};
+use opentelemetry_sdk::trace::TracerProvider;
+use tracing_newrelic::testing::{Captured, CapturingExporter};
+use tracing_newrelic::Value;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+/// Run `f` with both layers installed, `tracing-opentelemetry` below this one
+fn capture(f: impl FnOnce()) -> Captured {
+    let exporter = CapturingExporter::new();
+
+    // tracers only hold a weak reference to their provider
+    let provider = TracerProvider::builder().build();
+    let tracer = provider.tracer("otel_ids");
+
+    let subscriber = Registry::default()
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .with(tracing_newrelic::layer_with_exporter(exporter.clone()));
+
+    tracing::subscriber::with_default(subscriber, f);
+
+    exporter.captured()
+}
+
+/// The OpenTelemetry span context of the current span
+fn current() -> SpanContext {
+    tracing::Span::current()
+        .context()
+        .span()
+        .span_context()
+        .clone()
+}
+
+#[test]
+fn shares_trace_and_span_ids() {
+    let mut root_cx = None;
+    let mut child_cx = None;
+
+    let captured = capture(|| {
+        let _root = tracing::info_span!("root").entered();
+        root_cx = Some(current());
+
+        let _child = tracing::info_span!("child").entered();
+        child_cx = Some(current());
+    });
+
+    let (root_cx, child_cx) = (root_cx.unwrap(), child_cx.unwrap());
+    let root = captured.span("root").unwrap();
+    let child = captured.span("child").unwrap();
+
+    assert_eq!(root.id, root_cx.span_id().to_string());
+    assert_eq!(
+        root.trace_id.as_deref(),
+        Some(root_cx.trace_id().to_string().as_str())
+    );
+    assert_eq!(root.attributes.get("parent.id"), None);
+
+    assert_eq!(child.id, child_cx.span_id().to_string());
+    assert_eq!(child.trace_id, root.trace_id);
+    assert_eq!(
+        child.attributes.get("parent.id"),
+        Some(&Value::from(root_cx.span_id().to_string()))
+    );
+}
+
+#[test]
+fn continues_otel_context() {
+    let remote = SpanContext::new(
+        TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap(),
+        SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+        TraceFlags::SAMPLED,
+        true,
+        TraceState::default(),
+    );
+
+    let captured = capture(|| {
+        let _cx = opentelemetry::Context::new()
+            .with_remote_span_context(remote)
+            .attach();
+
+        let _root = tracing::info_span!("root").entered();
+    });
+
+    let root = captured.span("root").unwrap();
+
+    assert_eq!(
+        root.trace_id.as_deref(),
+        Some("4bf92f3577b34da6a3ce929d0e0e4736")
+    );
+    assert_eq!(
+        root.attributes.get("parent.id"),
+        Some(&Value::from("00f067aa0ba902b7"))
+    );
+}
+
+#[test]
+fn generates_ids_without_otel() {
+    let exporter = CapturingExporter::new();
+    let layer = tracing_newrelic::layer_with_exporter(exporter.clone()).with_deterministic_ids();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let _root = tracing::info_span!("root").entered();
+    });
+
+    let captured = exporter.captured();
+    assert_eq!(captured.spans()[0].id, "span_1");
+}
@@ -0,0 +1,130 @@
+#![cfg(feature = "otlp")]
+
+mod common;
+
+use std::time::Duration;
+
+use common::MockServer;
+use serde_json::{json, Value};
+use tracing_newrelic::{Api, ExportFormat};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn run(api: Api, f: impl FnOnce()) {
+    let layer = tracing_newrelic::layer(api.with_export_format(ExportFormat::Otlp));
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        f();
+
+        assert!(handle.flush_timeout(Duration::from_secs(5)));
+    });
+}
+
+fn spans(server: &MockServer) -> Vec<Value> {
+    server
+        .requests_to("/v1/traces")
+        .iter()
+        .flat_map(|r| {
+            r.body["resourceSpans"]
+                .as_array()
+                .cloned()
+                .unwrap_or_default()
+        })
+        .flat_map(|resource| {
+            resource["scopeSpans"][0]["spans"]
+                .as_array()
+                .cloned()
+                .unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn sent_to_the_otlp_endpoints() {
+    let server = MockServer::start();
+
+    run(server.api(), || {
+        let _span = tracing::info_span!("request").entered();
+        tracing::info!("handled");
+    });
+
+    assert!(server.trace_requests().is_empty());
+    assert!(server.log_requests().is_empty());
+
+    for path in ["/v1/traces", "/v1/logs"] {
+        let requests = server.requests_to(path);
+        assert_eq!(requests.len(), 1, "{}", path);
+        assert_eq!(requests[0].headers["api-key"], "key");
+        assert_eq!(requests[0].headers["content-type"], "application/json");
+    }
+}
+
+#[test]
+fn maps_attributes_onto_fields() {
+    let server = MockServer::start();
+
+    run(server.api(), || {
+        let _root = tracing::info_span!(
+            "request",
+            service.name = "checkout",
+            span.kind = "server",
+            otel.status_code = "ERROR",
+            otel.status_description = "not found",
+            user = "alice",
+        )
+        .entered();
+
+        let _child = tracing::info_span!("query", span.kind = "client").entered();
+    });
+
+    let spans = spans(&server);
+    let (root, child) = (&spans[0], &spans[1]);
+
+    assert_eq!(root["kind"], 2);
+    assert_eq!(root["status"], json!({ "code": 2, "message": "not found" }));
+    assert_eq!(root.get("parentSpanId"), None);
+    assert_eq!(root["traceId"].as_str().unwrap().len(), 32);
+    assert_eq!(root["spanId"].as_str().unwrap().len(), 16);
+
+    let keys: Vec<&str> = root["attributes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|kv| kv["key"].as_str().unwrap())
+        .collect();
+    assert!(keys.contains(&"user"));
+    for key in ["name", "span.kind", "service.name", "otel.status_code"] {
+        assert!(!keys.contains(&key), "{}", key);
+    }
+
+    assert_eq!(child["kind"], 3);
+    assert_eq!(child["status"], json!({ "code": 0 }));
+    assert_eq!(child["parentSpanId"], root["spanId"]);
+    assert_eq!(child["traceId"], root["traceId"]);
+
+    let resource = &server.requests_to("/v1/traces")[0].body["resourceSpans"][0]["resource"];
+    assert!(resource["attributes"]
+        .as_array()
+        .unwrap()
+        .contains(&json!({ "key": "service.name", "value": { "stringValue": "checkout" } })));
+}
+
+#[cfg(feature = "__testing")]
+#[test]
+fn nested_trace_with_logs() {
+    let server = MockServer::start();
+
+    run(server.api(), || {
+        let root = tracing::info_span!("root", service.name = "fixtures", span.kind = "server");
+        let _root = root.enter();
+
+        tracing::info!(answer = 42, "in root");
+
+        let child = tracing::debug_span!("child", flag = true);
+        let _child = child.enter();
+
+        tracing::warn!(ratio = 0.5, "in child");
+    });
+
+    common::fixtures::assert_otlp_fixture("otlp_nested_trace_with_logs", &server);
+}
@@ -0,0 +1,56 @@
+#![cfg(feature = "testing")]
+
+use std::panic;
+
+use tracing_newrelic::testing::with_captured;
+use tracing_newrelic::Value;
+
+#[test]
+fn panics_are_logged_on_the_current_span() {
+    tracing_newrelic::install_panic_hook();
+    // installing it again doesn't log panics twice
+    tracing_newrelic::install_panic_hook();
+
+    let captured = with_captured(|| {
+        let _root = tracing::info_span!("handler").entered();
+
+        let result = panic::catch_unwind(|| panic!("boom {}", 42));
+
+        assert!(result.is_err());
+    });
+
+    let logs = captured.logs();
+    assert_eq!(logs.len(), 1);
+
+    let log = logs[0];
+    assert_eq!(log.level, "ERROR");
+    assert_eq!(
+        log.attributes.get("panic.message"),
+        Some(&Value::from("boom 42"))
+    );
+    assert_eq!(
+        log.attributes.get("panic.file"),
+        Some(&Value::from("tests/panic_hook.rs"))
+    );
+    assert!(matches!(
+        log.attributes.get("panic.line"),
+        Some(Value::U64(_))
+    ));
+
+    let span = captured.span("handler").unwrap();
+    assert_eq!(
+        span.attributes.get("otel.status_code"),
+        Some(&Value::from("ERROR"))
+    );
+    assert_eq!(
+        span.attributes.get("error.message"),
+        Some(&Value::from("boom 42"))
+    );
+}
+
+#[test]
+fn no_subscriber() {
+    tracing_newrelic::install_panic_hook();
+
+    assert!(panic::catch_unwind(|| panic!("no one listening")).is_err());
+}
@@ -0,0 +1,108 @@
+//! Guard against accidental payload-shape regressions
+//!
+//! Requires deterministic ids and timestamps from the `__testing` feature.
+
+#![cfg(feature = "__testing")]
+
+mod common;
+
+use std::time::Duration;
+
+use common::{fixtures::assert_fixture, MockServer};
+use tracing_newrelic::NewRelicLayer;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn capture(f: impl FnOnce()) -> MockServer {
+    capture_with(|layer| layer, f)
+}
+
+fn capture_with(
+    configure: impl FnOnce(NewRelicLayer) -> NewRelicLayer,
+    f: impl FnOnce(),
+) -> MockServer {
+    let server = MockServer::start();
+
+    let subscriber = Registry::default().with(configure(tracing_newrelic::layer(server.api())));
+
+    tracing::subscriber::with_default(subscriber, f);
+
+    server
+}
+
+#[test]
+fn simple_trace() {
+    let server = capture(|| {
+        let _root = tracing::info_span!("simple", service.name = "fixtures").entered();
+    });
+
+    assert_fixture("simple_trace", &server);
+}
+
+#[test]
+fn nested_trace_with_logs() {
+    let server = capture(|| {
+        let root = tracing::info_span!("root", service.name = "fixtures", span.kind = "server");
+        let _root = root.enter();
+
+        tracing::info!(answer = 42, "in root");
+
+        let child = tracing::debug_span!("child", flag = true);
+        let _child = child.enter();
+
+        tracing::warn!(ratio = 0.5, "in child");
+    });
+
+    assert_fixture("nested_trace_with_logs", &server);
+}
+
+#[test]
+fn error_trace() {
+    let server = capture(|| {
+        let root = tracing::info_span!(
+            "request",
+            service.name = "fixtures",
+            otel.status_code = "ERROR",
+            otel.status_description = "not found",
+        );
+        let _root = root.enter();
+
+        tracing::error!(error = "not found", "request failed");
+    });
+
+    assert_fixture("error_trace", &server);
+}
+
+#[test]
+fn truncated_trace() {
+    // every span but the root is too short, their logs are moved onto the root
+    let server = capture_with(
+        |layer| layer.with_min_span_duration(Duration::from_secs(3600)),
+        || {
+            let root = tracing::info_span!("root", service.name = "fixtures");
+            let _root = root.enter();
+
+            let child = tracing::info_span!("child");
+            let _child = child.enter();
+
+            tracing::info!("in child");
+
+            tracing::info_span!("grandchild").in_scope(|| tracing::info!("in grandchild"));
+        },
+    );
+
+    assert_fixture("truncated_trace", &server);
+}
+
+#[test]
+fn batch() {
+    let server = capture(|| {
+        for i in 0..3_u64 {
+            let root = tracing::info_span!("batched", service.name = "fixtures", i);
+            let _root = root.enter();
+
+            tracing::info!(i, "batched log");
+        }
+    });
+
+    assert_fixture("batch", &server);
+}
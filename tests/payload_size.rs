@@ -0,0 +1,92 @@
+mod common;
+
+use std::collections::HashSet;
+
+use common::MockServer;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+const MAX_BYTES: usize = 4500;
+
+fn big_value() -> String {
+    "x".repeat(1500)
+}
+
+#[test]
+fn splits_traces_across_requests() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(
+        server
+            .api()
+            .with_trace_batch_size(6)
+            .with_max_payload_bytes(MAX_BYTES),
+    );
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        for _ in 0..6 {
+            let _span = tracing::info_span!("root", payload = %big_value()).entered();
+        }
+    });
+
+    let requests = server.trace_requests();
+
+    // two traces of ~1.6 KB fit in a request
+    assert_eq!(requests.len(), 3);
+    assert_eq!(server.spans().len(), 6);
+
+    for request in &requests {
+        assert!(serde_json::to_vec(&request.body).unwrap().len() <= MAX_BYTES);
+    }
+}
+
+#[test]
+fn splits_an_oversized_trace_by_span() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api().with_max_payload_bytes(MAX_BYTES));
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let _root = tracing::info_span!("root", service.name = "payload-size").entered();
+
+        for _ in 0..5 {
+            let _span = tracing::info_span!("child", payload = %big_value()).entered();
+        }
+    });
+
+    let requests = server.trace_requests();
+
+    assert_eq!(requests.len(), 3);
+
+    for request in &requests {
+        let payload = &request.body[0];
+
+        assert!(serde_json::to_vec(&request.body).unwrap().len() <= MAX_BYTES);
+        assert_eq!(
+            payload["common"]["attributes"]["service.name"],
+            "payload-size"
+        );
+    }
+
+    // every span arrives exactly once
+    let spans = server.spans();
+    let ids: HashSet<_> = spans.iter().map(|span| span["id"].clone()).collect();
+
+    assert_eq!(spans.len(), 6);
+    assert_eq!(ids.len(), 6);
+}
+
+#[test]
+fn keeps_small_payloads_together() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api().with_trace_batch_size(4));
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        for _ in 0..4 {
+            let _span = tracing::info_span!("root", payload = %big_value()).entered();
+        }
+    });
+
+    assert_eq!(server.trace_requests().len(), 1);
+    assert_eq!(server.spans().len(), 4);
+}
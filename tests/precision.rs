@@ -0,0 +1,66 @@
+mod common;
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use common::MockServer;
+use tracing_newrelic::TimestampPrecision;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+#[test]
+fn sub_millisecond_durations() {
+    let server = MockServer::start();
+
+    let subscriber = Registry::default().with(tracing_newrelic::layer(server.api()));
+
+    tracing::subscriber::with_default(subscriber, || {
+        let _span = tracing::info_span!("fast").entered();
+        sleep(Duration::from_micros(500));
+    });
+
+    let duration = server.spans()[0]["attributes"]["duration.ms"]
+        .as_f64()
+        .unwrap();
+
+    assert!(duration >= 0.5, "{}", duration);
+    assert_ne!(duration.fract(), 0.0);
+
+    // microsecond precision
+    let micros = duration * 1000.0;
+    assert!((micros - micros.round()).abs() < 1e-6, "{}", duration);
+}
+
+#[test]
+fn log_timestamps_in_milliseconds_by_default() {
+    let server = MockServer::start();
+
+    let subscriber = Registry::default().with(tracing_newrelic::layer(server.api()));
+
+    tracing::subscriber::with_default(subscriber, || {
+        let _span = tracing::info_span!("root").entered();
+        tracing::info!("log");
+    });
+
+    assert!(server.logs()[0]["timestamp"].is_u64());
+}
+
+#[test]
+fn log_timestamps_in_microseconds() {
+    let server = MockServer::start();
+
+    let layer =
+        tracing_newrelic::layer(server.api()).with_timestamp_precision(TimestampPrecision::Micros);
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let _span = tracing::info_span!("root").entered();
+        tracing::info!("log");
+    });
+
+    let log = &server.logs()[0];
+    assert!(log["timestamp"].is_f64());
+
+    // still milliseconds since the epoch
+    let span_timestamp = server.spans()[0]["timestamp"].as_f64().unwrap();
+    let log_timestamp = log["timestamp"].as_f64().unwrap();
+    assert!((log_timestamp - span_timestamp).abs() < 1000.0);
+}
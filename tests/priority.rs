@@ -0,0 +1,85 @@
+mod common;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::{MockResponse, MockServer};
+use serde_json::Value;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+/// A server slow to reply to the first trace request, while the queue fills up
+fn slow_server() -> MockServer {
+    let slow = Arc::new(AtomicBool::new(false));
+
+    MockServer::start_with(move |request| {
+        if request.path.contains("trace") && !slow.swap(true, Ordering::SeqCst) {
+            MockResponse::status(202).delay(Duration::from_millis(500))
+        } else {
+            MockResponse::status(202)
+        }
+    })
+}
+
+fn names(request: &Value) -> Vec<&str> {
+    request["spans"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|span| span["attributes"]["name"].as_str().unwrap())
+        .collect()
+}
+
+#[test]
+fn error_traces_are_sent_first() {
+    let server = slow_server();
+
+    let api = server
+        .api()
+        .with_trace_batch_size(1)
+        .with_max_concurrent_requests(1);
+
+    let layer = tracing_newrelic::layer(api);
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        tracing::info_span!("slow").in_scope(|| {});
+
+        for n in 0..10 {
+            tracing::info_span!("ok", n).in_scope(|| {});
+        }
+
+        tracing::info_span!("failed", otel.status_code = "ERROR").in_scope(|| {});
+    });
+
+    let requests = server.trace_requests();
+    assert_eq!(requests.len(), 12);
+    assert_eq!(names(&requests[0].body[0]), ["slow"]);
+
+    // the first request of the backlog
+    assert_eq!(names(&requests[1].body[0]), ["failed"]);
+    assert!(requests[2..]
+        .iter()
+        .all(|request| names(&request.body[0]) == ["ok"]));
+}
+
+#[test]
+fn error_logs_are_sent_first() {
+    let server = MockServer::start();
+
+    let api = server.api().with_trace_batch_size(100);
+
+    let layer = tracing_newrelic::layer(api);
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        for n in 0..10 {
+            tracing::info_span!("ok", n).in_scope(|| {});
+        }
+
+        tracing::info_span!("logged").in_scope(|| tracing::error!("failed"));
+    });
+
+    // sent all at once when shutting down
+    let requests = server.trace_requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(names(&requests[0].body[0])[0], "logged");
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use common::MockServer;
+use serde_json::Value;
+use tracing_newrelic::NewRelicLayer;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn common_attributes(layer: impl FnOnce(&MockServer) -> NewRelicLayer) -> Value {
+    let server = MockServer::start();
+
+    let subscriber = Registry::default().with(layer(&server));
+
+    tracing::subscriber::with_default(subscriber, || {
+        let _span = tracing::info_span!("root", os.type = "plan9").entered();
+    });
+
+    server.trace_requests()[0].body[0]["common"]["attributes"].clone()
+}
+
+#[test]
+fn stamped_on_every_payload() {
+    let attributes = common_attributes(|server| tracing_newrelic::layer(server.api()));
+
+    assert_eq!(attributes["process.pid"], std::process::id());
+    assert!(attributes["process.executable.name"]
+        .as_str()
+        .unwrap()
+        .starts_with("process_metadata"));
+    assert!(attributes["host.arch"].is_string());
+    assert_eq!(attributes["instrumentation.provider"], "tracing-newrelic");
+    assert_eq!(
+        attributes["instrumentation.version"],
+        env!("CARGO_PKG_VERSION")
+    );
+}
+
+#[test]
+fn root_span_values_win() {
+    let attributes = common_attributes(|server| tracing_newrelic::layer(server.api()));
+
+    assert_eq!(attributes["os.type"], "plan9");
+}
+
+#[test]
+fn opt_out() {
+    let attributes = common_attributes(|server| {
+        tracing_newrelic::layer(server.api()).with_process_metadata(false)
+    });
+
+    for key in ["process.pid", "os.type", "instrumentation.provider"] {
+        assert_eq!(attributes.get(key), None, "{}", key);
+    }
+}
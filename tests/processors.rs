@@ -0,0 +1,96 @@
+mod common;
+
+use std::time::Duration;
+
+use common::MockServer;
+use tracing_newrelic::{NewRelicLayer, Value};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn run(layer: impl FnOnce(&MockServer) -> NewRelicLayer) -> MockServer {
+    let server = MockServer::start();
+    let layer = layer(&server);
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let _root = tracing::info_span!("root", http.status = 200).entered();
+
+        tracing::info_span!("noise").in_scope(|| tracing::debug!("polling"));
+        tracing::info_span!("query").in_scope(|| tracing::info!("hello"));
+
+        assert!(handle.flush_timeout(Duration::from_secs(5)));
+    });
+
+    server
+}
+
+fn names(server: &MockServer) -> Vec<String> {
+    let mut names: Vec<_> = server
+        .spans()
+        .iter()
+        .map(|span| span["attributes"]["name"].as_str().unwrap().to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn renames_attributes() {
+    let server = run(|server| {
+        tracing_newrelic::layer(server.api()).with_span_processor(|span| {
+            if let Some(status) = span.attributes.remove("http.status") {
+                span.attributes.0.insert("http.status_code".into(), status);
+            }
+            true
+        })
+    });
+
+    let spans = server.spans();
+    let root = spans
+        .iter()
+        .find(|span| span["attributes"]["name"] == "root")
+        .unwrap();
+
+    assert_eq!(root["attributes"]["http.status_code"], 200);
+    assert_eq!(root["attributes"].get("http.status"), None);
+}
+
+#[test]
+fn drops_spans() {
+    let server = run(|server| {
+        tracing_newrelic::layer(server.api()).with_span_processor(|span| {
+            span.attributes.get("name") != Some(&Value::String("noise".into()))
+        })
+    });
+
+    assert_eq!(names(&server), ["query", "root"]);
+    assert_eq!(server.logs().len(), 2);
+}
+
+#[test]
+fn processes_logs() {
+    let server = run(|server| {
+        tracing_newrelic::layer(server.api()).with_log_processor(|log| {
+            log.attributes.insert("processed", true);
+            log.level != "DEBUG"
+        })
+    });
+
+    let logs = server.logs();
+
+    assert_eq!(logs.len(), 1);
+    assert_eq!(logs[0]["attributes"]["message"], "hello");
+    assert_eq!(logs[0]["attributes"]["processed"], true);
+    assert_eq!(names(&server), ["noise", "query", "root"]);
+}
+
+#[test]
+fn nothing_sent_once_everything_is_dropped() {
+    let server = run(|server| {
+        tracing_newrelic::layer(server.api())
+            .with_span_processor(|_| false)
+            .with_log_processor(|_| false)
+    });
+
+    assert!(server.trace_requests().is_empty());
+    assert!(server.log_requests().is_empty());
+}
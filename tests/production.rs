@@ -0,0 +1,167 @@
+//! The recommended production setup, with every feature working together
+
+mod common;
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use common::MockServer;
+use serde_json::Value;
+use tracing_newrelic::synthetic::health_check;
+use tracing_newrelic::{EmptyValuePolicy, ExportPolicy, NewRelicLayer};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn layer(server: &MockServer) -> NewRelicLayer {
+    let api = server
+        .api()
+        .with_log_batch_size(100)
+        .with_trace_batch_size(100)
+        .with_log_flush_interval(Duration::from_secs(60))
+        .with_trace_flush_interval(Duration::from_secs(60));
+
+    tracing_newrelic::try_layer(api)
+        .unwrap()
+        .with_export_policy(ExportPolicy::ErrorsOrSlowerThan(Duration::from_millis(50)))
+        .with_synthetic_detector(health_check)
+        .with_empty_value_policy(EmptyValuePolicy::Drop)
+        .with_shutdown_timeout(Duration::from_secs(5))
+}
+
+fn workload() {
+    {
+        let root = tracing::info_span!(
+            "POST /checkout",
+            service.name = "shop",
+            hostname = "web-1",
+            span.kind = "server",
+            user.id = ""
+        );
+        let _root = root.enter();
+
+        tracing::info!("checkout started");
+
+        tracing::info_span!("load cart").in_scope(|| tracing::info!(items = 3, "cart loaded"));
+
+        tracing::info_span!("charge").in_scope(|| sleep(Duration::from_millis(60)));
+    }
+
+    {
+        let root = tracing::info_span!("POST /pay", service.name = "shop", hostname = "web-1");
+        let _root = root.enter();
+
+        tracing::info_span!("charge").in_scope(|| tracing::error!("card declined"));
+    }
+
+    {
+        let root = tracing::info_span!("GET /healthz", service.name = "shop", hostname = "web-1");
+        let _root = root.enter();
+        sleep(Duration::from_millis(60));
+    }
+
+    // fast and successful, not worth exporting
+    drop(tracing::info_span!("GET /ping", service.name = "shop").entered());
+
+    tracing::info!("orphan event");
+}
+
+fn by_name<'a>(spans: &'a [Value], name: &str) -> Vec<&'a Value> {
+    spans
+        .iter()
+        .filter(|span| span["attributes"]["name"] == name)
+        .collect()
+}
+
+#[test]
+fn recommended_setup() {
+    let server = MockServer::start();
+    let layer = layer(&server);
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        workload();
+
+        // everything is still queued
+        assert!(server.requests().is_empty());
+
+        let dropped = tracing::dispatcher::get_default(|dispatch| {
+            dispatch
+                .downcast_ref::<NewRelicLayer>()
+                .unwrap()
+                .dropped_traces()
+        });
+        assert_eq!(dropped, 1);
+    });
+
+    // pending data is sent on shutdown, in a single request per endpoint
+    assert_eq!(server.trace_requests().len(), 1);
+    assert_eq!(server.log_requests().len(), 1);
+
+    let spans = server.spans();
+    let logs = server.logs();
+
+    assert_eq!(spans.len(), 6);
+    assert_eq!(logs.len(), 3);
+    assert!(by_name(&spans, "GET /ping").is_empty());
+    assert!(logs
+        .iter()
+        .all(|log| log["attributes"]["message"] != "orphan event"));
+
+    // nested spans and their logs are linked together
+    let checkout = by_name(&spans, "POST /checkout")[0];
+    let trace_id = &checkout["trace.id"];
+
+    assert_eq!(checkout["attributes"]["span.kind"], "server");
+    assert!(checkout["attributes"].get("user.id").is_none());
+
+    for name in ["load cart", "charge"] {
+        let child = by_name(&spans, name)
+            .into_iter()
+            .find(|span| &span["trace.id"] == trace_id)
+            .unwrap();
+
+        assert_eq!(child["attributes"]["parent.id"], checkout["id"]);
+    }
+
+    let cart_loaded = logs
+        .iter()
+        .find(|log| log["attributes"]["message"] == "cart loaded")
+        .unwrap();
+    let load_cart = by_name(&spans, "load cart")[0];
+
+    assert_eq!(cart_loaded["attributes"]["span.id"], load_cart["id"]);
+    assert_eq!(&cart_loaded["attributes"]["trace.id"], trace_id);
+    assert_eq!(cart_loaded["attributes"]["items"], 3);
+
+    // errors are recorded from events, and keep fast traces
+    let declined = logs
+        .iter()
+        .find(|log| log["attributes"]["message"] == "card declined")
+        .unwrap();
+
+    assert_eq!(declined["level"], "ERROR");
+
+    // common blocks carry the service and host, and mark health checks,
+    // traces sharing the same common block are merged
+    let payloads = server.trace_requests()[0].body.as_array().unwrap().clone();
+    assert_eq!(payloads.len(), 2);
+
+    for payload in &payloads {
+        let common = &payload["common"]["attributes"];
+        let name = &payload["spans"][0]["attributes"]["name"];
+
+        assert_eq!(common["service.name"], "shop");
+        assert_eq!(common["hostname"], "web-1");
+        assert_eq!(
+            common.get("traffic.synthetic").is_some(),
+            name == "GET /healthz"
+        );
+    }
+
+    let stats = handle.stats().snapshot();
+
+    assert_eq!(stats.spans_sent, 6);
+    assert_eq!(stats.logs_sent, 3);
+    assert_eq!(stats.batches_sent, 2);
+    assert_eq!(stats.send_failures, 0);
+    assert_eq!(stats.queue_drops, 0);
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use common::MockServer;
+use tracing_newrelic::propagation::traceparent;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+#[test]
+fn continues_remote_traces() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api());
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        tracing::info_span!("remote", traceparent = TRACEPARENT).in_scope(|| {
+            tracing::info_span!("child").in_scope(|| tracing::info!("inside"));
+        });
+    });
+
+    let spans = server.spans();
+    let root = &spans[0];
+    let child = &spans[1];
+
+    assert_eq!(root["trace.id"], "4bf92f3577b34da6a3ce929d0e0e4736");
+    assert_eq!(root["attributes"]["parent.id"], "00f067aa0ba902b7");
+    assert_eq!(root["attributes"]["nr.entryPoint"], true);
+    assert!(root["attributes"].get("traceparent").is_none());
+
+    assert_eq!(child["trace.id"], root["trace.id"]);
+    assert_eq!(child["attributes"]["parent.id"], root["id"]);
+    assert_eq!(server.logs()[0]["attributes"]["trace.id"], root["trace.id"]);
+}
+
+#[test]
+fn ignores_malformed_headers() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api());
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        for header in [
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e473g-00f067aa0ba902b7-01",
+        ]
+        .iter()
+        {
+            tracing::info_span!("remote", traceparent = header).in_scope(|| {});
+        }
+    });
+
+    for span in server.spans() {
+        assert_ne!(span["trace.id"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert!(span["attributes"].get("parent.id").is_none());
+    }
+}
+
+#[test]
+fn injects_the_current_span() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api());
+
+    let header = tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let span = tracing::info_span!("remote", traceparent = TRACEPARENT);
+        traceparent(&span).unwrap()
+    });
+
+    let spans = server.spans();
+    let id = spans[0]["id"].as_str().unwrap();
+
+    let is_hex = |part: &str, len: usize| {
+        part.len() == len
+            && part
+                .bytes()
+                .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+    };
+
+    let parts: Vec<&str> = header.split('-').collect();
+    assert_eq!(parts.len(), 4);
+    assert_eq!(parts[0], "00");
+    assert_eq!(parts[1], "4bf92f3577b34da6a3ce929d0e0e4736");
+    assert!(is_hex(parts[2], 16));
+    assert_eq!(parts[3], "01");
+
+    // W3C ids are used as is
+    if is_hex(id, 16) {
+        assert_eq!(parts[2], id);
+    }
+}
+
+#[test]
+fn keeps_traces_sampled_by_the_caller() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api()).with_sampling_ratio(0.0);
+
+    let headers = tracing::subscriber::with_default(Registry::default().with(layer), || {
+        [
+            TRACEPARENT,
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00",
+        ]
+        .iter()
+        .map(|header| {
+            let span = tracing::info_span!("remote", traceparent = header);
+            traceparent(&span).unwrap()
+        })
+        .collect::<Vec<_>>()
+    });
+
+    let spans = server.spans();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0]["trace.id"], "4bf92f3577b34da6a3ce929d0e0e4736");
+
+    // the decision is passed on
+    assert!(headers[0].ends_with("-01"));
+    assert!(headers[1].starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-"));
+    assert!(headers[1].ends_with("-00"));
+}
+
+#[test]
+fn no_header_without_layer() {
+    let span = tracing::info_span!("alone");
+    assert_eq!(traceparent(&span), None);
+}
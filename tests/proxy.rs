@@ -0,0 +1,34 @@
+mod common;
+
+use common::MockServer;
+use tracing_newrelic::{Api, ApiEndpoint};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+#[test]
+fn both_endpoints_go_through_the_proxy() {
+    let proxy = MockServer::start();
+
+    // unresolvable, only the proxy can reach it
+    let api = Api::from((
+        "key".to_string(),
+        ApiEndpoint::Custom("http://newrelic.invalid".into()),
+    ))
+    .with_proxy(proxy.url());
+
+    tracing::subscriber::with_default(
+        Registry::default().with(tracing_newrelic::layer(api)),
+        || {
+            let _span = tracing::info_span!("root").entered();
+            tracing::info!("inside root");
+        },
+    );
+
+    let requests = proxy.requests();
+
+    assert_eq!(proxy.trace_requests().len(), 1);
+    assert_eq!(proxy.log_requests().len(), 1);
+
+    for request in requests {
+        assert_eq!(request.headers["host"], "newrelic.invalid");
+    }
+}
@@ -0,0 +1,29 @@
+mod common;
+
+use common::MockServer;
+use tracing_newrelic::{Api, ApiEndpoint};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+// the proxy environment is read once per process, so this is the only test of the file
+#[test]
+fn proxy_from_environment() {
+    let proxy = MockServer::start();
+
+    std::env::set_var("HTTP_PROXY", proxy.url());
+
+    let api = Api::from((
+        "key".to_string(),
+        ApiEndpoint::Custom("http://newrelic.invalid".into()),
+    ));
+
+    tracing::subscriber::with_default(
+        Registry::default().with(tracing_newrelic::layer(api)),
+        || {
+            let _span = tracing::info_span!("root").entered();
+            tracing::info!("inside root");
+        },
+    );
+
+    assert_eq!(proxy.trace_requests().len(), 1);
+    assert_eq!(proxy.log_requests().len(), 1);
+}
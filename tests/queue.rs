@@ -0,0 +1,61 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{MockResponse, MockServer};
+use tracing_newrelic::{DropPolicy, NewRelicLayer};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn run(server: &MockServer, capacity: usize, policy: DropPolicy) -> u64 {
+    let api = server.api().with_log_batch_size(1).with_trace_batch_size(1);
+
+    let layer = tracing_newrelic::layer(api)
+        .with_queue_capacity(capacity)
+        .with_drop_policy(policy)
+        .with_shutdown_timeout(Duration::from_millis(100));
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        for _ in 0..10 {
+            let _span = tracing::info_span!("root").entered();
+        }
+
+        tracing::dispatcher::get_default(|dispatch| {
+            dispatch
+                .downcast_ref::<NewRelicLayer>()
+                .unwrap()
+                .overflowed_traces()
+        })
+    })
+}
+
+#[test]
+fn drop_newest_when_full() {
+    // the worker is stuck sending the first trace
+    let server =
+        MockServer::start_with(|_| MockResponse::status(202).delay(Duration::from_secs(5)));
+
+    let overflowed = run(&server, 2, DropPolicy::DropNewest);
+
+    // the first trace is either being sent or still queued
+    assert!((7..=8).contains(&overflowed), "{}", overflowed);
+}
+
+#[test]
+fn block_until_worker_catches_up() {
+    let server =
+        MockServer::start_with(|_| MockResponse::status(202).delay(Duration::from_millis(10)));
+
+    let overflowed = run(&server, 1, DropPolicy::Block(Duration::from_secs(5)));
+
+    assert_eq!(overflowed, 0);
+}
+
+#[test]
+fn block_then_drop() {
+    let server =
+        MockServer::start_with(|_| MockResponse::status(202).delay(Duration::from_secs(5)));
+
+    let overflowed = run(&server, 2, DropPolicy::Block(Duration::from_millis(10)));
+
+    assert!((7..=8).contains(&overflowed), "{}", overflowed);
+}
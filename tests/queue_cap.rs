@@ -0,0 +1,92 @@
+mod common;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::{MockResponse, MockServer};
+use serde_json::Value;
+use tracing_newrelic::Api;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+const MAX_QUEUE_BYTES: usize = 4_000;
+
+/// A server stalling on the first trace request, while the queue fills up
+fn stalled_server() -> MockServer {
+    let stalled = Arc::new(AtomicBool::new(false));
+
+    MockServer::start_with(move |request| {
+        if request.path.contains("trace") && !stalled.swap(true, Ordering::SeqCst) {
+            MockResponse::status(202).delay(Duration::from_secs(1))
+        } else {
+            MockResponse::status(202)
+        }
+    })
+}
+
+fn api(server: &MockServer) -> Api {
+    server
+        .api()
+        .with_trace_batch_size(1)
+        .with_max_concurrent_requests(1)
+        .with_max_queue_bytes(Some(MAX_QUEUE_BYTES))
+}
+
+fn name(span: &Value) -> &str {
+    span["attributes"]["name"].as_str().unwrap()
+}
+
+#[test]
+fn evicts_the_oldest_traces_but_errors() {
+    let server = stalled_server();
+
+    let layer = tracing_newrelic::layer(api(&server));
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        tracing::info_span!("stalled").in_scope(|| {});
+        tracing::info_span!("failed", otel.status_code = "ERROR").in_scope(|| {});
+
+        for n in 0..40 {
+            tracing::info_span!("ok", n).in_scope(|| {});
+        }
+    });
+
+    let requests = server.trace_requests();
+    assert_eq!(name(&requests[0].body[0]["spans"][0]), "stalled");
+
+    // the queue was capped while the first request was stalled
+    let queued: usize = requests[1..]
+        .iter()
+        .map(|request| serde_json::to_string(&request.body[0]).unwrap().len())
+        .sum();
+    assert!(queued <= MAX_QUEUE_BYTES, "{} bytes queued", queued);
+
+    let spans = server.spans();
+    assert!(spans.len() < 42);
+    assert!(spans.iter().any(|span| name(span) == "failed"));
+
+    // the newest traces are kept
+    let last = spans.last().unwrap();
+    assert_eq!(name(last), "ok");
+    assert_eq!(last["attributes"]["n"], 39);
+
+    assert!(handle.stats().snapshot().payloads_dropped > 0);
+}
+
+#[test]
+fn keeps_everything_without_cap() {
+    let server = stalled_server();
+
+    let layer = tracing_newrelic::layer(api(&server).with_max_queue_bytes(None));
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        for n in 0..40 {
+            tracing::info_span!("ok", n).in_scope(|| {});
+        }
+    });
+
+    assert_eq!(server.spans().len(), 40);
+    assert_eq!(handle.stats().snapshot().payloads_dropped, 0);
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::Duration;
+
+use common::{MockResponse, MockServer};
+use tracing_newrelic::{Api, NewRelicLayer, RetryPolicy};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn trace(name: &str) {
+    let _span = tracing::info_span!("root", name).entered();
+}
+
+fn layer(api: Api, max_age: Duration) -> NewRelicLayer {
+    let api = api
+        .with_retry_policy(RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        })
+        .with_requeue_max_age(Some(max_age));
+
+    tracing_newrelic::layer(api)
+}
+
+#[test]
+fn failed_flush_is_retried_by_the_next_one() {
+    let healthy = Arc::new(AtomicBool::new(false));
+
+    let server = MockServer::start_with({
+        let healthy = healthy.clone();
+        move |_| {
+            if healthy.load(Ordering::SeqCst) {
+                MockResponse::status(202)
+            } else {
+                MockResponse::status(503)
+            }
+        }
+    });
+
+    let layer = layer(server.api(), Duration::from_secs(60));
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        trace("first");
+
+        assert!(!handle.flush_timeout(Duration::from_secs(5)));
+        assert_eq!(server.trace_requests().len(), 1);
+
+        healthy.store(true, Ordering::SeqCst);
+
+        trace("second");
+
+        assert!(handle.flush_timeout(Duration::from_secs(5)));
+    });
+
+    // the first request failed
+    let names: Vec<_> = server.trace_requests()[1..]
+        .iter()
+        .flat_map(|request| request.body.as_array().cloned().unwrap())
+        .flat_map(|payload| payload["spans"].as_array().cloned().unwrap())
+        .map(|span| span["attributes"]["name"].clone())
+        .collect();
+
+    assert_eq!(names, ["first", "second"]);
+    assert_eq!(handle.stats().snapshot().payloads_dropped, 0);
+}
+
+#[test]
+fn rejected_data_is_dropped() {
+    let server = MockServer::start_with(|request| {
+        if request.path.contains("trace") {
+            MockResponse::status(403)
+        } else {
+            MockResponse::status(202)
+        }
+    });
+
+    let layer = layer(server.api(), Duration::from_secs(60));
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        trace("rejected");
+
+        assert!(!handle.flush_timeout(Duration::from_secs(5)));
+        assert!(handle.flush_timeout(Duration::from_secs(5)));
+    });
+
+    assert_eq!(server.trace_requests().len(), 1);
+    assert_eq!(handle.stats().snapshot().payloads_dropped, 1);
+}
+
+#[test]
+fn old_data_is_dropped() {
+    let server = MockServer::start_with(|request| {
+        if request.path.contains("trace") {
+            MockResponse::status(503)
+        } else {
+            MockResponse::status(202)
+        }
+    });
+
+    let layer = layer(server.api(), Duration::from_millis(200));
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        trace("expired");
+
+        assert!(!handle.flush_timeout(Duration::from_secs(5)));
+        assert_eq!(handle.stats().snapshot().payloads_dropped, 0);
+
+        sleep(Duration::from_millis(300));
+
+        handle.flush_timeout(Duration::from_secs(5));
+        assert_eq!(handle.stats().snapshot().payloads_dropped, 1);
+    });
+
+    assert_eq!(server.trace_requests().len(), 1);
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use common::MockServer;
+use tracing_newrelic::ReservedKeyPolicy;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn trace() {
+    tracing::info_span!(
+        "root",
+        id = "user",
+        trace.id = "user",
+        parent.id = "user",
+        duration.ms = 0,
+        timestamp = 0,
+    )
+    .in_scope(|| {
+        tracing::info_span!("child", parent.id = "user", duration.ms = 0).in_scope(|| {
+            tracing::info!(span.id = "user", trace.id = "user", "inside");
+        });
+    });
+}
+
+#[test]
+fn renames_reserved_keys() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api());
+
+    tracing::subscriber::with_default(Registry::default().with(layer), trace);
+
+    let spans = server.spans();
+    let logs = server.logs();
+    assert_eq!(spans.len(), 2);
+
+    let root = &spans[0];
+    let child = &spans[1];
+    assert_eq!(root["attributes"]["name"], "root");
+
+    // the values of the layer survive
+    assert_ne!(root["id"], "user");
+    assert_ne!(root["trace.id"], "user");
+    assert!(root["timestamp"].is_u64());
+    assert!(root["attributes"].get("parent.id").is_none());
+    assert!(root["attributes"]["duration.ms"].is_number());
+    assert_eq!(child["attributes"]["parent.id"], root["id"]);
+    assert_eq!(logs[0]["attributes"]["span.id"], child["id"]);
+    assert_eq!(logs[0]["attributes"]["trace.id"], root["trace.id"]);
+
+    // next to the user ones
+    for key in ["id", "trace.id", "parent.id"].iter() {
+        assert_eq!(root["attributes"][format!("custom.{}", key)], "user");
+    }
+    assert_eq!(root["attributes"]["custom.duration.ms"], 0);
+    assert_eq!(root["attributes"]["custom.timestamp"], 0);
+    assert_eq!(child["attributes"]["custom.parent.id"], "user");
+    assert_eq!(logs[0]["attributes"]["custom.span.id"], "user");
+    assert_eq!(logs[0]["attributes"]["custom.trace.id"], "user");
+}
+
+#[test]
+fn drops_reserved_keys() {
+    let server = MockServer::start();
+
+    let layer =
+        tracing_newrelic::layer(server.api()).with_reserved_key_policy(ReservedKeyPolicy::Drop);
+
+    tracing::subscriber::with_default(Registry::default().with(layer), trace);
+
+    let spans = server.spans();
+    let logs = server.logs();
+
+    let root = &spans[0];
+    let child = &spans[1];
+    assert_ne!(root["trace.id"], "user");
+    assert!(root["attributes"]["duration.ms"].is_number());
+    assert_eq!(child["attributes"]["parent.id"], root["id"]);
+    assert_eq!(logs[0]["attributes"]["span.id"], child["id"]);
+
+    for attributes in [
+        &root["attributes"],
+        &child["attributes"],
+        &logs[0]["attributes"],
+    ]
+    .iter()
+    {
+        let attributes = attributes.as_object().unwrap();
+        assert!(attributes.keys().all(|key| !key.starts_with("custom.")));
+    }
+}
+
+#[test]
+fn renames_reserved_json_keys() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api());
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        tracing::info_span!("root", id.json = "[1]").in_scope(|| {});
+    });
+
+    let spans = server.spans();
+    assert_ne!(spans[0]["id"], "[1]");
+    assert_eq!(spans[0]["attributes"]["custom.id"], serde_json::json!([1]));
+}
@@ -0,0 +1,135 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use common::{MockResponse, MockServer};
+use tracing_newrelic::{Api, Handle, RetryPolicy};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn policy(jitter: bool) -> RetryPolicy {
+    RetryPolicy {
+        max_attempts: 3,
+        initial_backoff: Duration::from_millis(20),
+        max_backoff: Duration::from_millis(50),
+        jitter,
+    }
+}
+
+/// Respond with `script` to trace requests in turn, then with 202
+fn scripted(script: Vec<MockResponse>) -> MockServer {
+    let script = std::sync::Mutex::new(script.into_iter());
+
+    MockServer::start_with(move |request| {
+        if request.path.contains("trace") {
+            script.lock().unwrap().next()
+        } else {
+            None
+        }
+        .unwrap_or_else(|| MockResponse::status(202))
+    })
+}
+
+fn run(api: Api) -> Handle {
+    let layer = tracing_newrelic::layer(api);
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let _span = tracing::info_span!("root").entered();
+    });
+
+    handle
+}
+
+#[test]
+fn backoff_without_jitter() {
+    let policy = policy(false);
+
+    assert_eq!(policy.backoff(1), Duration::from_millis(0));
+    assert_eq!(policy.backoff(2), Duration::from_millis(20));
+    assert_eq!(policy.backoff(3), Duration::from_millis(40));
+    assert_eq!(policy.backoff(4), Duration::from_millis(50));
+    assert_eq!(policy.backoff(100), Duration::from_millis(50));
+}
+
+#[test]
+fn backoff_with_full_jitter() {
+    let jittered = policy(true);
+    let fixed = policy(false);
+
+    for retry in 2..6 {
+        let max = fixed.backoff(retry);
+        let delays: Vec<_> = (0..100).map(|_| jittered.backoff(retry)).collect();
+
+        assert!(delays.iter().all(|delay| *delay <= max));
+        // not every instance waits the same
+        assert!(delays.iter().any(|delay| *delay != delays[0]));
+    }
+}
+
+#[test]
+fn retries_until_success() {
+    let server = scripted(vec![MockResponse::status(500), MockResponse::status(503)]);
+
+    let start = Instant::now();
+    let stats = run(server.api().with_retry_policy(policy(false)))
+        .stats()
+        .snapshot();
+
+    // immediately, then after 20ms
+    assert!(start.elapsed() >= Duration::from_millis(20));
+    assert_eq!(server.trace_requests().len(), 3);
+    assert_eq!(stats.spans_sent, 1);
+    assert_eq!(stats.send_failures, 2);
+}
+
+#[test]
+fn drops_after_max_attempts() {
+    let attempts = AtomicUsize::new(0);
+
+    let server = MockServer::start_with(move |request| {
+        if request.path.contains("trace") {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            MockResponse::status(500)
+        } else {
+            MockResponse::status(202)
+        }
+    });
+
+    let stats = run(server.api().with_retry_policy(policy(true)))
+        .stats()
+        .snapshot();
+
+    assert_eq!(server.trace_requests().len(), 3);
+    assert_eq!(stats.send_failures, 3);
+    assert_eq!(stats.payloads_dropped, 1);
+    assert_eq!(stats.spans_sent, 0);
+}
+
+#[test]
+fn retry_after_is_capped() {
+    let server = scripted(vec![MockResponse::status(429).header("retry-after", "3600")]);
+
+    let start = Instant::now();
+    let stats = run(server.api().with_retry_policy(policy(false)))
+        .stats()
+        .snapshot();
+
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert_eq!(server.trace_requests().len(), 2);
+    assert_eq!(stats.spans_sent, 1);
+}
+
+#[test]
+fn retry_after_http_date() {
+    let server = scripted(vec![
+        MockResponse::status(429).header("retry-after", "Wed, 21 Oct 2015 07:28:00 GMT")
+    ]);
+
+    let stats = run(server.api().with_retry_policy(policy(false)))
+        .stats()
+        .snapshot();
+
+    assert_eq!(server.trace_requests().len(), 2);
+    assert_eq!(stats.spans_sent, 1);
+}
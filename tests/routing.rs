@@ -0,0 +1,222 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use common::MockServer;
+use futures_util::future::BoxFuture;
+use tokio::runtime::Runtime;
+use tracing_newrelic::{
+    Api, ApiEndpoint, ApiKeyRoute, Credential, ExportError, HttpTransport, TelemetryRequest,
+    TelemetryResponse, TransportError,
+};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+/// An in-memory transport keeping the key of every trace request, so that no connection task is
+/// spawned on the runtime
+#[derive(Clone, Default)]
+struct Keys(Arc<Mutex<Vec<String>>>);
+
+impl HttpTransport for Keys {
+    fn post(
+        &self,
+        request: TelemetryRequest,
+    ) -> BoxFuture<'_, Result<TelemetryResponse, TransportError>> {
+        if request.url.contains("trace") {
+            let key = request.headers.iter().find(|(name, _)| name == "api-key");
+            self.0.lock().unwrap().push(key.unwrap().1.clone());
+        }
+
+        Box::pin(async {
+            Ok(TelemetryResponse {
+                status: 202,
+                ..TelemetryResponse::default()
+            })
+        })
+    }
+}
+
+fn routed(account: &str) {
+    tracing::info_span!("routed", newrelic.account = account).in_scope(|| {});
+}
+
+/// Wait for the number of tasks alive on `runtime` to reach `count`
+fn wait_for_tasks(runtime: &Runtime, count: usize) -> usize {
+    let deadline = Instant::now() + Duration::from_secs(5);
+
+    while runtime.metrics().num_alive_tasks() != count && Instant::now() < deadline {
+        sleep(Duration::from_millis(10));
+    }
+
+    runtime.metrics().num_alive_tasks()
+}
+
+#[test]
+fn routes_traces_by_account() {
+    let default = MockServer::start();
+    let tenant = MockServer::start();
+
+    let tenant_url = tenant.url();
+
+    let api = default
+        .api()
+        .with_account_router(move |account| match account {
+            "tenant-a" => Some(ApiKeyRoute {
+                credential: Credential::ApiKey("key-a".to_string()),
+                endpoint: Some(ApiEndpoint::Custom(tenant_url.clone())),
+            }),
+            _ => None,
+        });
+
+    let layer = tracing_newrelic::layer(api);
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        tracing::info_span!("routed", newrelic.account = "tenant-a")
+            .in_scope(|| tracing::info!("routed log"));
+
+        tracing::info_span!("default").in_scope(|| tracing::info!("default log"));
+    });
+
+    let spans = default.spans();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0]["attributes"]["name"], "default");
+    assert_eq!(default.logs().len(), 1);
+    assert_eq!(default.trace_requests()[0].headers["api-key"], "key");
+
+    let spans = tenant.spans();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0]["attributes"]["name"], "routed");
+    assert!(spans[0]["attributes"].get("newrelic.account").is_none());
+    assert_eq!(tenant.logs()[0]["attributes"]["message"], "routed log");
+    assert_eq!(tenant.trace_requests()[0].headers["api-key"], "key-a");
+    assert_eq!(tenant.log_requests()[0].headers["api-key"], "key-a");
+}
+
+#[test]
+fn drops_traces_of_unknown_accounts() {
+    let server = MockServer::start();
+
+    let errors = Arc::new(Mutex::new(Vec::<ExportError>::new()));
+
+    let asked = Arc::new(AtomicUsize::new(0));
+
+    let api = server
+        .api()
+        .with_account_router({
+            let asked = asked.clone();
+            move |_| {
+                asked.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        })
+        .with_error_handler({
+            let errors = errors.clone();
+            move |error| errors.lock().unwrap().push(error)
+        });
+
+    let layer = tracing_newrelic::layer(api);
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        for _ in 0..3 {
+            tracing::info_span!("unknown", newrelic.account = "tenant-b").in_scope(|| {});
+        }
+        tracing::info_span!("default").in_scope(|| {});
+    });
+
+    let spans = server.spans();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0]["attributes"]["name"], "default");
+    assert_eq!(handle.stats().snapshot().payloads_dropped, 6);
+
+    // until the idle timeout elapses
+    assert_eq!(asked.load(Ordering::Relaxed), 1);
+
+    let errors = errors.lock().unwrap();
+    assert_eq!(errors.len(), 6);
+    assert_eq!(errors[0].spans_dropped, 1);
+    assert!(errors
+        .iter()
+        .all(|error| error.status.is_none() && error.body == "no route to account tenant-b"));
+}
+
+#[test]
+fn uses_the_configured_endpoint_by_default() {
+    let server = MockServer::start();
+
+    let api = server.api().with_account_router(|_| Some("key-a".into()));
+
+    let layer = tracing_newrelic::layer(api);
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        tracing::info_span!("routed", newrelic.account = "tenant-a").in_scope(|| {});
+    });
+
+    let requests = server.trace_requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].headers["api-key"], "key-a");
+}
+
+#[test]
+fn closes_the_least_recently_used_routes() {
+    let runtime = Runtime::new().unwrap();
+    let keys = Keys::default();
+
+    let api = Api::from("key")
+        .with_transport(keys.clone())
+        .with_account_router(|account| Some(account.into()))
+        .with_account_routes_limits(2, Duration::from_secs(3600));
+
+    let layer = tracing_newrelic::layer_on_runtime(api, runtime.handle().clone());
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        routed("tenant-0");
+        routed("tenant-1");
+        assert!(handle.flush_timeout(Duration::from_secs(5)));
+
+        let two_routes = runtime.metrics().num_alive_tasks();
+
+        for n in 2..6 {
+            routed(&format!("tenant-{}", n));
+        }
+        assert!(handle.flush_timeout(Duration::from_secs(5)));
+
+        // the tasks of the evicted routes end once their queues are sent
+        assert_eq!(wait_for_tasks(&runtime, two_routes), two_routes);
+    });
+
+    let mut keys = keys.0.lock().unwrap().clone();
+    keys.sort();
+    assert_eq!(
+        keys,
+        (0..6).map(|n| format!("tenant-{}", n)).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn closes_idle_routes() {
+    let runtime = Runtime::new().unwrap();
+
+    // idle routes are closed on the metrics tick
+    let api = Api::from("key")
+        .with_transport(Keys::default())
+        .with_account_router(|account| Some(account.into()))
+        .with_account_routes_limits(64, Duration::from_millis(50))
+        .with_metrics_interval(Duration::from_millis(20));
+
+    let layer = tracing_newrelic::layer_on_runtime(api, runtime.handle().clone());
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let no_route = runtime.metrics().num_alive_tasks();
+
+        routed("tenant-0");
+        assert!(handle.flush_timeout(Duration::from_secs(5)));
+        assert!(runtime.metrics().num_alive_tasks() > no_route);
+
+        assert_eq!(wait_for_tasks(&runtime, no_route), no_route);
+    });
+}
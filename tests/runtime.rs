@@ -0,0 +1,51 @@
+mod common;
+
+use std::fs;
+
+use common::MockServer;
+use tokio::runtime::Handle;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+/// Names of the threads of this process
+fn threads() -> Vec<String> {
+    fs::read_dir("/proc/self/task")
+        .map(|tasks| {
+            tasks
+                .filter_map(|task| fs::read_to_string(task.ok()?.path().join("comm")).ok())
+                .map(|name| name.trim().to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[tokio::test]
+async fn runs_on_the_current_runtime() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer_on_runtime(server.api(), Handle::current());
+    let handle = layer.handle();
+
+    assert!(!threads().iter().any(|name| name == "newrelic-report"));
+
+    let _guard = tracing::subscriber::set_default(Registry::default().with(layer));
+
+    tracing::info_span!("root").in_scope(|| tracing::info!("log"));
+
+    assert!(handle.flush().await);
+    assert_eq!(server.spans().len(), 1);
+    assert_eq!(server.logs().len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn drop_waits_for_the_final_flush() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer_on_runtime(server.api(), Handle::current());
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        tracing::info_span!("root").in_scope(|| tracing::info!("log"));
+    });
+
+    assert_eq!(server.spans().len(), 1);
+    assert_eq!(server.logs().len(), 1);
+}
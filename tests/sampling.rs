@@ -0,0 +1,59 @@
+mod common;
+
+use common::MockServer;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn run_traces(server: &MockServer, ratio: f64, count: usize) {
+    let layer = tracing_newrelic::layer(server.api()).with_sampling_ratio(ratio);
+
+    let subscriber = Registry::default().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        for i in 0..count {
+            let root = tracing::info_span!("root", i);
+            let _root = root.enter();
+
+            tracing::info!("in root");
+
+            let child = tracing::info_span!("child");
+            let _child = child.enter();
+
+            tracing::info!("in child");
+        }
+    });
+}
+
+#[test]
+fn ratio_zero_exports_nothing() {
+    let server = MockServer::start();
+
+    run_traces(&server, 0.0, 50);
+
+    assert!(server.spans().is_empty());
+    assert!(server.logs().is_empty());
+}
+
+#[test]
+fn ratio_one_exports_everything() {
+    let server = MockServer::start();
+
+    run_traces(&server, 1.0, 50);
+
+    assert_eq!(server.spans().len(), 100);
+    assert_eq!(server.logs().len(), 100);
+}
+
+#[test]
+fn children_follow_root_decision() {
+    let server = MockServer::start();
+
+    run_traces(&server, 0.5, 200);
+
+    let spans = server.spans();
+    let roots = spans.iter().filter(|s| s["attributes"]["name"] == "root");
+    let children = spans.iter().filter(|s| s["attributes"]["name"] == "child");
+
+    // every exported trace is complete
+    assert_eq!(roots.count(), children.count());
+    assert_eq!(server.logs().len(), spans.len());
+}
@@ -0,0 +1,160 @@
+#![cfg(feature = "testing")]
+
+use tracing_newrelic::semconv::OTEL_ATTRIBUTES;
+use tracing_newrelic::testing::{with_captured_layer, Captured};
+use tracing_newrelic::{NewRelicLayer, Value};
+
+fn capture(configure: impl FnOnce(NewRelicLayer) -> NewRelicLayer, f: impl FnOnce()) -> Captured {
+    with_captured_layer(|layer| configure(layer.with_otel_compat(true)), f)
+}
+
+/// A span recording every translated attribute, `otel.kind` aside
+fn query() {
+    let _span = tracing::info_span!(
+        "query",
+        http.request.method = "POST",
+        http.response.status_code = 500,
+        url.full = "https://billing/charge",
+        server.address = "billing",
+        db.system.name = "postgresql",
+        db.query.text = "SELECT 1",
+        db.collection.name = "invoices",
+        exception.message = "connection reset",
+        exception.type = "io::Error",
+        exception.stacktrace = "at billing::charge",
+    )
+    .entered();
+}
+
+#[test]
+fn translates_attributes() {
+    let captured = capture(|layer| layer, query);
+    let span = captured.span("query").unwrap();
+
+    for (otel, newrelic) in OTEL_ATTRIBUTES.iter() {
+        if *otel == "otel.kind" {
+            continue;
+        }
+
+        assert!(span.attributes.get(newrelic).is_some(), "{}", newrelic);
+        assert_eq!(span.attributes.get(otel), None, "{}", otel);
+    }
+
+    let expected = [
+        ("http.method", Value::from("POST")),
+        ("http.statusCode", Value::from(500i64)),
+        ("http.url", Value::from("https://billing/charge")),
+        ("peer.hostname", Value::from("billing")),
+        ("db.system", Value::from("postgresql")),
+        ("db.statement", Value::from("SELECT 1")),
+        ("db.collection", Value::from("invoices")),
+        ("error.message", Value::from("connection reset")),
+        ("error.class", Value::from("io::Error")),
+        ("error.stack", Value::from("at billing::charge")),
+    ];
+
+    for (key, value) in expected.iter() {
+        assert_eq!(span.attributes.get(key), Some(value), "{}", key);
+    }
+}
+
+#[test]
+fn translates_span_kind() {
+    let captured = capture(
+        |layer| layer.with_default_span_kind("server"),
+        || {
+            let _root = tracing::info_span!("root", otel.kind = "CLIENT").entered();
+        },
+    );
+
+    let span = captured.span("root").unwrap();
+    assert_eq!(
+        span.attributes.get("span.kind"),
+        Some(&Value::from("client"))
+    );
+    assert_eq!(span.attributes.get("otel.kind"), None);
+}
+
+#[test]
+fn exceptions_fail_spans() {
+    let captured = capture(|layer| layer, query);
+    let span = captured.span("query").unwrap();
+
+    assert_eq!(
+        span.attributes.get("otel.status_code"),
+        Some(&Value::from("ERROR"))
+    );
+    assert_eq!(
+        span.attributes.get("otel.status_description"),
+        Some(&Value::from("connection reset"))
+    );
+
+    let captured = capture(
+        |layer| layer,
+        || {
+            let _root = tracing::info_span!("root").entered();
+
+            tracing::error!(exception.type = "Timeout", "charge failed");
+        },
+    );
+
+    let span = captured.span("root").unwrap();
+    assert_eq!(
+        span.attributes.get("otel.status_code"),
+        Some(&Value::from("ERROR"))
+    );
+    assert_eq!(
+        span.attributes.get("error.message"),
+        Some(&Value::from("Timeout"))
+    );
+    assert_eq!(
+        captured.logs()[0].attributes.get("error.class"),
+        Some(&Value::from("Timeout"))
+    );
+}
+
+#[test]
+fn keeps_recorded_attributes() {
+    let captured = capture(
+        |layer| layer,
+        || {
+            let _root =
+                tracing::info_span!("root", http.method = "GET", http.request.method = "POST")
+                    .entered();
+        },
+    );
+
+    let span = captured.span("root").unwrap();
+    assert_eq!(
+        span.attributes.get("http.method"),
+        Some(&Value::from("GET"))
+    );
+}
+
+#[test]
+fn keeps_original_keys() {
+    let captured = capture(|layer| layer.with_otel_original_keys(true), query);
+    let span = captured.span("query").unwrap();
+
+    assert_eq!(
+        span.attributes.get("exception.message"),
+        Some(&Value::from("connection reset"))
+    );
+    assert_eq!(
+        span.attributes.get("error.message"),
+        Some(&Value::from("connection reset"))
+    );
+}
+
+#[test]
+fn disabled_by_default() {
+    let captured = with_captured_layer(|layer| layer, query);
+    let span = captured.span("query").unwrap();
+
+    assert_eq!(span.attributes.get("http.method"), None);
+    assert_eq!(span.attributes.get("otel.status_code"), None);
+    assert_eq!(
+        span.attributes.get("http.request.method"),
+        Some(&Value::from("POST"))
+    );
+}
@@ -0,0 +1,58 @@
+#![cfg(feature = "testing")]
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use common::MockServer;
+use tracing_newrelic::testing::send_unserializable_spans;
+use tracing_newrelic::ExportError;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+#[test]
+fn unserializable_batch_is_dropped() {
+    let server = MockServer::start();
+
+    let errors = Arc::new(Mutex::new(Vec::<ExportError>::new()));
+
+    let api = server
+        .api()
+        .with_log_batch_size(1)
+        .with_trace_batch_size(1)
+        .with_error_handler({
+            let errors = errors.clone();
+            move |error| errors.lock().unwrap().push(error)
+        });
+
+    let stats = api.stats();
+
+    tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(send_unserializable_spans(&api));
+
+    let layer = tracing_newrelic::layer(api);
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        for _ in 0..2 {
+            let _span = tracing::info_span!("root").entered();
+            tracing::info!("still flowing");
+        }
+    });
+
+    let errors = errors.lock().unwrap();
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].status, None);
+    assert_eq!(errors[0].spans_dropped, 1);
+    assert!(errors[0].body.contains("failed to serialize traces"));
+
+    let names: Vec<_> = server
+        .spans()
+        .iter()
+        .map(|span| span["attributes"]["name"].clone())
+        .collect();
+
+    assert_eq!(names, ["root", "root"]);
+    assert_eq!(server.logs().len(), 2);
+    assert_eq!(stats.snapshot().payloads_dropped, 1);
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use common::MockServer;
+use serde_json::Value;
+use tracing_newrelic::NewRelicLayer;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+/// Service names of the logs and spans payloads of a trace
+fn service_names(
+    layer: impl FnOnce(&MockServer) -> NewRelicLayer,
+    root: impl FnOnce() -> tracing::Span,
+) -> (Value, Value) {
+    let server = MockServer::start();
+
+    tracing::subscriber::with_default(Registry::default().with(layer(&server)), || {
+        let _root = root().entered();
+        tracing::info!("in root");
+    });
+
+    let logs = &server.log_requests()[0].body[0]["common"]["attributes"]["service.name"];
+    let spans = &server.trace_requests()[0].body[0]["common"]["attributes"]["service.name"];
+
+    (logs.clone(), spans.clone())
+}
+
+#[test]
+fn taken_from_the_root_span() {
+    let (logs, spans) = service_names(
+        |server| tracing_newrelic::layer(server.api()),
+        || tracing::info_span!("root", service.name = "foo"),
+    );
+
+    assert_eq!(logs, "foo");
+    assert_eq!(spans, "foo");
+}
+
+#[test]
+fn configured_one_without_root_span_field() {
+    let (logs, spans) = service_names(
+        |server| tracing_newrelic::layer(server.api()).with_service_name("bar"),
+        || tracing::info_span!("root"),
+    );
+
+    assert_eq!(logs, "bar");
+    assert_eq!(spans, "bar");
+}
+
+#[test]
+fn configured_one_wins() {
+    let (logs, spans) = service_names(
+        |server| tracing_newrelic::layer(server.api()).with_service_name("bar"),
+        || tracing::info_span!("root", service.name = "foo"),
+    );
+
+    assert_eq!(logs, "bar");
+    assert_eq!(spans, "bar");
+}
@@ -0,0 +1,31 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{MockResponse, MockServer};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+#[test]
+fn drop_returns_within_timeout() {
+    let server =
+        MockServer::start_with(|_| MockResponse::status(429).header("retry-after", "3600"));
+
+    let layer =
+        tracing_newrelic::layer(server.api()).with_shutdown_timeout(Duration::from_millis(200));
+
+    let subscriber = Registry::default().with(layer);
+
+    let dispatch = tracing::Dispatch::new(subscriber);
+
+    tracing::dispatcher::with_default(&dispatch, || {
+        let span = tracing::info_span!("root");
+        let _span = span.enter();
+        tracing::info!("never delivered");
+    });
+
+    let start = Instant::now();
+    drop(dispatch);
+
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert!(!server.requests().is_empty());
+}
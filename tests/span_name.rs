@@ -0,0 +1,76 @@
+mod common;
+
+use common::MockServer;
+use serde_json::Value;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn spans(f: impl FnOnce()) -> Vec<Value> {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api());
+
+    tracing::subscriber::with_default(Registry::default().with(layer), f);
+
+    server.spans()
+}
+
+#[test]
+fn name_recorded_after_children_closed() {
+    let spans = spans(|| {
+        let root = tracing::info_span!("request", name = tracing::field::Empty);
+        let _root = root.enter();
+
+        for _ in 0..3 {
+            tracing::info_span!("middleware").in_scope(|| {});
+        }
+
+        root.record("name", "GET /users/:id");
+    });
+
+    let root = &spans[0]["attributes"];
+
+    assert_eq!(root["name"], "GET /users/:id");
+    assert_eq!(root["code.function"], "request");
+    assert_eq!(spans.len(), 4);
+}
+
+#[test]
+fn otel_name_is_an_alias() {
+    let spans = spans(|| {
+        let root = tracing::info_span!("request", otel.name = tracing::field::Empty);
+        let _root = root.enter();
+
+        tracing::info_span!("child", otel.name = "SELECT users").in_scope(|| {});
+
+        root.record("otel.name", "POST /orders");
+    });
+
+    let root = &spans[0]["attributes"];
+    assert_eq!(root["name"], "POST /orders");
+    assert_eq!(root.get("otel.name"), None);
+
+    assert_eq!(spans[1]["attributes"]["name"], "SELECT users");
+    assert_eq!(spans[1]["attributes"]["code.function"], "child");
+}
+
+#[test]
+fn latest_recorded_name_wins() {
+    let spans = spans(|| {
+        let root =
+            tracing::info_span!("request", name = "GET /", otel.name = tracing::field::Empty);
+
+        root.record("otel.name", "GET /health");
+        root.record("name", "GET /healthz");
+    });
+
+    assert_eq!(spans[0]["attributes"]["name"], "GET /healthz");
+}
+
+#[test]
+fn metadata_name_without_recorded_name() {
+    let spans = spans(|| {
+        let _root = tracing::info_span!("request", name = tracing::field::Empty).entered();
+    });
+
+    assert_eq!(spans[0]["attributes"]["name"], "request");
+}
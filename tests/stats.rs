@@ -0,0 +1,104 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{MockResponse, MockServer};
+use tracing_newrelic::{Handle, StatsSnapshot};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn run(server: &MockServer, traces: usize) -> Handle {
+    let layer = tracing_newrelic::layer(server.api());
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        for _ in 0..traces {
+            let root = tracing::info_span!("root");
+            let _root = root.enter();
+            tracing::info!("log");
+            let _child = tracing::info_span!("child").entered();
+        }
+    });
+
+    handle
+}
+
+#[test]
+fn success() {
+    let server = MockServer::start();
+
+    let stats = run(&server, 3).stats().snapshot();
+
+    assert_eq!(stats.spans_sent, 6);
+    assert_eq!(stats.logs_sent, 3);
+    assert_eq!(stats.batches_sent, 2);
+    assert!(stats.bytes_sent > 0);
+    assert_eq!(stats.send_failures, 0);
+    assert_eq!(stats.payloads_dropped, 0);
+}
+
+#[test]
+fn rejected() {
+    let server = MockServer::start_with(|request| {
+        if request.path.contains("trace") {
+            MockResponse::status(403)
+        } else {
+            MockResponse::status(202)
+        }
+    });
+
+    let stats = run(&server, 3).stats().snapshot();
+
+    assert_eq!(
+        stats,
+        StatsSnapshot {
+            logs_sent: 3,
+            batches_sent: 1,
+            bytes_sent: stats.bytes_sent,
+            send_failures: 1,
+            // the 3 traces are merged into a single payload
+            payloads_dropped: 1,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn retried() {
+    let attempts = std::sync::atomic::AtomicUsize::new(0);
+
+    // the first attempt fails and is retried immediately
+    let server = MockServer::start_with(move |_| {
+        if attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst) == 0 {
+            MockResponse::status(500)
+        } else {
+            MockResponse::status(202)
+        }
+    });
+
+    let stats = run(&server, 1).stats().snapshot();
+
+    assert_eq!(stats.send_failures, 1);
+    assert_eq!(stats.batches_sent, 2);
+    assert_eq!(stats.payloads_dropped, 0);
+}
+
+#[test]
+fn queue_drops() {
+    let server =
+        MockServer::start_with(|_| MockResponse::status(202).delay(Duration::from_secs(5)));
+
+    let api = server.api().with_log_batch_size(1).with_trace_batch_size(1);
+
+    let layer = tracing_newrelic::layer(api)
+        .with_queue_capacity(1)
+        .with_shutdown_timeout(Duration::from_millis(100));
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        for _ in 0..5 {
+            let _span = tracing::info_span!("root").entered();
+        }
+    });
+
+    assert!(handle.stats().snapshot().queue_drops >= 3);
+}
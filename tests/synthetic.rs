@@ -0,0 +1,67 @@
+#![cfg(feature = "testing")]
+
+use tracing_newrelic::synthetic::{health_check, synthetic_user_agent};
+use tracing_newrelic::testing::{with_captured_layer, Captured};
+use tracing_newrelic::{NewrSpan, NewrSpans, Value};
+
+fn is_synthetic(spans: &NewrSpans) -> bool {
+    spans.common.attributes().get("traffic.synthetic") == Some(&Value::Bool(true))
+}
+
+fn run<F>(detector: F, f: impl FnOnce()) -> Captured
+where
+    F: Fn(&NewrSpan) -> bool + Send + Sync + 'static,
+{
+    with_captured_layer(|layer| layer.with_synthetic_detector(detector), f)
+}
+
+#[test]
+fn health_checks() {
+    let captured = run(health_check, || {
+        drop(tracing::info_span!("GET /users").entered());
+        drop(tracing::info_span!("GET /healthz").entered());
+    });
+
+    assert_eq!(captured.payloads.len(), 2);
+
+    let (logs, spans) = &captured.payloads[0];
+    assert_eq!(
+        spans.spans[0].attributes.get("name"),
+        Some(&"GET /users".into())
+    );
+    assert!(!is_synthetic(spans));
+    assert!(logs.common.attributes().get("traffic.synthetic").is_none());
+
+    let (logs, spans) = &captured.payloads[1];
+    assert!(is_synthetic(spans));
+    assert_eq!(
+        logs.common.attributes().get("traffic.synthetic"),
+        Some(&Value::Bool(true))
+    );
+}
+
+#[test]
+fn synthetic_user_agents() {
+    let captured = run(synthetic_user_agent, || {
+        drop(tracing::info_span!("request", "user-agent" = "NewRelic-Synthetics/1.0").entered());
+        drop(tracing::info_span!("request", http.user_agent = "curl/8.0").entered());
+    });
+
+    assert_eq!(captured.payloads.len(), 2);
+    assert!(is_synthetic(&captured.payloads[0].1));
+    assert!(!is_synthetic(&captured.payloads[1].1));
+}
+
+#[test]
+fn custom_detector() {
+    let captured = run(
+        |span| span.attributes.get("smoke_test") == Some(&Value::Bool(true)),
+        || {
+            let _span = tracing::info_span!("root", smoke_test = true).entered();
+        },
+    );
+
+    // synthetic traces are still exported
+    assert_eq!(captured.spans().len(), 1);
+    assert!(is_synthetic(&captured.payloads[0].1));
+}
@@ -0,0 +1,42 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{MockResponse, MockServer};
+use tracing_newrelic::{RetryPolicy, TeeExporter};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+#[test]
+fn failing_destination_doesnt_affect_the_others() {
+    let healthy = MockServer::start();
+    let failing = MockServer::start_with(|_| MockResponse::status(503));
+
+    let healthy_api = healthy.api();
+    let failing_api = failing.api().with_retry_policy(RetryPolicy {
+        max_attempts: 2,
+        initial_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(10),
+        jitter: false,
+    });
+
+    let healthy_stats = healthy_api.stats();
+    let failing_stats = failing_api.stats();
+
+    let tee = TeeExporter::new().with(healthy_api).with(failing_api);
+    let layer = tracing_newrelic::layer_with_exporter(tee);
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        for n in 0..3 {
+            tracing::info_span!("root", n).in_scope(|| tracing::info!("log"));
+        }
+    });
+
+    assert_eq!(healthy.spans().len(), 3);
+    assert_eq!(healthy.logs().len(), 3);
+    assert_eq!(healthy_stats.snapshot().spans_sent, 3);
+    assert_eq!(healthy_stats.snapshot().send_failures, 0);
+
+    assert!(failing.spans().len() >= 3);
+    assert_eq!(failing_stats.snapshot().spans_sent, 0);
+    assert!(failing_stats.snapshot().payloads_dropped > 0);
+}
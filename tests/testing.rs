@@ -0,0 +1,162 @@
+#![cfg(feature = "testing")]
+
+use tracing_newrelic::testing::{with_captured, with_captured_layer};
+use tracing_newrelic::{NewrCommon, Value};
+
+#[test]
+fn captures_spans_and_logs() {
+    let captured = with_captured(|| {
+        let root = tracing::info_span!("root", service.name = "testing");
+        let _root = root.enter();
+
+        tracing::info!("in root");
+
+        let child = tracing::info_span!("child");
+        let _child = child.enter();
+
+        tracing::warn!("in child");
+    });
+
+    let root = captured.span("root").unwrap();
+    let child = captured.span("child").unwrap();
+
+    assert_eq!(root.id, "span_1");
+    assert_eq!(child.id, "span_2");
+    assert_eq!(root.trace_id.as_deref(), Some("trace_1"));
+    assert_eq!(
+        child.attributes.get("parent.id"),
+        Some(&Value::from("span_1"))
+    );
+
+    let logs = captured.logs();
+
+    assert_eq!(logs.len(), 2);
+    assert_eq!(logs[1].level, "WARN");
+    assert_eq!(
+        logs[1].attributes.get("span.id"),
+        Some(&Value::from("span_2"))
+    );
+
+    let (logs, spans) = &captured.payloads[0];
+    assert_eq!(
+        logs.common.attributes().get("service.name"),
+        Some(&Value::from("testing"))
+    );
+    assert_eq!(
+        spans.common.attributes().get("service.name"),
+        Some(&Value::from("testing"))
+    );
+}
+
+#[test]
+fn ids_do_not_leak_between_runs() {
+    for _ in 0..2 {
+        let captured = with_captured(|| {
+            let _span = tracing::info_span!("root").entered();
+        });
+
+        assert_eq!(captured.spans()[0].id, "span_1");
+        assert_eq!(captured.spans()[0].trace_id.as_deref(), Some("trace_1"));
+    }
+}
+
+#[test]
+fn configured_layer() {
+    let captured = with_captured_layer(
+        |layer| layer.with_sampling_ratio(0.0),
+        || {
+            let _span = tracing::info_span!("root").entered();
+        },
+    );
+
+    assert!(captured.spans().is_empty());
+}
+
+#[test]
+fn cached_common_attributes_are_byte_identical() {
+    let captured = with_captured(|| {
+        for i in 0..3 {
+            let root = tracing::info_span!("root", service.name = "testing", hostname = "host", i);
+            let _root = root.enter();
+            tracing::info!("in root");
+        }
+    });
+
+    assert_eq!(captured.payloads.len(), 3);
+
+    for (logs, spans) in &captured.payloads {
+        let mut uncached = (logs.clone(), spans.clone());
+        uncached.0.common = NewrCommon::new(logs.common.attributes().clone());
+        uncached.1.common = NewrCommon::new(spans.common.attributes().clone());
+
+        assert_eq!(
+            serde_json::to_vec(&(logs, spans)).unwrap(),
+            serde_json::to_vec(&uncached).unwrap()
+        );
+    }
+}
+
+#[test]
+fn changed_common_attributes_are_serialized() {
+    let captured = with_captured(|| {
+        let _root = tracing::info_span!("root", service.name = "testing").entered();
+    });
+
+    let mut spans = captured.payloads[0].1.clone();
+    spans
+        .common
+        .attributes_mut()
+        .insert("service.name", "changed");
+
+    let serialized = serde_json::to_value(&spans).unwrap();
+    assert_eq!(
+        serialized["common"]["attributes"]["service.name"],
+        "changed"
+    );
+}
+
+#[test]
+fn sampling_is_consistent_across_layers() {
+    let decisions = || {
+        let captured = with_captured_layer(
+            |layer| layer.with_sampling_ratio(0.5),
+            || {
+                for i in 0..20 {
+                    let _span = tracing::info_span!("root", i).entered();
+                }
+            },
+        );
+
+        captured
+            .spans()
+            .iter()
+            .map(|span| span.trace_id.clone().unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    // both layers generate the same trace ids, and keep the same traces
+    let sampled = decisions();
+    assert_eq!(sampled, decisions());
+
+    // but not every trace id leads to the same decision
+    assert!(!sampled.is_empty());
+    assert!(sampled.len() < 20);
+}
+
+#[test]
+fn durations_use_the_monotonic_clock() {
+    let captured = with_captured(|| {
+        let _root = tracing::info_span!("root").entered();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+    });
+
+    let root = captured.span("root").unwrap();
+
+    // the timestamp is pinned to the epoch, the duration is still measured
+    assert_eq!(root.timestamp, std::time::UNIX_EPOCH);
+
+    match root.attributes.get("duration.ms") {
+        Some(Value::F64(ms)) => assert!(*ms >= 5.0 && ms.fract() != 0.0, "{}", ms),
+        other => panic!("unexpected duration {:?}", other),
+    }
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use std::thread;
+
+use common::MockServer;
+use tracing_newrelic::NewRelicLayer;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn trace() {
+    let _span = tracing::info_span!("work").entered();
+    tracing::info!("working");
+}
+
+fn run_on_named_thread(layer: NewRelicLayer) {
+    thread::Builder::new()
+        .name("worker-1".into())
+        .spawn(move || tracing::subscriber::with_default(Registry::default().with(layer), trace))
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn records_the_thread() {
+    let server = MockServer::start();
+
+    run_on_named_thread(tracing_newrelic::layer(server.api()).with_thread_info(true));
+
+    let span = &server.spans()[0]["attributes"];
+    let log = &server.logs()[0]["attributes"];
+
+    for attributes in [span, log] {
+        assert_eq!(attributes["thread.name"], "worker-1");
+        assert!(attributes["thread.id"].as_u64().unwrap() > 0);
+        assert_eq!(attributes.get("tokio.task.id"), None);
+    }
+
+    assert_eq!(span["thread.id"], log["thread.id"]);
+}
+
+#[test]
+fn disabled_by_default() {
+    let server = MockServer::start();
+
+    run_on_named_thread(tracing_newrelic::layer(server.api()));
+
+    let span = &server.spans()[0]["attributes"];
+
+    assert_eq!(span.get("thread.name"), None);
+    assert_eq!(span.get("thread.id"), None);
+}
+
+#[test]
+fn records_the_tokio_task() {
+    let server = MockServer::start();
+
+    let layer = tracing_newrelic::layer(server.api()).with_thread_info(true);
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        rt.block_on(async {
+            tokio::spawn(async { trace() }).await.unwrap();
+        });
+    });
+
+    let span = &server.spans()[0]["attributes"];
+    let log = &server.logs()[0]["attributes"];
+
+    assert!(span["tokio.task.id"].is_string());
+    assert_eq!(span["tokio.task.id"], log["tokio.task.id"]);
+}
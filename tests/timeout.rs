@@ -0,0 +1,64 @@
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tracing_newrelic::{Api, ApiEndpoint, ExportError, RetryPolicy};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+/// Accept connections and never respond
+fn black_hole() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    thread::spawn(move || {
+        let mut streams = Vec::new();
+
+        for stream in listener.incoming() {
+            streams.push(stream);
+        }
+    });
+
+    format!("http://{}", addr)
+}
+
+#[test]
+fn requests_time_out_and_are_retried() {
+    let errors = Arc::new(Mutex::new(Vec::<ExportError>::new()));
+
+    let api = Api::from(("key".to_string(), ApiEndpoint::Custom(black_hole())))
+        .with_request_timeout(Duration::from_millis(200))
+        .with_retry_policy(RetryPolicy {
+            max_attempts: 2,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(10),
+            jitter: false,
+        })
+        .with_error_handler({
+            let errors = errors.clone();
+            move |err| errors.lock().unwrap().push(err)
+        });
+
+    let layer = tracing_newrelic::layer(api);
+    let handle = layer.handle();
+
+    let start = Instant::now();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let _span = tracing::info_span!("root").entered();
+        tracing::info!("inside root");
+    });
+
+    // two attempts of 200ms for both logs and traces, sent concurrently
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(400), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_secs(2), "{:?}", elapsed);
+
+    let stats = handle.stats().snapshot();
+    assert_eq!(stats.send_failures, 4);
+    assert_eq!(stats.payloads_dropped, 2);
+
+    let errors = errors.lock().unwrap();
+    assert_eq!(errors.len(), 2);
+    assert!(errors.iter().all(|err| err.status.is_none()));
+}
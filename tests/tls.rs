@@ -0,0 +1,43 @@
+#![cfg(any(feature = "tls-rustls", feature = "tls-native"))]
+
+mod common;
+
+use common::{fixtures::TLS_CA, MockServer};
+use tracing_newrelic::RetryPolicy;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+#[test]
+fn sends_over_tls() {
+    let server = MockServer::start_tls();
+
+    let layer = tracing_newrelic::try_layer(server.api().with_ca_certificate(TLS_CA)).unwrap();
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        tracing::info_span!("root").in_scope(|| tracing::info!("log"));
+    });
+
+    assert_eq!(server.spans().len(), 1);
+    assert_eq!(server.logs().len(), 1);
+    assert_eq!(handle.stats().snapshot().send_failures, 0);
+}
+
+#[test]
+fn rejects_unknown_certificates() {
+    let server = MockServer::start_tls();
+
+    let api = server.api().with_retry_policy(RetryPolicy {
+        max_attempts: 1,
+        ..RetryPolicy::default()
+    });
+
+    let layer = tracing_newrelic::layer(api);
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        tracing::info_span!("root").in_scope(|| {});
+    });
+
+    assert!(server.requests().is_empty());
+    assert!(handle.stats().snapshot().send_failures > 0);
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use std::time::Duration;
+
+use common::MockServer;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn run(f: impl FnOnce()) -> MockServer {
+    let server = MockServer::start();
+    let layer = tracing_newrelic::layer(server.api());
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        f();
+
+        assert!(handle.flush_timeout(Duration::from_secs(5)));
+    });
+
+    server
+}
+
+#[test]
+fn nested_spans_set_the_common_block() {
+    let server = run(|| {
+        let _root = tracing::info_span!("root").entered();
+        let _child = tracing::info_span!("child").entered();
+        let _grandchild = tracing::info_span!("grandchild", trace.customer.tier = "gold").entered();
+
+        tracing::info!("hello");
+    });
+
+    let traces = server.trace_requests();
+    let logs = server.log_requests();
+
+    assert_eq!(traces.len(), 1);
+    assert_eq!(logs.len(), 1);
+
+    for request in traces.iter().chain(&logs) {
+        assert_eq!(
+            request.body[0]["common"]["attributes"]["customer.tier"],
+            "gold"
+        );
+    }
+
+    for span in server.spans() {
+        assert_eq!(span["attributes"].get("trace.customer.tier"), None);
+        assert_eq!(span["attributes"].get("customer.tier"), None);
+    }
+}
+
+#[test]
+fn set_by_events_and_recorded_later() {
+    let server = run(|| {
+        let _root = tracing::info_span!("root").entered();
+
+        let child = tracing::info_span!("child", trace.region = tracing::field::Empty);
+        let _child = child.clone().entered();
+
+        tracing::info!(trace.plan = "pro", "signed in");
+        child.record("trace.region", "eu");
+    });
+
+    let common = &server.trace_requests()[0].body[0]["common"]["attributes"];
+    assert_eq!(common["plan"], "pro");
+    assert_eq!(common["region"], "eu");
+
+    let logs = server.logs();
+    assert_eq!(logs[0]["attributes"].get("trace.plan"), None);
+    assert!(logs[0]["attributes"]["trace.id"].is_string());
+}
+
+#[test]
+fn last_writer_wins() {
+    let server = run(|| {
+        let _root = tracing::info_span!("root", trace.step = "root").entered();
+
+        tracing::info_span!("first", trace.step = "first").in_scope(|| {});
+        tracing::info_span!("second", trace.step = "second").in_scope(|| {});
+    });
+
+    assert_eq!(
+        server.trace_requests()[0].body[0]["common"]["attributes"]["step"],
+        "second"
+    );
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use common::MockServer;
+use tracing_newrelic::{ExportPolicy, TraceSummary};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn trace() {
+    tracing::info_span!("checkout").in_scope(|| {
+        tracing::info_span!("cart").in_scope(|| tracing::info!("loaded"));
+
+        tracing::info_span!("payment").in_scope(|| {
+            thread::sleep(Duration::from_millis(20));
+            tracing::error!(error = "declined", "payment failed");
+        });
+    });
+}
+
+#[test]
+fn summarizes_completed_traces() {
+    let server = MockServer::start();
+    let summaries: Arc<Mutex<Vec<TraceSummary>>> = Arc::default();
+
+    let completed = summaries.clone();
+    let layer = tracing_newrelic::layer(server.api())
+        .with_trace_completed(move |summary| completed.lock().unwrap().push(summary));
+
+    tracing::subscriber::with_default(Registry::default().with(layer), trace);
+
+    let summaries = summaries.lock().unwrap();
+    assert_eq!(summaries.len(), 1);
+
+    let summary = &summaries[0];
+    let spans = server.spans();
+
+    assert_eq!(summary.trace_id, spans[0]["trace.id"]);
+    assert_eq!(summary.root_name, "checkout");
+    assert!(summary.duration >= Duration::from_millis(20));
+    assert_eq!(summary.span_count, 3);
+    assert_eq!(summary.log_count, 2);
+    assert!(summary.error);
+}
+
+#[test]
+fn summarizes_dropped_traces() {
+    let server = MockServer::start();
+    let summaries: Arc<Mutex<Vec<TraceSummary>>> = Arc::default();
+
+    let completed = summaries.clone();
+    let layer = tracing_newrelic::layer(server.api())
+        .with_export_policy(ExportPolicy::Custom(Box::new(|_, _| false)))
+        .with_trace_completed(move |summary| completed.lock().unwrap().push(summary));
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        tracing::info_span!("health").in_scope(|| {});
+    });
+
+    let summaries = summaries.lock().unwrap();
+    assert_eq!(summaries.len(), 1);
+    assert_eq!(summaries[0].root_name, "health");
+    assert_eq!(summaries[0].span_count, 1);
+    assert!(!summaries[0].error);
+    assert!(server.spans().is_empty());
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use common::{MockResponse, MockServer};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+fn trace() {
+    let span = tracing::info_span!("root");
+    let _span = span.enter();
+    tracing::info!("inside root");
+}
+
+#[test]
+fn logs_are_sent_after_their_trace_is_accepted() {
+    let delay = Duration::from_millis(200);
+
+    let server = MockServer::start_with(move |request| {
+        if request.path.contains("trace") {
+            MockResponse::status(202).delay(delay)
+        } else {
+            MockResponse::status(202)
+        }
+    });
+
+    let api = server
+        .api()
+        .with_log_batch_size(1)
+        .with_trace_batch_size(1)
+        .with_traces_before_logs(true);
+
+    let subscriber = Registry::default().with(tracing_newrelic::layer(api));
+
+    tracing::subscriber::with_default(subscriber, trace);
+
+    let traces = server.trace_requests();
+    let logs = server.log_requests();
+
+    assert_eq!(traces.len(), 1);
+    assert_eq!(logs.len(), 1);
+    assert!(logs[0].received >= traces[0].received + delay);
+}
+
+#[test]
+fn held_logs_are_released_after_timeout() {
+    let server = MockServer::start_with(|request| {
+        if request.path.contains("trace") {
+            MockResponse::status(403)
+        } else {
+            MockResponse::status(202)
+        }
+    });
+
+    let api = server
+        .api()
+        .with_log_batch_size(1)
+        .with_trace_batch_size(1)
+        .with_traces_before_logs(true)
+        .with_logs_hold_timeout(Duration::from_millis(100));
+
+    let subscriber = Registry::default().with(tracing_newrelic::layer(api));
+
+    tracing::subscriber::with_default(subscriber, || {
+        trace();
+
+        // the trace was rejected, so the logs wait for the timeout
+        sleep(Duration::from_millis(50));
+        assert!(server.log_requests().is_empty());
+
+        sleep(Duration::from_millis(300));
+        assert_eq!(server.logs().len(), 1);
+    });
+}
@@ -0,0 +1,155 @@
+use std::sync::{Arc, Mutex};
+
+use futures_util::future::BoxFuture;
+use tracing_newrelic::{
+    Api, HttpTransport, StatsSnapshot, TelemetryRequest, TelemetryResponse, TransportError,
+};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+/// An in-memory transport, responding to trace requests with `script` in turn, then with 202
+#[derive(Clone, Default)]
+struct Scripted {
+    script: Arc<Mutex<Vec<Result<TelemetryResponse, TransportError>>>>,
+    requests: Arc<Mutex<Vec<TelemetryRequest>>>,
+}
+
+impl Scripted {
+    fn new(script: Vec<Result<TelemetryResponse, TransportError>>) -> Self {
+        let scripted = Scripted::default();
+        *scripted.script.lock().unwrap() = script.into_iter().rev().collect();
+        scripted
+    }
+
+    fn trace_requests(&self) -> Vec<TelemetryRequest> {
+        let requests = self.requests.lock().unwrap();
+        requests
+            .iter()
+            .filter(|request| request.url.contains("trace"))
+            .cloned()
+            .collect()
+    }
+}
+
+impl HttpTransport for Scripted {
+    fn post(
+        &self,
+        request: TelemetryRequest,
+    ) -> BoxFuture<'_, Result<TelemetryResponse, TransportError>> {
+        let is_trace = request.url.contains("trace");
+
+        self.requests.lock().unwrap().push(request);
+
+        let response = Some(())
+            .filter(|_| is_trace)
+            .and_then(|_| self.script.lock().unwrap().pop())
+            .unwrap_or_else(|| Ok(status(202)));
+
+        Box::pin(async move { response })
+    }
+}
+
+fn status(status: u16) -> TelemetryResponse {
+    TelemetryResponse {
+        status,
+        ..TelemetryResponse::default()
+    }
+}
+
+/// Send `traces` traces, each with its own common attributes so they're not merged
+fn run(transport: &Scripted, traces: usize) -> StatsSnapshot {
+    let api = Api::from("key")
+        .with_trace_batch_size(traces)
+        .with_transport(transport.clone());
+
+    let layer = tracing_newrelic::layer(api);
+    let handle = layer.handle();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        for n in 0..traces {
+            tracing::info_span!("root", trace.n = n).in_scope(|| {});
+        }
+    });
+
+    handle.stats().snapshot()
+}
+
+#[test]
+fn sends_through_the_transport() {
+    let transport = Scripted::default();
+
+    let stats = run(&transport, 1);
+
+    let request = &transport.trace_requests()[0];
+    assert_eq!(request.url, "https://trace-api.newrelic.com/trace/v1");
+    assert!(request
+        .headers
+        .contains(&("api-key".to_string(), "key".to_string())));
+    assert!(request
+        .headers
+        .contains(&("content-encoding".to_string(), "gzip".to_string())));
+    assert_eq!(stats.spans_sent, 1);
+}
+
+#[test]
+fn splits_batch_on_413() {
+    let transport = Scripted::new(vec![Ok(status(413))]);
+
+    let stats = run(&transport, 4);
+
+    // all four payloads, then two halves
+    assert_eq!(transport.trace_requests().len(), 3);
+    assert_eq!(stats.spans_sent, 4);
+    assert_eq!(stats.payloads_dropped, 0);
+}
+
+#[test]
+fn retries_after_429() {
+    let transport = Scripted::new(vec![Ok(TelemetryResponse {
+        status: 429,
+        retry_after: Some("0".to_string()),
+        body_snippet: String::new(),
+    })]);
+
+    let stats = run(&transport, 1);
+
+    assert_eq!(transport.trace_requests().len(), 2);
+    assert_eq!(stats.spans_sent, 1);
+}
+
+#[test]
+fn retries_transport_errors() {
+    let transport = Scripted::new(vec![Err(TransportError::new("connection refused"))]);
+
+    let stats = run(&transport, 1);
+
+    assert_eq!(transport.trace_requests().len(), 2);
+    assert_eq!(stats.send_failures, 1);
+    assert_eq!(stats.spans_sent, 1);
+}
+
+#[test]
+fn reports_rejections() {
+    let transport = Scripted::new(vec![Ok(TelemetryResponse {
+        status: 403,
+        retry_after: None,
+        body_snippet: r#"{"requestId":"abc-123"}"#.to_string(),
+    })]);
+
+    let (tx, rx) = std::sync::mpsc::channel();
+
+    let api = Api::from("key")
+        .with_transport(transport.clone())
+        .with_error_handler(move |err| {
+            let _ = tx.send(err);
+        });
+
+    let layer = tracing_newrelic::layer(api);
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        tracing::info_span!("root").in_scope(|| {});
+    });
+
+    let err = rx.try_recv().unwrap();
+    assert_eq!(err.status, Some(403));
+    assert_eq!(err.request_id.as_deref(), Some("abc-123"));
+}
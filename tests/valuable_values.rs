@@ -0,0 +1,213 @@
+#![cfg(all(tracing_unstable, feature = "valuable"))]
+
+use std::sync::{Arc, Mutex};
+
+use futures_util::future::BoxFuture;
+use tracing_newrelic::{Exporter, NewrLogs, NewrSpans};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+use valuable::{
+    EnumDef, Enumerable, Fields, NamedField, NamedValues, StructDef, Structable, Valuable, Value,
+    Variant, VariantDef, Visit,
+};
+
+// the same as `#[derive(Valuable)]`
+struct User {
+    name: String,
+    roles: Vec<&'static str>,
+    address: Address,
+    status: Status,
+}
+
+struct Address {
+    city: &'static str,
+    zip: u32,
+}
+
+enum Status {
+    Active,
+    Suspended { days: u8 },
+}
+
+static USER_FIELDS: &[NamedField<'static>] = &[
+    NamedField::new("name"),
+    NamedField::new("roles"),
+    NamedField::new("address"),
+    NamedField::new("status"),
+];
+
+static ADDRESS_FIELDS: &[NamedField<'static>] = &[NamedField::new("city"), NamedField::new("zip")];
+
+static SUSPENDED_FIELDS: &[NamedField<'static>] = &[NamedField::new("days")];
+
+static STATUS_VARIANTS: &[VariantDef<'static>] = &[
+    VariantDef::new("Active", Fields::Unnamed(0)),
+    VariantDef::new("Suspended", Fields::Named(SUSPENDED_FIELDS)),
+];
+
+impl Valuable for User {
+    fn as_value(&self) -> Value<'_> {
+        Value::Structable(self)
+    }
+
+    fn visit(&self, visit: &mut dyn Visit) {
+        visit.visit_named_fields(&NamedValues::new(
+            USER_FIELDS,
+            &[
+                self.name.as_value(),
+                self.roles.as_value(),
+                self.address.as_value(),
+                self.status.as_value(),
+            ],
+        ));
+    }
+}
+
+impl Structable for User {
+    fn definition(&self) -> StructDef<'_> {
+        StructDef::new_static("User", Fields::Named(USER_FIELDS))
+    }
+}
+
+impl Valuable for Address {
+    fn as_value(&self) -> Value<'_> {
+        Value::Structable(self)
+    }
+
+    fn visit(&self, visit: &mut dyn Visit) {
+        visit.visit_named_fields(&NamedValues::new(
+            ADDRESS_FIELDS,
+            &[self.city.as_value(), self.zip.as_value()],
+        ));
+    }
+}
+
+impl Structable for Address {
+    fn definition(&self) -> StructDef<'_> {
+        StructDef::new_static("Address", Fields::Named(ADDRESS_FIELDS))
+    }
+}
+
+impl Valuable for Status {
+    fn as_value(&self) -> Value<'_> {
+        Value::Enumerable(self)
+    }
+
+    fn visit(&self, visit: &mut dyn Visit) {
+        match self {
+            Status::Active => visit.visit_unnamed_fields(&[]),
+            Status::Suspended { days } => {
+                visit.visit_named_fields(&NamedValues::new(SUSPENDED_FIELDS, &[days.as_value()]))
+            }
+        }
+    }
+}
+
+impl Enumerable for Status {
+    fn definition(&self) -> EnumDef<'_> {
+        EnumDef::new_static("Status", STATUS_VARIANTS)
+    }
+
+    fn variant(&self) -> Variant<'_> {
+        match self {
+            Status::Active => Variant::Static(&STATUS_VARIANTS[0]),
+            Status::Suspended { .. } => Variant::Static(&STATUS_VARIANTS[1]),
+        }
+    }
+}
+
+fn user(status: Status) -> User {
+    User {
+        name: "alice".to_string(),
+        roles: vec!["admin", "dev"],
+        address: Address {
+            city: "Paris",
+            zip: 75001,
+        },
+        status,
+    }
+}
+
+/// Keeps the serialized attributes of every span and log
+#[derive(Clone, Default)]
+struct Serialized(Arc<Mutex<Vec<String>>>);
+
+impl Exporter for Serialized {
+    fn export(&mut self, logs: NewrLogs, spans: NewrSpans) -> BoxFuture<'_, ()> {
+        let mut serialized = self.0.lock().unwrap();
+
+        for span in &spans.spans {
+            serialized.push(serde_json::to_string(&span.attributes).unwrap());
+        }
+
+        for log in &logs.logs {
+            serialized.push(serde_json::to_string(&log.attributes).unwrap());
+        }
+
+        Box::pin(async {})
+    }
+
+    fn shutdown(&mut self) -> BoxFuture<'_, ()> {
+        Box::pin(async {})
+    }
+}
+
+#[test]
+fn records_structured_values() {
+    let exporter = Serialized::default();
+
+    let layer = tracing_newrelic::layer_with_exporter(exporter.clone())
+        .with_metadata_fields(false)
+        .with_span_processor(|span| {
+            for key in ["duration.ms", "busy.ms", "idle.ms", "nr.entryPoint"] {
+                span.attributes.remove(key);
+            }
+            true
+        })
+        .with_log_processor(|log| {
+            for key in ["span.id", "trace.id"] {
+                log.attributes.remove(key);
+            }
+            true
+        });
+
+    let active = user(Status::Active);
+    let suspended = user(Status::Suspended { days: 3 });
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        tracing::info_span!("root", user = active.as_value()).in_scope(|| {
+            tracing::info!(
+                user = suspended.as_value(),
+                count = 2u8.as_value(),
+                "suspended"
+            );
+        });
+    });
+
+    let serialized = exporter.0.lock().unwrap();
+
+    assert_eq!(
+        *serialized,
+        [
+            r#"{"name":"root","user":{"address":{"city":"Paris","zip":75001},"name":"alice","roles":["admin","dev"],"status":"Active"}}"#,
+            r#"{"message":"suspended","user":{"address":{"city":"Paris","zip":75001},"name":"alice","roles":["admin","dev"],"status":{"Suspended":{"days":3}}},"count":2}"#,
+        ]
+    );
+}
+
+#[test]
+fn records_large_values_as_strings() {
+    let exporter = Serialized::default();
+
+    let layer = tracing_newrelic::layer_with_exporter(exporter.clone());
+
+    let ids: Vec<u32> = (0..2000).collect();
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        tracing::info_span!("root").in_scope(|| tracing::info!(ids = ids.as_value()));
+    });
+
+    let serialized = exporter.0.lock().unwrap();
+    let log: serde_json::Value = serde_json::from_str(&serialized[1]).unwrap();
+
+    assert_eq!(log["ids"], format!("{:?}", ids));
+}
@@ -0,0 +1,59 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures_util::future::BoxFuture;
+use tracing_newrelic::{Exporter, NewrLogs, NewrSpans};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+struct Panicking;
+
+impl Exporter for Panicking {
+    fn export(&mut self, _: NewrLogs, _: NewrSpans) -> BoxFuture<'_, ()> {
+        panic!("exporter failure");
+    }
+
+    fn shutdown(&mut self) -> BoxFuture<'_, ()> {
+        Box::pin(async {})
+    }
+}
+
+#[test]
+fn stops_collecting_once_the_worker_is_gone() {
+    let processed = Arc::new(AtomicUsize::new(0));
+
+    let layer = tracing_newrelic::layer_with_exporter(Panicking).with_span_processor({
+        let processed = processed.clone();
+        move |_| {
+            processed.fetch_add(1, Ordering::Relaxed);
+            true
+        }
+    });
+
+    tracing::subscriber::with_default(Registry::default().with(layer), || {
+        let deadline = Instant::now() + Duration::from_secs(5);
+
+        // the first trace makes the worker panic, later ones are processed until the layer notices
+        loop {
+            let before = processed.load(Ordering::Relaxed);
+
+            tracing::info_span!("root").in_scope(|| tracing::info!("log"));
+
+            if processed.load(Ordering::Relaxed) == before {
+                break;
+            }
+
+            assert!(Instant::now() < deadline, "the worker is still running");
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let processed_before = processed.load(Ordering::Relaxed);
+
+        for _ in 0..10 {
+            tracing::info_span!("ignored").in_scope(|| tracing::info!("ignored"));
+        }
+
+        assert_eq!(processed.load(Ordering::Relaxed), processed_before);
+    });
+}